@@ -0,0 +1,847 @@
+//! Map algebra over aligned rasters
+//!
+//! Expressions combine named rasters with arithmetic, comparisons and
+//! functions, e.g. `depth * vulnerability_curve(landuse)` or
+//! `where(a > 0.5, b, nodata)`. Values are evaluated as f64 with NaN
+//! standing for NoData, so NoData in any operand propagates to the result;
+//! `where` only propagates NoData from its condition and the chosen branch.
+//! Division by zero yields NoData rather than an infinity.
+//!
+//! Grammar, loosest binding first:
+//!
+//! ```text
+//! ||    &&    == != < <= > >=    + -    * / %    unary - !    ^ (right)
+//! ```
+//!
+//! Built-in functions: `where(c, a, b)`, `isnodata(x)`, `abs`, `sqrt`,
+//! `exp`, `ln`, `log10`, `sin`, `cos`, `tan`, `floor`, `ceil`, `round`,
+//! `pow(x, y)`, `min(...)`, `max(...)` and `clamp(x, lo, hi)`. Further
+//! functions (such as lookup curves) are registered on [`MapAlgebra`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use crate::compression::Compression;
+use crate::error::{Error, Result};
+use crate::formats::tiff::GeoTiffWriter;
+use crate::types::DataType;
+use super::raster::{map_blocks, Raster};
+use super::reclass::Reclassifier;
+
+/// A registered function
+pub type CustomFn = dyn Fn(&[f64]) -> f64 + Send + Sync;
+
+#[derive(Clone)]
+struct Function {
+    arity: usize,
+    output: DataType,
+    f: Arc<CustomFn>,
+}
+
+/// Output settings of [`MapAlgebra::calculate`]
+#[derive(Debug, Clone)]
+pub struct AlgebraOptions {
+    /// Output type; inferred from the expression when `None`
+    pub output_type: Option<DataType>,
+    /// Output NoData; defaults to NaN for floats and the type's extreme
+    /// value (maximum unsigned, minimum signed) for integers
+    pub nodata: Option<f64>,
+    pub compression: Compression,
+}
+
+impl Default for AlgebraOptions {
+    fn default() -> Self {
+        Self { output_type: None, nodata: None, compression: Compression::Deflate }
+    }
+}
+
+/// Expression engine with a registry of custom functions
+#[derive(Clone, Default)]
+pub struct MapAlgebra {
+    functions: HashMap<String, Function>,
+}
+
+impl MapAlgebra {
+    /// Creates an engine with only the built-in functions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a function of `arity` arguments returning `output` values
+    ///
+    /// Arguments may be NaN (NoData); the function decides what to return.
+    pub fn register_function<F>(&mut self, name: &str, arity: usize, output: DataType, f: F)
+    where
+        F: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        self.functions.insert(name.to_string(), Function { arity, output, f: Arc::new(f) });
+    }
+
+    /// Registers a piecewise-linear curve through (x, y) points
+    ///
+    /// Inputs outside the curve take the value of the nearest end point.
+    pub fn register_curve(&mut self, name: &str, mut points: Vec<(f64, f64)>) -> Result<()> {
+        if points.is_empty() || points.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+            return Err(Error::InvalidFormat(format!("Curve '{}' needs finite points", name)));
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        self.register_function(name, 1, DataType::F32, move |args| {
+            let x = args[0];
+            if x.is_nan() {
+                return f64::NAN;
+            }
+            let upper = points.partition_point(|p| p.0 < x);
+            if upper == 0 {
+                return points[0].1;
+            }
+            if upper == points.len() {
+                return points[upper - 1].1;
+            }
+            let (x0, y0) = points[upper - 1];
+            let (x1, y1) = points[upper];
+            y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+        });
+        Ok(())
+    }
+
+    /// Registers a lookup table as a one-argument function
+    pub fn register_table(&mut self, name: &str, table: Reclassifier) {
+        let output = table.output_type(DataType::F32);
+        self.register_function(name, 1, output, move |args| table.apply(args[0]));
+    }
+
+    /// Parses an expression
+    pub fn compile(&self, text: &str) -> Result<Expression> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0, functions: &self.functions, variables: Vec::new() };
+        let root = parser.expression(0)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(syntax(format!("unexpected '{}'", token)));
+        }
+        Ok(Expression { root, variables: parser.variables })
+    }
+
+    /// Evaluates `expression` over named rasters and writes a GeoTIFF on
+    /// their grid; returns the output data type
+    pub fn calculate(
+        &self,
+        expression: &str,
+        inputs: &mut [(String, Raster)],
+        output: &Path,
+        options: &AlgebraOptions,
+    ) -> Result<DataType> {
+        let expression = self.compile(expression)?;
+
+        let mut rasters = Vec::with_capacity(expression.variables.len());
+        for name in &expression.variables {
+            let index = inputs.iter().position(|(n, _)| n == name)
+                .ok_or_else(|| Error::InvalidFormat(format!("No input raster named '{}'", name)))?;
+            rasters.push(index);
+        }
+        let first = rasters.first().copied()
+            .ok_or_else(|| Error::InvalidFormat("Expression does not reference any raster".to_string()))?;
+        for &index in &rasters {
+            if !inputs[index].1.is_aligned_with(&inputs[first].1) {
+                return Err(Error::InvalidFormat(format!(
+                    "Raster '{}' is not aligned with '{}'", inputs[index].0, inputs[first].0
+                )));
+            }
+        }
+
+        let types = rasters.iter()
+            .map(|&index| inputs[index].1.data_type())
+            .collect::<Result<Vec<_>>>()?;
+        let data_type = options.output_type.unwrap_or_else(|| expression.output_type(&types));
+        let nodata = options.nodata.unwrap_or_else(|| default_nodata(data_type));
+
+        let mut config = inputs[first].1.output_config(data_type, Some(nodata));
+        config.compression = options.compression;
+        let mut writers = vec![GeoTiffWriter::create(output, config)?];
+
+        // Borrow the referenced rasters in variable order
+        let mut slots: Vec<Option<&mut Raster>> = inputs.iter_mut().map(|(_, raster)| Some(raster)).collect();
+        let mut selected: Vec<&mut Raster> = rasters.iter().filter_map(|&index| slots[index].take()).collect();
+
+        map_blocks(&mut selected, 0, &mut writers, |blocks| {
+            let values: Vec<&[f64]> = blocks.iter().map(|block| block.values.as_slice()).collect();
+            Ok(vec![expression.evaluate(&values)?])
+        })?;
+
+        for writer in writers {
+            writer.finish()?;
+        }
+        Ok(data_type)
+    }
+}
+
+/// A parsed expression
+#[derive(Clone)]
+pub struct Expression {
+    root: Node,
+    variables: Vec<String>,
+}
+
+impl Expression {
+    /// Returns the referenced raster names in first-use order
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Infers the result type from the types of the variables
+    pub fn output_type(&self, variable_types: &[DataType]) -> DataType {
+        self.root.data_type(variable_types).unwrap_or(DataType::F32)
+    }
+
+    /// Evaluates the expression element-wise; `inputs` follow
+    /// [`Expression::variables`] and must have equal lengths
+    pub fn evaluate(&self, inputs: &[&[f64]]) -> Result<Vec<f64>> {
+        if inputs.len() != self.variables.len() {
+            return Err(Error::InvalidFormat(format!(
+                "Expression uses {} rasters but {} inputs were given", self.variables.len(), inputs.len()
+            )));
+        }
+        let len = inputs.first().map_or(1, |v| v.len());
+        if let Some(index) = inputs.iter().position(|v| v.len() != len) {
+            return Err(Error::InvalidFormat(format!(
+                "Input '{}' has {} values, expected {}", self.variables[index], inputs[index].len(), len
+            )));
+        }
+        Ok(self.root.evaluate(inputs, len))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Builtin {
+    Where,
+    IsNoData,
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Sin,
+    Cos,
+    Tan,
+    Floor,
+    Ceil,
+    Round,
+    Pow,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl Builtin {
+    /// Helper: Looks up a built-in by name; returns it with its (min, max) arity
+    fn lookup(name: &str) -> Option<(Self, usize, usize)> {
+        use Builtin::*;
+        Some(match name {
+            "where" => (Where, 3, 3),
+            "isnodata" => (IsNoData, 1, 1),
+            "abs" => (Abs, 1, 1),
+            "sqrt" => (Sqrt, 1, 1),
+            "exp" => (Exp, 1, 1),
+            "ln" => (Ln, 1, 1),
+            "log10" => (Log10, 1, 1),
+            "sin" => (Sin, 1, 1),
+            "cos" => (Cos, 1, 1),
+            "tan" => (Tan, 1, 1),
+            "floor" => (Floor, 1, 1),
+            "ceil" => (Ceil, 1, 1),
+            "round" => (Round, 1, 1),
+            "pow" => (Pow, 2, 2),
+            "min" => (Min, 2, usize::MAX),
+            "max" => (Max, 2, usize::MAX),
+            "clamp" => (Clamp, 3, 3),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone)]
+enum Node {
+    Number(f64),
+    NoData,
+    Variable(usize),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Builtin(Builtin, Vec<Node>),
+    Custom(Function, Vec<Node>),
+}
+
+impl Node {
+    /// Helper: Infers the node type; `None` for NoData, which fits any type
+    fn data_type(&self, variables: &[DataType]) -> Option<DataType> {
+        let promote_all = |args: &[Node]| {
+            args.iter().filter_map(|a| a.data_type(variables)).reduce(DataType::promote)
+        };
+
+        match self {
+            Node::Number(v) => Some(literal_type(*v)),
+            Node::NoData => None,
+            Node::Variable(i) => Some(variables[*i]),
+            Node::Unary(UnaryOp::Neg, a) => a.data_type(variables).map(signed),
+            Node::Unary(UnaryOp::Not, _) => Some(DataType::U8),
+            Node::Binary(op, a, b) => {
+                let t = match (a.data_type(variables), b.data_type(variables)) {
+                    (Some(a), Some(b)) => Some(a.promote(b)),
+                    (a, b) => a.or(b),
+                };
+                match op {
+                    BinaryOp::Add | BinaryOp::Mul => t.map(widen),
+                    BinaryOp::Sub => t.map(|t| if t.is_signed_integer() { widen(t) } else { signed(t) }),
+                    BinaryOp::Rem => t,
+                    BinaryOp::Div | BinaryOp::Pow => t.map(float),
+                    _ => Some(DataType::U8),
+                }
+            }
+            Node::Builtin(builtin, args) => match builtin {
+                Builtin::Where => promote_all(&args[1..]),
+                Builtin::IsNoData => Some(DataType::U8),
+                Builtin::Abs | Builtin::Floor | Builtin::Ceil | Builtin::Round
+                | Builtin::Min | Builtin::Max | Builtin::Clamp => promote_all(args),
+                _ => promote_all(args).map(float).or(Some(DataType::F32)),
+            },
+            Node::Custom(function, _) => Some(function.output),
+        }
+    }
+
+    /// Helper: Evaluates the node over `len` elements
+    fn evaluate(&self, inputs: &[&[f64]], len: usize) -> Vec<f64> {
+        match self {
+            Node::Number(v) => vec![*v; len],
+            Node::NoData => vec![f64::NAN; len],
+            Node::Variable(i) => inputs[*i].to_vec(),
+            Node::Unary(op, a) => {
+                let mut values = a.evaluate(inputs, len);
+                for v in values.iter_mut() {
+                    *v = match op {
+                        UnaryOp::Neg => -*v,
+                        UnaryOp::Not => logical(*v).map_or(f64::NAN, |b| f64::from(u8::from(!b))),
+                    };
+                }
+                values
+            }
+            Node::Binary(op, a, b) => {
+                let mut left = a.evaluate(inputs, len);
+                let right = b.evaluate(inputs, len);
+                for (l, r) in left.iter_mut().zip(right) {
+                    *l = binary(*op, *l, r);
+                }
+                left
+            }
+            Node::Builtin(Builtin::Where, args) => {
+                let condition = args[0].evaluate(inputs, len);
+                let then = args[1].evaluate(inputs, len);
+                let otherwise = args[2].evaluate(inputs, len);
+                condition.iter()
+                    .zip(then.iter().zip(otherwise))
+                    .map(|(c, (t, o))| match logical(*c) {
+                        Some(true) => *t,
+                        Some(false) => o,
+                        None => f64::NAN,
+                    })
+                    .collect()
+            }
+            Node::Builtin(builtin, args) => {
+                let args: Vec<Vec<f64>> = args.iter().map(|a| a.evaluate(inputs, len)).collect();
+                let mut row = vec![0.0; args.len()];
+                (0..len)
+                    .map(|i| {
+                        for (slot, arg) in row.iter_mut().zip(&args) {
+                            *slot = arg[i];
+                        }
+                        apply_builtin(*builtin, &row)
+                    })
+                    .collect()
+            }
+            Node::Custom(function, args) => {
+                let args: Vec<Vec<f64>> = args.iter().map(|a| a.evaluate(inputs, len)).collect();
+                let mut row = vec![0.0; args.len()];
+                (0..len)
+                    .map(|i| {
+                        for (slot, arg) in row.iter_mut().zip(&args) {
+                            *slot = arg[i];
+                        }
+                        (function.f)(&row)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Helper: Interprets a value as a condition; `None` for NoData
+fn logical(v: f64) -> Option<bool> {
+    (!v.is_nan()).then_some(v != 0.0)
+}
+
+/// Helper: Applies a binary operator with NoData propagation
+fn binary(op: BinaryOp, l: f64, r: f64) -> f64 {
+    if l.is_nan() || r.is_nan() {
+        return f64::NAN;
+    }
+    let truth = |b: bool| f64::from(u8::from(b));
+    match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Sub => l - r,
+        BinaryOp::Mul => l * r,
+        BinaryOp::Div if r == 0.0 => f64::NAN,
+        BinaryOp::Div => l / r,
+        BinaryOp::Rem => l % r,
+        BinaryOp::Pow => l.powf(r),
+        BinaryOp::Lt => truth(l < r),
+        BinaryOp::Le => truth(l <= r),
+        BinaryOp::Gt => truth(l > r),
+        BinaryOp::Ge => truth(l >= r),
+        BinaryOp::Eq => truth(l == r),
+        BinaryOp::Ne => truth(l != r),
+        BinaryOp::And => truth(l != 0.0 && r != 0.0),
+        BinaryOp::Or => truth(l != 0.0 || r != 0.0),
+    }
+}
+
+/// Helper: Applies a built-in (other than `where`) to one element
+fn apply_builtin(builtin: Builtin, args: &[f64]) -> f64 {
+    if builtin == Builtin::IsNoData {
+        return f64::from(u8::from(args[0].is_nan()));
+    }
+    if args.iter().any(|v| v.is_nan()) {
+        return f64::NAN;
+    }
+    let x = args[0];
+    match builtin {
+        Builtin::Abs => x.abs(),
+        Builtin::Sqrt => x.sqrt(),
+        Builtin::Exp => x.exp(),
+        Builtin::Ln => x.ln(),
+        Builtin::Log10 => x.log10(),
+        Builtin::Sin => x.sin(),
+        Builtin::Cos => x.cos(),
+        Builtin::Tan => x.tan(),
+        Builtin::Floor => x.floor(),
+        Builtin::Ceil => x.ceil(),
+        Builtin::Round => x.round(),
+        Builtin::Pow => x.powf(args[1]),
+        Builtin::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+        Builtin::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        Builtin::Clamp => x.max(args[1]).min(args[2]),
+        Builtin::Where | Builtin::IsNoData => unreachable!("handled above"),
+    }
+}
+
+/// Helper: Smallest type holding a literal
+pub(crate) fn literal_type(v: f64) -> DataType {
+    if v.fract() != 0.0 || !v.is_finite() {
+        return DataType::F32;
+    }
+    [DataType::U8, DataType::I8, DataType::U16, DataType::I16, DataType::U32, DataType::I32]
+        .into_iter()
+        .find(|t| {
+            let (min, max) = t.value_range();
+            v >= min && v <= max
+        })
+        .unwrap_or(DataType::F64)
+}
+
+/// Helper: Next wider type of the same kind, for sums and products
+fn widen(t: DataType) -> DataType {
+    match t {
+        DataType::U8 => DataType::U16,
+        DataType::U16 => DataType::U32,
+        DataType::I8 => DataType::I16,
+        DataType::I16 => DataType::I32,
+        other => other,
+    }
+}
+
+/// Helper: Smallest signed type holding every value of `t`
+fn signed(t: DataType) -> DataType {
+    if t.is_integer() && !t.is_signed_integer() { t.promote(DataType::I8) } else { t }
+}
+
+/// Helper: Floating-point type precise enough for `t`
+fn float(t: DataType) -> DataType {
+    if t == DataType::F64 || (t.is_integer() && t.size() == 4) { DataType::F64 } else { DataType::F32 }
+}
+
+/// Default output NoData for a type
+pub(crate) fn default_nodata(t: DataType) -> f64 {
+    match t {
+        DataType::F32 | DataType::F64 => f64::NAN,
+        t if t.is_signed_integer() => t.value_range().0,
+        t => t.value_range().1,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    Open,
+    Close,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(v) => write!(f, "{}", v),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Op(op) => write!(f, "{}", op),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+/// Helper: Builds an expression syntax error
+fn syntax(message: String) -> Error {
+    Error::InvalidFormat(format!("Invalid expression: {}", message))
+}
+
+/// Helper: Splits an expression into tokens
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    const OPERATORS: [&str; 18] = [
+        "&&", "||", "<=", ">=", "==", "!=", "+", "-", "*", "/", "%", "^", "<", ">", "!", "(", ")", ",",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() || c == '.' {
+            let mut end = rest.find(|ch: char| !(ch.is_ascii_digit() || ch == '.')).unwrap_or(rest.len());
+            // Exponent, e.g. 1e-3
+            if rest[end..].starts_with(['e', 'E']) {
+                let exp = &rest[end + 1..];
+                let sign = usize::from(exp.starts_with(['+', '-']));
+                let digits = exp[sign..].find(|ch: char| !ch.is_ascii_digit()).unwrap_or(exp.len() - sign);
+                if digits > 0 {
+                    end += 1 + sign + digits;
+                }
+            }
+            let number = rest[..end].parse()
+                .map_err(|_| syntax(format!("invalid number '{}'", &rest[..end])))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest.find(|ch: char| !(ch.is_alphanumeric() || ch == '_')).unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS.iter().find(|op| rest.starts_with(**op))
+                .ok_or_else(|| syntax(format!("unexpected character '{}'", c)))?;
+            tokens.push(match *op {
+                "(" => Token::Open,
+                ")" => Token::Close,
+                "," => Token::Comma,
+                op => Token::Op(op),
+            });
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser with precedence climbing
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    functions: &'a HashMap<String, Function>,
+    variables: Vec<String>,
+}
+
+impl Parser<'_> {
+    /// Helper: Parses operators binding at least as tightly as `min_level`
+    fn expression(&mut self, min_level: u8) -> Result<Node> {
+        let mut left = self.unary()?;
+
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            let (level, binary_op) = match *op {
+                "||" => (1, BinaryOp::Or),
+                "&&" => (2, BinaryOp::And),
+                "==" => (3, BinaryOp::Eq),
+                "!=" => (3, BinaryOp::Ne),
+                "<" => (3, BinaryOp::Lt),
+                "<=" => (3, BinaryOp::Le),
+                ">" => (3, BinaryOp::Gt),
+                ">=" => (3, BinaryOp::Ge),
+                "+" => (4, BinaryOp::Add),
+                "-" => (4, BinaryOp::Sub),
+                "*" => (5, BinaryOp::Mul),
+                "/" => (5, BinaryOp::Div),
+                "%" => (5, BinaryOp::Rem),
+                _ => break,
+            };
+            if level < min_level {
+                break;
+            }
+            self.pos += 1;
+            let right = self.expression(level + 1)?;
+            left = Node::Binary(binary_op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    /// Helper: Parses prefix operators, binding looser than `^`
+    fn unary(&mut self) -> Result<Node> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op("-")) => {
+                self.pos += 1;
+                Ok(Node::Unary(UnaryOp::Neg, Box::new(self.unary()?)))
+            }
+            Some(Token::Op("!")) => {
+                self.pos += 1;
+                Ok(Node::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// Helper: Parses right-associative exponentiation
+    fn power(&mut self) -> Result<Node> {
+        let base = self.primary()?;
+        if self.tokens.get(self.pos) == Some(&Token::Op("^")) {
+            self.pos += 1;
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(BinaryOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    /// Helper: Parses numbers, names, calls and parentheses
+    fn primary(&mut self) -> Result<Node> {
+        let token = self.tokens.get(self.pos).cloned()
+            .ok_or_else(|| syntax("unexpected end".to_string()))?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(v) => Ok(Node::Number(v)),
+            Token::Open => {
+                let inner = self.expression(0)?;
+                self.expect(Token::Close)?;
+                Ok(inner)
+            }
+            Token::Ident(name) if self.tokens.get(self.pos) == Some(&Token::Open) => {
+                self.pos += 1;
+                let args = self.arguments()?;
+                self.call(&name, args)
+            }
+            Token::Ident(name) if name == "nodata" => Ok(Node::NoData),
+            Token::Ident(name) => {
+                let index = match self.variables.iter().position(|v| *v == name) {
+                    Some(index) => index,
+                    None => {
+                        self.variables.push(name);
+                        self.variables.len() - 1
+                    }
+                };
+                Ok(Node::Variable(index))
+            }
+            other => Err(syntax(format!("unexpected '{}'", other))),
+        }
+    }
+
+    /// Helper: Parses a call's arguments after the opening parenthesis
+    fn arguments(&mut self) -> Result<Vec<Node>> {
+        let mut args = Vec::new();
+        if self.tokens.get(self.pos) == Some(&Token::Close) {
+            self.pos += 1;
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression(0)?);
+            match self.tokens.get(self.pos) {
+                Some(Token::Comma) => self.pos += 1,
+                Some(Token::Close) => {
+                    self.pos += 1;
+                    return Ok(args);
+                }
+                _ => return Err(syntax("expected ',' or ')' in call".to_string())),
+            }
+        }
+    }
+
+    /// Helper: Resolves a function call and checks its arity
+    fn call(&self, name: &str, args: Vec<Node>) -> Result<Node> {
+        let (node, min, max) = if let Some(function) = self.functions.get(name) {
+            let arity = function.arity;
+            (Node::Custom(function.clone(), Vec::new()), arity, arity)
+        } else if let Some((builtin, min, max)) = Builtin::lookup(name) {
+            (Node::Builtin(builtin, Vec::new()), min, max)
+        } else {
+            return Err(syntax(format!("unknown function '{}'", name)));
+        };
+
+        if args.len() < min || args.len() > max {
+            return Err(syntax(format!("wrong number of arguments ({}) for '{}'", args.len(), name)));
+        }
+        Ok(match node {
+            Node::Custom(function, _) => Node::Custom(function, args),
+            Node::Builtin(builtin, _) => Node::Builtin(builtin, args),
+            _ => unreachable!("only calls are resolved"),
+        })
+    }
+
+    /// Helper: Consumes an expected token
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.tokens.get(self.pos) {
+            Some(token) if *token == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => Err(syntax(format!("expected '{}', found '{}'", expected, token))),
+            None => Err(syntax(format!("expected '{}'", expected))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+    use crate::formats::tiff::GeoInfo;
+
+    fn eval(text: &str, inputs: &[&[f64]]) -> Vec<f64> {
+        MapAlgebra::new().compile(text).unwrap().evaluate(inputs).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_evaluate() {
+        assert_eq!(eval("1 + 2 * 3 - 4 / 2", &[]), vec![5.0]);
+        assert_eq!(eval("-2 ^ 2 + 2 ^ 3 ^ 2", &[]), vec![508.0]);
+        assert_eq!(eval("(1 + 2) * 3 % 4", &[]), vec![1.0]);
+        assert_eq!(eval("1 < 2 && !(3 >= 4) || 0", &[]), vec![1.0]);
+        assert_eq!(eval("max(1, 5, 3) + min(2, -1) + clamp(7, 0, 5) + 1.5e1", &[]), vec![24.0]);
+
+        let a = [1.0, f64::NAN, 0.2];
+        let b = [10.0, 20.0, 30.0];
+        let out = eval("where(a > 0.5, b, nodata)", &[&a, &b]);
+        assert_eq!(out[0], 10.0);
+        assert!(out[1].is_nan() && out[2].is_nan());
+        assert_eq!(eval("isnodata(a)", &[&a]), vec![0.0, 1.0, 0.0]);
+        assert!(eval("a == a", &[&a])[1].is_nan());
+
+        // A zero divisor is NoData, as is 0 / 0
+        let out = eval("x / y", &[&[1.0, 0.0, 4.0], &[0.0, -0.0, 2.0]]);
+        assert!(out[0].is_nan() && out[1].is_nan());
+        assert_eq!(out[2], 2.0);
+        assert_eq!(eval("isnodata(1 / 0)", &[]), vec![1.0]);
+
+        let mut algebra = MapAlgebra::new();
+        algebra.register_table("lut", Reclassifier::new().with_value(2.0, 7.0));
+        let out = algebra.compile("lut(a) + 1").unwrap().evaluate(&[&[2.0, 3.0]]).unwrap();
+        assert_eq!(out[0], 8.0);
+        assert!(out[1].is_nan());
+
+        for bad in ["1 +", "(1", "foo(1)", "where(1, 2)", "1 $ 2", "max(1)"] {
+            assert!(MapAlgebra::new().compile(bad).is_err(), "{}", bad);
+        }
+
+        // Missing or short inputs are errors, not panics
+        let sum = MapAlgebra::new().compile("a + b").unwrap();
+        assert!(sum.evaluate(&[&a]).is_err());
+        assert!(sum.evaluate(&[&a, &b[..2]]).is_err());
+        assert_eq!(sum.evaluate(&[&b, &b]).unwrap(), vec![20.0, 40.0, 60.0]);
+    }
+
+    #[test]
+    fn test_type_inference() {
+        let algebra = MapAlgebra::new();
+        let infer = |text: &str, types: &[DataType]| algebra.compile(text).unwrap().output_type(types);
+        assert_eq!(infer("a + b", &[DataType::U8, DataType::U8]), DataType::U16);
+        assert_eq!(infer("a - 1", &[DataType::U8]), DataType::I16);
+        assert_eq!(infer("a * b", &[DataType::I16, DataType::F32]), DataType::F32);
+        assert_eq!(infer("a / 2", &[DataType::I32]), DataType::F64);
+        assert_eq!(infer("a > 0.5", &[DataType::F64]), DataType::U8);
+        assert_eq!(infer("where(a > 0, b, nodata)", &[DataType::F32, DataType::I16]), DataType::I16);
+        assert_eq!(infer("nodata", &[]), DataType::F32);
+    }
+
+    #[test]
+    fn test_calculate_over_rasters() {
+        let mut spec = FixtureSpec::new(40, 24);
+        spec.data_type = DataType::F32;
+        spec.nodata = Some(-1.0);
+        let depth = write_geotiff(&spec, |x, y| if (x, y) == (2, 3) { -1.0 } else { x as f64 * 0.1 });
+        spec.data_type = DataType::U8;
+        spec.nodata = None;
+        let landuse = write_geotiff(&spec, |_, y| (y % 3) as f64);
+
+        let mut algebra = MapAlgebra::new();
+        algebra.register_curve("vulnerability_curve", vec![(0.0, 0.0), (2.0, 1.0)]).unwrap();
+        let mut inputs = vec![
+            ("depth".to_string(), Raster::open(depth.path()).unwrap()),
+            ("landuse".to_string(), Raster::open(landuse.path()).unwrap()),
+        ];
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("damage.tif");
+        let data_type = algebra.calculate("depth * vulnerability_curve(landuse)", &mut inputs, &path, &AlgebraOptions::default()).unwrap();
+        assert_eq!(data_type, DataType::F32);
+
+        let (mut reader, ifd) = open_fixture(&path);
+        assert!(GeoInfo::from_ifd(&ifd, &mut reader).unwrap().unwrap().nodata.unwrap().is_nan());
+        assert!((reader.read_pixel_as_f64(&ifd, 30, 4).unwrap() - 1.5).abs() < 1e-6);
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 30, 3).unwrap(), 0.0);
+        assert!(reader.read_pixel_as_f64(&ifd, 2, 3).unwrap().is_nan());
+
+        // Integer output with the default NoData
+        let path = dir.path().join("mask.tif");
+        let data_type = algebra.calculate("where(depth > 1, landuse, nodata)", &mut inputs, &path, &AlgebraOptions::default()).unwrap();
+        assert_eq!(data_type, DataType::U8);
+        let (mut reader, ifd) = open_fixture(&path);
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 20, 5).unwrap(), 2.0);
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 5, 5).unwrap(), 255.0);
+
+        assert!(algebra.calculate("depth + missing", &mut inputs, &path, &AlgebraOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_misaligned_inputs() {
+        let spec = FixtureSpec::new(32, 32);
+        let mut shifted = FixtureSpec::new(32, 32);
+        shifted.origin = (5.0, 32.0);
+        let a = write_geotiff(&spec, |_, _| 1.0);
+        let b = write_geotiff(&shifted, |_, _| 1.0);
+
+        let mut inputs = vec![
+            ("a".to_string(), Raster::open(a.path()).unwrap()),
+            ("b".to_string(), Raster::open(b.path()).unwrap()),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let err = MapAlgebra::new()
+            .calculate("a + b", &mut inputs, &dir.path().join("out.tif"), &AlgebraOptions::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("not aligned"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Radius statistics around points
+//!
+//! Distances are great-circle metres between longitude/latitude positions,
+//! so buffers stay circular on the ground in geographic CRSs (where a
+//! pixel's width shrinks with latitude) as well as in projected ones.
+
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, TiffReader, IFD};
+use crate::projection::coordinate::EARTH_RADIUS_M;
+use crate::projection::{epsg, Coordinate, Transformer};
+use super::zonal::{summarize, ZonalStats};
+
+/// Buffer sampling parameters
+#[derive(Debug, Clone, Default)]
+pub struct BufferOptions {
+    /// Buffer radius in metres
+    pub radius_m: f64,
+    /// Percentiles to compute, in the range 0-100
+    pub percentiles: Vec<f64>,
+}
+
+/// Computes statistics over the pixels within `radius_m` of each point
+///
+/// A pixel belongs to a buffer when its centre lies within the radius; the
+/// pixel under the point always does, so small radii degrade to plain point
+/// sampling. Points are given in `source_epsg`; the radius must be positive
+/// and finite.
+pub fn sample_buffers(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    points: &[Coordinate],
+    source_epsg: u16,
+    options: &BufferOptions,
+) -> Result<Vec<ZonalStats>> {
+    if !options.radius_m.is_finite() || options.radius_m <= 0.0 {
+        return Err(Error::InvalidFormat(format!("Buffer radius must be positive, got {}", options.radius_m)));
+    }
+    let geo_info = GeoInfo::from_ifd(ifd, reader)?
+        .ok_or_else(|| Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+    let raster_epsg = geo_info.epsg_code
+        .ok_or_else(|| Error::Projection("No EPSG code available".to_string()))?;
+    let dims = ifd.dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+    let transform = geo_info.affine_transform()
+        .ok_or_else(|| Error::InvalidFormat("Missing geotransform".to_string()))?;
+
+    let lonlats = to_lonlat(points, source_epsg)?;
+
+    // Pixel-space extent of each buffer from its lon/lat bounding box
+    let mut probes = Vec::with_capacity(lonlats.len() * 9);
+    for p in &lonlats {
+        let dlat = (options.radius_m / EARTH_RADIUS_M).to_degrees();
+        let dlon = dlat / p.y.to_radians().cos().max(1e-6);
+        for (fx, fy) in [(-1.0, -1.0), (0.0, -1.0), (1.0, -1.0), (-1.0, 0.0), (0.0, 0.0),
+                         (1.0, 0.0), (-1.0, 1.0), (0.0, 1.0), (1.0, 1.0)] {
+            probes.push(Coordinate::new(p.x + fx * dlon, p.y + fy * dlat));
+        }
+    }
+    let probe_pixels = geo_info.transform_crs_to_pixel_batch(&probes, epsg::WGS84)?;
+
+    let to_wgs84 = (raster_epsg != epsg::WGS84)
+        .then(|| Transformer::new(raster_epsg, epsg::WGS84))
+        .transpose()?;
+
+    let mut pixels = Vec::new();
+    let mut owners = Vec::new();
+
+    for (i, centre) in lonlats.iter().enumerate() {
+        let corners = &probe_pixels[i * 9..(i + 1) * 9];
+        let min_x = corners.iter().map(|p| p.0).fold(f64::INFINITY, f64::min).floor().max(0.0);
+        let min_y = corners.iter().map(|p| p.1).fold(f64::INFINITY, f64::min).floor().max(0.0);
+        let max_x = corners.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max).ceil().min(dims.width as f64);
+        let max_y = corners.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max).ceil().min(dims.height as f64);
+        if min_x >= max_x || min_y >= max_y {
+            continue;
+        }
+
+        let mut candidates = Vec::new();
+        let mut cells = Vec::new();
+        for row in min_y as u64..max_y as u64 {
+            for col in min_x as u64..max_x as u64 {
+                let (px, py) = (col as f64 + 0.5, row as f64 + 0.5);
+                candidates.push(Coordinate::new(
+                    transform[0] + transform[1] * px + transform[2] * py,
+                    transform[3] + transform[4] * px + transform[5] * py,
+                ));
+                cells.push((col, row));
+            }
+        }
+
+        let candidates = match &to_wgs84 {
+            Some(t) => t.transform_many(&candidates)?,
+            None => candidates,
+        };
+
+        let (own_x, own_y) = corners[4];
+        let own = (own_x >= 0.0 && own_y >= 0.0 && own_x < dims.width as f64 && own_y < dims.height as f64)
+            .then_some((own_x as u64, own_y as u64));
+
+        for (cell, lonlat) in cells.into_iter().zip(candidates.iter()) {
+            if Some(cell) == own || centre.haversine_distance(lonlat) <= options.radius_m {
+                pixels.push(cell);
+                owners.push(i);
+            }
+        }
+    }
+
+    let values = reader.read_pixels_batch_f64(ifd, &pixels)?;
+
+    let mut per_point: Vec<Vec<f64>> = vec![Vec::new(); points.len()];
+    let mut nodata: Vec<usize> = vec![0; points.len()];
+    for (value, owner) in values.into_iter().zip(owners) {
+        if geo_info.is_nodata(value) {
+            nodata[owner] += 1;
+        } else {
+            per_point[owner].push(value);
+        }
+    }
+
+    Ok(per_point.into_iter()
+        .zip(nodata)
+        .map(|(values, nodata_count)| {
+            let mut stats = summarize(values, &options.percentiles);
+            stats.nodata_count = nodata_count;
+            stats.coverage = stats.count as f64;
+            stats.weighted_sum = stats.sum;
+            stats.weighted_mean = stats.mean;
+            stats
+        })
+        .collect())
+}
+
+/// Helper: Converts points to WGS84 longitude/latitude
+fn to_lonlat(points: &[Coordinate], source_epsg: u16) -> Result<Vec<Coordinate>> {
+    if source_epsg == epsg::WGS84 {
+        return Ok(points.to_vec());
+    }
+    Transformer::new(source_epsg, epsg::WGS84)?.transform_many(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+
+    #[test]
+    fn test_geographic_buffer_follows_latitude() {
+        // 0.001 degree pixels at 60N: ~55.6 m wide, ~111.2 m tall
+        let mut spec = FixtureSpec::new(64, 64);
+        spec.epsg = 4326;
+        spec.origin = (10.0, 60.032);
+        spec.pixel_size = (0.001, 0.001);
+
+        let by_col = write_geotiff(&spec, |x, _| x as f64);
+        let by_row = write_geotiff(&spec, |_, y| y as f64);
+        let point = Coordinate::from_lonlat(10.0325, 60.0005);
+        let options = BufferOptions { radius_m: 250.0, percentiles: vec![50.0] };
+
+        let (mut reader, ifd) = open_fixture(by_col.path());
+        let stats = &sample_buffers(&mut reader, &ifd, &[point], 4326, &options).unwrap()[0];
+        assert_eq!((stats.min, stats.max), (Some(28.0), Some(36.0)));
+        assert_eq!(stats.percentiles, vec![(50.0, 32.0)]);
+
+        let (mut reader, ifd) = open_fixture(by_row.path());
+        let stats = &sample_buffers(&mut reader, &ifd, &[point], 4326, &options).unwrap()[0];
+        assert_eq!((stats.min, stats.max), (Some(29.0), Some(33.0)));
+    }
+
+    #[test]
+    fn test_small_radius_and_outside_points() {
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.nodata = Some(7.0);
+        let file = write_geotiff(&spec, |x, y| if (x, y) == (3, 3) { 7.0 } else { (x + y) as f64 });
+        let (mut reader, ifd) = open_fixture(file.path());
+
+        let points = [
+            Coordinate::new(10.5, 20.5),
+            Coordinate::new(500.0, 500.0),
+            Coordinate::new(3.5, 28.5),
+        ];
+        let options = BufferOptions { radius_m: 0.1, percentiles: Vec::new() };
+        let stats = sample_buffers(&mut reader, &ifd, &points, 3857, &options).unwrap();
+
+        assert_eq!(stats[0].count, 1);
+        assert_eq!(stats[0].mean, Some(21.0));
+        assert_eq!(stats[1], ZonalStats::empty());
+        assert_eq!((stats[2].count, stats[2].nodata_count), (0, 1));
+
+        for radius_m in [0.0, -5.0, f64::NAN, f64::INFINITY] {
+            let options = BufferOptions { radius_m, percentiles: Vec::new() };
+            assert!(sample_buffers(&mut reader, &ifd, &points, 3857, &options).is_err(), "{}", radius_m);
+        }
+    }
+}
@@ -0,0 +1,671 @@
+//! Contour lines and filled isobands by marching squares
+//!
+//! Cells are squares between four neighbouring pixel centres, read one tile
+//! row at a time with a one-row overlap. Cells touching NoData are skipped,
+//! so lines end and bands are cut at NoData. A cell that is a saddle for
+//! any of the levels crossing it is split into four triangles around its
+//! mean value; every other cell is cut with straight segments. Lines and
+//! band boundaries come from the same cut, so unsmoothed they coincide.
+//! Smoothing runs on each line and ring on its own: a closed line still
+//! matches the ring made of the same vertices, but where a ring also
+//! follows the raster edge, NoData or another level, its smoothed outline
+//! cuts corners the line does not have and the two drift apart.
+//!
+//! Band pieces are merged cell by cell: edges shared by two pieces of the
+//! same band cancel out, and only the outline is kept for ring assembly.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::error::{Error, Result};
+use crate::projection::Coordinate;
+use crate::vector::geometry::ring_contains;
+use crate::vector::{Feature, Geometry, Polygon};
+use super::polygonize::{json_number, orient, split_touching};
+use super::raster::Raster;
+
+/// Band height for rasters without tiles
+const DEFAULT_BAND_ROWS: u64 = 256;
+
+/// Most levels an interval may place across the raster's value range
+pub const MAX_CONTOUR_LEVELS: usize = 10_000;
+
+/// Point in pixel-centre space: (column, row) of the centre grid
+type Point = (f64, f64);
+/// Exact identity of a point, for joining segments
+type Key = (u64, u64);
+
+/// Contour levels
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContourLevels {
+    /// Every `base + k * interval`
+    Interval { interval: f64, base: f64 },
+    /// Explicit levels; bands lie between consecutive levels
+    Explicit(Vec<f64>),
+}
+
+impl ContourLevels {
+    /// Helper: Level of an index
+    fn level(&self, index: i64) -> f64 {
+        match self {
+            ContourLevels::Interval { interval, base } => base + index as f64 * interval,
+            ContourLevels::Explicit(levels) => levels[index as usize],
+        }
+    }
+
+    /// Helper: Candidate level indices around [min, max]
+    fn candidates(&self, min: f64, max: f64) -> std::ops::Range<i64> {
+        match self {
+            ContourLevels::Interval { interval, base } => {
+                ((min - base) / interval).floor() as i64 - 1..((max - base) / interval).floor() as i64 + 2
+            }
+            ContourLevels::Explicit(levels) => 0..levels.len() as i64,
+        }
+    }
+
+    /// Helper: Indices of levels crossing a cell spanning [min, max]
+    fn crossing(&self, min: f64, max: f64) -> impl Iterator<Item = i64> + '_ {
+        self.candidates(min, max).filter(move |&k| min < self.level(k) && self.level(k) <= max)
+    }
+
+    /// Helper: Indices of bands [level(k), level(k + 1)) overlapping [min, max]
+    fn bands(&self, min: f64, max: f64) -> impl Iterator<Item = i64> + '_ {
+        let last = match self {
+            ContourLevels::Interval { .. } => i64::MAX,
+            ContourLevels::Explicit(levels) => levels.len() as i64 - 1,
+        };
+        self.candidates(min, max)
+            .filter(move |&k| k < last && self.level(k) <= max && self.level(k + 1) > min)
+    }
+
+    /// Helper: Checks the levels are well formed
+    fn validate(&self) -> Result<()> {
+        match self {
+            ContourLevels::Interval { interval, base } if interval.is_finite() && *interval > 0.0 && base.is_finite() => Ok(()),
+            ContourLevels::Explicit(levels) if levels.is_empty() => {
+                Err(Error::InvalidFormat("Explicit contour levels must not be empty".to_string()))
+            }
+            ContourLevels::Explicit(levels) if levels.windows(2).all(|w| w[0] < w[1]) && levels.iter().all(|l| l.is_finite()) => Ok(()),
+            _ => Err(Error::InvalidFormat(
+                "Contour interval and base must be finite, the interval positive and explicit levels strictly increasing".to_string()
+            )),
+        }
+    }
+}
+
+/// Running value range of the scanned cells
+///
+/// An interval may place at most [`MAX_CONTOUR_LEVELS`] levels across the
+/// values seen so far, so a tiny interval fails early instead of stalling
+/// every cell.
+struct LevelBudget<'a> {
+    levels: &'a ContourLevels,
+    range: Option<(f64, f64)>,
+}
+
+impl<'a> LevelBudget<'a> {
+    fn new(levels: &'a ContourLevels) -> Self {
+        Self { levels, range: None }
+    }
+
+    /// Helper: Widens the range by a cell's values and checks the level count
+    fn check(&mut self, min: f64, max: f64) -> Result<()> {
+        let ContourLevels::Interval { interval, .. } = self.levels else {
+            return Ok(());
+        };
+        let (lo, hi) = self.range.map_or((min, max), |(lo, hi)| (lo.min(min), hi.max(max)));
+        self.range = Some((lo, hi));
+        if (hi - lo) / interval <= MAX_CONTOUR_LEVELS as f64 {
+            Ok(())
+        } else {
+            Err(Error::InvalidFormat(format!(
+                "Contour interval {} gives over {} levels across values {} to {}", interval, MAX_CONTOUR_LEVELS, lo, hi
+            )))
+        }
+    }
+}
+
+/// Contouring parameters
+#[derive(Debug, Clone)]
+pub struct ContourOptions {
+    pub levels: ContourLevels,
+    /// Chaikin smoothing iterations (0 keeps the raw geometry)
+    pub smoothing: usize,
+    /// Output CRS; defaults to the raster's
+    pub target_epsg: Option<u16>,
+}
+
+impl ContourOptions {
+    /// Creates options without smoothing or reprojection
+    pub fn new(levels: ContourLevels) -> Self {
+        Self { levels, smoothing: 0, target_epsg: None }
+    }
+}
+
+/// Traces contour lines as LineString features with a `level` property
+///
+/// Lines come ordered by level. Closed lines repeat their first vertex;
+/// on north-up rasters higher values lie to the right.
+pub fn contour_lines(raster: &mut Raster, options: &ContourOptions) -> Result<Vec<Feature>> {
+    options.levels.validate()?;
+    let projection = projection(raster, options.target_epsg)?;
+    let levels = &options.levels;
+    let mut budget = LevelBudget::new(levels);
+    let mut segments: BTreeMap<i64, Vec<(Point, Point)>> = BTreeMap::new();
+
+    scan_cells(raster, |col, row, corners| {
+        let (min, max) = min_max(&corners);
+        budget.check(min, max)?;
+        let split = is_saddle(levels, &corners, min, max);
+        for shape in cell_shapes(col, row, corners, split) {
+            for k in levels.crossing(min, max) {
+                if let Some(segment) = shape_segment(&shape, levels.level(k)) {
+                    segments.entry(k).or_default().push(segment);
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    let mut features = Vec::new();
+    for (k, level_segments) in segments {
+        for (mut line, closed) in chain_segments(&level_segments) {
+            if options.smoothing > 0 {
+                line = chaikin(&line, closed, options.smoothing);
+            }
+            if closed {
+                line.push(line[0]);
+            }
+            let geometry = Geometry::LineString(to_world(raster, &line));
+            let mut feature = Feature::new(reproject(geometry, projection)?);
+            feature.properties.insert("level".to_string(), json_number(levels.level(k)));
+            features.push(feature);
+        }
+    }
+    Ok(features)
+}
+
+/// Fills isobands as Polygon features with `min` and `max` properties
+///
+/// A band holds values in [min, max). Features come ordered by band, one
+/// per outer ring. With interval levels every value falls in some band;
+/// explicit levels leave values outside the first and last level unfilled.
+pub fn contour_bands(raster: &mut Raster, options: &ContourOptions) -> Result<Vec<Feature>> {
+    options.levels.validate()?;
+    let projection = projection(raster, options.target_epsg)?;
+    let levels = &options.levels;
+    let mut budget = LevelBudget::new(levels);
+    let mut outlines: BTreeMap<i64, HashSet<(Key, Key)>> = BTreeMap::new();
+
+    scan_cells(raster, |col, row, corners| {
+        let (min, max) = min_max(&corners);
+        budget.check(min, max)?;
+        let split = is_saddle(levels, &corners, min, max);
+        for shape in cell_shapes(col, row, corners, split) {
+            for k in levels.bands(min, max) {
+                let piece = clip(&clip(&shape, levels.level(k), true), levels.level(k + 1), false);
+                if piece.len() < 3 {
+                    continue;
+                }
+                let outline = outlines.entry(k).or_default();
+                for i in 0..piece.len() {
+                    let (a, b) = (key(piece[i].0), key(piece[(i + 1) % piece.len()].0));
+                    // Edges shared with a neighbouring piece cancel
+                    if !outline.remove(&(b, a)) {
+                        outline.insert((a, b));
+                    }
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    let mut features = Vec::new();
+    for (k, outline) in outlines {
+        let edges: Vec<(Key, Key)> = outline.into_iter().collect();
+        for (exterior, holes) in assemble_rings(&edges) {
+            let ring = |r: Vec<Point>| {
+                let r = if options.smoothing > 0 { chaikin(&r, true, options.smoothing) } else { r };
+                to_world(raster, &r)
+            };
+            let polygon = Polygon::with_holes(ring(exterior), holes.into_iter().map(ring).collect());
+            let mut geometry = reproject(Geometry::Polygon(polygon), projection)?;
+            orient(&mut geometry);
+
+            let mut feature = Feature::new(geometry);
+            feature.properties.insert("min".to_string(), json_number(levels.level(k)));
+            feature.properties.insert("max".to_string(), json_number(levels.level(k + 1)));
+            features.push(feature);
+        }
+    }
+    Ok(features)
+}
+
+/// Helper: Calls `visit` with the corner values of every cell without NoData
+///
+/// Corners are ordered top-left, top-right, bottom-right, bottom-left.
+fn scan_cells(raster: &mut Raster, mut visit: impl FnMut(usize, usize, [f64; 4]) -> Result<()>) -> Result<()> {
+    let band_rows = raster.ifd().tile_dimensions().map_or(DEFAULT_BAND_ROWS, |t| t.height);
+    let (width, height) = (raster.width, raster.height);
+    let w = width as usize;
+
+    let mut y = 0;
+    while y + 1 < height {
+        let rows = band_rows.min(height - 1 - y);
+        let block = raster.read_block(0, y, width, rows + 1, 0)?;
+        for r in 0..rows as usize {
+            let top = &block.values[r * w..(r + 1) * w];
+            let bottom = &block.values[(r + 1) * w..(r + 2) * w];
+            for c in 0..w.saturating_sub(1) {
+                let corners = [top[c], top[c + 1], bottom[c + 1], bottom[c]];
+                if !corners.iter().any(|v| v.is_nan()) {
+                    visit(c, y as usize + r, corners)?;
+                }
+            }
+        }
+        y += rows;
+    }
+    Ok(())
+}
+
+fn min_max(values: &[f64]) -> (f64, f64) {
+    values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)))
+}
+
+/// Helper: Returns whether opposite corners fall on the same side of a crossing level
+/// while neighbouring corners do not
+fn is_saddle(levels: &ContourLevels, corners: &[f64; 4], min: f64, max: f64) -> bool {
+    levels.crossing(min, max).any(|k| {
+        let above = corners.map(|v| v >= levels.level(k));
+        above[0] == above[2] && above[1] == above[3] && above[0] != above[1]
+    })
+}
+
+/// Helper: The cell as one square or four triangles around its centre, clockwise on screen
+fn cell_shapes(col: usize, row: usize, corners: [f64; 4], split: bool) -> Vec<Vec<(Point, f64)>> {
+    let (x, y) = (col as f64, row as f64);
+    let points = [(x, y), (x + 1.0, y), (x + 1.0, y + 1.0), (x, y + 1.0)];
+    let vertices: Vec<(Point, f64)> = points.into_iter().zip(corners).collect();
+    if !split {
+        return vec![vertices];
+    }
+    let centre = ((x + 0.5, y + 0.5), corners.iter().sum::<f64>() / 4.0);
+    (0..4).map(|i| vec![vertices[i], vertices[(i + 1) % 4], centre]).collect()
+}
+
+/// Helper: Point where the level crosses the edge p-q
+///
+/// Endpoints are put in a fixed order first so both cells sharing an edge
+/// compute bit-identical points.
+fn crossing_point((p, a): (Point, f64), (q, b): (Point, f64), level: f64) -> Point {
+    let ((p, a), (q, b)) = if p <= q { ((p, a), (q, b)) } else { ((q, b), (p, a)) };
+    let t = (level - a) / (b - a);
+    (p.0 + t * (q.0 - p.0), p.1 + t * (q.1 - p.1))
+}
+
+/// Helper: The segment a level cuts through a non-saddle shape, higher values on its right
+fn shape_segment(shape: &[(Point, f64)], level: f64) -> Option<(Point, Point)> {
+    let (mut rising, mut falling) = (None, None);
+    for i in 0..shape.len() {
+        let (a, b) = (shape[i], shape[(i + 1) % shape.len()]);
+        match (a.1 >= level, b.1 >= level) {
+            (false, true) => rising = Some(crossing_point(a, b, level)),
+            (true, false) => falling = Some(crossing_point(a, b, level)),
+            _ => {}
+        }
+    }
+    match (falling, rising) {
+        (Some(from), Some(to)) if from != to => Some((from, to)),
+        _ => None,
+    }
+}
+
+/// Helper: Clips a shape to values >= level (or < level), linear along its edges
+fn clip(shape: &[(Point, f64)], level: f64, keep_above: bool) -> Vec<(Point, f64)> {
+    let inside = |v: f64| (v >= level) == keep_above;
+    let mut out: Vec<(Point, f64)> = Vec::with_capacity(shape.len() + 2);
+    for i in 0..shape.len() {
+        let (a, b) = (shape[i], shape[(i + 1) % shape.len()]);
+        if inside(a.1) {
+            out.push(a);
+        }
+        if inside(a.1) != inside(b.1) {
+            out.push((crossing_point(a, b, level), level));
+        }
+    }
+    out.dedup_by(|a, b| a.0 == b.0);
+    while out.len() > 1 && out[0].0 == out[out.len() - 1].0 {
+        out.pop();
+    }
+    out
+}
+
+fn key(p: Point) -> Key {
+    // Normalise -0.0 so equal points share a key
+    ((p.0 + 0.0).to_bits(), (p.1 + 0.0).to_bits())
+}
+
+fn point(k: Key) -> Point {
+    (f64::from_bits(k.0), f64::from_bits(k.1))
+}
+
+/// Helper: Joins directed segments into lines; returns (points, closed)
+fn chain_segments(segments: &[(Point, Point)]) -> Vec<(Vec<Point>, bool)> {
+    let mut outgoing: HashMap<Key, Vec<Key>> = HashMap::new();
+    let mut incoming: HashMap<Key, usize> = HashMap::new();
+    for &(a, b) in segments {
+        outgoing.entry(key(a)).or_default().push(key(b));
+        *incoming.entry(key(b)).or_default() += 1;
+    }
+
+    let mut lines = Vec::new();
+    let mut follow = |start: Key, outgoing: &mut HashMap<Key, Vec<Key>>| {
+        let mut line = vec![point(start)];
+        let mut current = start;
+        while let Some(next) = outgoing.get_mut(&current).and_then(Vec::pop) {
+            line.push(point(next));
+            current = next;
+            if current == start {
+                break;
+            }
+        }
+        let closed = current == start && line.len() > 2;
+        lines.push((line, closed));
+    };
+
+    // Open lines start where nothing leads in, then the remaining loops
+    for &(a, _) in segments {
+        let start = key(a);
+        if !incoming.contains_key(&start) && outgoing.get(&start).is_some_and(|o| !o.is_empty()) {
+            follow(start, &mut outgoing);
+        }
+    }
+    for &(a, _) in segments {
+        if outgoing.get(&key(a)).is_some_and(|o| !o.is_empty()) {
+            follow(key(a), &mut outgoing);
+        }
+    }
+    for (line, closed) in &mut lines {
+        if *closed {
+            line.pop();
+        }
+    }
+    lines
+}
+
+/// Helper: Links outline edges into rings and groups holes with exteriors
+///
+/// Exterior rings run clockwise on screen (positive shoelace area in pixel
+/// space) and holes the other way. At vertices shared by two rings the
+/// sharpest right turn is taken so the ring stays with its own face.
+fn assemble_rings(edges: &[(Key, Key)]) -> Vec<(Vec<Point>, Vec<Vec<Point>>)> {
+    let mut outgoing: HashMap<Key, Vec<Key>> = HashMap::with_capacity(edges.len());
+    for &(a, b) in edges {
+        outgoing.entry(a).or_default().push(b);
+    }
+
+    let mut exteriors: Vec<(Vec<Point>, f64)> = Vec::new();
+    let mut holes = Vec::new();
+    for &(start, _) in edges {
+        let Some(first) = outgoing.get_mut(&start).and_then(Vec::pop) else { continue };
+
+        let mut ring = vec![start];
+        let (mut prev, mut current) = (start, first);
+        while current != start {
+            ring.push(current);
+            let Some(candidates) = outgoing.get_mut(&current).filter(|c| !c.is_empty()) else { break };
+            let (p, c) = (point(prev), point(current));
+            let incoming = (c.0 - p.0, c.1 - p.1);
+            let turn = |k: &Key| {
+                let n = point(*k);
+                let out = (n.0 - c.0, n.1 - c.1);
+                (incoming.0 * out.1 - incoming.1 * out.0).atan2(incoming.0 * out.0 + incoming.1 * out.1)
+            };
+            let pick = (0..candidates.len())
+                .max_by(|&i, &j| turn(&candidates[i]).total_cmp(&turn(&candidates[j])))
+                .unwrap_or(0);
+            prev = current;
+            current = candidates.swap_remove(pick);
+        }
+
+        for ring in split_touching(ring) {
+            let ring = drop_collinear(&ring.into_iter().map(point).collect::<Vec<_>>());
+            let area = shoelace(&ring);
+            if area > 0.0 {
+                exteriors.push((ring, area));
+            } else if area < 0.0 {
+                holes.push(ring);
+            }
+        }
+    }
+
+    let coords: Vec<Vec<Coordinate>> = exteriors.iter()
+        .map(|(ring, _)| ring.iter().map(|&(x, y)| Coordinate::new(x, y)).collect())
+        .collect();
+    let mut polygons: Vec<_> = exteriors.iter().map(|(ring, _)| (ring.clone(), Vec::new())).collect();
+    for hole in holes {
+        // Just left of the first edge is inside the hole
+        let (a, b) = (hole[0], hole[1]);
+        let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+        let probe = (
+            (a.0 + b.0) / 2.0 + (b.1 - a.1) / length * 1e-6,
+            (a.1 + b.1) / 2.0 - (b.0 - a.0) / length * 1e-6,
+        );
+        let owner = (0..exteriors.len())
+            .filter(|&i| ring_contains(&coords[i], probe.0, probe.1))
+            .min_by(|&i, &j| exteriors[i].1.total_cmp(&exteriors[j].1));
+        if let Some(i) = owner {
+            polygons[i].1.push(hole);
+        }
+    }
+    polygons
+}
+
+/// Helper: Removes vertices between exactly collinear edges
+fn drop_collinear(ring: &[Point]) -> Vec<Point> {
+    let n = ring.len();
+    (0..n)
+        .filter(|&i| {
+            let (p, c, q) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+            let cross = (c.0 - p.0) * (q.1 - c.1) - (c.1 - p.1) * (q.0 - c.0);
+            let dot = (c.0 - p.0) * (q.0 - c.0) + (c.1 - p.1) * (q.1 - c.1);
+            cross != 0.0 || dot <= 0.0
+        })
+        .map(|i| ring[i])
+        .collect()
+}
+
+fn shoelace(ring: &[Point]) -> f64 {
+    let n = ring.len();
+    (0..n).map(|i| {
+        let (a, b) = (ring[i], ring[(i + 1) % n]);
+        a.0 * b.1 - b.0 * a.1
+    }).sum::<f64>() / 2.0
+}
+
+/// Helper: Chaikin corner cutting; open lines keep their end points
+fn chaikin(points: &[Point], closed: bool, iterations: usize) -> Vec<Point> {
+    let mut current = points.to_vec();
+    for _ in 0..iterations {
+        if current.len() < 3 {
+            break;
+        }
+        let n = current.len();
+        let edges = if closed { n } else { n - 1 };
+        let mut next = Vec::with_capacity(2 * n);
+        if !closed {
+            next.push(current[0]);
+        }
+        for i in 0..edges {
+            let (p, q) = (current[i], current[(i + 1) % n]);
+            next.push((0.75 * p.0 + 0.25 * q.0, 0.75 * p.1 + 0.25 * q.1));
+            next.push((0.25 * p.0 + 0.75 * q.0, 0.25 * p.1 + 0.75 * q.1));
+        }
+        if !closed {
+            next.push(current[n - 1]);
+        }
+        current = next;
+    }
+    current
+}
+
+/// Helper: Maps pixel-centre points to raster CRS coordinates
+fn to_world(raster: &Raster, points: &[Point]) -> Vec<Coordinate> {
+    let t = raster.geo_info().affine_transform().unwrap_or([0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    points.iter()
+        .map(|&(x, y)| {
+            let (x, y) = (x + 0.5, y + 0.5);
+            Coordinate::new(t[0] + x * t[1] + y * t[2], t[3] + x * t[4] + y * t[5])
+        })
+        .collect()
+}
+
+/// Helper: Source and target EPSG codes when reprojecting; a target needs a known source
+fn projection(raster: &Raster, target_epsg: Option<u16>) -> Result<Option<(u16, u16)>> {
+    match (raster.geo_info().epsg_code, target_epsg) {
+        (Some(from), Some(to)) => Ok(Some((from, to))),
+        (None, Some(to)) => Err(Error::Projection(format!("Cannot reproject to EPSG:{} without a source CRS", to))),
+        _ => Ok(None),
+    }
+}
+
+fn reproject(geometry: Geometry, projection: Option<(u16, u16)>) -> Result<Geometry> {
+    match projection {
+        Some((from, to)) => geometry.transform(from, to),
+        None => Ok(geometry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+    use crate::types::DataType;
+    use crate::vector::geometry::ring_area;
+
+    /// Cone rising to 100 at pixel (16, 16)
+    fn cone() -> (tempfile::NamedTempFile, Raster) {
+        let mut spec = FixtureSpec::new(33, 33);
+        spec.data_type = DataType::F32;
+        spec.nodata = Some(-9999.0);
+        let file = write_geotiff(&spec, |x, y| {
+            if x == 0 && y == 0 {
+                return -9999.0;
+            }
+            let d = ((x as f64 - 16.0).powi(2) + (y as f64 - 16.0).powi(2)).sqrt();
+            100.0 - 5.0 * d
+        });
+        let raster = Raster::open(file.path()).unwrap();
+        (file, raster)
+    }
+
+    #[test]
+    fn test_contour_lines() {
+        let (_file, mut raster) = cone();
+        let options = ContourOptions::new(ContourLevels::Explicit(vec![50.0, 90.0]));
+        let lines = contour_lines(&mut raster, &options).unwrap();
+        assert_eq!(lines.len(), 2);
+
+        for (feature, radius) in lines.iter().zip([10.0, 2.0]) {
+            let Some(Geometry::LineString(line)) = &feature.geometry else { panic!("not a line") };
+            assert_eq!(line.first(), line.last());
+            // Centre of pixel (16, 16) in world coordinates; y grows upwards
+            for c in line {
+                let d = ((c.x - 16.5).powi(2) + (c.y - 16.5).powi(2)).sqrt();
+                assert!((d - radius).abs() < 0.15, "{} vs {}", d, radius);
+            }
+            // Higher values on the right: clockwise around the peak
+            assert!(ring_area(line) < 0.0);
+        }
+
+        let smoothed = ContourOptions { smoothing: 2, ..options.clone() };
+        let smooth = contour_lines(&mut raster, &smoothed).unwrap();
+        let count = |f: &Feature| match &f.geometry { Some(Geometry::LineString(l)) => l.len(), _ => 0 };
+        assert!(count(&smooth[0]) > 3 * count(&lines[0]));
+
+        // Lines cut off by the raster edge stay open; the NoData corner has none
+        let open = contour_lines(&mut raster, &ContourOptions::new(ContourLevels::Explicit(vec![-13.0]))).unwrap();
+        assert_eq!(open.len(), 3);
+        for feature in &open {
+            let Some(Geometry::LineString(line)) = &feature.geometry else { panic!("not a line") };
+            assert_ne!(line.first(), line.last());
+        }
+
+        // Reprojecting needs the raster's CRS
+        let mut spec = FixtureSpec::new(8, 8);
+        spec.epsg = 0;
+        let file = write_geotiff(&spec, |x, _| x as f64);
+        let mut raster = Raster::open(file.path()).unwrap();
+        let options = ContourOptions { target_epsg: Some(4326), ..ContourOptions::new(ContourLevels::Explicit(vec![3.5])) };
+        assert!(matches!(contour_lines(&mut raster, &options), Err(Error::Projection(_))));
+        assert!(matches!(contour_bands(&mut raster, &options), Err(Error::Projection(_))));
+    }
+
+    #[test]
+    fn test_smoothing_runs_per_geometry() {
+        let (_file, mut raster) = cone();
+        let vertices = |features: &[Feature], k: usize| -> Vec<Coordinate> {
+            match &features[k].geometry {
+                Some(Geometry::LineString(line)) => line.clone(),
+                Some(Geometry::Polygon(polygon)) => polygon.exterior.clone(),
+                _ => panic!("unexpected geometry"),
+            }
+        };
+        let shared = |a: &Coordinate, ring: &[Coordinate]| ring.iter().any(|b| (a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9);
+
+        for smoothing in [0, 2] {
+            // A closed line and the ring made of the same vertices coincide
+            let options = ContourOptions { smoothing, ..ContourOptions::new(ContourLevels::Explicit(vec![50.0, 75.0])) };
+            let line = vertices(&contour_lines(&mut raster, &options).unwrap(), 0);
+            let ring = vertices(&contour_bands(&mut raster, &options).unwrap(), 0);
+            assert!(line.iter().all(|c| shared(c, &ring)), "smoothing {}", smoothing);
+
+            // A line ending on the raster edge keeps its end point, while the
+            // band ring also follows the edge and gets its corner cut there
+            let options = ContourOptions { smoothing, ..ContourOptions::new(ContourLevels::Explicit(vec![-13.0, 0.0])) };
+            let lines = contour_lines(&mut raster, &options).unwrap();
+            let bands = contour_bands(&mut raster, &options).unwrap();
+            let end = *vertices(&lines, 0).last().unwrap();
+            let on_ring = (0..bands.len()).any(|k| shared(&end, &vertices(&bands, k)));
+            assert_eq!(on_ring, smoothing == 0, "smoothing {}", smoothing);
+        }
+    }
+
+    #[test]
+    fn test_contour_bands() {
+        let (_file, mut raster) = cone();
+        let options = ContourOptions::new(ContourLevels::Interval { interval: 25.0, base: 0.0 });
+        let bands = contour_bands(&mut raster, &options).unwrap();
+
+        let mut ranges: Vec<_> = bands.iter()
+            .map(|f| (f.property_f64("min").unwrap(), f.property_f64("max").unwrap()))
+            .collect();
+        // Values below zero only occur in the four image corners
+        assert_eq!(ranges.iter().filter(|r| r.0 == -25.0).count(), 4);
+        ranges.dedup();
+        assert_eq!(ranges, vec![(-25.0, 0.0), (0.0, 25.0), (25.0, 50.0), (50.0, 75.0), (75.0, 100.0)]);
+
+        // Bands tile the area between pixel centres except the NoData cell
+        let area: f64 = bands.iter().map(|f| f.geometry.as_ref().unwrap().polygons()[0].area()).sum();
+        assert!((area - (32.0 * 32.0 - 1.0)).abs() < 1e-6, "{}", area);
+
+        // The 50-75 band is an annulus
+        let annulus = bands.iter().find(|f| f.property_f64("min") == Some(50.0)).unwrap();
+        let annulus = annulus.geometry.as_ref().unwrap().polygons()[0].clone();
+        assert_eq!(annulus.interiors.len(), 1);
+        let expected = std::f64::consts::PI * (10.0f64.powi(2) - 5.0f64.powi(2));
+        assert!((annulus.area() - expected).abs() < 3.0, "{}", annulus.area());
+
+        let bad = ContourOptions::new(ContourLevels::Explicit(vec![2.0, 1.0]));
+        assert!(contour_bands(&mut raster, &bad).is_err());
+        let empty = ContourOptions::new(ContourLevels::Explicit(Vec::new()));
+        assert!(contour_bands(&mut raster, &empty).is_err());
+        assert!(contour_lines(&mut raster, &empty).is_err());
+
+        // Values span about 113 units: 0.01 gives over 11,000 levels
+        for (interval, base) in [(0.01, 0.0), (1e-12, 0.0), (f64::INFINITY, 0.0), (1.0, f64::NAN)] {
+            let bad = ContourOptions::new(ContourLevels::Interval { interval, base });
+            assert!(contour_lines(&mut raster, &bad).is_err(), "{} {}", interval, base);
+        }
+        let fine = ContourOptions::new(ContourLevels::Interval { interval: 0.05, base: 0.0 });
+        assert!(contour_lines(&mut raster, &fine).is_ok());
+    }
+}
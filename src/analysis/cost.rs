@@ -0,0 +1,551 @@
+//! Accumulated cost surfaces and least-cost paths over a friction raster
+//!
+//! Friction tiles are decoded on first use and held in a bounded
+//! [`TileCache`], so only the tiles around the expanding front stay in
+//! memory. Accumulated costs are stored per tile and allocated only for
+//! tiles the search reaches; `max_cost` bounds the search on rasters too
+//! large to cover completely.
+//!
+//! Friction is the cost per ground metre. NoData, NaN and negative friction
+//! cells are impassable.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use crate::cache::TileCache;
+use crate::compression::Compression;
+use crate::error::{Error, Result};
+use crate::formats::tiff::reader::pixels::PixelReader;
+use crate::formats::tiff::{GeoInfo, GeoReference, GeoTiffWriter, TiffReader, WriterConfig, IFD};
+use crate::projection::{Coordinate, Transformer};
+use crate::types::DataType;
+
+/// NoData of written cost surfaces
+pub const COST_NODATA: f64 = -1.0;
+
+/// Predecessor marker of source cells
+const SOURCE: u8 = u8::MAX;
+
+const MOVES_4: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const MOVES_8: [(i64, i64); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+const MOVES_16: [(i64, i64); 16] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1),
+    (2, 1), (2, -1), (-2, 1), (-2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2),
+];
+
+/// Propagation scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostMethod {
+    /// Dijkstra over king moves
+    Dijkstra8,
+    /// Dijkstra over king and knight moves, for less direction bias
+    ///
+    /// Knight moves are charged the friction of the cells they cross.
+    Dijkstra16,
+    /// Fast marching solution of the eikonal equation on 4 neighbours
+    FastMarching,
+}
+
+impl CostMethod {
+    fn moves(self) -> &'static [(i64, i64)] {
+        match self {
+            CostMethod::Dijkstra8 => &MOVES_8,
+            CostMethod::Dijkstra16 => &MOVES_16,
+            CostMethod::FastMarching => &MOVES_4,
+        }
+    }
+}
+
+/// Cost-distance parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostOptions {
+    pub method: CostMethod,
+    /// Stop expanding beyond this accumulated cost
+    pub max_cost: Option<f64>,
+    /// Friction tiles kept in memory
+    pub cache_tiles: usize,
+}
+
+impl Default for CostOptions {
+    fn default() -> Self {
+        Self {
+            method: CostMethod::Dijkstra8,
+            max_cost: None,
+            cache_tiles: 256,
+        }
+    }
+}
+
+/// Accumulated costs of the cells reached from the sources
+pub struct CostSurface {
+    geo_info: GeoInfo,
+    pub width: u64,
+    pub height: u64,
+    tile_width: u64,
+    tile_height: u64,
+    moves: &'static [(i64, i64)],
+    tiles: HashMap<usize, CostTile>,
+}
+
+/// Helper: Costs and predecessors of one tile
+struct CostTile {
+    /// Accumulated cost, NaN until reached
+    cost: Vec<f64>,
+    /// Index of the move that reached the cell, or SOURCE
+    from: Vec<u8>,
+    settled: Vec<bool>,
+}
+
+impl CostSurface {
+    /// Returns the accumulated cost of a cell, `None` where not reached
+    pub fn cost(&self, col: u64, row: u64) -> Option<f64> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        let (tile, index) = self.locate(col, row);
+        self.tiles.get(&tile).map(|t| t.cost[index]).filter(|c| !c.is_nan())
+    }
+
+    /// Returns the accumulated cost at a coordinate given in `epsg`
+    pub fn cost_at(&self, coord: Coordinate, epsg: u16) -> Result<Option<f64>> {
+        Ok(self.cell_of(coord, epsg)?.and_then(|(col, row)| self.cost(col, row)))
+    }
+
+    /// Returns the number of cells reached
+    pub fn reached_cells(&self) -> usize {
+        self.tiles.values().map(|t| t.cost.iter().filter(|c| !c.is_nan()).count()).sum()
+    }
+
+    /// Traces the cheapest path from the nearest source to `target`
+    ///
+    /// Vertices are pixel centres in `epsg`, ordered from the source to the
+    /// target.
+    pub fn least_cost_path(&self, target: Coordinate, epsg: u16) -> Result<Vec<Coordinate>> {
+        let (mut col, mut row) = self.cell_of(target, epsg)?
+            .ok_or_else(|| Error::OutOfBounds("Path target outside the raster".to_string()))?;
+        if self.cost(col, row).is_none() {
+            return Err(Error::OutOfBounds("Path target was not reached from any source".to_string()));
+        }
+
+        let mut cells = vec![(col, row)];
+        loop {
+            let (tile, index) = self.locate(col, row);
+            let from = self.tiles[&tile].from[index];
+            if from == SOURCE {
+                break;
+            }
+            let (dx, dy) = self.moves[from as usize];
+            col = (col as i64 - dx) as u64;
+            row = (row as i64 - dy) as u64;
+            cells.push((col, row));
+        }
+        cells.reverse();
+
+        let transform = self.geo_info.affine_transform()
+            .ok_or_else(|| Error::InvalidFormat("Missing geotransform".to_string()))?;
+        let points: Vec<Coordinate> = cells.iter()
+            .map(|&(c, r)| {
+                let (x, y) = (c as f64 + 0.5, r as f64 + 0.5);
+                Coordinate::new(
+                    transform[0] + x * transform[1] + y * transform[2],
+                    transform[3] + x * transform[4] + y * transform[5],
+                )
+            })
+            .collect();
+        match self.geo_info.epsg_code {
+            Some(source) if source != epsg => {
+                let transformer = Transformer::new(source, epsg)?;
+                points.into_iter().map(|p| transformer.transform(p)).collect()
+            }
+            _ => Ok(points),
+        }
+    }
+
+    /// Writes the surface as an F32 GeoTIFF; unreached cells are [`COST_NODATA`]
+    ///
+    /// Tiles follow the friction raster's tiling where the writer allows;
+    /// output tiles the search never reached are filled with NoData by the
+    /// writer.
+    pub fn write_geotiff(&self, path: &Path, compression: Compression) -> Result<()> {
+        let mut config = WriterConfig::new(self.width, self.height, DataType::F32);
+        config.compression = compression;
+        config.nodata = Some(COST_NODATA);
+        config.georef = GeoReference::from_geo_info(&self.geo_info);
+        if self.tile_width.is_multiple_of(16) && self.tile_height.is_multiple_of(16) {
+            config.tile_width = self.tile_width;
+            config.tile_height = self.tile_height;
+        }
+
+        let (across, down) = config.tiles_across_down();
+        let mut writer = GeoTiffWriter::create(path, config.clone())?;
+        for index in 0..(across * down) as usize {
+            let (x, y, w, h) = config.tile_window(index);
+            let reached = (y / self.tile_height..=(y + h - 1) / self.tile_height).any(|ty| {
+                (x / self.tile_width..=(x + w - 1) / self.tile_width)
+                    .any(|tx| self.tiles.contains_key(&self.tile_index(tx, ty)))
+            });
+            if !reached {
+                continue;
+            }
+            let mut values = Vec::with_capacity((w * h) as usize);
+            for row in y..y + h {
+                values.extend((x..x + w).map(|col| self.cost(col, row).unwrap_or(f64::NAN)));
+            }
+            writer.write_tile(index, &values)?;
+        }
+        writer.finish()
+    }
+
+    fn is_settled(&self, col: u64, row: u64) -> bool {
+        let (tile, index) = self.locate(col, row);
+        self.tiles.get(&tile).is_some_and(|t| t.settled[index])
+    }
+
+    fn tile_index(&self, tx: u64, ty: u64) -> usize {
+        (ty * self.width.div_ceil(self.tile_width) + tx) as usize
+    }
+
+    fn locate(&self, col: u64, row: u64) -> (usize, usize) {
+        let tile = self.tile_index(col / self.tile_width, row / self.tile_height);
+        let index = (row % self.tile_height) * self.tile_width + col % self.tile_width;
+        (tile, index as usize)
+    }
+
+    /// Helper: Returns the mutable tile and index of a cell, allocating the tile
+    fn slot(&mut self, col: u64, row: u64) -> (&mut CostTile, usize) {
+        let (tile, index) = self.locate(col, row);
+        let size = (self.tile_width * self.tile_height) as usize;
+        let tile = self.tiles.entry(tile).or_insert_with(|| CostTile {
+            cost: vec![f64::NAN; size],
+            from: vec![SOURCE; size],
+            settled: vec![false; size],
+        });
+        (tile, index)
+    }
+
+    fn cell_of(&self, coord: Coordinate, epsg: u16) -> Result<Option<(u64, u64)>> {
+        let (x, y) = self.geo_info.transform_crs_to_pixel_batch(&[coord], epsg)?[0];
+        let inside = x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64;
+        Ok(inside.then_some((x as u64, y as u64)))
+    }
+}
+
+/// Spreads accumulated cost from the sources over a friction raster
+///
+/// Sources are given in `epsg`; each must lie inside the raster on a
+/// passable cell.
+pub fn cost_distance(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    sources: &[Coordinate],
+    epsg: u16,
+    options: &CostOptions,
+) -> Result<CostSurface> {
+    PixelReader::validate_tiled_access(ifd)?;
+    let geo_info = GeoInfo::from_ifd(ifd, reader)?
+        .ok_or_else(|| Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+    let dims = ifd.dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+    let tile_dims = ifd.tile_dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing tile dimensions".to_string()))?;
+    if sources.is_empty() {
+        return Err(Error::InvalidFormat("Cost distance needs at least one source".to_string()));
+    }
+
+    let mut surface = CostSurface {
+        geo_info,
+        width: dims.width,
+        height: dims.height,
+        tile_width: tile_dims.width,
+        tile_height: tile_dims.height,
+        moves: options.method.moves(),
+        tiles: HashMap::new(),
+    };
+    let mut friction = FrictionTiles::new(reader, ifd, surface.geo_info.clone(), options.cache_tiles)?;
+
+    let mut queue = BinaryHeap::new();
+    for &source in sources {
+        let (col, row) = surface.cell_of(source, epsg)?
+            .ok_or_else(|| Error::OutOfBounds("Cost source outside the raster".to_string()))?;
+        if friction.get(col, row)?.is_none() {
+            return Err(Error::InvalidFormat("Cost source on an impassable cell".to_string()));
+        }
+        let (tile, index) = surface.slot(col, row);
+        tile.cost[index] = 0.0;
+        tile.from[index] = SOURCE;
+        queue.push(Trial { cost: 0.0, col, row });
+    }
+
+    let max_cost = options.max_cost.unwrap_or(f64::INFINITY);
+    let moves = surface.moves;
+    while let Some(Trial { cost, col, row }) = queue.pop() {
+        if cost > max_cost {
+            break;
+        }
+        {
+            let (tile, index) = surface.slot(col, row);
+            if tile.settled[index] || cost > tile.cost[index] {
+                continue;
+            }
+            tile.settled[index] = true;
+        }
+        let Some(here) = friction.get(col, row)? else { continue };
+
+        for (m, &(dx, dy)) in moves.iter().enumerate() {
+            let (nc, nr) = (col as i64 + dx, row as i64 + dy);
+            if nc < 0 || nr < 0 || nc >= surface.width as i64 || nr >= surface.height as i64 {
+                continue;
+            }
+            let (nc, nr) = (nc as u64, nr as u64);
+            if surface.is_settled(nc, nr) {
+                continue;
+            }
+            let Some(there) = friction.get(nc, nr)? else { continue };
+            let (sx, sy) = surface.geo_info.ground_pixel_size((row + nr) as f64 / 2.0 + 0.5)
+                .ok_or_else(|| Error::InvalidFormat("Missing pixel scale".to_string()))?;
+
+            let candidate = match options.method {
+                CostMethod::FastMarching => eikonal(&surface, nc, nr, there, sx, sy),
+                _ => {
+                    let Some(mean) = friction.along_move(col, row, dx, dy, here, there)? else { continue };
+                    cost + (dx as f64 * sx).hypot(dy as f64 * sy) * mean
+                }
+            };
+            let (tile, index) = surface.slot(nc, nr);
+            if tile.cost[index].is_nan() || candidate < tile.cost[index] {
+                tile.cost[index] = candidate;
+                tile.from[index] = m as u8;
+                queue.push(Trial { cost: candidate, col: nc, row: nr });
+            }
+        }
+    }
+
+    // Drop tentative costs beyond the limit so only settled cells remain
+    for tile in surface.tiles.values_mut() {
+        for (cost, settled) in tile.cost.iter_mut().zip(&tile.settled) {
+            if !settled {
+                *cost = f64::NAN;
+            }
+        }
+    }
+    Ok(surface)
+}
+
+/// Helper: Eikonal update of a cell from its settled 4-neighbours
+fn eikonal(surface: &CostSurface, col: u64, row: u64, friction: f64, sx: f64, sy: f64) -> f64 {
+    let settled = |c: i64, r: i64| -> f64 {
+        if c < 0 || r < 0 || c >= surface.width as i64 || r >= surface.height as i64 {
+            return f64::INFINITY;
+        }
+        let (tile, index) = surface.locate(c as u64, r as u64);
+        match surface.tiles.get(&tile) {
+            Some(t) if t.settled[index] => t.cost[index],
+            _ => f64::INFINITY,
+        }
+    };
+    let (c, r) = (col as i64, row as i64);
+    let a = settled(c - 1, r).min(settled(c + 1, r));
+    let b = settled(c, r - 1).min(settled(c, r + 1));
+
+    let along_x = a + friction * sx;
+    let along_y = b + friction * sy;
+    if !a.is_finite() || !b.is_finite() {
+        return along_x.min(along_y);
+    }
+    // (t - a)² / sx² + (t - b)² / sy² = friction²
+    let (wx, wy) = (1.0 / (sx * sx), 1.0 / (sy * sy));
+    let half_b = a * wx + b * wy;
+    let disc = half_b * half_b - (wx + wy) * (a * a * wx + b * b * wy - friction * friction);
+    if disc >= 0.0 {
+        let t = (half_b + disc.sqrt()) / (wx + wy);
+        if t >= a.max(b) {
+            return t;
+        }
+    }
+    along_x.min(along_y)
+}
+
+/// Helper: Queue entry ordered by ascending cost
+struct Trial {
+    cost: f64,
+    col: u64,
+    row: u64,
+}
+
+impl PartialEq for Trial {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Trial {}
+
+impl PartialOrd for Trial {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Trial {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+            .then_with(|| (other.row, other.col).cmp(&(self.row, self.col)))
+    }
+}
+
+/// Helper: Friction values read tile by tile through a bounded cache
+struct FrictionTiles<'a> {
+    reader: &'a mut TiffReader,
+    ifd: &'a IFD,
+    geo_info: GeoInfo,
+    data_type: DataType,
+    cache: TileCache,
+    /// Most recently used tile, to skip cache lookups within a tile
+    last: Option<(usize, Arc<Vec<u8>>)>,
+}
+
+impl<'a> FrictionTiles<'a> {
+    fn new(reader: &'a mut TiffReader, ifd: &'a IFD, geo_info: GeoInfo, cache_tiles: usize) -> Result<Self> {
+        let data_type = PixelReader::require_data_type(ifd)?;
+        Ok(Self { reader, ifd, geo_info, data_type, cache: TileCache::new(cache_tiles), last: None })
+    }
+
+    /// Returns the friction of a cell, `None` where impassable
+    fn get(&mut self, col: u64, row: u64) -> Result<Option<f64>> {
+        let tile_index = PixelReader::calculate_tile_index(self.ifd, col, row)?;
+        let tile = match &self.last {
+            Some((index, tile)) if *index == tile_index => Arc::clone(tile),
+            _ => {
+                let tile = match self.cache.get(0, tile_index) {
+                    Some(tile) => tile,
+                    None => {
+                        let data = self.reader.read_tiles_direct(self.ifd, &[tile_index])?
+                            .pop()
+                            .ok_or_else(|| Error::InvalidFormat("Missing tile data".to_string()))?;
+                        let data = Arc::new(data);
+                        self.cache.put_shared(0, tile_index, Arc::clone(&data));
+                        data
+                    }
+                };
+                self.last = Some((tile_index, Arc::clone(&tile)));
+                tile
+            }
+        };
+        let pixel_index = PixelReader::calculate_pixel_index(self.ifd, col, row)?;
+        let value = PixelReader::read_as_f64_from_tile(&tile, pixel_index, self.data_type)?;
+        Ok((!self.geo_info.is_nodata(value) && value >= 0.0).then_some(value))
+    }
+
+    /// Returns the mean friction along a move, `None` where it crosses an impassable cell
+    ///
+    /// A knight move from (0, 0) to (2, 1) also crosses (1, 0) and (1, 1),
+    /// spending a quarter of its length in each of the four cells.
+    fn along_move(&mut self, col: u64, row: u64, dx: i64, dy: i64, here: f64, there: f64) -> Result<Option<f64>> {
+        if dx.abs() < 2 && dy.abs() < 2 {
+            return Ok(Some((here + there) / 2.0));
+        }
+        let mut sum = here + there;
+        for (cx, cy) in [(dx - dx.signum(), dy - dy.signum()), (dx.signum(), dy.signum())] {
+            match self.get((col as i64 + cx) as u64, (row as i64 + cy) as u64)? {
+                Some(value) => sum += value,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(sum / 4.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+
+    /// Centre of pixel (col, row) in the 32x32 fixture CRS
+    fn centre(col: u64, row: u64) -> Coordinate {
+        Coordinate::new(col as f64 + 0.5, 32.0 - row as f64 - 0.5)
+    }
+
+    #[test]
+    fn test_uniform_friction() {
+        let file = write_geotiff(&FixtureSpec::new(32, 32), |_, _| 1.0);
+        let (mut reader, ifd) = open_fixture(file.path());
+        let sources = [centre(5, 5)];
+        let close = |a: Option<f64>, b: f64, tolerance: f64| (a.unwrap() - b).abs() < tolerance;
+
+        let options = CostOptions::default();
+        let eight = cost_distance(&mut reader, &ifd, &sources, 3857, &options).unwrap();
+        assert_eq!(eight.reached_cells(), 32 * 32);
+        assert_eq!(eight.cost(5, 5), Some(0.0));
+        assert!(close(eight.cost(15, 5), 10.0, 1e-3));
+        assert!(close(eight.cost(15, 15), 10.0 * 2f64.sqrt(), 1e-3));
+        assert!(close(eight.cost(15, 10), 5.0 + 5.0 * 2f64.sqrt(), 1e-3));
+
+        let sixteen = CostOptions { method: CostMethod::Dijkstra16, ..options.clone() };
+        let sixteen = cost_distance(&mut reader, &ifd, &sources, 3857, &sixteen).unwrap();
+        assert!(close(sixteen.cost(15, 10), 125f64.sqrt(), 1e-3));
+
+        let marching = CostOptions { method: CostMethod::FastMarching, ..options.clone() };
+        let marching = cost_distance(&mut reader, &ifd, &sources, 3857, &marching).unwrap();
+        assert!(close(marching.cost(15, 5), 10.0, 1e-3));
+        assert!(close(marching.cost(15, 15), 200f64.sqrt(), 1.0));
+
+        // A cost limit stops the search early
+        let limited = CostOptions { max_cost: Some(3.0), ..options };
+        let limited = cost_distance(&mut reader, &ifd, &sources, 3857, &limited).unwrap();
+        assert_eq!(limited.reached_cells(), 29);
+        assert_eq!(limited.cost(9, 5), None);
+    }
+
+    #[test]
+    fn test_least_cost_path_around_barrier() {
+        // Impassable wall in column 10 except for rows 28-31; single-tile cache
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.nodata = Some(255.0);
+        let file = write_geotiff(&spec, |x, y| if x == 10 && y < 28 { 255.0 } else { 2.0 });
+        let (mut reader, ifd) = open_fixture(file.path());
+        let options = CostOptions { cache_tiles: 1, ..Default::default() };
+        let surface = cost_distance(&mut reader, &ifd, &[centre(5, 5)], 3857, &options).unwrap();
+
+        assert_eq!(surface.cost(10, 5), None);
+        let path = surface.least_cost_path(centre(15, 5), 3857).unwrap();
+        assert_eq!(path.first(), Some(&centre(5, 5)));
+        assert_eq!(path.last(), Some(&centre(15, 5)));
+        assert!(path.iter().any(|p| p.y < 4.0), "path must pass the gap");
+        // Friction 2 per metre, two legs of 18 + 5√2 cells through (10, 28)
+        let detour = surface.cost(15, 5).unwrap();
+        assert!((detour - 4.0 * (18.0 + 5.0 * 2f64.sqrt())).abs() < 1e-3, "{}", detour);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cost.tif");
+        surface.write_geotiff(&path, Compression::Deflate).unwrap();
+        let (mut reader, ifd) = open_fixture(&path);
+        assert!((reader.read_pixel_as_f64(&ifd, 15, 5).unwrap() - detour).abs() < 1e-3);
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 10, 5).unwrap(), COST_NODATA);
+
+        assert!(cost_distance(&mut reader, &ifd, &[centre(10, 5)], 3857, &options).is_err());
+    }
+
+    #[test]
+    fn test_knight_moves_do_not_cross_barrier() {
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.nodata = Some(255.0);
+        let file = write_geotiff(&spec, |x, y| match (x, y) {
+            (10, 0..=27) => 255.0,
+            (20, _) => 100.0,
+            _ => 1.0,
+        });
+        let (mut reader, ifd) = open_fixture(file.path());
+        let options = CostOptions { method: CostMethod::Dijkstra16, ..Default::default() };
+        let surface = cost_distance(&mut reader, &ifd, &[centre(5, 5)], 3857, &options).unwrap();
+
+        // Straight across would cost 10; the wall forces a detour through the gap
+        let path = surface.least_cost_path(centre(15, 5), 3857).unwrap();
+        assert!(path.iter().any(|p| p.y < 4.0), "path must pass the gap");
+        let gap = (25f64 + 23.0 * 23.0).sqrt();
+        assert!(surface.cost(15, 5).unwrap() > 2.0 * gap, "{:?}", surface.cost(15, 5));
+
+        // Knight moves over the high-friction column pay for the crossed cells
+        assert!(surface.cost(25, 30).unwrap() > 100.0, "{:?}", surface.cost(25, 30));
+    }
+}
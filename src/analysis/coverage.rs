@@ -0,0 +1,381 @@
+//! Polygon coverage against a pixel grid
+//!
+//! Rings are given in pixel space, where pixel (col, row) covers the unit
+//! square [col, col + 1] x [row, row + 1]. Interior pixels are classified by
+//! a scanline test at their centre; pixels crossed by an edge get their exact
+//! covered fraction by clipping the rings to the pixel square.
+
+use crate::error::{Error, Result};
+use crate::formats::tiff::GeoInfo;
+use crate::projection::Transformer;
+use crate::vector::Polygon;
+
+/// Coverage of a set of polygons over the raster cells they touch
+#[derive(Debug, Clone)]
+pub struct PolygonCoverage {
+    /// First column of the covered window
+    pub x0: u64,
+    /// First row of the covered window
+    pub y0: u64,
+    /// Window width in pixels
+    pub width: u64,
+    /// Window height in pixels
+    pub height: u64,
+    /// Covered fraction per window cell (0.0 - 1.0)
+    pub fraction: Vec<f32>,
+    /// Whether each window cell's centre lies inside
+    pub centre: Vec<bool>,
+}
+
+/// A ring in pixel space; holes subtract from the coverage
+pub struct PixelRing {
+    pub points: Vec<(f64, f64)>,
+    pub is_hole: bool,
+}
+
+/// Converts polygons into pixel-space rings of a raster
+///
+/// Vertices are first reprojected with `transformer` when one is given.
+pub fn pixel_rings(polygons: &[&Polygon], geo_info: &GeoInfo, transformer: Option<&Transformer>) -> Result<Vec<PixelRing>> {
+    let mut rings = Vec::new();
+
+    for polygon in polygons {
+        let polygon = match transformer {
+            Some(t) => polygon.map_coords(|c| t.transform(c))?,
+            None => (*polygon).clone(),
+        };
+
+        for (i, ring) in polygon.rings().enumerate() {
+            let points = ring.iter()
+                .map(|&c| geo_info.geo_to_pixel(c)
+                    .ok_or_else(|| Error::InvalidFormat("Missing or singular geotransform".to_string())))
+                .collect::<Result<_>>()?;
+            rings.push(PixelRing { points, is_hole: i > 0 });
+        }
+    }
+
+    Ok(rings)
+}
+
+impl PolygonCoverage {
+    /// Computes coverage for rings clipped to a raster of the given size
+    ///
+    /// Returns `None` when the rings do not overlap the raster.
+    pub fn compute(rings: &[PixelRing], raster_width: u64, raster_height: u64) -> Option<Self> {
+        let mut bounds = (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in rings.iter().flat_map(|r| r.points.iter()) {
+            bounds = (bounds.0.min(x), bounds.1.min(y), bounds.2.max(x), bounds.3.max(y));
+        }
+        if !bounds.0.is_finite() || !bounds.3.is_finite() {
+            return None;
+        }
+
+        let x0 = bounds.0.floor().max(0.0) as u64;
+        let y0 = bounds.1.floor().max(0.0) as u64;
+        let x1 = (bounds.2.ceil().max(0.0) as u64).min(raster_width);
+        let y1 = (bounds.3.ceil().max(0.0) as u64).min(raster_height);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        let mut coverage = Self {
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            fraction: vec![0.0; ((x1 - x0) * (y1 - y0)) as usize],
+            centre: vec![false; ((x1 - x0) * (y1 - y0)) as usize],
+        };
+
+        coverage.scan_centres(rings);
+        let boundary = coverage.mark_boundary(rings);
+        coverage.compute_fractions(rings, &boundary);
+        Some(coverage)
+    }
+
+    /// Returns the window index of a raster cell, if inside the window
+    pub fn index_of(&self, col: u64, row: u64) -> Option<usize> {
+        if col < self.x0 || row < self.y0 || col >= self.x0 + self.width || row >= self.y0 + self.height {
+            return None;
+        }
+        Some(((row - self.y0) * self.width + (col - self.x0)) as usize)
+    }
+
+    /// Helper: Marks cells whose centre is inside any polygon
+    ///
+    /// Each polygon is filled even-odd over its exterior and its own holes,
+    /// so overlapping or touching parts of a multipolygon do not cancel out.
+    fn scan_centres(&mut self, rings: &[PixelRing]) {
+        let mut crossings = Vec::new();
+
+        for polygon in polygons(rings) {
+            let (min_y, max_y) = y_range(polygon);
+
+            for row in 0..self.height {
+                let yc = (self.y0 + row) as f64 + 0.5;
+                if yc < min_y || yc > max_y {
+                    continue;
+                }
+                crossings.clear();
+
+                for ring in polygon {
+                    for_each_edge(&ring.points, |(ax, ay), (bx, by)| {
+                        if (ay > yc) != (by > yc) {
+                            crossings.push(ax + (yc - ay) * (bx - ax) / (by - ay));
+                        }
+                    });
+                }
+                crossings.sort_by(|a, b| a.total_cmp(b));
+
+                for pair in crossings.chunks_exact(2) {
+                    let start = ((pair[0] - 0.5).ceil().max(self.x0 as f64) as u64).max(self.x0);
+                    let end = ((pair[1] - 0.5).ceil().max(0.0) as u64).min(self.x0 + self.width);
+                    for col in start..end {
+                        let idx = (row * self.width + col - self.x0) as usize;
+                        self.centre[idx] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Helper: Flags every window cell an edge passes through
+    fn mark_boundary(&self, rings: &[PixelRing]) -> Vec<bool> {
+        let mut boundary = vec![false; self.fraction.len()];
+        let (wx0, wy0) = (self.x0 as f64, self.y0 as f64);
+        let (wx1, wy1) = (wx0 + self.width as f64, wy0 + self.height as f64);
+
+        for ring in rings {
+            for_each_edge(&ring.points, |(ax, ay), (bx, by)| {
+                let (lx, rx) = (ax.min(bx), ax.max(bx));
+                let first_col = lx.floor().max(wx0);
+                let last_col = rx.floor().min(wx1 - 1.0);
+
+                let mut col = first_col;
+                while col <= last_col {
+                    // y-range of the edge within this column
+                    let (ya, yb) = if (bx - ax).abs() < f64::EPSILON {
+                        (ay, by)
+                    } else {
+                        let t0 = ((col.max(lx) - ax) / (bx - ax)).clamp(0.0, 1.0);
+                        let t1 = ((((col + 1.0).min(rx)) - ax) / (bx - ax)).clamp(0.0, 1.0);
+                        (ay + t0 * (by - ay), ay + t1 * (by - ay))
+                    };
+
+                    let first_row = ya.min(yb).floor().max(wy0);
+                    let last_row = ya.max(yb).floor().min(wy1 - 1.0);
+                    let mut row = first_row;
+                    while row <= last_row {
+                        let idx = ((row - wy0) as u64 * self.width + (col - wx0) as u64) as usize;
+                        boundary[idx] = true;
+                        row += 1.0;
+                    }
+                    col += 1.0;
+                }
+            });
+        }
+
+        boundary
+    }
+
+    /// Helper: Computes exact fractions for boundary cells, 0/1 elsewhere
+    fn compute_fractions(&mut self, rings: &[PixelRing], boundary: &[bool]) {
+        let signs: Vec<f64> = rings.iter()
+            .map(|r| {
+                let orientation = signed_area(&r.points).signum();
+                if r.is_hole { -orientation } else { orientation }
+            })
+            .collect();
+
+        for row in 0..self.height {
+            let top = (self.y0 + row) as f64;
+            let row_start = (row * self.width) as usize;
+            let row_cells = row_start..row_start + self.width as usize;
+
+            if !boundary[row_cells.clone()].iter().any(|&b| b) {
+                for idx in row_cells {
+                    self.fraction[idx] = if self.centre[idx] { 1.0 } else { 0.0 };
+                }
+                continue;
+            }
+
+            // Only rings reaching into this row, with the x-range of their band
+            let bands: Vec<Band> = rings.iter()
+                .zip(&signs)
+                .filter(|(r, _)| {
+                    let (min_y, max_y) = y_range(std::slice::from_ref(*r));
+                    max_y > top && min_y < top + 1.0
+                })
+                .map(|(r, &sign)| (clip(&clip(&r.points, Axis::Y, top, true), Axis::Y, top + 1.0, false), sign))
+                .filter(|(band, _)| band.len() >= 3)
+                .map(|(band, sign)| {
+                    let (min_x, max_x) = band.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+                    Band { points: band, sign, min_x, max_x }
+                })
+                .collect();
+
+            for idx in row_cells {
+                if !boundary[idx] {
+                    self.fraction[idx] = if self.centre[idx] { 1.0 } else { 0.0 };
+                    continue;
+                }
+
+                // Areas of separate polygons add up; overlaps are clamped to 1
+                let left = self.x0 as f64 + (idx - row_start) as f64;
+                let mut area = 0.0;
+                for band in &bands {
+                    if band.max_x <= left || band.min_x >= left + 1.0 {
+                        continue;
+                    }
+                    let cell = clip(&clip(&band.points, Axis::X, left, true), Axis::X, left + 1.0, false);
+                    area += band.sign * signed_area(&cell);
+                }
+                self.fraction[idx] = area.clamp(0.0, 1.0) as f32;
+            }
+        }
+    }
+}
+
+/// A ring clipped to one row, with its area sign and horizontal extent
+struct Band {
+    points: Vec<(f64, f64)>,
+    sign: f64,
+    min_x: f64,
+    max_x: f64,
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    X,
+    Y,
+}
+
+/// Helper: Splits rings into polygons, each an exterior followed by its holes
+fn polygons(rings: &[PixelRing]) -> impl Iterator<Item = &[PixelRing]> {
+    let mut rest = rings;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let len = 1 + rest[1..].iter().take_while(|r| r.is_hole).count();
+        let (polygon, tail) = rest.split_at(len);
+        rest = tail;
+        Some(polygon)
+    })
+}
+
+/// Helper: Returns the vertical extent of a set of rings
+fn y_range(rings: &[PixelRing]) -> (f64, f64) {
+    rings.iter()
+        .flat_map(|r| r.points.iter())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)))
+}
+
+/// Helper: Calls `f` for every edge of a ring, closing it implicitly
+fn for_each_edge<F: FnMut((f64, f64), (f64, f64))>(points: &[(f64, f64)], mut f: F) {
+    if points.len() < 2 {
+        return;
+    }
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        if a != b {
+            f(a, b);
+        }
+    }
+}
+
+/// Helper: Sutherland-Hodgman clip of a ring against an axis-aligned half-plane
+fn clip(points: &[(f64, f64)], axis: Axis, value: f64, keep_greater: bool) -> Vec<(f64, f64)> {
+    let coord = |p: (f64, f64)| match axis {
+        Axis::X => p.0,
+        Axis::Y => p.1,
+    };
+    let inside = |p: (f64, f64)| if keep_greater { coord(p) >= value } else { coord(p) <= value };
+
+    let mut output = Vec::with_capacity(points.len() + 2);
+    for i in 0..points.len() {
+        let current = points[i];
+        let previous = points[(i + points.len() - 1) % points.len()];
+
+        if inside(current) != inside(previous) {
+            let t = (value - coord(previous)) / (coord(current) - coord(previous));
+            output.push((
+                previous.0 + t * (current.0 - previous.0),
+                previous.1 + t * (current.1 - previous.1),
+            ));
+        }
+        if inside(current) {
+            output.push(current);
+        }
+    }
+    output
+}
+
+/// Helper: Signed shoelace area of a pixel-space ring
+fn signed_area(points: &[(f64, f64)]) -> f64 {
+    let mut sum = 0.0;
+    for i in 0..points.len() {
+        let (ax, ay) = points[i];
+        let (bx, by) = points[(i + 1) % points.len()];
+        sum += ax * by - bx * ay;
+    }
+    sum / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(points: &[(f64, f64)], is_hole: bool) -> PixelRing {
+        PixelRing { points: points.to_vec(), is_hole }
+    }
+
+    #[test]
+    fn test_fractional_square() {
+        let square = ring(&[(0.5, 0.5), (2.5, 0.5), (2.5, 2.5), (0.5, 2.5)], false);
+        let coverage = PolygonCoverage::compute(&[square], 10, 10).unwrap();
+
+        assert_eq!((coverage.x0, coverage.y0, coverage.width, coverage.height), (0, 0, 3, 3));
+        let total: f32 = coverage.fraction.iter().sum();
+        assert!((total - 4.0).abs() < 1e-5);
+        assert_eq!(coverage.fraction[coverage.index_of(0, 0).unwrap()], 0.25);
+        assert_eq!(coverage.fraction[coverage.index_of(1, 1).unwrap()], 1.0);
+        assert_eq!(coverage.fraction[coverage.index_of(1, 0).unwrap()], 0.5);
+        assert!(coverage.centre[coverage.index_of(1, 1).unwrap()]);
+        // Centres on the left/top edge count as inside
+        assert_eq!(coverage.centre.iter().filter(|&&c| c).count(), 4);
+        assert!(!coverage.centre[coverage.index_of(2, 2).unwrap()]);
+    }
+
+    #[test]
+    fn test_hole_and_clipping() {
+        let outer = ring(&[(-5.0, -5.0), (6.0, -5.0), (6.0, 6.0), (-5.0, 6.0)], false);
+        let hole = ring(&[(2.0, 2.0), (2.0, 4.0), (4.0, 4.0), (4.0, 2.0)], true);
+        let coverage = PolygonCoverage::compute(&[outer, hole], 8, 8).unwrap();
+
+        assert_eq!((coverage.x0, coverage.y0, coverage.width, coverage.height), (0, 0, 6, 6));
+        let total: f32 = coverage.fraction.iter().sum();
+        assert!((total - 32.0).abs() < 1e-5);
+        assert_eq!(coverage.fraction[coverage.index_of(3, 3).unwrap()], 0.0);
+        assert!(!coverage.centre[coverage.index_of(3, 3).unwrap()]);
+        assert_eq!(coverage.centre.iter().filter(|&&c| c).count(), 32);
+    }
+
+    #[test]
+    fn test_outside_raster() {
+        let square = ring(&[(20.0, 20.0), (21.0, 20.0), (21.0, 21.0)], false);
+        assert!(PolygonCoverage::compute(&[square], 10, 10).is_none());
+    }
+
+    #[test]
+    fn test_overlapping_polygons() {
+        // Two parts of one multipolygon overlapping on columns 2-3
+        let left = ring(&[(0.0, 0.0), (4.0, 0.0), (4.0, 2.0), (0.0, 2.0)], false);
+        let right = ring(&[(2.0, 0.0), (6.0, 0.0), (6.0, 2.0), (2.0, 2.0)], false);
+        let coverage = PolygonCoverage::compute(&[left, right], 8, 8).unwrap();
+
+        assert_eq!(coverage.centre.iter().filter(|&&c| c).count(), 12);
+        assert!(coverage.fraction.iter().all(|&f| f == 1.0));
+    }
+}
@@ -0,0 +1,338 @@
+//! Focal (moving-window) statistics
+//!
+//! Each output cell summarizes the input cells under a kernel centred on
+//! it, e.g. the maximum flood depth within a 5x5 neighbourhood. Kernels
+//! are squares, circles or custom weight matrices; square and circle radii
+//! may be given in metres, in which case the kernel is resolved per row
+//! from [`GeoInfo::ground_pixel_size`](crate::formats::tiff::GeoInfo::ground_pixel_size).
+//! Inputs are read with a halo of the kernel size, so results are seamless
+//! across tiles. NoData cells under the kernel are skipped.
+
+use std::collections::HashMap;
+use std::path::Path;
+use crate::compression::Compression;
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, GeoTiffWriter};
+use crate::types::DataType;
+use super::algebra::default_nodata;
+use super::raster::{map_blocks, Block, Raster};
+
+/// Summary computed over the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocalStatistic {
+    /// Weighted mean
+    Mean,
+    Max,
+    Min,
+    /// Weighted sum
+    Sum,
+    /// Value with the largest total weight (smallest on ties)
+    Majority,
+}
+
+/// Kernel radius
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Radius {
+    Pixels(f64),
+    Metres(f64),
+}
+
+/// Kernel shape; cells with zero weight are ignored
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kernel {
+    /// Cells whose offset along each axis is within the radius (a 5x5
+    /// window is `Square(Radius::Pixels(2.0))`)
+    Square(Radius),
+    /// Cells whose centre is within the radius
+    Circle(Radius),
+    /// Weight matrix with odd dimensions, row-major
+    Weights { width: usize, height: usize, weights: Vec<f64> },
+}
+
+/// A resolved kernel cell: (column offset, row offset, weight)
+type Cell = (isize, isize, f64);
+
+impl Kernel {
+    /// Returns the non-zero cells for a pixel spacing of (dx, dy) metres
+    pub fn cells(&self, dx: f64, dy: f64) -> Result<Vec<Cell>> {
+        match self {
+            Kernel::Square(radius) | Kernel::Circle(radius) => {
+                let (rx, ry) = radius.in_pixels(dx, dy)?;
+                let (hx, hy) = ((rx + 1e-9).floor() as isize, (ry + 1e-9).floor() as isize);
+                let circle = matches!(self, Kernel::Circle(_));
+
+                let mut cells = Vec::new();
+                for row in -hy..=hy {
+                    for col in -hx..=hx {
+                        let inside = !circle || {
+                            let (u, v) = (col as f64 / rx.max(1e-12), row as f64 / ry.max(1e-12));
+                            u * u + v * v <= 1.0 + 1e-9
+                        };
+                        if inside {
+                            cells.push((col, row, 1.0));
+                        }
+                    }
+                }
+                Ok(cells)
+            }
+            Kernel::Weights { width, height, weights } => {
+                if width % 2 == 0 || height % 2 == 0 || weights.len() != width * height {
+                    return Err(Error::InvalidFormat(format!(
+                        "Kernel weights must form an odd-sized {}x{} matrix", width, height
+                    )));
+                }
+                let (hx, hy) = ((width / 2) as isize, (height / 2) as isize);
+                Ok(weights.iter().enumerate()
+                    .filter(|(_, w)| **w != 0.0)
+                    .map(|(i, &w)| ((i % width) as isize - hx, (i / width) as isize - hy, w))
+                    .collect())
+            }
+        }
+    }
+
+    /// Helper: Whether the kernel depends on the pixel spacing
+    fn is_metric(&self) -> bool {
+        matches!(self, Kernel::Square(Radius::Metres(_)) | Kernel::Circle(Radius::Metres(_)))
+    }
+}
+
+impl Radius {
+    /// Helper: Radius in pixels along x and y
+    fn in_pixels(&self, dx: f64, dy: f64) -> Result<(f64, f64)> {
+        let (rx, ry) = match *self {
+            Radius::Pixels(r) => (r, r),
+            Radius::Metres(r) => (r / dx, r / dy),
+        };
+        if !(rx.is_finite() && ry.is_finite() && rx >= 0.0 && ry >= 0.0) {
+            return Err(Error::InvalidFormat(format!("Invalid kernel radius {:?}", self)));
+        }
+        Ok((rx, ry))
+    }
+}
+
+/// Focal processing parameters
+#[derive(Debug, Clone)]
+pub struct FocalOptions {
+    pub statistic: FocalStatistic,
+    pub kernel: Kernel,
+    /// Also compute cells whose own value is NoData
+    pub fill_nodata: bool,
+    /// Output type; floats for mean and sum, the input type otherwise
+    pub output_type: Option<DataType>,
+    pub compression: Compression,
+}
+
+impl FocalOptions {
+    /// Creates options for a statistic over a kernel
+    pub fn new(statistic: FocalStatistic, kernel: Kernel) -> Self {
+        Self { statistic, kernel, fill_nodata: false, output_type: None, compression: Compression::Deflate }
+    }
+}
+
+/// Writes the focal statistic of a raster to a GeoTIFF; returns the output data type
+pub fn focal_statistics(input: &mut Raster, output: &Path, options: &FocalOptions) -> Result<DataType> {
+    let geo_info = input.geo_info().clone();
+    let input_type = input.data_type()?;
+
+    let spacing = |row: f64| pixel_spacing(&geo_info, row);
+    let fixed = if options.kernel.is_metric() { None } else { Some(options.kernel.cells(1.0, 1.0)?) };
+
+    // The kernel is widest where pixels are narrowest, at the top or bottom row
+    let halo = match &fixed {
+        Some(cells) => extent(cells),
+        None => {
+            let (top_dx, top_dy) = spacing(0.5)?;
+            let (bottom_dx, bottom_dy) = spacing(input.height as f64 - 0.5)?;
+            extent(&options.kernel.cells(top_dx, top_dy)?).max(extent(&options.kernel.cells(bottom_dx, bottom_dy)?))
+        }
+    };
+
+    let data_type = options.output_type.unwrap_or(match options.statistic {
+        FocalStatistic::Mean | FocalStatistic::Sum if input_type == DataType::F64 => DataType::F64,
+        FocalStatistic::Mean | FocalStatistic::Sum => DataType::F32,
+        _ => input_type,
+    });
+    let nodata = match geo_info.nodata {
+        Some(nodata) if data_type == input_type => nodata,
+        _ => default_nodata(data_type),
+    };
+
+    let mut config = input.output_config(data_type, Some(nodata));
+    config.compression = options.compression;
+    let mut writers = vec![GeoTiffWriter::create(output, config)?];
+
+    map_blocks(std::slice::from_mut(input), halo as u64, &mut writers, |blocks| {
+        Ok(vec![focal_block(&blocks[0], fixed.as_deref(), &geo_info, options)?])
+    })?;
+
+    for writer in writers {
+        writer.finish()?;
+    }
+    Ok(data_type)
+}
+
+/// Helper: Ground pixel size of a row; metric kernels cannot fall back to cells
+fn pixel_spacing(geo_info: &GeoInfo, row: f64) -> Result<(f64, f64)> {
+    geo_info.ground_pixel_size(row)
+        .ok_or_else(|| Error::InvalidFormat("Metric kernel needs a pixel scale".to_string()))
+}
+
+/// Helper: Largest offset of any kernel cell
+fn extent(cells: &[Cell]) -> usize {
+    cells.iter().map(|(c, r, _)| c.unsigned_abs().max(r.unsigned_abs())).max().unwrap_or(0)
+}
+
+/// Helper: Computes the statistic for the interior of a block
+fn focal_block(block: &Block, fixed: Option<&[Cell]>, geo_info: &GeoInfo, options: &FocalOptions) -> Result<Vec<f64>> {
+    let mut out = Vec::with_capacity(block.width * block.height);
+    let mut values = Vec::new();
+
+    for row in 0..block.height as isize {
+        let resolved;
+        let cells = match fixed {
+            Some(cells) => cells,
+            None => {
+                let (dx, dy) = pixel_spacing(geo_info, block.y as f64 + row as f64 + 0.5)?;
+                resolved = options.kernel.cells(dx, dy)?;
+                &resolved
+            }
+        };
+
+        for col in 0..block.width as isize {
+            if !options.fill_nodata && block.get(col, row).is_nan() {
+                out.push(f64::NAN);
+                continue;
+            }
+            values.clear();
+            values.extend(cells.iter()
+                .map(|&(dc, dr, w)| (block.get(col + dc, row + dr), w))
+                .filter(|(v, _)| !v.is_nan()));
+            out.push(summarize(options.statistic, &values));
+        }
+    }
+    Ok(out)
+}
+
+/// Helper: Summarizes (value, weight) pairs; NaN when there are none
+fn summarize(statistic: FocalStatistic, values: &[(f64, f64)]) -> f64 {
+    if values.is_empty() {
+        return f64::NAN;
+    }
+    match statistic {
+        FocalStatistic::Sum => values.iter().map(|(v, w)| v * w).sum(),
+        FocalStatistic::Mean => {
+            let weight: f64 = values.iter().map(|(_, w)| w).sum();
+            values.iter().map(|(v, w)| v * w).sum::<f64>() / weight
+        }
+        FocalStatistic::Max => values.iter().map(|(v, _)| *v).fold(f64::NEG_INFINITY, f64::max),
+        FocalStatistic::Min => values.iter().map(|(v, _)| *v).fold(f64::INFINITY, f64::min),
+        FocalStatistic::Majority => {
+            let mut weights: HashMap<u64, (f64, f64)> = HashMap::new();
+            for &(v, w) in values {
+                weights.entry(v.to_bits()).or_insert((v, 0.0)).1 += w;
+            }
+            weights.into_values()
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.total_cmp(&a.0)))
+                .map_or(f64::NAN, |(v, _)| v)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+
+    fn run(spec: &FixtureSpec, value: impl Fn(u64, u64) -> f64, options: &FocalOptions) -> (Vec<f64>, u64, DataType) {
+        let file = write_geotiff(spec, value);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("focal.tif");
+        let mut raster = Raster::open(file.path()).unwrap();
+        let data_type = focal_statistics(&mut raster, &path, options).unwrap();
+
+        let (mut reader, ifd) = open_fixture(&path);
+        let window = reader.read_window_f64(&ifd, 0, 0, spec.width, spec.height).unwrap();
+        (window, spec.width, data_type)
+    }
+
+    #[test]
+    fn test_kernel_cells() {
+        assert_eq!(Kernel::Square(Radius::Pixels(2.0)).cells(1.0, 1.0).unwrap().len(), 25);
+        // Radius 2 circle: 5x5 without the 4 corners and the 8 cells next to them
+        assert_eq!(Kernel::Circle(Radius::Pixels(2.0)).cells(1.0, 1.0).unwrap().len(), 13);
+        // 25 m at 10 x 5 m pixels: 2 columns and 5 rows either side
+        let cells = Kernel::Square(Radius::Metres(25.0)).cells(10.0, 5.0).unwrap();
+        assert_eq!((cells.len(), extent(&cells)), (5 * 11, 5));
+
+        let weights = Kernel::Weights { width: 3, height: 1, weights: vec![1.0, 0.0, 2.0] };
+        assert_eq!(weights.cells(1.0, 1.0).unwrap(), vec![(-1, 0, 1.0), (1, 0, 2.0)]);
+        assert!(Kernel::Weights { width: 2, height: 1, weights: vec![1.0, 1.0] }.cells(1.0, 1.0).is_err());
+        assert!(Kernel::Circle(Radius::Pixels(-1.0)).cells(1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_max_across_tiles_and_nodata() {
+        let mut spec = FixtureSpec::new(40, 40);
+        spec.nodata = Some(0.0);
+        let depth = |x: u64, y: u64| match (x, y) {
+            (17, 17) => 9.0,
+            (30, 2) => 0.0,
+            _ => 1.0,
+        };
+
+        let options = FocalOptions::new(FocalStatistic::Max, Kernel::Square(Radius::Pixels(2.0)));
+        let (out, width, data_type) = run(&spec, depth, &options);
+        let at = |x: u64, y: u64| out[(y * width + x) as usize];
+        assert_eq!(data_type, DataType::U8);
+        // The peak in tile (1, 1) reaches cells in tile (0, 0)
+        assert_eq!((at(15, 15), at(19, 19), at(14, 15), at(20, 17)), (9.0, 9.0, 1.0, 1.0));
+        assert_eq!(at(30, 2), 0.0);
+
+        let filled = FocalOptions { fill_nodata: true, ..options };
+        let (out, _, _) = run(&spec, depth, &filled);
+        assert_eq!(out[(2 * width + 30) as usize], 1.0);
+    }
+
+    #[test]
+    fn test_mean_sum_majority() {
+        let spec = FixtureSpec::new(32, 32);
+        let class = |x: u64, _: u64| (x / 2 % 3) as f64;
+
+        let weights = Kernel::Weights { width: 3, height: 3, weights: vec![0.0, 1.0, 0.0, 1.0, 4.0, 1.0, 0.0, 1.0, 0.0] };
+        let (mean, width, data_type) = run(&spec, class, &FocalOptions::new(FocalStatistic::Mean, weights.clone()));
+        assert_eq!(data_type, DataType::F32);
+        // x = 3: left neighbour class 1, centre 1, right neighbour class 2
+        assert!((mean[(5 * width + 3) as usize] - (1.0 + 4.0 + 2.0 + 2.0) / 8.0).abs() < 1e-6);
+
+        let (sum, _, _) = run(&spec, class, &FocalOptions::new(FocalStatistic::Sum, Kernel::Square(Radius::Pixels(1.0))));
+        assert_eq!(sum[(5 * width + 3) as usize], 12.0);
+        // Corner cells only see 4 cells
+        assert_eq!(sum[0], 0.0);
+
+        let (majority, _, _) = run(&spec, class, &FocalOptions::new(FocalStatistic::Majority, Kernel::Square(Radius::Pixels(1.0))));
+        assert_eq!(majority[(5 * width + 3) as usize], 1.0);
+        assert_eq!(majority[(5 * width + 1) as usize], 0.0);
+        // Equal weights: the smallest value wins
+        assert_eq!(summarize(FocalStatistic::Majority, &[(2.0, 1.0), (1.0, 1.0)]), 1.0);
+    }
+
+    #[test]
+    fn test_metric_radius_in_degrees() {
+        // 0.001 degree pixels at 60N: ~55.6 m wide and ~111.2 m tall
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.epsg = 4326;
+        spec.origin = (10.0, 60.016);
+        spec.pixel_size = (0.001, 0.001);
+        let options = FocalOptions::new(FocalStatistic::Sum, Kernel::Square(Radius::Metres(120.0)));
+        let (sum, width, _) = run(&spec, |_, _| 1.0, &options);
+        // 2 columns and 1 row either side
+        assert_eq!(sum[(16 * width + 16) as usize], 15.0);
+
+        // Without a pixel scale a metric radius is an error, not a 1x1 kernel
+        let file = write_geotiff(&spec, |_, _| 1.0);
+        let mut geo_info = Raster::open(file.path()).unwrap().geo_info().clone();
+        geo_info.pixel_scale = None;
+        let block = Block { x: 0, y: 0, width: 1, height: 1, halo: 0, values: vec![1.0] };
+        assert!(focal_block(&block, None, &geo_info, &options).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! Raster analysis operations
+
+pub mod algebra;
+pub mod buffer;
+pub mod contour;
+pub mod cost;
+pub mod coverage;
+pub mod focal;
+pub mod polygonize;
+pub mod profile;
+pub mod raster;
+pub mod rasterize;
+pub mod reclass;
+pub mod stats;
+pub mod terrain;
+pub mod visibility;
+pub mod zonal;
+
+pub use algebra::{AlgebraOptions, Expression, MapAlgebra};
+pub use buffer::{sample_buffers, BufferOptions};
+pub use contour::{contour_bands, contour_lines, ContourLevels, ContourOptions, MAX_CONTOUR_LEVELS};
+pub use cost::{cost_distance, CostMethod, CostOptions, CostSurface};
+pub use focal::{focal_statistics, FocalOptions, FocalStatistic, Kernel, Radius};
+pub use polygonize::{polygonize, Connectivity, PolygonizeOptions};
+pub use profile::{densify_line, sample_profile, ProfileSample};
+pub use raster::{map_blocks, Block, Raster};
+pub use rasterize::{rasterize, BurnValue, Grid, MergeStrategy, RasterizeOptions};
+pub use reclass::{reclassify_raster, sample_reclassified, ReclassOptions, Reclassifier, Unmatched};
+pub use stats::{compute_statistics, statistics_metadata, write_statistics, BandStatistics, Histogram, StatisticsOptions};
+pub use terrain::{derive_terrain, SlopeUnits, TerrainOptions, TerrainProduct};
+pub use visibility::{line_of_sight, viewshed, LineOfSight, ProfilePoint, VisibilityOptions};
+pub use zonal::{zonal_statistics, ZonalOptions, ZonalStats};
+
+/// Returns the `p`-th percentile (0-100) of sorted values by linear interpolation
+pub fn percentile_sorted(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+
+    let rank = (p.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
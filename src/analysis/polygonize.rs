@@ -0,0 +1,578 @@
+//! Tracing connected regions of equal value into polygons
+//!
+//! The raster is scanned one tile row at a time. Cells are labelled with a
+//! union-find as rows arrive, so a region continuing across tile boundaries
+//! ends up under one label. Boundaries are recorded as unit cell edges,
+//! oriented with the region on their right. Once a row holds no cell of a
+//! region, the region is complete: its edges are assembled into rings and
+//! dropped, and the label table is compacted to the labels of that row.
+//! Memory therefore grows with the raster width and the boundaries of the
+//! regions still open at the current row, plus the finished features; a
+//! region spanning the whole raster keeps its edges until the last row.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use serde_json::Value;
+use crate::error::{Error, Result};
+use crate::projection::Coordinate;
+use crate::vector::geometry::{ring_area, ring_contains};
+use crate::vector::{Feature, Geometry, Polygon};
+use super::raster::Raster;
+
+/// Label of NoData cells
+const NO_LABEL: usize = usize::MAX;
+/// Band height for rasters without tiles
+const DEFAULT_BAND_ROWS: u64 = 256;
+
+/// Which neighbours join cells into one region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Edge neighbours only
+    Four,
+    /// Edge and corner neighbours
+    Eight,
+}
+
+/// Polygonize parameters
+#[derive(Debug, Clone)]
+pub struct PolygonizeOptions {
+    pub connectivity: Connectivity,
+    /// Output CRS; defaults to the raster's
+    pub target_epsg: Option<u16>,
+    /// Name of the property holding the region value
+    pub property: String,
+}
+
+impl Default for PolygonizeOptions {
+    fn default() -> Self {
+        Self {
+            connectivity: Connectivity::Four,
+            target_epsg: None,
+            property: "value".to_string(),
+        }
+    }
+}
+
+/// Pixel corner coordinates
+type Corner = (i64, i64);
+/// Simplified ring in pixel coordinates
+type PixelRing = Vec<(f64, f64)>;
+
+/// Traces every region of equal value into a feature
+///
+/// Features come in scan order of each region's first cell. A region is a
+/// Polygon, or a MultiPolygon when 8-connected parts only touch at corners.
+/// Exterior rings are counter-clockwise and holes clockwise. NoData cells
+/// belong to no region.
+pub fn polygonize(raster: &mut Raster, options: &PolygonizeOptions) -> Result<Vec<Feature>> {
+    let transform = raster.geo_info().affine_transform()
+        .ok_or_else(|| Error::InvalidFormat("Missing geotransform".to_string()))?;
+    let projection = match (raster.geo_info().epsg_code, options.target_epsg) {
+        (Some(from), Some(to)) => Some((from, to)),
+        (None, Some(to)) => return Err(Error::Projection(format!("Cannot reproject to EPSG:{} without a source CRS", to))),
+        _ => None,
+    };
+    let band_rows = raster.ifd().tile_dimensions().map_or(DEFAULT_BAND_ROWS, |t| t.height);
+    let (width, height) = (raster.width, raster.height);
+
+    let to_world = |(x, y): (f64, f64)| Coordinate::new(
+        transform[0] + x * transform[1] + y * transform[2],
+        transform[3] + x * transform[4] + y * transform[5],
+    );
+    let mut features = Vec::new();
+    let mut emit = |regions: Vec<Region>| -> Result<()> {
+        for region in regions {
+            let polygons = assemble_polygons(&region.edges)
+                .into_iter()
+                .map(|(exterior, holes)| {
+                    let ring = |r: PixelRing| r.into_iter().map(to_world).collect::<Vec<_>>();
+                    Polygon::with_holes(ring(exterior), holes.into_iter().map(ring).collect())
+                })
+                .collect::<Vec<_>>();
+
+            let mut geometry = match <[Polygon; 1]>::try_from(polygons) {
+                Ok([polygon]) => Geometry::Polygon(polygon),
+                Err(polygons) => Geometry::MultiPolygon(polygons),
+            };
+            if let Some((from, to)) = projection {
+                geometry = geometry.transform(from, to)?;
+            }
+            orient(&mut geometry);
+
+            let mut feature = Feature::new(geometry);
+            feature.properties.insert(options.property.clone(), json_number(region.value));
+            features.push((region.first, feature));
+        }
+        Ok(())
+    };
+
+    let mut regions = Regions::default();
+    let mut edges: Vec<(usize, Corner, Corner)> = Vec::new();
+    let mut prev_values = vec![f64::NAN; width as usize];
+    let mut prev_labels = vec![NO_LABEL; width as usize];
+    let mut row_labels = vec![NO_LABEL; width as usize];
+
+    let mut y = 0;
+    while y < height {
+        let rows = band_rows.min(height - y);
+        let block = raster.read_block(0, y, width, rows, 0)?;
+        for (r, row) in block.values.chunks_exact(width as usize).enumerate() {
+            let r = (y + r as u64) as i64;
+            label_row(row, &prev_values, &prev_labels, &mut row_labels, &mut regions.labels, options.connectivity);
+            row_edges(r, row, &prev_values, &prev_labels, &row_labels, &mut edges);
+            prev_values.copy_from_slice(row);
+            std::mem::swap(&mut prev_labels, &mut row_labels);
+            emit(regions.finish_row(&mut prev_labels, &mut edges))?;
+        }
+        y += rows;
+    }
+    // Bottom edges of the last row close every remaining region
+    let nan_row = vec![f64::NAN; width as usize];
+    row_labels.fill(NO_LABEL);
+    row_edges(height as i64, &nan_row, &prev_values, &prev_labels, &row_labels, &mut edges);
+    emit(regions.finish_row(&mut row_labels, &mut edges))?;
+
+    features.sort_by_key(|&(first, _)| first);
+    Ok(features.into_iter().map(|(_, feature)| feature).collect())
+}
+
+/// Helper: Union-find over provisional region labels
+///
+/// The root of a set carries the smallest `first`, the creation order of
+/// the region's first cell in scan order.
+#[derive(Default)]
+struct Labels {
+    parent: Vec<usize>,
+    value: Vec<f64>,
+    first: Vec<u64>,
+    created: u64,
+}
+
+impl Labels {
+    fn add(&mut self, value: f64) -> usize {
+        self.created += 1;
+        self.insert(value, self.created - 1)
+    }
+
+    fn insert(&mut self, value: f64, first: u64) -> usize {
+        self.parent.push(self.parent.len());
+        self.value.push(value);
+        self.first.push(first);
+        self.parent.len() - 1
+    }
+
+    fn find(&mut self, mut label: usize) -> usize {
+        while self.parent[label] != label {
+            self.parent[label] = self.parent[self.parent[label]];
+            label = self.parent[label];
+        }
+        label
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            let (root, child) = if self.first[a] <= self.first[b] { (a, b) } else { (b, a) };
+            self.parent[child] = root;
+        }
+    }
+}
+
+/// Helper: A region whose cells have all been seen
+struct Region {
+    first: u64,
+    value: f64,
+    edges: Vec<(Corner, Corner)>,
+}
+
+/// Helper: Labels and boundary edges of the regions still open
+#[derive(Default)]
+struct Regions {
+    labels: Labels,
+    open: HashMap<usize, Vec<(Corner, Corner)>>,
+}
+
+impl Regions {
+    /// Files a row's edges under their regions and returns the regions
+    /// without a cell in `row_labels`, which are relabelled in a fresh table
+    fn finish_row(&mut self, row_labels: &mut [usize], edges: &mut Vec<(usize, Corner, Corner)>) -> Vec<Region> {
+        let mut labels = Labels { created: self.labels.created, ..Labels::default() };
+        let mut renamed: HashMap<usize, usize> = HashMap::new();
+        for label in row_labels.iter_mut().filter(|l| **l != NO_LABEL) {
+            let root = self.labels.find(*label);
+            *label = *renamed.entry(root)
+                .or_insert_with(|| labels.insert(self.labels.value[root], self.labels.first[root]));
+        }
+
+        let mut open: HashMap<usize, Vec<(Corner, Corner)>> = HashMap::with_capacity(renamed.len());
+        let mut done: BTreeMap<usize, Vec<(Corner, Corner)>> = BTreeMap::new();
+        // Edge lists are moved, and merged by appending the shorter one
+        for (label, mut region_edges) in self.open.drain() {
+            let target = edge_list(&renamed, &mut open, &mut done, self.labels.find(label));
+            if target.len() < region_edges.len() {
+                std::mem::swap(target, &mut region_edges);
+            }
+            target.append(&mut region_edges);
+        }
+        for (label, from, to) in edges.drain(..) {
+            edge_list(&renamed, &mut open, &mut done, self.labels.find(label)).push((from, to));
+        }
+
+        let finished = done.into_iter()
+            .map(|(root, edges)| Region { first: self.labels.first[root], value: self.labels.value[root], edges })
+            .collect();
+        self.labels = labels;
+        self.open = open;
+        finished
+    }
+}
+
+/// Helper: Returns the edge list a region's root files into, under its
+/// new label if it is still open
+fn edge_list<'a>(
+    renamed: &HashMap<usize, usize>,
+    open: &'a mut HashMap<usize, Vec<(Corner, Corner)>>,
+    done: &'a mut BTreeMap<usize, Vec<(Corner, Corner)>>,
+    root: usize,
+) -> &'a mut Vec<(Corner, Corner)> {
+    match renamed.get(&root) {
+        Some(&next) => open.entry(next).or_default(),
+        None => done.entry(root).or_default(),
+    }
+}
+
+/// Helper: Labels one row against the previous one
+fn label_row(
+    row: &[f64],
+    prev_values: &[f64],
+    prev_labels: &[usize],
+    row_labels: &mut [usize],
+    labels: &mut Labels,
+    connectivity: Connectivity,
+) {
+    for c in 0..row.len() {
+        let value = row[c];
+        if value.is_nan() {
+            row_labels[c] = NO_LABEL;
+            continue;
+        }
+        let left = c > 0 && row[c - 1] == value;
+        let up = prev_values[c] == value;
+        let label = if left {
+            row_labels[c - 1]
+        } else if up {
+            prev_labels[c]
+        } else {
+            labels.add(value)
+        };
+        if up {
+            labels.union(label, prev_labels[c]);
+        }
+        if connectivity == Connectivity::Eight {
+            for n in [c.wrapping_sub(1), c + 1] {
+                if n < row.len() && prev_values[n] == value {
+                    labels.union(label, prev_labels[n]);
+                }
+            }
+        }
+        row_labels[c] = label;
+    }
+}
+
+/// Helper: Records the boundary edges above row `r` and between its cells
+///
+/// Edges run clockwise around their cell in pixel space (y down), keeping
+/// the region on the right.
+fn row_edges(
+    r: i64,
+    row: &[f64],
+    prev_values: &[f64],
+    prev_labels: &[usize],
+    row_labels: &[usize],
+    edges: &mut Vec<(usize, Corner, Corner)>,
+) {
+    for c in 0..row.len() {
+        if prev_values[c] == row[c] {
+            continue;
+        }
+        let x = c as i64;
+        if prev_labels[c] != NO_LABEL {
+            edges.push((prev_labels[c], (x + 1, r), (x, r)));
+        }
+        if row_labels[c] != NO_LABEL {
+            edges.push((row_labels[c], (x, r), (x + 1, r)));
+        }
+    }
+
+    for c in 0..=row.len() {
+        let left = c.checked_sub(1).map_or(f64::NAN, |l| row[l]);
+        let right = row.get(c).copied().unwrap_or(f64::NAN);
+        if left == right {
+            continue;
+        }
+        let x = c as i64;
+        if c > 0 && row_labels[c - 1] != NO_LABEL {
+            edges.push((row_labels[c - 1], (x, r), (x, r + 1)));
+        }
+        if c < row.len() && row_labels[c] != NO_LABEL {
+            edges.push((row_labels[c], (x, r + 1), (x, r)));
+        }
+    }
+}
+
+/// Helper: Links a region's edges into rings and groups holes with exteriors
+///
+/// Where two cells of the region meet only at a corner, the ring turns
+/// right and stays with the current cell, so exteriors never touch
+/// themselves; holes that still do are split into simple rings.
+/// Returns (exterior, holes) pairs in pixel coordinates.
+fn assemble_polygons(edges: &[(Corner, Corner)]) -> Vec<(PixelRing, Vec<PixelRing>)> {
+    let mut outgoing: HashMap<Corner, Vec<Corner>> = HashMap::with_capacity(edges.len());
+    for &(from, to) in edges {
+        outgoing.entry(from).or_default().push(to);
+    }
+
+    let mut exteriors = Vec::new();
+    let mut holes = Vec::new();
+    for &(start, _) in edges {
+        let Some(first) = outgoing.get_mut(&start).and_then(Vec::pop) else { continue };
+
+        let mut ring = vec![start];
+        let (mut prev, mut current) = (start, first);
+        while current != start {
+            ring.push(current);
+            let direction = (current.0 - prev.0, current.1 - prev.1);
+            let right = (-direction.1, direction.0);
+            let Some(candidates) = outgoing.get_mut(&current).filter(|c| !c.is_empty()) else { break };
+            let pick = candidates.iter()
+                .position(|&n| (n.0 - current.0, n.1 - current.1) == right)
+                .unwrap_or(0);
+            prev = current;
+            current = candidates.swap_remove(pick);
+        }
+
+        for ring in split_touching(ring) {
+            let ring = simplify(&ring);
+            let area = pixel_ring_area(&ring);
+            if area > 0.0 {
+                exteriors.push((ring, area));
+            } else if area < 0.0 {
+                holes.push(ring);
+            }
+        }
+    }
+
+    let mut polygons: Vec<_> = exteriors.iter().map(|(ring, _)| (ring.clone(), Vec::new())).collect();
+    let coords: Vec<Vec<Coordinate>> = exteriors.iter()
+        .map(|(ring, _)| ring.iter().map(|&(x, y)| Coordinate::new(x, y)).collect())
+        .collect();
+    for hole in holes {
+        // Centre of the cell left of the first edge lies strictly inside the hole
+        let (a, b) = (hole[0], hole[1]);
+        let (dx, dy) = ((b.0 - a.0).signum(), (b.1 - a.1).signum());
+        let probe = ((a.0 + b.0) / 2.0 + dy * 0.5, (a.1 + b.1) / 2.0 - dx * 0.5);
+        let owner = (0..exteriors.len())
+            .filter(|&i| ring_contains(&coords[i], probe.0, probe.1))
+            .min_by(|&i, &j| exteriors[i].1.total_cmp(&exteriors[j].1));
+        if let Some(i) = owner {
+            polygons[i].1.push(hole);
+        }
+    }
+    polygons
+}
+
+/// Helper: Splits a ring passing a corner more than once into simple loops
+pub(crate) fn split_touching<T: Copy + Eq + Hash>(ring: Vec<T>) -> Vec<Vec<T>> {
+    let mut loops = Vec::new();
+    let mut path: Vec<T> = Vec::with_capacity(ring.len());
+    let mut seen: HashMap<T, usize> = HashMap::new();
+    for corner in ring {
+        match seen.get(&corner) {
+            Some(&i) => {
+                let mut sub = vec![corner];
+                sub.extend(path.drain(i + 1..));
+                for c in &sub[1..] {
+                    seen.remove(c);
+                }
+                loops.push(sub);
+            }
+            None => {
+                seen.insert(corner, path.len());
+                path.push(corner);
+            }
+        }
+    }
+    loops.push(path);
+    loops
+}
+
+/// Helper: Drops vertices between collinear edges
+fn simplify(ring: &[Corner]) -> PixelRing {
+    let n = ring.len();
+    (0..n)
+        .filter(|&i| {
+            let (p, c, q) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+            ((c.0 - p.0).signum(), (c.1 - p.1).signum()) != ((q.0 - c.0).signum(), (q.1 - c.1).signum())
+        })
+        .map(|i| (ring[i].0 as f64, ring[i].1 as f64))
+        .collect()
+}
+
+/// Helper: Shoelace area in pixel space; positive for exteriors
+fn pixel_ring_area(ring: &[(f64, f64)]) -> f64 {
+    let n = ring.len();
+    (0..n).map(|i| {
+        let (a, b) = (ring[i], ring[(i + 1) % n]);
+        a.0 * b.1 - b.0 * a.1
+    }).sum::<f64>() / 2.0
+}
+
+/// Helper: Makes exteriors counter-clockwise and holes clockwise, closing every ring
+pub(crate) fn orient(geometry: &mut Geometry) {
+    let fix = |ring: &mut Vec<Coordinate>, counter_clockwise: bool| {
+        if (ring_area(ring) > 0.0) != counter_clockwise {
+            ring.reverse();
+        }
+        if let Some(&first) = ring.first() {
+            ring.push(first);
+        }
+    };
+    let polygons: Vec<&mut Polygon> = match geometry {
+        Geometry::Polygon(p) => vec![p],
+        Geometry::MultiPolygon(ps) => ps.iter_mut().collect(),
+        _ => Vec::new(),
+    };
+    for polygon in polygons {
+        fix(&mut polygon.exterior, true);
+        for hole in &mut polygon.interiors {
+            fix(hole, false);
+        }
+    }
+}
+
+/// Helper: Integral values become JSON integers
+pub(crate) fn json_number(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < 9e15 {
+        Value::from(value as i64)
+    } else {
+        Value::from(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+
+    fn polygons(feature: &Feature) -> Vec<&Polygon> {
+        feature.geometry.as_ref().unwrap().polygons()
+    }
+
+    #[test]
+    fn test_regions_across_tiles() {
+        // Square of 1s over four tiles with a 2-valued hole, on a background of 0
+        let file = write_geotiff(&FixtureSpec::new(32, 32), |x, y| {
+            match (x, y) {
+                (14..=17, 14..=17) => 2.0,
+                (10..=21, 10..=21) => 1.0,
+                _ => 0.0,
+            }
+        });
+        let mut raster = Raster::open(file.path()).unwrap();
+        let features = polygonize(&mut raster, &PolygonizeOptions::default()).unwrap();
+        assert_eq!(features.len(), 3);
+
+        let values: Vec<_> = features.iter().map(|f| f.property_f64("value").unwrap()).collect();
+        assert_eq!(values, vec![0.0, 1.0, 2.0]);
+
+        let background = polygons(&features[0]);
+        assert_eq!(background.len(), 1);
+        assert_eq!(background[0].interiors.len(), 1);
+        assert_eq!(background[0].area(), 1024.0 - 144.0);
+
+        let square = polygons(&features[1])[0];
+        assert_eq!(square.exterior.len(), 5);
+        assert_eq!(square.area(), 128.0);
+        assert!(ring_area(&square.exterior) > 0.0);
+        assert!(ring_area(&square.interiors[0]) < 0.0);
+        // World y grows upwards from the origin at (0, 32)
+        assert!(square.exterior.contains(&Coordinate::new(10.0, 22.0)));
+        assert!(square.exterior.contains(&Coordinate::new(22.0, 10.0)));
+
+        assert_eq!(polygons(&features[2])[0].area(), 16.0);
+
+        let reprojected = PolygonizeOptions { target_epsg: Some(4326), ..Default::default() };
+        let features = polygonize(&mut raster, &reprojected).unwrap();
+        let square = polygons(&features[1])[0];
+        assert!(square.exterior.iter().all(|c| c.x.abs() < 0.001 && c.y.abs() < 0.001));
+        assert!(ring_area(&square.exterior) > 0.0);
+    }
+
+    #[test]
+    fn test_reprojection_needs_source_crs() {
+        let mut spec = FixtureSpec::new(8, 8);
+        spec.epsg = 0;
+        let file = write_geotiff(&spec, |x, _| (x / 4) as f64);
+        let mut raster = Raster::open(file.path()).unwrap();
+        assert_eq!(polygonize(&mut raster, &PolygonizeOptions::default()).unwrap().len(), 2);
+
+        let reprojected = PolygonizeOptions { target_epsg: Some(4326), ..Default::default() };
+        assert!(matches!(polygonize(&mut raster, &reprojected), Err(Error::Projection(_))));
+    }
+
+    #[test]
+    fn test_regions_close_as_rows_complete() {
+        // A 1-cell region above a 2-valued region that runs on below it
+        let grid = [[1.0, 2.0], [2.0, 2.0], [2.0, 2.0]];
+        let mut regions = Regions::default();
+        let mut edges = Vec::new();
+        let mut prev_values = vec![f64::NAN; 2];
+        let mut prev_labels = vec![NO_LABEL; 2];
+        let mut row_labels = vec![NO_LABEL; 2];
+        let mut finished = Vec::new();
+        for (r, row) in grid.iter().enumerate() {
+            label_row(row, &prev_values, &prev_labels, &mut row_labels, &mut regions.labels, Connectivity::Four);
+            row_edges(r as i64, row, &prev_values, &prev_labels, &row_labels, &mut edges);
+            prev_values.copy_from_slice(row);
+            std::mem::swap(&mut prev_labels, &mut row_labels);
+            finished.push(regions.finish_row(&mut prev_labels, &mut edges));
+            // Only the labels of the current row are kept
+            assert!(regions.labels.parent.len() <= 2);
+        }
+
+        assert!(finished[0].is_empty());
+        assert_eq!(finished[1].len(), 1);
+        assert_eq!((finished[1][0].first, finished[1][0].value, finished[1][0].edges.len()), (0, 1.0, 4));
+        assert!(finished[2].is_empty());
+        assert_eq!(regions.open.len(), 1);
+    }
+
+    #[test]
+    fn test_connectivity_and_nodata() {
+        // Two diagonal 1-cells, a NoData column and a 0 background
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.nodata = Some(255.0);
+        let file = write_geotiff(&spec, |x, y| {
+            match (x, y) {
+                (3, 3) | (4, 4) => 1.0,
+                (31, _) => 255.0,
+                _ => 0.0,
+            }
+        });
+        let mut raster = Raster::open(file.path()).unwrap();
+
+        let four = polygonize(&mut raster, &PolygonizeOptions::default()).unwrap();
+        let ones: Vec<_> = four.iter().filter(|f| f.property_f64("value") == Some(1.0)).collect();
+        assert_eq!(ones.len(), 2);
+        assert_eq!(four.len(), 3);
+        // The diagonal cells make two holes touching at a corner
+        let background = polygons(&four[0]);
+        assert_eq!(background[0].interiors.len(), 2);
+        assert_eq!(background[0].area(), 31.0 * 32.0 - 2.0);
+
+        let options = PolygonizeOptions { connectivity: Connectivity::Eight, ..Default::default() };
+        let eight = polygonize(&mut raster, &options).unwrap();
+        assert_eq!(eight.len(), 2);
+        assert!(matches!(eight[1].geometry, Some(Geometry::MultiPolygon(ref ps)) if ps.len() == 2));
+    }
+}
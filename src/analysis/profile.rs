@@ -0,0 +1,147 @@
+//! Value profiles along polylines
+//!
+//! Lines are measured with great-circle distances, so the spacing is in
+//! metres whatever the input CRS. Sample positions are interpolated
+//! linearly in the input CRS between vertices.
+
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, TiffReader, IFD};
+use crate::projection::{Coordinate, Transformer};
+
+/// Largest number of samples a single profile may produce
+pub const MAX_PROFILE_SAMPLES: usize = 1_000_000;
+
+/// One profile sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileSample {
+    /// Distance along the line in metres
+    pub distance: f64,
+    /// Position in the input CRS
+    pub coordinate: Coordinate,
+    /// Raster value; `None` outside the raster or on NoData
+    pub value: Option<f64>,
+}
+
+/// Places points every `spacing` metres along a line given in `epsg`
+///
+/// Returns (distance, coordinate) pairs starting at the first vertex; the
+/// last vertex is always included.
+pub fn densify_line(line: &[Coordinate], epsg: u16, spacing: f64) -> Result<Vec<(f64, Coordinate)>> {
+    if line.len() < 2 {
+        return Err(Error::InvalidFormat("A profile line needs at least two vertices".to_string()));
+    }
+    if !spacing.is_finite() || spacing <= 0.0 {
+        return Err(Error::InvalidFormat("Profile spacing must be positive".to_string()));
+    }
+
+    let lonlat = if epsg == 4326 {
+        line.to_vec()
+    } else {
+        let transformer = Transformer::new(epsg, 4326)?;
+        line.iter().map(|&c| transformer.transform(c)).collect::<Result<Vec<_>>>()?
+    };
+    let lengths: Vec<f64> = lonlat.windows(2).map(|w| w[0].haversine_distance(&w[1])).collect();
+    let total: f64 = lengths.iter().sum();
+    if total / spacing > MAX_PROFILE_SAMPLES as f64 {
+        return Err(Error::InvalidFormat(format!(
+            "Profile of {:.0} m at {} m spacing exceeds {} samples", total, spacing, MAX_PROFILE_SAMPLES
+        )));
+    }
+
+    let mut points = Vec::with_capacity((total / spacing) as usize + 2);
+    let mut start = 0.0;
+    let mut next = 0.0;
+    for (segment, &length) in line.windows(2).zip(&lengths) {
+        while next < start + length {
+            let t = (next - start) / length;
+            let (a, b) = (segment[0], segment[1]);
+            points.push((next, Coordinate::new(a.x + t * (b.x - a.x), a.y + t * (b.y - a.y))));
+            next += spacing;
+        }
+        start += length;
+    }
+    let last = line[line.len() - 1];
+    points.push((total, Coordinate::new(last.x, last.y)));
+    Ok(points)
+}
+
+/// Samples a raster every `spacing` metres along a line given in `epsg`
+///
+/// Values are read in one batch through the tile-grouped reader.
+pub fn sample_profile(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    line: &[Coordinate],
+    epsg: u16,
+    spacing: f64,
+) -> Result<Vec<ProfileSample>> {
+    let geo_info = GeoInfo::from_ifd(ifd, reader)?
+        .ok_or_else(|| Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+    let dims = ifd.dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+
+    let points = densify_line(line, epsg, spacing)?;
+    let coords: Vec<Coordinate> = points.iter().map(|&(_, c)| c).collect();
+    let pixels = geo_info.transform_crs_to_pixel_batch(&coords, epsg)?;
+
+    let inside: Vec<usize> = pixels.iter().enumerate()
+        .filter(|(_, (x, y))| *x >= 0.0 && *y >= 0.0 && *x < dims.width as f64 && *y < dims.height as f64)
+        .map(|(i, _)| i)
+        .collect();
+    let cells: Vec<(u64, u64)> = inside.iter().map(|&i| (pixels[i].0 as u64, pixels[i].1 as u64)).collect();
+    let values = reader.read_pixels_batch_f64(ifd, &cells)?;
+
+    let mut samples: Vec<ProfileSample> = points.into_iter()
+        .map(|(distance, coordinate)| ProfileSample { distance, coordinate, value: None })
+        .collect();
+    for (index, value) in inside.into_iter().zip(values) {
+        samples[index].value = (!geo_info.is_nodata(value)).then_some(value);
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+
+    #[test]
+    fn test_densify_line() {
+        // Two legs along the equator of about 1113 m and 556 m
+        let line = [Coordinate::new(0.0, 0.0), Coordinate::new(0.01, 0.0), Coordinate::new(0.01, 0.005)];
+        let points = densify_line(&line, 4326, 250.0).unwrap();
+
+        let total = points.last().unwrap().0;
+        assert!((total - 1667.9).abs() < 1.0, "{}", total);
+        assert_eq!(points.len(), 8);
+        assert_eq!(points[4].0, 1000.0);
+        assert!((points[4].1.x - 0.0089932).abs() < 1e-6);
+        // 1250 m lies 138 m into the second leg
+        assert!((points[5].1.y - 0.0012415).abs() < 1e-6);
+        assert_eq!(points[7].1, line[2]);
+
+        assert!(densify_line(&line[..1], 4326, 10.0).is_err());
+        assert!(densify_line(&line, 4326, 0.0).is_err());
+        assert!(densify_line(&line, 4326, 1e-6).is_err());
+    }
+
+    #[test]
+    fn test_sample_profile() {
+        // 100 m pixels near the equator; value = column
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.pixel_size = (100.0, 100.0);
+        spec.origin = (0.0, 3200.0);
+        let file = write_geotiff(&spec, |x, _| x as f64);
+        let (mut reader, ifd) = open_fixture(file.path());
+
+        // Along row 16 from x = 50 m to beyond the east edge
+        let line = [Coordinate::new(50.0, 1650.0), Coordinate::new(3500.0, 1650.0)];
+        let samples = sample_profile(&mut reader, &ifd, &line, 3857, 500.0).unwrap();
+
+        assert_eq!(samples[0].value, Some(0.0));
+        assert_eq!(samples[1].distance, 500.0);
+        assert_eq!(samples[1].value, Some(5.0));
+        assert_eq!(samples.last().unwrap().value, None);
+        assert!(samples.windows(2).all(|w| w[0].distance < w[1].distance));
+    }
+}
@@ -0,0 +1,258 @@
+//! Block-wise raster processing
+//!
+//! [`map_blocks`] walks the output tile grid one tile row at a time. Each
+//! input is read once per row as a band padded with a halo of neighbouring
+//! pixels, so windowed operations see across tile boundaries; tiles of the
+//! row are then computed in parallel and written in order.
+
+use std::borrow::BorrowMut;
+use std::path::Path;
+use rayon::prelude::*;
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, GeoReference, GeoTiffWriter, TiffReader, WriterConfig, IFD};
+use crate::types::DataType;
+
+/// A single-band raster opened for block reads
+pub struct Raster {
+    reader: TiffReader,
+    ifd: IFD,
+    geo_info: GeoInfo,
+    pub width: u64,
+    pub height: u64,
+}
+
+impl Raster {
+    /// Opens the main image of a GeoTIFF
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut reader = TiffReader::open(path)?;
+        let ifd = reader.ifd(0)?
+            .ok_or_else(|| Error::InvalidFormat("No image in file".to_string()))?;
+        Self::from_reader(reader, ifd)
+    }
+
+    /// Wraps an already opened reader and IFD
+    pub fn from_reader(mut reader: TiffReader, ifd: IFD) -> Result<Self> {
+        let geo_info = GeoInfo::from_ifd(&ifd, &mut reader)?
+            .ok_or_else(|| Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+        let dims = ifd.dimensions()
+            .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+        Ok(Self { reader, ifd, geo_info, width: dims.width, height: dims.height })
+    }
+
+    /// Returns the georeferencing
+    pub fn geo_info(&self) -> &GeoInfo {
+        &self.geo_info
+    }
+
+    /// Returns the image IFD
+    pub fn ifd(&self) -> &IFD {
+        &self.ifd
+    }
+
+    /// Returns the underlying reader
+    pub fn reader_mut(&mut self) -> &mut TiffReader {
+        &mut self.reader
+    }
+
+    /// Returns the pixel data type
+    pub fn data_type(&self) -> Result<DataType> {
+        self.ifd.data_type()
+            .ok_or_else(|| Error::Unsupported("Unknown pixel data type".to_string()))
+    }
+
+    /// Returns whether both rasters share size, CRS and pixel grid, including
+    /// any rotation or shear
+    pub fn is_aligned_with(&self, other: &Raster) -> bool {
+        let close = |a: f64, b: f64, scale: f64| (a - b).abs() <= scale.abs() * 1e-6;
+        match (self.geo_info.affine_transform(), other.geo_info.affine_transform()) {
+            (Some(a), Some(b)) => {
+                self.width == other.width
+                    && self.height == other.height
+                    && self.geo_info.epsg_code == other.geo_info.epsg_code
+                    && close(a[0], b[0], a[1]) && close(a[3], b[3], a[5])
+                    && close(a[1], b[1], a[1]) && close(a[5], b[5], a[5])
+                    && close(a[2], b[2], a[5]) && close(a[4], b[4], a[1])
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns a writer configuration for an output on the same grid
+    ///
+    /// Tiles follow the input when they are valid output tiles (multiples
+    /// of 16), otherwise 256x256.
+    pub fn output_config(&self, data_type: DataType, nodata: Option<f64>) -> WriterConfig {
+        let mut config = WriterConfig::new(self.width, self.height, data_type);
+        if let Some(tile) = self.ifd.tile_dimensions() {
+            if tile.width.is_multiple_of(16) && tile.height.is_multiple_of(16) {
+                config.tile_width = tile.width;
+                config.tile_height = tile.height;
+            }
+        }
+        config.nodata = nodata;
+        config.georef = GeoReference::from_geo_info(&self.geo_info);
+        config
+    }
+
+    /// Reads a window plus `halo` pixels on every side
+    ///
+    /// NoData and pixels outside the image become NaN.
+    pub fn read_block(&mut self, x: u64, y: u64, width: u64, height: u64, halo: u64) -> Result<Block> {
+        let padded_w = (width + 2 * halo) as usize;
+        let padded_h = (height + 2 * halo) as usize;
+        let mut values = vec![f64::NAN; padded_w * padded_h];
+
+        // Clip the padded window to the image
+        let x0 = x.saturating_sub(halo);
+        let y0 = y.saturating_sub(halo);
+        let x1 = (x + width + halo).min(self.width);
+        let y1 = (y + height + halo).min(self.height);
+
+        if x0 < x1 && y0 < y1 {
+            let window = self.reader.read_window_f64(&self.ifd, x0, y0, x1 - x0, y1 - y0)?;
+            let read_w = (x1 - x0) as usize;
+            let col_shift = (x0 + halo - x) as usize;
+            let row_shift = (y0 + halo - y) as usize;
+
+            for (r, row) in window.chunks_exact(read_w).enumerate() {
+                let start = (r + row_shift) * padded_w + col_shift;
+                for (dst, &value) in values[start..start + read_w].iter_mut().zip(row) {
+                    if !self.geo_info.is_nodata(value) {
+                        *dst = value;
+                    }
+                }
+            }
+        }
+
+        Ok(Block {
+            x,
+            y,
+            width: width as usize,
+            height: height as usize,
+            halo: halo as usize,
+            values,
+        })
+    }
+}
+
+/// Pixel values of a window with a halo; NaN marks missing values
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Image column of the first interior pixel
+    pub x: u64,
+    /// Image row of the first interior pixel
+    pub y: u64,
+    pub width: usize,
+    pub height: usize,
+    pub halo: usize,
+    /// Row-major values of the padded window
+    pub values: Vec<f64>,
+}
+
+impl Block {
+    /// Returns the value at an interior-relative position; the halo is at
+    /// negative positions and at `width`/`height` onwards
+    pub fn get(&self, col: isize, row: isize) -> f64 {
+        let padded_w = self.width + 2 * self.halo;
+        let c = col + self.halo as isize;
+        let r = row + self.halo as isize;
+        if c < 0 || r < 0 || c as usize >= padded_w || r as usize >= self.height + 2 * self.halo {
+            return f64::NAN;
+        }
+        self.values[r as usize * padded_w + c as usize]
+    }
+
+    /// Copies out a sub-window (interior-relative) keeping the same halo
+    pub fn window(&self, col: usize, row: usize, width: usize, height: usize) -> Block {
+        let mut values = Vec::with_capacity((width + 2 * self.halo) * (height + 2 * self.halo));
+        for r in 0..height + 2 * self.halo {
+            for c in 0..width + 2 * self.halo {
+                let src_col = (col + c) as isize - self.halo as isize;
+                let src_row = (row + r) as isize - self.halo as isize;
+                values.push(self.get(src_col, src_row));
+            }
+        }
+        Block {
+            x: self.x + col as u64,
+            y: self.y + row as u64,
+            width,
+            height,
+            halo: self.halo,
+            values,
+        }
+    }
+
+    /// Returns the interior values, row-major
+    pub fn interior(&self) -> Vec<f64> {
+        let mut values = Vec::with_capacity(self.width * self.height);
+        for row in 0..self.height as isize {
+            for col in 0..self.width as isize {
+                values.push(self.get(col, row));
+            }
+        }
+        values
+    }
+}
+
+/// Computes output tiles from blocks of aligned inputs
+///
+/// `f` receives one block per input (with `halo` pixels around the output
+/// tile) and returns one tile of values per writer, covering the block
+/// interior row-major. All writers must share the tile layout of the first.
+pub fn map_blocks<R, F>(inputs: &mut [R], halo: u64, writers: &mut [GeoTiffWriter], f: F) -> Result<()>
+where
+    R: BorrowMut<Raster>,
+    F: Fn(&[Block]) -> Result<Vec<Vec<f64>>> + Sync,
+{
+    let config = match writers.first() {
+        Some(writer) => writer.config().clone(),
+        None => return Ok(()),
+    };
+    if writers.iter().any(|w| {
+        let c = w.config();
+        (c.width, c.height, c.tile_width, c.tile_height)
+            != (config.width, config.height, config.tile_width, config.tile_height)
+    }) {
+        return Err(Error::InvalidFormat("Outputs have different tile layouts".to_string()));
+    }
+    if inputs.iter().any(|r| {
+        let r: &Raster = r.borrow();
+        (r.width, r.height) != (config.width, config.height)
+    }) {
+        return Err(Error::InvalidFormat("Inputs do not match the output size".to_string()));
+    }
+
+    let (across, down) = config.tiles_across_down();
+    for tile_row in 0..down {
+        let y = tile_row * config.tile_height;
+        let height = config.tile_height.min(config.height - y);
+
+        let bands = inputs.iter_mut()
+            .map(|raster| raster.borrow_mut().read_block(0, y, config.width, height, halo))
+            .collect::<Result<Vec<_>>>()?;
+
+        let tiles = (0..across)
+            .into_par_iter()
+            .map(|tile_col| {
+                let index = (tile_row * across + tile_col) as usize;
+                let (x, _, width, _) = config.tile_window(index);
+                let blocks: Vec<Block> = bands.iter()
+                    .map(|band| band.window(x as usize, 0, width as usize, height as usize))
+                    .collect();
+                Ok((index, f(&blocks)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (index, outputs) in tiles {
+            if outputs.len() != writers.len() {
+                return Err(Error::InvalidFormat(format!(
+                    "Block function returned {} outputs for {} writers", outputs.len(), writers.len()
+                )));
+            }
+            for (writer, values) in writers.iter_mut().zip(outputs) {
+                writer.write_tile(index, &values)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,418 @@
+//! Burning vector features into a raster grid
+//!
+//! Features are reprojected into the grid CRS and converted to pixel space.
+//! Polygons burn the cells whose centre lies inside (or, with
+//! `all_touched`, every cell they overlap); lines burn one cell per step
+//! along their major axis (or every cell they cross); points burn the cell
+//! containing them. Each feature burns a cell at most once, and overlapping
+//! features combine according to a [`MergeStrategy`].
+
+use std::path::Path;
+use crate::compression::Compression;
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, GeoReference, GeoTiffWriter, WriterConfig};
+use crate::projection::{Coordinate, Transformer};
+use crate::types::Pixel;
+use crate::vector::{Feature, Geometry};
+use super::coverage::{pixel_rings, PolygonCoverage};
+
+/// Value burned for a feature
+#[derive(Debug, Clone, PartialEq)]
+pub enum BurnValue {
+    Constant(f64),
+    /// Numeric feature property; features without it are an error
+    Attribute(String),
+}
+
+/// How values of overlapping features combine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Later features overwrite earlier ones
+    Replace,
+    /// The first feature burning a cell wins
+    First,
+    Min,
+    Max,
+    Sum,
+    /// Number of features burning the cell; the burn value is ignored
+    Count,
+}
+
+/// Rasterization parameters
+#[derive(Debug, Clone)]
+pub struct RasterizeOptions {
+    /// Burn every touched cell, not only cells whose centre is covered
+    pub all_touched: bool,
+    pub value: BurnValue,
+    pub merge: MergeStrategy,
+    /// Value of cells no feature burns
+    pub background: f64,
+    /// NoData tag of the grid
+    pub nodata: Option<f64>,
+}
+
+impl Default for RasterizeOptions {
+    fn default() -> Self {
+        Self {
+            all_touched: false,
+            value: BurnValue::Constant(1.0),
+            merge: MergeStrategy::Replace,
+            background: 0.0,
+            nodata: None,
+        }
+    }
+}
+
+/// An in-memory georeferenced grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T: Pixel> {
+    pub width: u64,
+    pub height: u64,
+    pub georef: GeoReference,
+    pub nodata: Option<f64>,
+    /// Row-major cell values
+    pub data: Vec<T>,
+}
+
+impl<T: Pixel> Grid<T> {
+    /// Returns the value of a cell
+    pub fn get(&self, col: u64, row: u64) -> Option<T> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        self.data.get((row * self.width + col) as usize).copied()
+    }
+
+    /// Writes the grid as a tiled GeoTIFF
+    pub fn write_geotiff(&self, path: &Path, compression: Compression) -> Result<()> {
+        let mut config = WriterConfig::new(self.width, self.height, T::DATA_TYPE);
+        config.compression = compression;
+        config.nodata = self.nodata;
+        config.georef = Some(self.georef);
+
+        let (across, down) = config.tiles_across_down();
+        let mut writer = GeoTiffWriter::create(path, config.clone())?;
+        for index in 0..(across * down) as usize {
+            let (x, y, width, height) = config.tile_window(index);
+            let mut values = Vec::with_capacity((width * height) as usize);
+            for row in y..y + height {
+                let start = (row * self.width + x) as usize;
+                values.extend(self.data[start..start + width as usize].iter().map(|v| v.to_f64()));
+            }
+            writer.write_tile(index, &values)?;
+        }
+        writer.finish()
+    }
+}
+
+/// Burns features given in `epsg` into a `width` x `height` grid aligned with `geo_info`
+pub fn rasterize<T: Pixel>(
+    features: &[Feature],
+    epsg: u16,
+    geo_info: &GeoInfo,
+    width: u64,
+    height: u64,
+    options: &RasterizeOptions,
+) -> Result<Grid<T>> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidFormat(format!("Grid must have at least one cell, got {}x{}", width, height)));
+    }
+    let georef = GeoReference::from_geo_info(geo_info)
+        .ok_or_else(|| Error::InvalidFormat("Grid needs a geotransform and EPSG code".to_string()))?;
+    let transformer = (georef.epsg != epsg)
+        .then(|| Transformer::new(epsg, georef.epsg))
+        .transpose()?;
+
+    let mut burned = vec![f64::NAN; (width * height) as usize];
+    let mut cells = Vec::new();
+
+    for (index, feature) in features.iter().enumerate() {
+        let Some(geometry) = &feature.geometry else { continue };
+        let value = match &options.value {
+            BurnValue::Constant(v) => *v,
+            BurnValue::Attribute(name) => feature.property_f64(name).ok_or_else(|| Error::InvalidFormat(
+                format!("Feature {} has no numeric '{}' property", index, name)
+            ))?,
+        };
+
+        let geometry = match &transformer {
+            Some(t) => geometry.map_coords(|c| t.transform(c))?,
+            None => geometry.clone(),
+        };
+
+        cells.clear();
+        geometry_cells(&geometry, geo_info, width, height, options.all_touched, &mut cells)?;
+        cells.sort_unstable();
+        cells.dedup();
+
+        for &(col, row) in &cells {
+            let cell = &mut burned[(row * width + col) as usize];
+            *cell = merge(options.merge, *cell, value);
+        }
+    }
+
+    Ok(Grid {
+        width,
+        height,
+        georef,
+        nodata: options.nodata,
+        data: burned.into_iter()
+            .map(|v| T::from_f64(if v.is_nan() { options.background } else { v }))
+            .collect(),
+    })
+}
+
+/// Helper: Combines a burned value into a cell; NaN marks unburned cells
+fn merge(strategy: MergeStrategy, current: f64, value: f64) -> f64 {
+    if current.is_nan() {
+        return if strategy == MergeStrategy::Count { 1.0 } else { value };
+    }
+    match strategy {
+        MergeStrategy::Replace => value,
+        MergeStrategy::First => current,
+        MergeStrategy::Min => current.min(value),
+        MergeStrategy::Max => current.max(value),
+        MergeStrategy::Sum => current + value,
+        MergeStrategy::Count => current + 1.0,
+    }
+}
+
+/// Helper: Collects the grid cells a geometry (in grid CRS) burns
+fn geometry_cells(
+    geometry: &Geometry,
+    geo_info: &GeoInfo,
+    width: u64,
+    height: u64,
+    all_touched: bool,
+    cells: &mut Vec<(u64, u64)>,
+) -> Result<()> {
+    let to_pixel = |c: Coordinate| geo_info.geo_to_pixel(c)
+        .ok_or_else(|| Error::InvalidFormat("Missing or singular geotransform".to_string()));
+
+    let burn_line = |line: &[Coordinate], cells: &mut Vec<(u64, u64)>| -> Result<()> {
+        let points = line.iter().map(|&c| to_pixel(c)).collect::<Result<Vec<_>>>()?;
+        for pair in points.windows(2) {
+            line_cells(pair[0], pair[1], width, height, all_touched, cells);
+        }
+        if let [single] = points.as_slice() {
+            point_cell(*single, width, height, cells);
+        }
+        Ok(())
+    };
+
+    match geometry {
+        Geometry::Point(c) => point_cell(to_pixel(*c)?, width, height, cells),
+        Geometry::MultiPoint(points) => {
+            for c in points {
+                point_cell(to_pixel(*c)?, width, height, cells);
+            }
+        }
+        Geometry::LineString(line) => burn_line(line, cells)?,
+        Geometry::MultiLineString(lines) => {
+            for line in lines {
+                burn_line(line, cells)?;
+            }
+        }
+        Geometry::Polygon(_) | Geometry::MultiPolygon(_) => {
+            let rings = pixel_rings(&geometry.polygons(), geo_info, None)?;
+            if let Some(coverage) = PolygonCoverage::compute(&rings, width, height) {
+                for row in 0..coverage.height {
+                    for col in 0..coverage.width {
+                        let idx = (row * coverage.width + col) as usize;
+                        if coverage.centre[idx] || (all_touched && coverage.fraction[idx] > 0.0) {
+                            cells.push((coverage.x0 + col, coverage.y0 + row));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Helper: Adds the cell containing a pixel-space point
+fn point_cell((x, y): (f64, f64), width: u64, height: u64, cells: &mut Vec<(u64, u64)>) {
+    if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+        cells.push((x as u64, y as u64));
+    }
+}
+
+/// Helper: Adds the cells of a pixel-space segment clipped to the grid
+///
+/// All-touched mode walks every crossed cell; otherwise one cell is taken
+/// per column (or row, for steep segments) at the cell centre line.
+fn line_cells(a: (f64, f64), b: (f64, f64), width: u64, height: u64, all_touched: bool, cells: &mut Vec<(u64, u64)>) {
+    let Some((a, b)) = clip_segment(a, b, width as f64, height as f64) else { return };
+    let clamp = |v: f64, size: u64| (v.floor().max(0.0) as u64).min(size - 1);
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+
+    if all_touched {
+        // Amanatides-Woo grid traversal
+        let (mut col, mut row) = (clamp(a.0, width) as i64, clamp(a.1, height) as i64);
+        let (end_col, end_row) = (clamp(b.0, width) as i64, clamp(b.1, height) as i64);
+        let (step_x, step_y) = (dx.signum() as i64, dy.signum() as i64);
+        let boundary = |cell: i64, step: i64| (cell + i64::from(step > 0)) as f64;
+        let mut t_max_x = if dx != 0.0 { (boundary(col, step_x) - a.0) / dx } else { f64::INFINITY };
+        let mut t_max_y = if dy != 0.0 { (boundary(row, step_y) - a.1) / dy } else { f64::INFINITY };
+        let (t_delta_x, t_delta_y) = (1.0 / dx.abs(), 1.0 / dy.abs());
+
+        let steps = (end_col - col).abs() + (end_row - row).abs();
+        cells.push((col as u64, row as u64));
+        for _ in 0..steps {
+            if t_max_x < t_max_y {
+                col += step_x;
+                t_max_x += t_delta_x;
+            } else {
+                row += step_y;
+                t_max_y += t_delta_y;
+            }
+            if col < 0 || row < 0 || col >= width as i64 || row >= height as i64 {
+                break;
+            }
+            cells.push((col as u64, row as u64));
+        }
+        return;
+    }
+
+    cells.push((clamp(a.0, width), clamp(a.1, height)));
+    cells.push((clamp(b.0, width), clamp(b.1, height)));
+    if dx.abs() >= dy.abs() {
+        let (start, end) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+        let first = (start.0 - 0.5).ceil().max(0.0) as u64;
+        let mut col = first;
+        while (col as f64 + 0.5) <= end.0 && col < width {
+            let y = start.1 + (col as f64 + 0.5 - start.0) * dy / dx;
+            cells.push((col, clamp(y, height)));
+            col += 1;
+        }
+    } else {
+        let (start, end) = if a.1 <= b.1 { (a, b) } else { (b, a) };
+        let mut row = (start.1 - 0.5).ceil().max(0.0) as u64;
+        while (row as f64 + 0.5) <= end.1 && row < height {
+            let x = start.0 + (row as f64 + 0.5 - start.1) * dx / dy;
+            cells.push((clamp(x, width), row));
+            row += 1;
+        }
+    }
+}
+
+/// Helper: Clips a segment to [0, width] x [0, height] (Liang-Barsky)
+fn clip_segment(a: (f64, f64), b: (f64, f64), width: f64, height: f64) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [(-dx, a.0), (dx, width - a.0), (-dy, a.1), (dy, height - a.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    (t0 <= t1).then_some(((a.0 + t0 * dx, a.1 + t0 * dy), (a.0 + t1 * dx, a.1 + t1 * dy)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+    use crate::vector::{parse_features, parse_wkt, Polygon};
+
+    fn grid_info(width: u64, height: u64) -> (tempfile::NamedTempFile, GeoInfo) {
+        let file = write_geotiff(&FixtureSpec::new(width, height), |_, _| 0.0);
+        let (mut reader, ifd) = open_fixture(file.path());
+        let geo_info = GeoInfo::from_ifd(&ifd, &mut reader).unwrap().unwrap();
+        (file, geo_info)
+    }
+
+    fn count<T: Pixel + PartialEq>(grid: &Grid<T>, value: T) -> usize {
+        grid.data.iter().filter(|v| **v == value).count()
+    }
+
+    #[test]
+    fn test_polygon_modes() {
+        let (_file, geo_info) = grid_info(32, 32);
+        // 3 x 3 cells, partly touching one more row and column; y grows upwards in the CRS
+        let feature = Feature::new(Geometry::Polygon(Polygon::rectangle(2.75, 20.75, 5.75, 23.75)));
+        let options = RasterizeOptions::default();
+
+        let centre: Grid<u8> = rasterize(std::slice::from_ref(&feature), 3857, &geo_info, 32, 32, &options).unwrap();
+        assert_eq!(count(&centre, 1), 9);
+        assert_eq!(centre.get(3, 9), Some(1));
+        assert_eq!(centre.get(2, 9), Some(0));
+
+        let touched = RasterizeOptions { all_touched: true, ..options };
+        let touched: Grid<u8> = rasterize(&[feature], 3857, &geo_info, 32, 32, &touched).unwrap();
+        assert_eq!(count(&touched, 1), 16);
+    }
+
+    #[test]
+    fn test_lines_points_and_merge() {
+        let (_file, geo_info) = grid_info(32, 32);
+        let features = parse_features(r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"zone": 4}, "geometry": {"type": "LineString", "coordinates": [[0.5, 31.5], [10.5, 31.5]]}},
+            {"type": "Feature", "properties": {"zone": 7}, "geometry": {"type": "Point", "coordinates": [5.5, 31.5]}},
+            {"type": "Feature", "properties": {"zone": 2}, "geometry": {"type": "MultiPoint", "coordinates": [[5.5, 31.5], [100, 100]]}}
+        ]}"#).unwrap();
+
+        let burn = |merge| {
+            let options = RasterizeOptions { value: BurnValue::Attribute("zone".to_string()), merge, ..Default::default() };
+            rasterize::<i16>(&features, 3857, &geo_info, 32, 32, &options).unwrap()
+        };
+        assert_eq!(count(&burn(MergeStrategy::Replace), 4), 10);
+        assert_eq!(burn(MergeStrategy::Replace).get(5, 0), Some(2));
+        assert_eq!(burn(MergeStrategy::First).get(5, 0), Some(4));
+        assert_eq!(burn(MergeStrategy::Max).get(5, 0), Some(7));
+        assert_eq!(burn(MergeStrategy::Min).get(5, 0), Some(2));
+        assert_eq!(burn(MergeStrategy::Sum).get(5, 0), Some(13));
+        assert_eq!(burn(MergeStrategy::Count).get(5, 0), Some(3));
+        assert_eq!(burn(MergeStrategy::Count).get(11, 0), Some(0));
+
+        let missing = RasterizeOptions { value: BurnValue::Attribute("other".to_string()), ..Default::default() };
+        assert!(rasterize::<u8>(&features, 3857, &geo_info, 32, 32, &missing).is_err());
+    }
+
+    #[test]
+    fn test_diagonal_line_modes() {
+        let (_file, geo_info) = grid_info(32, 32);
+        // From pixel (0.2, 0.2) to (4.2, 2.2)
+        let line = vec![Feature::new(parse_wkt("LINESTRING (0.2 31.8, 4.2 29.8)").unwrap())];
+
+        let centre: Grid<u8> = rasterize(&line, 3857, &geo_info, 32, 32, &RasterizeOptions::default()).unwrap();
+        assert_eq!(count(&centre, 1), 5);
+        let touched = RasterizeOptions { all_touched: true, ..Default::default() };
+        let touched: Grid<u8> = rasterize(&line, 3857, &geo_info, 32, 32, &touched).unwrap();
+        assert_eq!(count(&touched, 1), 7);
+        for (col, row) in [(0, 0), (1, 0), (1, 1), (2, 1), (3, 1), (3, 2), (4, 2)] {
+            assert_eq!(touched.get(col, row), Some(1), "({}, {})", col, row);
+        }
+
+        // Empty grids are rejected before any cell is clamped
+        for (width, height) in [(0, 32), (32, 0)] {
+            assert!(rasterize::<u8>(&line, 3857, &geo_info, width, height, &RasterizeOptions::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_write_grid() {
+        let (_file, geo_info) = grid_info(300, 20);
+        let options = RasterizeOptions { value: BurnValue::Constant(2.5), background: -1.0, nodata: Some(-1.0), ..Default::default() };
+        let feature = Feature::new(parse_wkt("POLYGON ((30 0, 40 0, 40 10, 30 10, 30 0))").unwrap());
+        let grid: Grid<f32> = rasterize(&[feature], 3857, &geo_info, 300, 20, &options).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("burned.tif");
+        grid.write_geotiff(&path, Compression::Deflate).unwrap();
+
+        let (mut reader, ifd) = open_fixture(&path);
+        let written = GeoInfo::from_ifd(&ifd, &mut reader).unwrap().unwrap();
+        assert_eq!(written.nodata, Some(-1.0));
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 35, 15).unwrap(), 2.5);
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 35, 5).unwrap(), -1.0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Reclassification with lookup tables
+//!
+//! A table maps exact values and half-open value ranges `[min, max)` to new
+//! values. Exact entries win over ranges; ranges are tried in table order.
+//! Tables load from CSV with the columns `value` or `min`/`max` (blank for
+//! an open end) plus `new_value`, or from JSON:
+//!
+//! ```json
+//! {"rules": [{"value": 11, "new_value": 0.8}, {"min": 20, "max": 30, "new_value": 0.4}],
+//!  "unmatched": "nodata"}
+//! ```
+//!
+//! `unmatched` is `"nodata"`, `"keep"` or a number; a bare rule array is
+//! accepted too.
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::Deserialize;
+use crate::compression::Compression;
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, GeoTiffWriter, TiffReader, IFD};
+use crate::projection::Coordinate;
+use crate::types::DataType;
+use super::algebra::{default_nodata, literal_type};
+use super::raster::{map_blocks, Raster};
+
+/// What happens to values no rule matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unmatched {
+    NoData,
+    Keep,
+    Value(f64),
+}
+
+/// A lookup table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reclassifier {
+    exact: HashMap<u64, f64>,
+    /// (min, max, new value), tried in order
+    ranges: Vec<(f64, f64, f64)>,
+    unmatched: Unmatched,
+}
+
+/// Helper: One table row as loaded from CSV or JSON
+#[derive(Debug, Deserialize)]
+struct Rule {
+    #[serde(default)]
+    value: Option<f64>,
+    #[serde(default)]
+    min: Option<f64>,
+    #[serde(default)]
+    max: Option<f64>,
+    #[serde(alias = "to", alias = "output")]
+    new_value: f64,
+}
+
+impl Reclassifier {
+    /// Creates an empty table; unmatched values become NoData
+    pub fn new() -> Self {
+        Self { exact: HashMap::new(), ranges: Vec::new(), unmatched: Unmatched::NoData }
+    }
+
+    /// Maps one exact value
+    pub fn with_value(mut self, value: f64, new_value: f64) -> Self {
+        self.exact.insert(key(value), new_value);
+        self
+    }
+
+    /// Maps the range `[min, max)`; use infinities for open ends
+    pub fn with_range(mut self, min: f64, max: f64, new_value: f64) -> Result<Self> {
+        if min.is_nan() || max.is_nan() || min >= max {
+            return Err(Error::InvalidFormat(format!("Invalid reclass range [{}, {})", min, max)));
+        }
+        self.ranges.push((min, max, new_value));
+        Ok(self)
+    }
+
+    /// Sets the handling of unmatched values
+    pub fn with_unmatched(mut self, unmatched: Unmatched) -> Self {
+        self.unmatched = unmatched;
+        self
+    }
+
+    /// Loads a table from CSV text
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(text.as_bytes());
+        let mut table = Self::new();
+        for (line, row) in reader.deserialize::<Rule>().enumerate() {
+            let rule = row.map_err(|e| Error::InvalidFormat(format!("Invalid reclass CSV row {}: {}", line + 1, e)))?;
+            table = table.with_rule(rule)?;
+        }
+        Ok(table)
+    }
+
+    /// Loads a table from JSON text
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(text)
+            .map_err(|e| Error::InvalidFormat(format!("Invalid reclass JSON: {}", e)))?;
+        let (rules, unmatched) = match &value {
+            serde_json::Value::Array(_) => (value.clone(), None),
+            serde_json::Value::Object(object) => (
+                object.get("rules").cloned()
+                    .ok_or_else(|| Error::InvalidFormat("Reclass JSON without \"rules\"".to_string()))?,
+                object.get("unmatched"),
+            ),
+            _ => return Err(Error::InvalidFormat("Reclass JSON must be an object or array".to_string())),
+        };
+
+        let rules: Vec<Rule> = serde_json::from_value(rules)
+            .map_err(|e| Error::InvalidFormat(format!("Invalid reclass rule: {}", e)))?;
+        let mut table = Self::new();
+        for rule in rules {
+            table = table.with_rule(rule)?;
+        }
+
+        table.unmatched = match unmatched {
+            None | Some(serde_json::Value::Null) => Unmatched::NoData,
+            Some(serde_json::Value::String(s)) if s == "nodata" => Unmatched::NoData,
+            Some(serde_json::Value::String(s)) if s == "keep" => Unmatched::Keep,
+            Some(other) => Unmatched::Value(other.as_f64()
+                .ok_or_else(|| Error::InvalidFormat(format!("Invalid \"unmatched\": {}", other)))?),
+        };
+        Ok(table)
+    }
+
+    /// Loads a table from a `.csv` or `.json` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("csv") => Self::from_csv(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(Error::Unsupported(format!("Reclass table format of {}", path.display()))),
+        }
+    }
+
+    /// Maps a value by the rules alone; `None` where no rule matches
+    pub fn lookup(&self, value: f64) -> Option<f64> {
+        if value.is_nan() {
+            return None;
+        }
+        self.exact.get(&key(value)).copied().or_else(|| {
+            self.ranges.iter().find(|(min, max, _)| value >= *min && value < *max).map(|r| r.2)
+        })
+    }
+
+    /// Maps a value; NaN (NoData) stays NaN
+    pub fn apply(&self, value: f64) -> f64 {
+        if value.is_nan() {
+            return f64::NAN;
+        }
+        if let Some(mapped) = self.lookup(value) {
+            return mapped;
+        }
+        match self.unmatched {
+            Unmatched::NoData => f64::NAN,
+            Unmatched::Keep => value,
+            Unmatched::Value(v) => v,
+        }
+    }
+
+    /// Maps values in place
+    pub fn apply_slice(&self, values: &mut [f64]) {
+        for value in values {
+            *value = self.apply(*value);
+        }
+    }
+
+    /// Returns the smallest type holding every output for inputs of `input_type`
+    pub fn output_type(&self, input_type: DataType) -> DataType {
+        let mut outputs: Vec<f64> = self.exact.values().copied()
+            .chain(self.ranges.iter().map(|r| r.2))
+            .collect();
+        if let Unmatched::Value(v) = self.unmatched {
+            outputs.push(v);
+        }
+
+        let mapped = outputs.into_iter().map(literal_type).reduce(DataType::promote);
+        match (mapped, self.unmatched) {
+            (Some(t), Unmatched::Keep) => t.promote(input_type),
+            (Some(t), _) => t,
+            (None, _) => input_type,
+        }
+    }
+
+    /// Helper: Adds a loaded rule
+    fn with_rule(self, rule: Rule) -> Result<Self> {
+        match (rule.value, rule.min, rule.max) {
+            (Some(value), None, None) => Ok(self.with_value(value, rule.new_value)),
+            (None, min, max) if min.is_some() || max.is_some() => self.with_range(
+                min.unwrap_or(f64::NEG_INFINITY),
+                max.unwrap_or(f64::INFINITY),
+                rule.new_value,
+            ),
+            _ => Err(Error::InvalidFormat("Reclass rule needs either value or min/max".to_string())),
+        }
+    }
+}
+
+impl Default for Reclassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Helper: Hash key of a value, treating 0.0 and -0.0 alike
+fn key(value: f64) -> u64 {
+    if value == 0.0 { 0 } else { value.to_bits() }
+}
+
+/// Samples points and maps the values through a table
+///
+/// Points are given in `source_epsg`. `None` marks points outside the
+/// raster; NaN marks NoData, either in the raster or from the table.
+pub fn sample_reclassified(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    points: &[Coordinate],
+    source_epsg: u16,
+    table: &Reclassifier,
+) -> Result<Vec<Option<f64>>> {
+    let geo_info = GeoInfo::from_ifd(ifd, reader)?
+        .ok_or_else(|| Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+    let dims = ifd.dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+
+    let pixels = geo_info.transform_crs_to_pixel_batch(points, source_epsg)?;
+    let inside: Vec<usize> = pixels.iter().enumerate()
+        .filter(|(_, (x, y))| *x >= 0.0 && *y >= 0.0 && *x < dims.width as f64 && *y < dims.height as f64)
+        .map(|(i, _)| i)
+        .collect();
+    let coords: Vec<(u64, u64)> = inside.iter().map(|&i| (pixels[i].0 as u64, pixels[i].1 as u64)).collect();
+    let values = reader.read_pixels_batch_f64(ifd, &coords)?;
+
+    let mut mapped = vec![None; points.len()];
+    for (index, value) in inside.into_iter().zip(values) {
+        let value = if geo_info.is_nodata(value) { f64::NAN } else { value };
+        mapped[index] = Some(table.apply(value));
+    }
+    Ok(mapped)
+}
+
+/// Output settings of [`reclassify_raster`]
+#[derive(Debug, Clone)]
+pub struct ReclassOptions {
+    /// Output type; the smallest type holding the table outputs when `None`
+    pub output_type: Option<DataType>,
+    /// Output NoData; defaults as in map algebra
+    pub nodata: Option<f64>,
+    pub compression: Compression,
+}
+
+impl Default for ReclassOptions {
+    fn default() -> Self {
+        Self { output_type: None, nodata: None, compression: Compression::Deflate }
+    }
+}
+
+/// Maps every pixel of a raster through a table into a new GeoTIFF; returns
+/// the output data type
+pub fn reclassify_raster(
+    input: &mut Raster,
+    output: &Path,
+    table: &Reclassifier,
+    options: &ReclassOptions,
+) -> Result<DataType> {
+    let data_type = match options.output_type {
+        Some(t) => t,
+        None => table.output_type(input.data_type()?),
+    };
+    let nodata = options.nodata.unwrap_or_else(|| default_nodata(data_type));
+
+    let mut config = input.output_config(data_type, Some(nodata));
+    config.compression = options.compression;
+    let mut writers = vec![GeoTiffWriter::create(output, config)?];
+
+    map_blocks(std::slice::from_mut(input), 0, &mut writers, |blocks| {
+        let mut values = blocks[0].values.clone();
+        table.apply_slice(&mut values);
+        Ok(vec![values])
+    })?;
+
+    for writer in writers {
+        writer.finish()?;
+    }
+    Ok(data_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+
+    #[test]
+    fn test_load_and_apply() {
+        let csv = "value,min,max,new_value\n11,,,0.8\n,20,30,0.4\n,30,,1\n,,5,0\n";
+        let table = Reclassifier::from_csv(csv).unwrap();
+        assert_eq!(table.apply(11.0), 0.8);
+        assert_eq!(table.apply(20.0), 0.4);
+        assert_eq!(table.apply(30.0), 1.0);
+        assert_eq!(table.apply(-100.0), 0.0);
+        assert!(table.apply(12.0).is_nan());
+        assert!(table.apply(f64::NAN).is_nan());
+        assert_eq!(table.lookup(25.0), Some(0.4));
+        assert_eq!(table.lookup(12.0), None);
+
+        let json = r#"{"rules": [{"value": 11, "new_value": 0.8}, {"min": 20, "max": 30, "to": 0.4},
+                       {"min": 30, "new_value": 1}, {"max": 5, "new_value": 0}], "unmatched": "nodata"}"#;
+        assert_eq!(Reclassifier::from_json(json).unwrap(), table);
+
+        let keep = Reclassifier::from_json(r#"{"rules": [{"value": 1, "new_value": 100}], "unmatched": "keep"}"#).unwrap();
+        assert_eq!((keep.apply(1.0), keep.apply(7.0)), (100.0, 7.0));
+        assert_eq!(keep.output_type(DataType::U8), DataType::U8);
+        assert_eq!(keep.output_type(DataType::I16), DataType::I16);
+        assert_eq!(Reclassifier::from_json(r#"[{"value": 2, "new_value": 5}]"#).unwrap().apply(2.0), 5.0);
+
+        assert!(Reclassifier::from_csv("value,new_value\nx,1\n").is_err());
+        assert!(Reclassifier::from_json(r#"[{"min": 5, "max": 1, "new_value": 0}]"#).is_err());
+        assert!(Reclassifier::from_json(r#"[{"new_value": 0}]"#).is_err());
+    }
+
+    #[test]
+    fn test_sample_and_bulk() {
+        let mut spec = FixtureSpec::new(40, 20);
+        spec.nodata = Some(0.0);
+        let file = write_geotiff(&spec, |x, _| (x % 4) as f64);
+        let table = Reclassifier::new()
+            .with_value(1.0, 10.0)
+            .with_range(2.0, 3.0, 20.0).unwrap()
+            .with_unmatched(Unmatched::Value(99.0));
+
+        let (mut reader, ifd) = open_fixture(file.path());
+        let points = [
+            Coordinate::new(1.5, 10.0),
+            Coordinate::new(2.5, 10.0),
+            Coordinate::new(3.5, 10.0),
+            Coordinate::new(4.5, 10.0),
+            Coordinate::new(-4.0, 10.0),
+        ];
+        let sampled = sample_reclassified(&mut reader, &ifd, &points, 3857, &table).unwrap();
+        assert_eq!(&sampled[..3], &[Some(10.0), Some(20.0), Some(99.0)]);
+        assert!(sampled[3].unwrap().is_nan());
+        assert_eq!(sampled[4], None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("classes.tif");
+        let mut raster = Raster::open(file.path()).unwrap();
+        let data_type = reclassify_raster(&mut raster, &path, &table, &ReclassOptions::default()).unwrap();
+        assert_eq!(data_type, DataType::U8);
+
+        let (mut reader, ifd) = open_fixture(&path);
+        let row = reader.read_window_f64(&ifd, 16, 3, 8, 1).unwrap();
+        assert_eq!(row, vec![255.0, 10.0, 20.0, 99.0, 255.0, 10.0, 20.0, 99.0]);
+    }
+}
@@ -0,0 +1,935 @@
+//! Whole-raster statistics and histograms
+//!
+//! Tiles are streamed in chunks through the parallel reader and folded into
+//! per-band accumulators with rayon, so memory stays bounded by the chunk
+//! size. A first pass gathers moments and the value range, a second builds
+//! histograms and, for exact percentiles, a few more passes narrow the
+//! histogram bins that hold the requested ranks without keeping any values.
+
+use std::collections::HashMap;
+use std::path::Path;
+use rayon::prelude::*;
+use crate::error::{Error, Result};
+use crate::formats::tiff::{tags, write_gdal_metadata, GdalMetadata, GeoInfo, Tiff, TiffReader, IFD};
+use crate::formats::tiff::reader::pixels::PixelReader;
+use crate::types::DataType;
+
+/// NewSubfileType bit of reduced-resolution images
+const SUBFILE_OVERVIEW: u64 = 1;
+/// NewSubfileType bit of transparency masks
+const SUBFILE_MASK: u64 = 4;
+/// Sub-bins per refinement pass of exact percentiles
+const REFINE_BINS: usize = 256;
+/// Refinement passes before interpolating inside the narrowed sub-bin
+const MAX_REFINE_PASSES: usize = 4;
+
+/// Statistics parameters
+#[derive(Debug, Clone)]
+pub struct StatisticsOptions {
+    /// Number of histogram bins (integer rasters use at most one bin per value)
+    pub bins: usize,
+    /// Histogram range; defaults to the band's min/max
+    pub histogram_range: Option<(f64, f64)>,
+    /// Percentiles to compute, in the range 0-100
+    pub percentiles: Vec<f64>,
+    /// Compute exact percentiles with extra passes instead of interpolating the histogram
+    pub exact_percentiles: bool,
+    /// Compute from an overview instead of full resolution
+    pub approximate: bool,
+    /// Smallest overview edge (in pixels) acceptable for approximate statistics
+    pub approx_min_size: u64,
+    /// Exclude pixels masked out by an internal mask IFD
+    pub use_mask: bool,
+    /// Tiles read per parallel chunk
+    pub chunk_tiles: usize,
+}
+
+impl Default for StatisticsOptions {
+    fn default() -> Self {
+        Self {
+            bins: 256,
+            histogram_range: None,
+            percentiles: Vec::new(),
+            exact_percentiles: false,
+            approximate: false,
+            approx_min_size: 1024,
+            use_mask: true,
+            chunk_tiles: 64,
+        }
+    }
+}
+
+/// Equal-width histogram over [min, max]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    /// Creates an empty histogram
+    pub fn new(min: f64, max: f64, bins: usize) -> Self {
+        let max = if max > min { max } else { min + 1.0 };
+        Self { min, max, counts: vec![0; bins.max(1)] }
+    }
+
+    /// Returns the width of one bin
+    pub fn bin_width(&self) -> f64 {
+        (self.max - self.min) / self.counts.len() as f64
+    }
+
+    /// Returns the bin of a value; values equal to `max` fall into the last bin
+    pub fn bin_of(&self, value: f64) -> Option<usize> {
+        if !(value >= self.min && value <= self.max) {
+            return None;
+        }
+        let bin = ((value - self.min) / self.bin_width()) as usize;
+        Some(bin.min(self.counts.len() - 1))
+    }
+
+    /// Returns the value range of a bin
+    pub fn bin_range(&self, bin: usize) -> (f64, f64) {
+        let width = self.bin_width();
+        (self.min + bin as f64 * width, self.min + (bin + 1) as f64 * width)
+    }
+
+    /// Returns the number of counted values
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Approximates a percentile (0-100) by interpolating inside the bin holding its rank
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (total - 1) as f64;
+        let (bin, before) = self.rank_bin(rank as u64)?;
+        let (low, _) = self.bin_range(bin);
+        let fraction = (rank - before as f64 + 0.5) / self.counts[bin] as f64;
+        Some((low + fraction * self.bin_width()).clamp(self.min, self.max))
+    }
+
+    /// Helper: Finds the bin holding the 0-based rank and the count before it
+    fn rank_bin(&self, rank: u64) -> Option<(usize, u64)> {
+        let mut before = 0;
+        for (bin, &count) in self.counts.iter().enumerate() {
+            if rank < before + count {
+                return Some((bin, before));
+            }
+            before += count;
+        }
+        None
+    }
+}
+
+/// Statistics of one band
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandStatistics {
+    /// 0-based band index
+    pub band: usize,
+    /// Number of valid samples
+    pub count: u64,
+    /// Number of samples excluded as NoData or masked
+    pub excluded: u64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    /// Population standard deviation
+    pub std: Option<f64>,
+    pub histogram: Option<Histogram>,
+    /// (percentile, value) pairs in the requested order
+    pub percentiles: Vec<(f64, f64)>,
+    /// Whether the statistics were computed from an overview
+    pub approximate: bool,
+}
+
+impl BandStatistics {
+    /// Returns the share of valid samples in percent
+    pub fn valid_percent(&self) -> f64 {
+        let total = self.count + self.excluded;
+        if total == 0 { 0.0 } else { self.count as f64 * 100.0 / total as f64 }
+    }
+}
+
+/// Computes per-band statistics of the main image of a TIFF
+///
+/// In approximate mode the coarsest overview whose larger edge is at least
+/// `approx_min_size` is used instead. NoData comes from the main IFD's
+/// GDAL_NODATA tag; an internal mask IFD of matching size excludes pixels
+/// when `use_mask` is set.
+pub fn compute_statistics(reader: &mut TiffReader, tiff: &Tiff, options: &StatisticsOptions) -> Result<Vec<BandStatistics>> {
+    let main = tiff.main_ifd()
+        .ok_or_else(|| Error::InvalidFormat("No main IFD found".to_string()))?;
+    let nodata = GeoInfo::from_ifd(main, reader)?.and_then(|g| g.nodata);
+
+    let ifd = if options.approximate { select_overview(tiff, main, options.approx_min_size) } else { main };
+    let mask = if options.use_mask { find_mask(tiff, ifd) } else { None };
+
+    let mut stats = ifd_statistics(reader, ifd, mask, nodata, options)?;
+    let approximate = !std::ptr::eq(ifd, main);
+    for band in &mut stats {
+        band.approximate = approximate;
+    }
+    Ok(stats)
+}
+
+/// Computes per-band statistics of one IFD
+pub fn ifd_statistics(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    mask: Option<&IFD>,
+    nodata: Option<f64>,
+    options: &StatisticsOptions,
+) -> Result<Vec<BandStatistics>> {
+    let layout = BandLayout::from_ifd(reader, ifd)?;
+    if let Some(mask) = mask {
+        if mask.dimensions() != ifd.dimensions() || mask.tile_dimensions() != ifd.tile_dimensions() {
+            return Err(Error::InvalidFormat("Mask IFD does not match the image layout".to_string()));
+        }
+    }
+    let scan = Scan { layout: &layout, ifd, mask, nodata, chunk_tiles: options.chunk_tiles.max(1) };
+
+    // Pass 1: moments and range
+    let moments = scan.fold(reader, Moments::default(), |acc, _, value| acc.push(value), Moments::merge)?;
+
+    // Pass 2: histograms (one over the full range for percentiles, one for reporting)
+    let full: Vec<Option<Histogram>> = moments.iter()
+        .map(|m| m.range().map(|(min, max)| histogram_for(min, max, layout.data_type, options.bins)))
+        .collect();
+    let report: Vec<Option<Histogram>> = match options.histogram_range {
+        Some((min, max)) => moments.iter()
+            .map(|m| m.range().map(|_| Histogram::new(min, max, options.bins)))
+            .collect(),
+        None => full.clone(),
+    };
+    let separate_report = options.histogram_range.is_some();
+
+    let empty: Vec<(Vec<u64>, Vec<u64>)> = (0..layout.bands)
+        .map(|b| (
+            vec![0; full[b].as_ref().map_or(0, |h| h.counts.len())],
+            vec![0; if separate_report { report[b].as_ref().map_or(0, |h| h.counts.len()) } else { 0 }],
+        ))
+        .collect();
+    let counts = scan.fold_indexed(reader, empty, |acc, band, value| {
+        let Some(value) = value else { return };
+        if let Some(bin) = full[band].as_ref().and_then(|h| h.bin_of(value)) {
+            acc.0[bin] += 1;
+        }
+        if separate_report {
+            if let Some(bin) = report[band].as_ref().and_then(|h| h.bin_of(value)) {
+                acc.1[bin] += 1;
+            }
+        }
+    }, |a, b| {
+        add_counts(&mut a.0, &b.0);
+        add_counts(&mut a.1, &b.1);
+    })?;
+
+    let mut full = full;
+    let mut report = report;
+    for (band, (full_counts, report_counts)) in counts.into_iter().enumerate() {
+        if let Some(h) = full[band].as_mut() {
+            h.counts = full_counts;
+        }
+        if separate_report {
+            if let Some(h) = report[band].as_mut() {
+                h.counts = report_counts;
+            }
+        } else {
+            report[band] = full[band].clone();
+        }
+    }
+
+    // Further passes (optional): exact percentiles from the bins holding the requested ranks
+    let percentiles = if options.exact_percentiles && !options.percentiles.is_empty() {
+        exact_percentiles(reader, &scan, &full, &options.percentiles)?
+    } else {
+        full.iter()
+            .map(|h| match h {
+                Some(h) => options.percentiles.iter()
+                    .filter_map(|&p| h.percentile(p).map(|v| (p, v)))
+                    .collect(),
+                None => Vec::new(),
+            })
+            .collect()
+    };
+
+    Ok(moments.into_iter()
+        .zip(report)
+        .zip(percentiles)
+        .enumerate()
+        .map(|(band, ((m, histogram), percentiles))| BandStatistics {
+            band,
+            count: m.count,
+            excluded: m.excluded,
+            min: m.range().map(|r| r.0),
+            max: m.range().map(|r| r.1),
+            mean: (m.count > 0).then_some(m.mean),
+            std: (m.count > 0).then(|| (m.m2 / m.count as f64).sqrt()),
+            histogram,
+            percentiles,
+            approximate: false,
+        })
+        .collect())
+}
+
+/// Builds GDAL_METADATA statistics items (STATISTICS_*) for each band
+pub fn statistics_metadata(stats: &[BandStatistics]) -> GdalMetadata {
+    let mut metadata = GdalMetadata::new();
+    for band in stats {
+        let sample = Some(band.band);
+        if let (Some(min), Some(max), Some(mean), Some(std)) = (band.min, band.max, band.mean, band.std) {
+            metadata.set("STATISTICS_MINIMUM", sample, min.to_string());
+            metadata.set("STATISTICS_MAXIMUM", sample, max.to_string());
+            metadata.set("STATISTICS_MEAN", sample, mean.to_string());
+            metadata.set("STATISTICS_STDDEV", sample, std.to_string());
+        }
+        metadata.set("STATISTICS_VALID_PERCENT", sample, band.valid_percent().to_string());
+        if band.approximate {
+            metadata.set("STATISTICS_APPROXIMATE", sample, "YES");
+        }
+    }
+    metadata
+}
+
+/// Stores statistics in the main IFD's GDAL_METADATA, keeping existing items
+pub fn write_statistics<P: AsRef<Path>>(path: P, stats: &[BandStatistics]) -> Result<()> {
+    let path = path.as_ref();
+    let mut metadata = {
+        let mut reader = TiffReader::open_with_options(path, false, 0)?;
+        let main = reader.ifd(0)?
+            .ok_or_else(|| Error::InvalidFormat("No main IFD found".to_string()))?;
+        GdalMetadata::from_ifd(&main, &mut reader)?.unwrap_or_default()
+    };
+
+    metadata.merge(&statistics_metadata(stats));
+    write_gdal_metadata(path, 0, &metadata)
+}
+
+/// Helper: Picks the coarsest overview still at least `min_size` on its larger edge
+fn select_overview<'a>(tiff: &'a Tiff, main: &'a IFD, min_size: u64) -> &'a IFD {
+    let bands = main.samples_per_pixel();
+    tiff.all_ifds().iter()
+        .filter(|ifd| subfile_type(ifd) & SUBFILE_OVERVIEW != 0 && subfile_type(ifd) & SUBFILE_MASK == 0)
+        .filter(|ifd| ifd.samples_per_pixel() == bands && ifd.is_tiled())
+        .filter_map(|ifd| ifd.dimensions().map(|d| (ifd, d.width.max(d.height))))
+        .filter(|(_, edge)| *edge >= min_size)
+        .min_by_key(|(_, edge)| *edge)
+        .map(|(ifd, _)| ifd)
+        .unwrap_or(main)
+}
+
+/// Helper: Finds the mask IFD matching an image's size
+fn find_mask<'a>(tiff: &'a Tiff, ifd: &IFD) -> Option<&'a IFD> {
+    tiff.all_ifds().iter()
+        .find(|m| subfile_type(m) & SUBFILE_MASK != 0 && m.dimensions() == ifd.dimensions())
+}
+
+fn subfile_type(ifd: &IFD) -> u64 {
+    ifd.get_tag_value(tags::NEW_SUBFILE_TYPE).unwrap_or(0)
+}
+
+/// Helper: Chooses a histogram layout; integer data gets one bin per value when it fits
+fn histogram_for(min: f64, max: f64, data_type: DataType, bins: usize) -> Histogram {
+    let values = (max - min) as usize + 1;
+    if data_type.is_integer() && values <= bins {
+        Histogram::new(min - 0.5, max + 0.5, values)
+    } else {
+        Histogram::new(min, max, bins)
+    }
+}
+
+fn add_counts(total: &mut [u64], part: &[u64]) {
+    for (t, p) in total.iter_mut().zip(part) {
+        *t += p;
+    }
+}
+
+/// Helper: Selects exact percentile values by narrowing the bins holding their ranks
+///
+/// Bins of integer data that hold a single value answer directly; any other
+/// bin is split into [`REFINE_BINS`] sub-bins per pass until the rank lands
+/// on a sub-bin edge or a sub-bin of equal values. Memory per requested rank
+/// stays at one sub-histogram, and after [`MAX_REFINE_PASSES`] the remaining
+/// ranks are interpolated inside their (by then very narrow) sub-bin.
+fn exact_percentiles(
+    reader: &mut TiffReader,
+    scan: &Scan,
+    histograms: &[Option<Histogram>],
+    percentiles: &[f64],
+) -> Result<Vec<Vec<(f64, f64)>>> {
+    // Ranks needed per band (both neighbours of each interpolated rank)
+    let ranks: Vec<Vec<(f64, u64, u64)>> = histograms.iter()
+        .map(|h| match h {
+            Some(h) if h.total() > 0 => percentiles.iter()
+                .map(|&p| {
+                    let rank = p.clamp(0.0, 100.0) / 100.0 * (h.total() - 1) as f64;
+                    (p, rank.floor() as u64, rank.ceil() as u64)
+                })
+                .collect(),
+            _ => Vec::new(),
+        })
+        .collect();
+
+    let mut values: HashMap<(usize, u64), f64> = HashMap::new();
+    let mut targets: Vec<RankTarget> = Vec::new();
+    for (band, h) in histograms.iter().enumerate() {
+        let Some(h) = h else { continue };
+        let unit_bins = scan.layout.data_type.is_integer() && h.bin_width() == 1.0 && (h.min + 0.5).fract() == 0.0;
+        for &(_, low, high) in &ranks[band] {
+            for rank in [low, high] {
+                if values.contains_key(&(band, rank)) || targets.iter().any(|t| t.band == band && t.rank == rank) {
+                    continue;
+                }
+                let Some((bin, before)) = h.rank_bin(rank) else { continue };
+                let (min, max) = h.bin_range(bin);
+                if unit_bins {
+                    values.insert((band, rank), min + 0.5);
+                } else {
+                    targets.push(RankTarget { band, rank, bin, path: Vec::new(), grid: Grid::new(min, max), before });
+                }
+            }
+        }
+    }
+
+    for pass in 1..=MAX_REFINE_PASSES {
+        if targets.is_empty() {
+            break;
+        }
+
+        let by_band: Vec<Vec<usize>> = (0..histograms.len())
+            .map(|band| (0..targets.len()).filter(|&t| targets[t].band == band).collect())
+            .collect();
+        let empty: Vec<Vec<Vec<SubBin>>> = by_band.iter()
+            .map(|list| vec![vec![SubBin::default(); REFINE_BINS]; list.len()])
+            .collect();
+        let subs = scan.fold_indexed(reader, empty, |acc, band, value| {
+            let Some(value) = value else { return };
+            let Some(h) = histograms[band].as_ref() else { return };
+            for (k, &t) in by_band[band].iter().enumerate() {
+                let target = &targets[t];
+                if target.contains(h, value) {
+                    acc[k][target.grid.index(value)].push(value);
+                }
+            }
+        }, |a, b| {
+            for (x, y) in a.iter_mut().zip(b) {
+                for (x, y) in x.iter_mut().zip(y) {
+                    x.merge(y);
+                }
+            }
+        })?;
+
+        let mut refined = Vec::new();
+        for (band, subs) in subs.into_iter().enumerate() {
+            for (k, bins) in subs.into_iter().enumerate() {
+                let mut target = targets[by_band[band][k]].clone();
+                let Some((j, sub)) = target.locate(&bins) else { continue };
+                let offset = target.rank - target.before;
+                let value = if offset == 0 || sub.min == sub.max {
+                    Some(sub.min)
+                } else if offset == sub.count - 1 {
+                    Some(sub.max)
+                } else if pass == MAX_REFINE_PASSES {
+                    Some(sub.min + (sub.max - sub.min) * offset as f64 / (sub.count - 1) as f64)
+                } else {
+                    None
+                };
+
+                match value {
+                    Some(value) => {
+                        values.insert((target.band, target.rank), value);
+                    }
+                    None => {
+                        target.path.push((target.grid, j));
+                        target.grid = Grid::new(sub.min, sub.max);
+                        refined.push(target);
+                    }
+                }
+            }
+        }
+        targets = refined;
+    }
+
+    Ok(histograms.iter()
+        .enumerate()
+        .map(|(band, h)| {
+            let Some(h) = h.as_ref() else { return Vec::new() };
+            ranks[band].iter()
+                .filter_map(|&(p, low, high)| {
+                    let rank = p.clamp(0.0, 100.0) / 100.0 * (h.total() - 1) as f64;
+                    let (a, b) = (*values.get(&(band, low))?, *values.get(&(band, high))?);
+                    Some((p, a + (b - a) * (rank - low as f64)))
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Equal-width split of a value range; out-of-range values clamp to the end bins
+#[derive(Debug, Clone, Copy)]
+struct Grid {
+    min: f64,
+    width: f64,
+}
+
+impl Grid {
+    fn new(min: f64, max: f64) -> Self {
+        Self { min, width: (max - min) / REFINE_BINS as f64 }
+    }
+
+    fn index(&self, value: f64) -> usize {
+        if self.width > 0.0 {
+            (((value - self.min) / self.width) as usize).min(REFINE_BINS - 1)
+        } else {
+            0
+        }
+    }
+}
+
+/// Count and range of the values in one sub-bin
+#[derive(Debug, Clone, Copy)]
+struct SubBin {
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for SubBin {
+    fn default() -> Self {
+        Self { count: 0, min: f64::INFINITY, max: f64::NEG_INFINITY }
+    }
+}
+
+impl SubBin {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn merge(&mut self, other: SubBin) {
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// A rank of one band being narrowed to ever smaller value ranges
+#[derive(Debug, Clone)]
+struct RankTarget {
+    band: usize,
+    rank: u64,
+    /// Histogram bin holding the rank
+    bin: usize,
+    /// Sub-bins chosen by earlier passes
+    path: Vec<(Grid, usize)>,
+    /// Split used by the next pass
+    grid: Grid,
+    /// Number of values ranked below the current range
+    before: u64,
+}
+
+impl RankTarget {
+    /// Helper: Tests whether a value lies in the range still holding the rank
+    fn contains(&self, histogram: &Histogram, value: f64) -> bool {
+        histogram.bin_of(value) == Some(self.bin)
+            && self.path.iter().all(|(grid, j)| grid.index(value) == *j)
+    }
+
+    /// Helper: Finds the sub-bin holding the rank, moving `before` past the bins below it
+    fn locate(&mut self, bins: &[SubBin]) -> Option<(usize, SubBin)> {
+        for (j, sub) in bins.iter().enumerate() {
+            if self.rank < self.before + sub.count {
+                return Some((j, *sub));
+            }
+            self.before += sub.count;
+        }
+        None
+    }
+}
+
+/// Running count, range, mean and sum of squared deviations (Welford)
+#[derive(Debug, Clone)]
+struct Moments {
+    count: u64,
+    excluded: u64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Default for Moments {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            excluded: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+}
+
+impl Moments {
+    fn push(&mut self, value: Option<f64>) {
+        let Some(value) = value else {
+            self.excluded += 1;
+            return;
+        };
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Combines two partial results (Chan et al.)
+    fn merge(&mut self, other: Moments) {
+        if other.count == 0 {
+            self.excluded += other.excluded;
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * (self.count as f64 * other.count as f64) / count as f64;
+        self.count = count;
+        self.excluded += other.excluded;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    fn range(&self) -> Option<(f64, f64)> {
+        (self.count > 0).then_some((self.min, self.max))
+    }
+}
+
+/// Sample layout of an IFD
+struct BandLayout {
+    data_type: DataType,
+    bands: usize,
+    planar: bool,
+    width: u64,
+    height: u64,
+    tile_width: u64,
+    tile_height: u64,
+    tiles_across: u64,
+    tiles_per_band: usize,
+}
+
+impl BandLayout {
+    fn from_ifd(reader: &mut TiffReader, ifd: &IFD) -> Result<Self> {
+        PixelReader::validate_tiled_access(ifd)?;
+        let dims = ifd.dimensions()
+            .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+        let tile_dims = ifd.tile_dimensions()
+            .ok_or_else(|| Error::InvalidFormat("Missing tile dimensions".to_string()))?;
+        let bands = ifd.samples_per_pixel().max(1) as usize;
+
+        // BitsPerSample/SampleFormat hold one value per band; the first describes all
+        let data_type = if bands == 1 {
+            PixelReader::require_data_type(ifd)?
+        } else {
+            let first = |reader: &mut TiffReader, tag: u16, default: u64| -> Result<u64> {
+                match ifd.get_entry(tag) {
+                    Some(entry) => Ok(reader.read_tag_u16s(entry)?.first().copied().unwrap_or(default as u16) as u64),
+                    None => Ok(default),
+                }
+            };
+            let bits = first(reader, tags::BITS_PER_SAMPLE, 8)?;
+            let format = first(reader, tags::SAMPLE_FORMAT, 1)?;
+            DataType::from_tiff(format, bits)
+                .ok_or_else(|| Error::Unsupported("Unsupported or missing sample format".to_string()))?
+        };
+
+        let tiles_across = dims.width.div_ceil(tile_dims.width);
+        let tiles_down = dims.height.div_ceil(tile_dims.height);
+
+        Ok(Self {
+            data_type,
+            bands,
+            planar: ifd.get_tag_value(tags::PLANAR_CONFIGURATION) == Some(2),
+            width: dims.width,
+            height: dims.height,
+            tile_width: tile_dims.width,
+            tile_height: tile_dims.height,
+            tiles_across,
+            tiles_per_band: (tiles_across * tiles_down) as usize,
+        })
+    }
+}
+
+/// One streaming pass over all tiles of an IFD
+struct Scan<'a> {
+    layout: &'a BandLayout,
+    ifd: &'a IFD,
+    mask: Option<&'a IFD>,
+    nodata: Option<f64>,
+    chunk_tiles: usize,
+}
+
+impl Scan<'_> {
+    /// Folds every sample into one accumulator per band
+    fn fold<A, V, M>(&self, reader: &mut TiffReader, init: A, visit: V, merge: M) -> Result<Vec<A>>
+    where
+        A: Clone + Send + Sync,
+        V: Fn(&mut A, usize, Option<f64>) + Sync,
+        M: Fn(&mut A, A) + Sync,
+    {
+        self.fold_indexed(reader, vec![init; self.layout.bands], visit, merge)
+    }
+
+    /// Like [`Scan::fold`] with distinct initial accumulators per band
+    fn fold_indexed<A, V, M>(&self, reader: &mut TiffReader, init: Vec<A>, visit: V, merge: M) -> Result<Vec<A>>
+    where
+        A: Clone + Send + Sync,
+        V: Fn(&mut A, usize, Option<f64>) + Sync,
+        M: Fn(&mut A, A) + Sync,
+    {
+        let layout = self.layout;
+        let positions: Vec<usize> = (0..layout.tiles_per_band).collect();
+        let mut total = init.clone();
+
+        for chunk in positions.chunks(self.chunk_tiles) {
+            let indices: Vec<usize> = if layout.planar {
+                (0..layout.bands)
+                    .flat_map(|b| chunk.iter().map(move |&t| b * layout.tiles_per_band + t))
+                    .collect()
+            } else {
+                chunk.to_vec()
+            };
+            let tiles = reader.read_tiles_direct(self.ifd, &indices)?;
+            let mask_tiles = match self.mask {
+                Some(mask) => Some((reader.read_tiles_direct(mask, chunk)?, mask.bits_per_sample() == Some(1))),
+                None => None,
+            };
+
+            let partial = chunk.par_iter()
+                .enumerate()
+                .map(|(k, &position)| {
+                    let mut accs = init.clone();
+                    let mask = mask_tiles.as_ref().map(|(tiles, packed)| (tiles[k].as_slice(), *packed));
+                    self.visit_tile(&tiles, chunk.len(), k, position, mask, &mut accs, &visit)?;
+                    Ok::<_, Error>(accs)
+                })
+                .try_reduce(|| init.clone(), |mut a, b| {
+                    for (x, y) in a.iter_mut().zip(b) {
+                        merge(x, y);
+                    }
+                    Ok(a)
+                })?;
+
+            for (x, y) in total.iter_mut().zip(partial) {
+                merge(x, y);
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Helper: Visits the samples of one tile position (all bands)
+    #[allow(clippy::too_many_arguments)]
+    fn visit_tile<A, V>(
+        &self,
+        tiles: &[Vec<u8>],
+        chunk_len: usize,
+        k: usize,
+        position: usize,
+        mask: Option<(&[u8], bool)>,
+        accs: &mut [A],
+        visit: &V,
+    ) -> Result<()>
+    where
+        V: Fn(&mut A, usize, Option<f64>),
+    {
+        let layout = self.layout;
+        let x0 = (position as u64 % layout.tiles_across) * layout.tile_width;
+        let y0 = (position as u64 / layout.tiles_across) * layout.tile_height;
+        let cols = layout.tile_width.min(layout.width - x0) as usize;
+        let rows = layout.tile_height.min(layout.height - y0) as usize;
+        let tile_width = layout.tile_width as usize;
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let pixel = row * tile_width + col;
+                let masked = match mask {
+                    Some((data, true)) => {
+                        let byte = row * tile_width.div_ceil(8) + col / 8;
+                        data.get(byte).is_none_or(|b| b & (0x80 >> (col % 8)) == 0)
+                    }
+                    Some((data, false)) => data.get(pixel).is_none_or(|&b| b == 0),
+                    None => false,
+                };
+
+                for (band, acc) in accs.iter_mut().enumerate() {
+                    if masked {
+                        visit(acc, band, None);
+                        continue;
+                    }
+
+                    let value = if layout.planar {
+                        PixelReader::read_as_f64_from_tile(&tiles[band * chunk_len + k], pixel, layout.data_type)?
+                    } else {
+                        PixelReader::read_as_f64_from_tile(&tiles[k], pixel * layout.bands + band, layout.data_type)?
+                    };
+
+                    visit(acc, band, (!GeoInfo::is_nodata_value(value, self.nodata)).then_some(value));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{build_geotiff_images, open_fixture_tiff, write_geotiff, FixtureSpec};
+
+    fn open_bytes(bytes: Vec<u8>) -> (tempfile::NamedTempFile, TiffReader, Tiff) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, &bytes).unwrap();
+        let (reader, tiff) = open_fixture_tiff(file.path());
+        (file, reader, tiff)
+    }
+
+    #[test]
+    fn test_exact_statistics_with_nodata() {
+        let mut spec = FixtureSpec::new(40, 24);
+        spec.data_type = DataType::F32;
+        spec.nodata = Some(-1.0);
+        let file = write_geotiff(&spec, |x, y| if y == 0 { -1.0 } else { (x % 10) as f64 });
+
+        let (mut reader, tiff) = open_fixture_tiff(file.path());
+        let options = StatisticsOptions {
+            percentiles: vec![0.0, 25.0, 50.0, 90.0],
+            exact_percentiles: true,
+            chunk_tiles: 2,
+            ..Default::default()
+        };
+        let stats = compute_statistics(&mut reader, &tiff, &options).unwrap();
+        let band = &stats[0];
+
+        assert_eq!((band.count, band.excluded), (920, 40));
+        assert_eq!((band.min, band.max), (Some(0.0), Some(9.0)));
+        assert!((band.mean.unwrap() - 4.5).abs() < 1e-9);
+        assert!((band.std.unwrap() - 8.25f64.sqrt()).abs() < 1e-9);
+        let expected = [(0.0, 0.0), (25.0, 2.0), (50.0, 4.5), (90.0, 8.1)];
+        for ((p, value), (ep, ev)) in band.percentiles.iter().zip(expected) {
+            assert_eq!(*p, ep);
+            assert!((value - ev).abs() < 1e-9, "p{}: {}", p, value);
+        }
+
+        let histogram = band.histogram.as_ref().unwrap();
+        assert_eq!(histogram.counts.len(), 256);
+        assert_eq!(histogram.total(), 920);
+        assert!(!band.approximate);
+
+        write_statistics(file.path(), &stats).unwrap();
+        let (mut reader, tiff) = open_fixture_tiff(file.path());
+        let metadata = GdalMetadata::from_ifd(tiff.main_ifd().unwrap(), &mut reader).unwrap().unwrap();
+        assert!(metadata.get("STATISTICS_MEAN", Some(0)).unwrap().starts_with("4.4999"));
+        assert!(metadata.get("STATISTICS_APPROXIMATE", Some(0)).is_none());
+        assert_eq!(compute_statistics(&mut reader, &tiff, &options).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_multiband_mask_and_overview() {
+        let mut full = FixtureSpec::new(64, 64);
+        full.bands = 2;
+        let mut overview = FixtureSpec::new(32, 32);
+        overview.bands = 2;
+        overview.subfile_type = 1;
+        let mut mask = FixtureSpec::new(64, 64);
+        mask.subfile_type = 4;
+
+        let bytes = build_geotiff_images(&[
+            (&full, &|x, _, band| if band == 0 { (x % 4) as f64 } else { 200.0 }),
+            (&overview, &|_, _, band| if band == 0 { 7.0 } else { 100.0 }),
+            (&mask, &|x, _, _| if x < 32 { 255.0 } else { 0.0 }),
+        ]);
+        let (_file, mut reader, tiff) = open_bytes(bytes);
+
+        let stats = compute_statistics(&mut reader, &tiff, &StatisticsOptions {
+            percentiles: vec![50.0],
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].count, stats[0].excluded), (2048, 2048));
+        assert_eq!(stats[0].valid_percent(), 50.0);
+        assert!((stats[0].mean.unwrap() - 1.5).abs() < 1e-9);
+        assert_eq!(stats[1].max, Some(200.0));
+
+        // One bin per integer value, [-0.5, 3.5]: rank 1023.5 ends the bin of 1
+        let histogram = stats[0].histogram.as_ref().unwrap();
+        assert_eq!(histogram.counts, vec![512, 512, 512, 512]);
+        assert_eq!(stats[0].percentiles[0], (50.0, 1.5));
+
+        let approximate = compute_statistics(&mut reader, &tiff, &StatisticsOptions {
+            approximate: true,
+            approx_min_size: 8,
+            ..Default::default()
+        }).unwrap();
+        assert!(approximate[0].approximate);
+        assert_eq!(approximate[0].mean, Some(7.0));
+        assert_eq!(approximate[1].count, 1024);
+        assert_eq!(statistics_metadata(&approximate).get("STATISTICS_APPROXIMATE", Some(1)), Some("YES"));
+    }
+
+    #[test]
+    fn test_exact_percentiles_refine_shared_bins() {
+        // Two histogram bins for 960 distinct values, so every rank needs refinement
+        let mut spec = FixtureSpec::new(40, 24);
+        spec.data_type = DataType::F32;
+        let value = |x: u64, y: u64| ((x * 24 + y) * 37 % 960) as f64 * 0.25;
+        let file = write_geotiff(&spec, value);
+        let (mut reader, tiff) = open_fixture_tiff(file.path());
+
+        let percentiles = vec![0.0, 10.0, 33.3, 50.0, 99.0, 100.0];
+        let options = StatisticsOptions { bins: 2, percentiles: percentiles.clone(), exact_percentiles: true, ..Default::default() };
+        let stats = compute_statistics(&mut reader, &tiff, &options).unwrap();
+
+        let mut sorted: Vec<f64> = (0..24).flat_map(|y| (0..40).map(move |x| value(x, y))).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        for (&p, &(sp, v)) in percentiles.iter().zip(&stats[0].percentiles) {
+            let rank = p / 100.0 * (sorted.len() - 1) as f64;
+            let (low, high) = (sorted[rank.floor() as usize], sorted[rank.ceil() as usize]);
+            assert_eq!(sp, p);
+            assert!((v - (low + (high - low) * rank.fract())).abs() < 1e-9, "p{}: {}", p, v);
+        }
+    }
+
+    #[test]
+    fn test_exact_percentiles_unit_bins() {
+        let spec = FixtureSpec::new(20, 20);
+        let file = write_geotiff(&spec, |x, y| ((x + y) % 7) as f64);
+        let (mut reader, tiff) = open_fixture_tiff(file.path());
+
+        let options = StatisticsOptions { percentiles: vec![0.0, 50.0, 100.0], exact_percentiles: true, ..Default::default() };
+        let stats = compute_statistics(&mut reader, &tiff, &options).unwrap();
+        assert_eq!(stats[0].histogram.as_ref().unwrap().counts.len(), 7);
+        assert_eq!(stats[0].percentiles, vec![(0.0, 0.0), (50.0, 3.0), (100.0, 6.0)]);
+    }
+
+    #[test]
+    fn test_histogram_percentile() {
+        let mut histogram = Histogram::new(0.0, 10.0, 10);
+        for v in 0..10 {
+            let bin = histogram.bin_of(v as f64).unwrap();
+            histogram.counts[bin] += 1;
+        }
+        assert_eq!(histogram.bin_of(10.0), Some(9));
+        assert_eq!(histogram.bin_of(10.5), None);
+        assert_eq!(histogram.percentile(0.0), Some(0.5));
+        assert_eq!(histogram.percentile(100.0), Some(9.5));
+    }
+}
@@ -0,0 +1,271 @@
+//! Terrain derivatives of elevation models
+//!
+//! Every product is computed from the 3x3 neighbourhood of a cell using
+//! Horn's finite differences. Windows are read with a one-pixel halo, so
+//! results are seamless across tile boundaries. Cell spacing comes from
+//! [`GeoInfo::ground_pixel_size`] per row, which keeps slopes correct for
+//! rasters in degrees.
+//!
+//! Missing neighbours (NoData or outside the image) take the centre value;
+//! cells without an elevation stay NoData.
+
+use std::path::PathBuf;
+use crate::compression::Compression;
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, GeoTiffWriter};
+use crate::types::DataType;
+use super::raster::{map_blocks, Block, Raster};
+
+/// NoData value of the written products
+pub const TERRAIN_NODATA: f64 = -9999.0;
+
+/// Units of slope output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlopeUnits {
+    Degrees,
+    Percent,
+}
+
+/// A terrain product
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerrainProduct {
+    /// Steepest slope
+    Slope(SlopeUnits),
+    /// Downslope direction in degrees clockwise from north; flat cells are NoData
+    Aspect,
+    /// Illumination 0-255 for a light source at `azimuth` (clockwise from
+    /// north) and `altitude` above the horizon, both in degrees
+    Hillshade { azimuth: f64, altitude: f64 },
+    /// Total curvature in 1/100 elevation units, positive on convex cells
+    Curvature,
+    /// Terrain Ruggedness Index (Riley): root of the summed squared
+    /// differences to the eight neighbours
+    Tri,
+    /// Largest minus smallest value of the neighbourhood
+    Roughness,
+}
+
+/// Terrain processing parameters
+#[derive(Debug, Clone)]
+pub struct TerrainOptions {
+    /// Vertical exaggeration applied to slope, aspect and hillshade
+    pub z_factor: f64,
+    /// Output compression (`None` or `Deflate`)
+    pub compression: Compression,
+}
+
+impl Default for TerrainOptions {
+    fn default() -> Self {
+        Self { z_factor: 1.0, compression: Compression::Deflate }
+    }
+}
+
+/// Writes each requested product as a tiled F32 GeoTIFF on the input grid
+pub fn derive_terrain(
+    input: &mut Raster,
+    outputs: &[(TerrainProduct, PathBuf)],
+    options: &TerrainOptions,
+) -> Result<()> {
+    let geo_info = input.geo_info().clone();
+    if geo_info.affine_transform().is_none() {
+        return Err(Error::InvalidFormat("Missing geotransform".to_string()));
+    }
+
+    let mut config = input.output_config(DataType::F32, Some(TERRAIN_NODATA));
+    config.compression = options.compression;
+    let mut writers = outputs.iter()
+        .map(|(_, path)| GeoTiffWriter::create(path, config.clone()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let products: Vec<TerrainProduct> = outputs.iter().map(|(product, _)| *product).collect();
+    map_blocks(std::slice::from_mut(input), 1, &mut writers, |blocks| {
+        Ok(terrain_block(&blocks[0], &products, &geo_info, options.z_factor))
+    })?;
+
+    for writer in writers {
+        writer.finish()?;
+    }
+    Ok(())
+}
+
+/// Helper: Computes every product for the interior of a block
+fn terrain_block(block: &Block, products: &[TerrainProduct], geo_info: &GeoInfo, z_factor: f64) -> Vec<Vec<f64>> {
+    let mut outputs = vec![Vec::with_capacity(block.width * block.height); products.len()];
+
+    for row in 0..block.height as isize {
+        let (dx, dy) = geo_info.ground_pixel_size(block.y as f64 + row as f64 + 0.5)
+            .unwrap_or((1.0, 1.0));
+
+        for col in 0..block.width as isize {
+            let window = neighbourhood(block, col, row);
+            for (product, out) in products.iter().zip(outputs.iter_mut()) {
+                let value = window
+                    .map(|w| evaluate(*product, &w, dx, dy, z_factor))
+                    .unwrap_or(f64::NAN);
+                out.push(value);
+            }
+        }
+    }
+    outputs
+}
+
+/// Helper: Returns the 3x3 window row-major (a b c / d e f / g h i), or
+/// `None` when the centre is missing
+fn neighbourhood(block: &Block, col: isize, row: isize) -> Option<[f64; 9]> {
+    let centre = block.get(col, row);
+    if centre.is_nan() {
+        return None;
+    }
+
+    let mut window = [centre; 9];
+    for (i, value) in window.iter_mut().enumerate() {
+        let v = block.get(col + (i % 3) as isize - 1, row + (i / 3) as isize - 1);
+        if !v.is_nan() {
+            *value = v;
+        }
+    }
+    Some(window)
+}
+
+/// Helper: Evaluates one product on a 3x3 window with cell spacing (dx, dy)
+fn evaluate(product: TerrainProduct, w: &[f64; 9], dx: f64, dy: f64, z_factor: f64) -> f64 {
+    let [a, b, c, d, e, f, g, h, i] = *w;
+
+    // Horn gradients; rows grow southwards, so dz_dy is positive when the
+    // terrain rises towards the south
+    let dz_dx = ((c + 2.0 * f + i) - (a + 2.0 * d + g)) / (8.0 * dx) * z_factor;
+    let dz_dy = ((g + 2.0 * h + i) - (a + 2.0 * b + c)) / (8.0 * dy) * z_factor;
+    let gradient = dz_dx.hypot(dz_dy);
+
+    match product {
+        TerrainProduct::Slope(SlopeUnits::Degrees) => gradient.atan().to_degrees(),
+        TerrainProduct::Slope(SlopeUnits::Percent) => gradient * 100.0,
+        TerrainProduct::Aspect => {
+            if gradient == 0.0 {
+                return f64::NAN;
+            }
+            let angle = dz_dy.atan2(-dz_dx).to_degrees();
+            (90.0 - angle).rem_euclid(360.0)
+        }
+        TerrainProduct::Hillshade { azimuth, altitude } => {
+            let zenith = (90.0 - altitude).to_radians();
+            let light = (360.0 - azimuth + 90.0).rem_euclid(360.0).to_radians();
+            let slope = gradient.atan();
+            let aspect = dz_dy.atan2(-dz_dx);
+            let shade = zenith.cos() * slope.cos() + zenith.sin() * slope.sin() * (light - aspect).cos();
+            (255.0 * shade).max(0.0)
+        }
+        TerrainProduct::Curvature => {
+            let along_x = ((d + f) / 2.0 - e) / (dx * dx);
+            let along_y = ((b + h) / 2.0 - e) / (dy * dy);
+            -2.0 * (along_x + along_y) * 100.0
+        }
+        TerrainProduct::Tri => w.iter().map(|v| (v - e).powi(2)).sum::<f64>().sqrt(),
+        TerrainProduct::Roughness => {
+            let max = w.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let min = w.iter().copied().fold(f64::INFINITY, f64::min);
+            max - min
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+
+    const HILLSHADE: TerrainProduct = TerrainProduct::Hillshade { azimuth: 315.0, altitude: 45.0 };
+
+    fn read_all(path: &std::path::Path) -> (Vec<f64>, u64) {
+        let (mut reader, ifd) = open_fixture(path);
+        let dims = ifd.dimensions().unwrap();
+        (reader.read_window_f64(&ifd, 0, 0, dims.width, dims.height).unwrap(), dims.width)
+    }
+
+    #[test]
+    fn test_plane_across_tiles() {
+        // Rises 1 m per metre eastwards; 10 m pixels, so 10 m per pixel
+        let mut spec = FixtureSpec::new(40, 36);
+        spec.data_type = DataType::F32;
+        spec.pixel_size = (10.0, 10.0);
+        spec.nodata = Some(-1.0);
+        let file = write_geotiff(&spec, |x, y| if (x, y) == (5, 5) { -1.0 } else { x as f64 * 10.0 });
+
+        let dir = tempfile::tempdir().unwrap();
+        let outputs = vec![
+            (TerrainProduct::Slope(SlopeUnits::Degrees), dir.path().join("slope.tif")),
+            (TerrainProduct::Slope(SlopeUnits::Percent), dir.path().join("percent.tif")),
+            (TerrainProduct::Aspect, dir.path().join("aspect.tif")),
+            (TerrainProduct::Curvature, dir.path().join("curvature.tif")),
+            (TerrainProduct::Roughness, dir.path().join("roughness.tif")),
+        ];
+        let mut raster = Raster::open(file.path()).unwrap();
+        derive_terrain(&mut raster, &outputs, &TerrainOptions::default()).unwrap();
+
+        let (slope, width) = read_all(&outputs[0].1);
+        let at = |values: &[f64], x: u64, y: u64| values[(y * width + x) as usize];
+        // Interior cells on both sides of the tile seams at x=16 and y=16
+        for (x, y) in [(15, 15), (16, 16), (17, 30), (31, 1)] {
+            assert!((at(&slope, x, y) - 45.0).abs() < 1e-4);
+        }
+        assert_eq!(at(&slope, 5, 5), TERRAIN_NODATA);
+
+        let (percent, _) = read_all(&outputs[1].1);
+        assert!((at(&percent, 20, 20) - 100.0).abs() < 1e-3);
+
+        // Downslope faces west
+        let (aspect, _) = read_all(&outputs[2].1);
+        assert!((at(&aspect, 20, 20) - 270.0).abs() < 1e-4);
+
+        let (curvature, _) = read_all(&outputs[3].1);
+        assert!(at(&curvature, 20, 20).abs() < 1e-6);
+
+        let (roughness, _) = read_all(&outputs[4].1);
+        assert_eq!(at(&roughness, 20, 20), 20.0);
+        // The left edge replicates the centre for the missing column
+        assert_eq!(at(&roughness, 0, 20), 10.0);
+    }
+
+    #[test]
+    fn test_window_products() {
+        let flat = [5.0; 9];
+        let hill = HILLSHADE;
+        assert_eq!(evaluate(hill, &flat, 1.0, 1.0, 1.0).round(), (255.0 * 45f64.to_radians().cos()).round());
+        assert!(evaluate(TerrainProduct::Aspect, &flat, 1.0, 1.0, 1.0).is_nan());
+
+        // Peak: convex with a ruggedness of sqrt(8)
+        let peak = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+        assert_eq!(evaluate(TerrainProduct::Curvature, &peak, 1.0, 1.0, 1.0), 400.0);
+        assert!((evaluate(TerrainProduct::Tri, &peak, 1.0, 1.0, 1.0) - 8f64.sqrt()).abs() < 1e-12);
+
+        // Rising southwards faces north; lit from the north-west it is brighter
+        // than the same slope facing south
+        let north = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0];
+        let south = [2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0];
+        assert_eq!(evaluate(TerrainProduct::Aspect, &north, 1.0, 1.0, 1.0), 0.0);
+        assert_eq!(evaluate(TerrainProduct::Aspect, &south, 1.0, 1.0, 1.0), 180.0);
+        assert!(evaluate(hill, &north, 1.0, 1.0, 1.0) > evaluate(hill, &south, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_geographic_spacing() {
+        // 0.001 degree pixels at 60N rising 1 m per pixel eastwards
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.data_type = DataType::F32;
+        spec.epsg = 4326;
+        spec.origin = (10.0, 60.016);
+        spec.pixel_size = (0.001, 0.001);
+        let file = write_geotiff(&spec, |x, _| x as f64);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slope.tif");
+        let mut raster = Raster::open(file.path()).unwrap();
+        let options = TerrainOptions { compression: Compression::None, ..Default::default() };
+        derive_terrain(&mut raster, &[(TerrainProduct::Slope(SlopeUnits::Percent), path.clone())], &options).unwrap();
+
+        let (slope, width) = read_all(&path);
+        let metres = (0.001f64).to_radians() * crate::projection::coordinate::EARTH_RADIUS_M * 60.008f64.to_radians().cos();
+        let expected = 100.0 / metres;
+        assert!((slope[(8 * width + 8) as usize] - expected).abs() < 1e-3);
+    }
+}
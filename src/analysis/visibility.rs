@@ -0,0 +1,432 @@
+//! Line of sight and viewsheds over a DEM
+//!
+//! Elevations are taken from the nearest pixel. Distances are ground metres
+//! (see [`GeoInfo::ground_pixel_size`]). With curvature correction enabled,
+//! terrain at distance `d` is lowered by `d² (1 - k) / 2R`, where `k` is the
+//! refraction coefficient and `R` the earth radius.
+
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, GeoReference, TiffReader, IFD};
+use crate::projection::coordinate::EARTH_RADIUS_M;
+use crate::projection::Coordinate;
+use super::rasterize::Grid;
+
+/// Viewshed cell values
+pub const VIEWSHED_VISIBLE: u8 = 1;
+pub const VIEWSHED_HIDDEN: u8 = 0;
+/// Cells outside the radius or without elevation
+pub const VIEWSHED_NODATA: u8 = 255;
+
+/// Default cap on the viewshed window, in cells (128 MiB of elevations)
+pub const DEFAULT_MAX_VIEWSHED_CELLS: u64 = 4096 * 4096;
+
+/// Observer, target and earth model parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisibilityOptions {
+    /// Observer height above ground in metres
+    pub observer_height: f64,
+    /// Target height above ground in metres
+    pub target_height: f64,
+    /// Apply earth curvature and refraction
+    pub curvature: bool,
+    /// Refraction coefficient
+    pub refraction: f64,
+    /// Largest viewshed window in cells; wider radii are rejected
+    pub max_viewshed_cells: u64,
+}
+
+impl Default for VisibilityOptions {
+    fn default() -> Self {
+        Self {
+            observer_height: 1.7,
+            target_height: 0.0,
+            curvature: true,
+            refraction: 0.13,
+            max_viewshed_cells: DEFAULT_MAX_VIEWSHED_CELLS,
+        }
+    }
+}
+
+impl VisibilityOptions {
+    /// Helper: Apparent drop of the terrain at a ground distance
+    fn drop_at(&self, distance: f64) -> f64 {
+        if self.curvature {
+            distance * distance * (1.0 - self.refraction) / (2.0 * EARTH_RADIUS_M)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// One sample of a line-of-sight profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfilePoint {
+    /// Ground distance from the observer in metres
+    pub distance: f64,
+    /// Position in the raster CRS
+    pub coordinate: Coordinate,
+    /// Terrain elevation, NaN for NoData
+    pub elevation: f64,
+    /// Height of the sight line, in the same frame as `elevation`
+    pub sight_line: f64,
+    /// Whether a target of the configured height here would be seen
+    pub visible: bool,
+}
+
+/// Result of a line-of-sight query
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineOfSight {
+    /// Samples from observer to target, one per pixel step
+    pub profile: Vec<ProfilePoint>,
+    /// Whether the target is visible from the observer
+    pub visible: bool,
+    /// First sample where terrain rises above the sight line
+    pub obstruction: Option<ProfilePoint>,
+}
+
+/// Traces the sight line between two points given in `epsg`
+///
+/// Both points must lie inside the raster and the observer on valid
+/// elevation; NoData samples along the way never obstruct.
+pub fn line_of_sight(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    observer: Coordinate,
+    target: Coordinate,
+    epsg: u16,
+    options: &VisibilityOptions,
+) -> Result<LineOfSight> {
+    let (geo_info, width, height) = dem_info(reader, ifd)?;
+    let transform = geo_info.affine_transform()
+        .ok_or_else(|| Error::InvalidFormat("Missing geotransform".to_string()))?;
+    let ends = geo_info.transform_crs_to_pixel_batch(&[observer, target], epsg)?;
+    let inside = |(x, y): (f64, f64)| x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64;
+    if !ends.iter().all(|&p| inside(p)) {
+        return Err(Error::InvalidFormat("Line of sight end point outside the raster".to_string()));
+    }
+
+    // One sample per pixel step along the major axis
+    let ((x0, y0), (x1, y1)) = (ends[0], ends[1]);
+    let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+    let positions: Vec<(f64, f64)> = (0..=steps)
+        .map(|i| {
+            let t = i as f64 / steps as f64;
+            (x0 + t * (x1 - x0), y0 + t * (y1 - y0))
+        })
+        .collect();
+    let cells: Vec<(u64, u64)> = positions.iter()
+        .map(|&(x, y)| ((x as u64).min(width - 1), (y as u64).min(height - 1)))
+        .collect();
+    let elevations: Vec<f64> = reader.read_pixels_batch_f64(ifd, &cells)?
+        .into_iter()
+        .map(|v| if geo_info.is_nodata(v) { f64::NAN } else { v })
+        .collect();
+
+    let mut distances = Vec::with_capacity(positions.len());
+    let mut distance = 0.0;
+    for (i, &(x, y)) in positions.iter().enumerate() {
+        if i > 0 {
+            let (px, py) = positions[i - 1];
+            let (sx, sy) = geo_info.ground_pixel_size((y + py) / 2.0).unwrap_or((1.0, 1.0));
+            distance += ((x - px) * sx).hypot((y - py) * sy);
+        }
+        distances.push(distance);
+    }
+
+    let eye = elevations[0] + options.observer_height;
+    if eye.is_nan() {
+        return Err(Error::InvalidFormat("Observer has no elevation".to_string()));
+    }
+    if elevations[steps].is_nan() {
+        return Err(Error::InvalidFormat("Target has no elevation".to_string()));
+    }
+    let total = distances[steps];
+    let end = elevations[steps] + options.target_height - options.drop_at(total);
+
+    let mut profile = Vec::with_capacity(positions.len());
+    let mut obstruction = None;
+    let mut max_slope = f64::NEG_INFINITY;
+    for (i, (&(x, y), &elevation)) in positions.iter().zip(&elevations).enumerate() {
+        let d = distances[i];
+        let drop = options.drop_at(d);
+        let sight = if total > 0.0 { eye + (end - eye) * d / total } else { eye };
+        let ground = elevation - drop;
+
+        let visible = i == 0 || (d > 0.0 && (ground + options.target_height - eye) / d >= max_slope);
+        let point = ProfilePoint {
+            distance: d,
+            coordinate: Coordinate::new(
+                transform[0] + x * transform[1] + y * transform[2],
+                transform[3] + x * transform[4] + y * transform[5],
+            ),
+            elevation,
+            sight_line: sight + drop,
+            visible: visible && !elevation.is_nan(),
+        };
+        if i > 0 && i < steps && obstruction.is_none() && ground > sight {
+            obstruction = Some(point);
+        }
+        if d > 0.0 && !ground.is_nan() {
+            max_slope = max_slope.max((ground - eye) / d);
+        }
+        profile.push(point);
+    }
+
+    Ok(LineOfSight { profile, visible: obstruction.is_none(), obstruction })
+}
+
+/// Computes which cells within `radius` metres are visible from an observer
+///
+/// Rays are cast from the observer to every cell on the border of the
+/// window around it, tracking the steepest terrain slope so far; a cell
+/// crossed by several rays is visible if any of them sees it. The result
+/// covers that window with [`VIEWSHED_VISIBLE`], [`VIEWSHED_HIDDEN`] or
+/// [`VIEWSHED_NODATA`]. Ground distances use the pixel size halfway between
+/// the observer's and the cell's row, so the window widens towards the poles
+/// on geographic DEMs. Windows over `max_viewshed_cells` are rejected.
+pub fn viewshed(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    observer: Coordinate,
+    epsg: u16,
+    radius: f64,
+    options: &VisibilityOptions,
+) -> Result<Grid<u8>> {
+    let (geo_info, width, height) = dem_info(reader, ifd)?;
+    let (ox, oy) = geo_info.transform_crs_to_pixel_batch(&[observer], epsg)?[0];
+    if ox < 0.0 || oy < 0.0 || ox >= width as f64 || oy >= height as f64 {
+        return Err(Error::InvalidFormat("Observer outside the raster".to_string()));
+    }
+    if !radius.is_finite() || radius <= 0.0 {
+        return Err(Error::InvalidFormat("Viewshed radius must be positive".to_string()));
+    }
+    let (cx, cy) = (ox as u64, oy as u64);
+    // Ground pixel size halfway between the observer row and row `y`
+    let spacing = |y: f64| geo_info.ground_pixel_size((cy as f64 + y) / 2.0 + 0.5)
+        .ok_or_else(|| Error::InvalidFormat("Missing pixel scale".to_string()));
+
+    // Rows within the radius, then the widest column reach among them
+    let reach = |step: i64, limit: u64| -> Result<u64> {
+        let mut rows = 0;
+        while rows < limit && (rows + 1) as f64 * spacing((cy as i64 + step * (rows as i64 + 1)) as f64)?.1 <= radius {
+            rows += 1;
+        }
+        Ok(rows)
+    };
+    let (y0, y1) = (cy - reach(-1, cy)?, cy + reach(1, height - 1 - cy)? + 1);
+    let mut rx = 0;
+    for y in y0..y1 {
+        rx = rx.max((radius / spacing(y as f64)?.0).floor().min(width as f64) as u64);
+    }
+    let (x0, x1) = (cx.saturating_sub(rx), (cx + rx + 1).min(width));
+    let (w, h) = (x1 - x0, y1 - y0);
+    if w * h > options.max_viewshed_cells {
+        return Err(Error::InvalidFormat(format!(
+            "Viewshed window of {}x{} cells exceeds {} cells", w, h, options.max_viewshed_cells
+        )));
+    }
+    let elevations: Vec<f64> = reader.read_window_f64(ifd, x0, y0, w, h)?
+        .into_iter()
+        .map(|v| if geo_info.is_nodata(v) { f64::NAN } else { v })
+        .collect();
+
+    let (ocol, orow) = ((cx - x0) as i64, (cy - y0) as i64);
+    let eye = elevations[(orow as u64 * w + ocol as u64) as usize] + options.observer_height;
+    if eye.is_nan() {
+        return Err(Error::InvalidFormat("Observer has no elevation".to_string()));
+    }
+
+    let mut cells = vec![VIEWSHED_NODATA; (w * h) as usize];
+    cells[(orow as u64 * w + ocol as u64) as usize] = VIEWSHED_VISIBLE;
+
+    // Indexed by the sum of two window rows, i.e. per half row
+    let spacings: Vec<(f64, f64)> = (0..2 * h - 1)
+        .map(|k| spacing((2 * y0 + k) as f64 - cy as f64))
+        .collect::<Result<_>>()?;
+
+    let (w, h) = (w as i64, h as i64);
+    let border = (0..w).flat_map(|c| [(c, 0), (c, h - 1)])
+        .chain((1..h - 1).flat_map(|r| [(0, r), (w - 1, r)]));
+    for (ec, er) in border {
+        let (dc, dr) = (ec - ocol, er - orow);
+        let steps = dc.abs().max(dr.abs());
+        let mut max_slope = f64::NEG_INFINITY;
+        for s in 1..=steps {
+            let col = ocol + (dc as f64 * s as f64 / steps as f64).round() as i64;
+            let row = orow + (dr as f64 * s as f64 / steps as f64).round() as i64;
+            let (sx, sy) = spacings[(row + orow) as usize];
+            let d = ((col - ocol) as f64 * sx).hypot((row - orow) as f64 * sy);
+            if d > radius {
+                break;
+            }
+            let index = (row * w + col) as usize;
+            let ground = elevations[index] - options.drop_at(d);
+            if ground.is_nan() {
+                continue;
+            }
+            if (ground + options.target_height - eye) / d >= max_slope {
+                cells[index] = VIEWSHED_VISIBLE;
+            } else if cells[index] == VIEWSHED_NODATA {
+                cells[index] = VIEWSHED_HIDDEN;
+            }
+            max_slope = max_slope.max((ground - eye) / d);
+        }
+    }
+
+    let mut georef = GeoReference::from_geo_info(&geo_info)
+        .ok_or_else(|| Error::InvalidFormat("Missing geotransform or EPSG code".to_string()))?;
+    georef.origin.0 += x0 as f64 * georef.pixel_size.0;
+    georef.origin.1 -= y0 as f64 * georef.pixel_size.1;
+    Ok(Grid {
+        width: w as u64,
+        height: h as u64,
+        georef,
+        nodata: Some(VIEWSHED_NODATA as f64),
+        data: cells,
+    })
+}
+
+/// Helper: Reads georeferencing and size of a DEM image
+fn dem_info(reader: &mut TiffReader, ifd: &IFD) -> Result<(GeoInfo, u64, u64)> {
+    let geo_info = GeoInfo::from_ifd(ifd, reader)?
+        .ok_or_else(|| Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+    let dims = ifd.dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+    Ok((geo_info, dims.width, dims.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+    use crate::types::DataType;
+
+    /// Flat DEM at 0 with a 50 m north-south wall in column 20 and a NoData cell at (40, 5)
+    fn wall_dem() -> (tempfile::NamedTempFile, TiffReader, IFD) {
+        let mut spec = FixtureSpec::new(48, 32);
+        spec.data_type = DataType::I16;
+        spec.nodata = Some(-9999.0);
+        let file = write_geotiff(&spec, |x, y| match (x, y) {
+            (20, _) => 50.0,
+            (40, 5) => -9999.0,
+            _ => 0.0,
+        });
+        let (reader, ifd) = open_fixture(file.path());
+        (file, reader, ifd)
+    }
+
+    /// Centre of pixel (col, row) in the fixture CRS
+    fn centre(col: f64, row: f64) -> Coordinate {
+        Coordinate::new(col + 0.5, 32.0 - row - 0.5)
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let (_file, mut reader, ifd) = wall_dem();
+        let options = VisibilityOptions::default();
+
+        let clear = line_of_sight(&mut reader, &ifd, centre(5.0, 16.0), centre(15.0, 16.0), 3857, &options).unwrap();
+        assert!(clear.visible);
+        assert_eq!(clear.profile.len(), 11);
+        assert!((clear.profile[10].distance - 10.0).abs() < 1e-3);
+
+        let blocked = line_of_sight(&mut reader, &ifd, centre(5.0, 16.0), centre(30.0, 10.0), 3857, &options).unwrap();
+        assert!(!blocked.visible);
+        let obstruction = blocked.obstruction.unwrap();
+        assert_eq!(obstruction.elevation, 50.0);
+        assert!((obstruction.coordinate.x - 20.5).abs() < 0.5);
+        assert!(blocked.profile.iter().filter(|p| p.coordinate.x > 21.0).all(|p| !p.visible));
+
+        // A tall enough target shows over the wall
+        let mast = VisibilityOptions { target_height: 200.0, ..options };
+        assert!(line_of_sight(&mut reader, &ifd, centre(5.0, 16.0), centre(30.0, 16.0), 3857, &mast).unwrap().visible);
+
+        assert!(line_of_sight(&mut reader, &ifd, centre(5.0, 16.0), centre(60.0, 16.0), 3857, &options).is_err());
+        assert!(line_of_sight(&mut reader, &ifd, centre(40.0, 5.0), centre(30.0, 5.0), 3857, &options).is_err());
+    }
+
+    #[test]
+    fn test_target_without_elevation() {
+        let (_file, mut reader, ifd) = wall_dem();
+        let options = VisibilityOptions::default();
+        let result = line_of_sight(&mut reader, &ifd, centre(30.0, 5.0), centre(40.0, 5.0), 3857, &options);
+        assert!(matches!(result, Err(Error::InvalidFormat(ref message)) if message == "Target has no elevation"));
+    }
+
+    #[test]
+    fn test_earth_curvature() {
+        // 1 km pixels over flat terrain near the equator
+        let mut spec = FixtureSpec::new(64, 32);
+        spec.pixel_size = (1000.0, 1000.0);
+        spec.origin = (0.0, 32000.0);
+        let file = write_geotiff(&spec, |_, _| 0.0);
+        let (mut reader, ifd) = open_fixture(file.path());
+
+        let (from, to) = (Coordinate::new(500.0, 16500.0), Coordinate::new(40500.0, 16500.0));
+        let flat = VisibilityOptions { observer_height: 10.0, target_height: 10.0, curvature: false, ..Default::default() };
+        assert!(line_of_sight(&mut reader, &ifd, from, to, 3857, &flat).unwrap().visible);
+
+        // 40 km apart, 10 m masts are hidden by the bulge
+        let curved = VisibilityOptions { curvature: true, ..flat };
+        assert!(!line_of_sight(&mut reader, &ifd, from, to, 3857, &curved).unwrap().visible);
+        assert!((curved.drop_at(40_000.0) - 109.2).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_viewshed() {
+        let (_file, mut reader, ifd) = wall_dem();
+        let grid = viewshed(&mut reader, &ifd, centre(10.0, 16.0), 3857, 15.0, &VisibilityOptions::default()).unwrap();
+
+        // Window of +-15 pixels, clipped to the image
+        assert_eq!((grid.width, grid.height), (26, 31));
+        assert_eq!(grid.georef.origin, (0.0, 31.0));
+        let at = |col: u64, row: u64| grid.get(col, row - 1).unwrap();
+        assert_eq!(at(10, 16), VIEWSHED_VISIBLE);
+        assert_eq!(at(18, 16), VIEWSHED_VISIBLE);
+        assert_eq!(at(20, 16), VIEWSHED_VISIBLE);
+        assert_eq!(at(22, 16), VIEWSHED_HIDDEN);
+        assert_eq!(at(23, 12), VIEWSHED_HIDDEN);
+        assert_eq!(at(0, 16), VIEWSHED_VISIBLE);
+        // Beyond the radius
+        assert_eq!(at(0, 1), VIEWSHED_NODATA);
+    }
+
+    #[test]
+    fn test_geographic_viewshed_uses_row_spacing() {
+        // Flat DEM of 0.05 degree pixels around 61N
+        let mut spec = FixtureSpec::new(160, 80);
+        spec.epsg = 4326;
+        spec.origin = (10.0, 63.0);
+        spec.pixel_size = (0.05, 0.05);
+        let file = write_geotiff(&spec, |_, _| 0.0);
+        let (mut reader, ifd) = open_fixture(file.path());
+        let geo_info = GeoInfo::from_ifd(&ifd, &mut reader).unwrap().unwrap();
+
+        let options = VisibilityOptions { curvature: false, ..Default::default() };
+        let radius = 200_000.0;
+        let grid = viewshed(&mut reader, &ifd, Coordinate::new(14.025, 60.975), 4326, radius, &options).unwrap();
+
+        // The window reaches further east and west than the observer row alone allows
+        let (sx, _) = geo_info.ground_pixel_size(40.5).unwrap();
+        assert!(grid.width > 2 * (radius / sx) as u64 + 1);
+
+        let x0 = ((grid.georef.origin.0 - 10.0) / 0.05).round() as i64;
+        let y0 = ((63.0 - grid.georef.origin.1) / 0.05).round() as i64;
+        for row in 0..grid.height as i64 {
+            let (sx, sy) = geo_info.ground_pixel_size((40 + y0 + row) as f64 / 2.0 + 0.5).unwrap();
+            for col in 0..grid.width as i64 {
+                let d = ((x0 + col - 80) as f64 * sx).hypot((y0 + row - 40) as f64 * sy);
+                let cell = grid.get(col as u64, row as u64).unwrap();
+                if d > radius {
+                    assert_eq!(cell, VIEWSHED_NODATA, "({}, {}) at {} m", col, row, d);
+                } else if d < 0.98 * radius {
+                    assert_eq!(cell, VIEWSHED_VISIBLE, "({}, {}) at {} m", col, row, d);
+                }
+            }
+        }
+
+        let capped = VisibilityOptions { max_viewshed_cells: 1000, ..options };
+        assert!(viewshed(&mut reader, &ifd, Coordinate::new(14.025, 60.975), 4326, radius, &capped).is_err());
+        assert!(viewshed(&mut reader, &ifd, Coordinate::new(14.025, 60.975), 4326, 10_000.0, &capped).is_ok());
+    }
+}
@@ -0,0 +1,286 @@
+//! Zonal statistics over polygons
+//!
+//! Polygons are reprojected into the raster CRS, rasterized against the IFD
+//! grid with exact per-pixel coverage, and only the tiles holding covered
+//! pixels are read (in parallel).
+
+use std::collections::HashMap;
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, TiffReader, IFD};
+use crate::formats::tiff::reader::pixels::PixelReader;
+use crate::projection::Transformer;
+use crate::vector::Geometry;
+use super::coverage::{pixel_rings, PolygonCoverage};
+use super::percentile_sorted;
+
+/// Options controlling which pixels count towards a zone
+#[derive(Debug, Clone, Default)]
+pub struct ZonalOptions {
+    /// Percentiles to compute, in the range 0-100
+    pub percentiles: Vec<f64>,
+    /// Count every touched pixel in the unweighted statistics, not only
+    /// pixels whose centre lies inside the polygon
+    pub all_touched: bool,
+}
+
+/// Statistics of the raster values inside one geometry
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZonalStats {
+    /// Number of valid pixels
+    pub count: usize,
+    /// Number of NoData pixels that would otherwise have counted
+    pub nodata_count: usize,
+    pub sum: f64,
+    pub mean: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Population standard deviation
+    pub std: Option<f64>,
+    /// (percentile, value) pairs in the requested order
+    pub percentiles: Vec<(f64, f64)>,
+    /// Most frequent value (smallest on ties)
+    pub majority: Option<f64>,
+    /// Sum of covered pixel fractions over valid pixels
+    pub coverage: f64,
+    /// Sum of values weighted by covered fraction
+    pub weighted_sum: f64,
+    /// Coverage-weighted mean
+    pub weighted_mean: Option<f64>,
+}
+
+impl ZonalStats {
+    /// Statistics of a zone without any valid pixels
+    pub fn empty() -> Self {
+        Self {
+            count: 0,
+            nodata_count: 0,
+            sum: 0.0,
+            mean: None,
+            min: None,
+            max: None,
+            std: None,
+            percentiles: Vec::new(),
+            majority: None,
+            coverage: 0.0,
+            weighted_sum: 0.0,
+            weighted_mean: None,
+        }
+    }
+}
+
+/// Computes zonal statistics for each geometry
+///
+/// Geometries are given in `epsg` and reprojected into the raster CRS when
+/// it differs. Points and lines yield empty statistics.
+pub fn zonal_statistics(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    geometries: &[Geometry],
+    epsg: u16,
+    options: &ZonalOptions,
+) -> Result<Vec<ZonalStats>> {
+    let geo_info = GeoInfo::from_ifd(ifd, reader)?
+        .ok_or_else(|| Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+    let dims = ifd.dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+
+    let transformer = match geo_info.epsg_code {
+        Some(raster_epsg) if raster_epsg != epsg => Some(Transformer::new(epsg, raster_epsg)?),
+        _ => None,
+    };
+
+    geometries.iter()
+        .map(|geometry| {
+            let polygons = geometry.polygons();
+            let rings = pixel_rings(&polygons, &geo_info, transformer.as_ref())?;
+
+            match PolygonCoverage::compute(&rings, dims.width, dims.height) {
+                Some(coverage) => zone_stats(reader, ifd, &geo_info, &coverage, options),
+                None => Ok(ZonalStats::empty()),
+            }
+        })
+        .collect()
+}
+
+/// Helper: Reads the tiles under a coverage window and summarizes them
+fn zone_stats(
+    reader: &mut TiffReader,
+    ifd: &IFD,
+    geo_info: &GeoInfo,
+    coverage: &PolygonCoverage,
+    options: &ZonalOptions,
+) -> Result<ZonalStats> {
+    PixelReader::validate_tiled_access(ifd)?;
+    let data_type = PixelReader::require_data_type(ifd)?;
+    let tile_dims = ifd.tile_dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing tile dimensions".to_string()))?;
+
+    let counts = |fraction: f32, centre: bool| {
+        if options.all_touched { fraction > 0.0 } else { centre }
+    };
+
+    // Tiles overlapping the window that hold at least one contributing pixel
+    let mut tiles = Vec::new();
+    let x_end = coverage.x0 + coverage.width;
+    let y_end = coverage.y0 + coverage.height;
+    for tile_row in coverage.y0 / tile_dims.height..=(y_end - 1) / tile_dims.height {
+        for tile_col in coverage.x0 / tile_dims.width..=(x_end - 1) / tile_dims.width {
+            let cols = (tile_col * tile_dims.width).max(coverage.x0)..((tile_col + 1) * tile_dims.width).min(x_end);
+            let rows = (tile_row * tile_dims.height).max(coverage.y0)..((tile_row + 1) * tile_dims.height).min(y_end);
+
+            let used = rows.clone().any(|row| cols.clone().any(|col| {
+                let idx = coverage.index_of(col, row).unwrap_or_default();
+                coverage.fraction[idx] > 0.0 || coverage.centre[idx]
+            }));
+            if used {
+                let index = PixelReader::calculate_tile_index(ifd, tile_col * tile_dims.width, tile_row * tile_dims.height)?;
+                tiles.push((index, cols, rows));
+            }
+        }
+    }
+
+    let indices: Vec<usize> = tiles.iter().map(|(index, _, _)| *index).collect();
+    let tile_data = reader.read_tiles_direct(ifd, &indices)?;
+
+    let mut values = Vec::new();
+    let mut nodata_count = 0;
+    let mut weighted_sum = 0.0;
+    let mut weight = 0.0;
+
+    for ((_, cols, rows), data) in tiles.into_iter().zip(tile_data.iter()) {
+        for row in rows {
+            for col in cols.clone() {
+                let idx = coverage.index_of(col, row).unwrap_or_default();
+                let fraction = coverage.fraction[idx];
+                let counted = counts(fraction, coverage.centre[idx]);
+                if fraction <= 0.0 && !counted {
+                    continue;
+                }
+
+                let pixel_index = PixelReader::calculate_pixel_index(ifd, col, row)?;
+                let value = PixelReader::read_as_f64_from_tile(data, pixel_index, data_type)?;
+                if geo_info.is_nodata(value) {
+                    if counted {
+                        nodata_count += 1;
+                    }
+                    continue;
+                }
+
+                if counted {
+                    values.push(value);
+                }
+                weighted_sum += value * fraction as f64;
+                weight += fraction as f64;
+            }
+        }
+    }
+
+    let mut stats = summarize(values, &options.percentiles);
+    stats.nodata_count = nodata_count;
+    stats.coverage = weight;
+    stats.weighted_sum = weighted_sum;
+    stats.weighted_mean = (weight > 0.0).then(|| weighted_sum / weight);
+    Ok(stats)
+}
+
+/// Helper: Computes the unweighted statistics of a value list
+pub(crate) fn summarize(mut values: Vec<f64>, percentiles: &[f64]) -> ZonalStats {
+    let mut stats = ZonalStats::empty();
+    if values.is_empty() {
+        return stats;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    let count = values.len();
+    let sum: f64 = values.iter().sum();
+    let mean = sum / count as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+
+    let mut frequencies: HashMap<u64, usize> = HashMap::new();
+    for value in &values {
+        *frequencies.entry(value.to_bits()).or_default() += 1;
+    }
+    // Values are sorted, so the first value reaching the top count is the smallest
+    let top = frequencies.values().copied().max().unwrap_or(0);
+    let majority = values.iter().copied().find(|v| frequencies[&v.to_bits()] == top);
+
+    stats.count = count;
+    stats.sum = sum;
+    stats.mean = Some(mean);
+    stats.min = values.first().copied();
+    stats.max = values.last().copied();
+    stats.std = Some(variance.sqrt());
+    stats.percentiles = percentiles.iter()
+        .map(|&p| (p, percentile_sorted(&values, p)))
+        .collect();
+    stats.majority = majority;
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{open_fixture, write_geotiff, FixtureSpec};
+    use crate::types::DataType;
+    use crate::vector::Polygon;
+
+    fn open(spec: &FixtureSpec, value: impl Fn(u64, u64) -> f64) -> (tempfile::NamedTempFile, TiffReader, IFD) {
+        let file = write_geotiff(spec, value);
+        let (reader, ifd) = open_fixture(file.path());
+        (file, reader, ifd)
+    }
+
+    #[test]
+    fn test_rectangle_across_tiles() {
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.data_type = DataType::F32;
+        let (_file, mut reader, ifd) = open(&spec, |x, _| x as f64);
+
+        // Pixel columns 10..20 and rows 22..32 (y grows upwards in the CRS)
+        let zone = Geometry::Polygon(Polygon::rectangle(10.0, 0.0, 20.0, 10.0));
+        let options = ZonalOptions { percentiles: vec![0.0, 50.0, 100.0], all_touched: false };
+        let stats = zonal_statistics(&mut reader, &ifd, &[zone], 3857, &options).unwrap();
+        let stats = &stats[0];
+
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.sum, 1450.0);
+        assert_eq!(stats.mean, Some(14.5));
+        assert_eq!(stats.min, Some(10.0));
+        assert_eq!(stats.max, Some(19.0));
+        assert!((stats.std.unwrap() - 8.25f64.sqrt()).abs() < 1e-9);
+        assert_eq!(stats.percentiles, vec![(0.0, 10.0), (50.0, 14.5), (100.0, 19.0)]);
+        assert_eq!(stats.majority, Some(10.0));
+        assert!((stats.coverage - 100.0).abs() < 1e-6);
+        assert_eq!(stats.weighted_mean, Some(14.5));
+    }
+
+    #[test]
+    fn test_partial_coverage_and_nodata() {
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.nodata = Some(0.0);
+        let (_file, mut reader, ifd) = open(&spec, |x, _| if x == 3 { 0.0 } else { 5.0 });
+
+        // Covers columns 1..4 fully and half of column 4 over rows 0..2
+        let zone = Geometry::Polygon(Polygon::rectangle(1.0, 30.0, 4.5, 32.0));
+        let centre = zonal_statistics(&mut reader, &ifd, std::slice::from_ref(&zone), 3857, &ZonalOptions::default()).unwrap();
+        assert_eq!(centre[0].count, 4);
+        assert_eq!(centre[0].nodata_count, 2);
+        assert!((centre[0].coverage - 5.0).abs() < 1e-6);
+        assert_eq!(centre[0].weighted_mean, Some(5.0));
+
+        let touched = ZonalOptions { all_touched: true, ..Default::default() };
+        let touched = zonal_statistics(&mut reader, &ifd, &[zone], 3857, &touched).unwrap();
+        assert_eq!(touched[0].count, 6);
+    }
+
+    #[test]
+    fn test_outside_and_non_polygonal() {
+        let spec = FixtureSpec::new(32, 32);
+        let (_file, mut reader, ifd) = open(&spec, |_, _| 1.0);
+
+        let outside = Geometry::Polygon(Polygon::rectangle(100.0, 100.0, 110.0, 110.0));
+        let point = Geometry::Point(crate::projection::Coordinate::new(1.0, 1.0));
+        let stats = zonal_statistics(&mut reader, &ifd, &[outside, point], 3857, &ZonalOptions::default()).unwrap();
+        assert_eq!(stats, vec![ZonalStats::empty(), ZonalStats::empty()]);
+    }
+}
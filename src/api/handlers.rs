@@ -5,26 +5,62 @@ use axum::{
     Json,
     body::Body,
 };
-use axum::extract::multipart::Multipart;
+use axum::extract::multipart::{Multipart, MultipartError};
+use bytes::Bytes;
 use std::time::Instant;
 use std::io::Cursor;
 
-use crate::{TiffReader, Result as RasterkitResult};
+use crate::{AsyncTiffReader, TiffReader, Mosaic, Result as RasterkitResult};
+use crate::analysis::profile::{densify_line, sample_profile, ProfileSample};
+use crate::analysis::reclass::{sample_reclassified, Reclassifier, Unmatched};
+use crate::projection::Coordinate;
 use super::models::*;
 
 pub async fn get_coordinate_value(
     Query(req): Query<CoordinateRequest>,
 ) -> Result<Json<CoordinateResponse>, (StatusCode, Json<ErrorResponse>)> {
     let start = Instant::now();
+    check_tiff_path(&req.tiff_path)?;
 
-    match extract_single_value(&req.tiff_path, req.latitude, req.longitude, req.epsg) {
-        Ok(value) => {
+    // Tables come inline only; the server never opens a client-named table file
+    let table = match req.reclass.as_deref().map(parse_table).transpose() {
+        Ok(table) => table,
+        Err(e) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("Invalid reclass table: {}", e),
+            }),
+        )),
+    };
+
+    let result = match table {
+        Some(table) => {
+            let tiff_path = req.tiff_path.clone();
+            let coord = Coordinate::from_lonlat(req.longitude, req.latitude);
+            let epsg = req.epsg;
+            blocking(move || {
+                // A rule-less table keeping every value yields the raw value
+                let keep = Reclassifier::new().with_unmatched(Unmatched::Keep);
+                let value = sample_values(&tiff_path, &[coord], epsg, &keep)?[0].filter(|v| !v.is_nan());
+                let mapped = value.map(|v| table.apply(v)).filter(|v| !v.is_nan());
+                Ok((None, mapped, value.map(|v| table.lookup(v).is_some())))
+            }).await
+        }
+        None => extract_single_value(&req.tiff_path, req.latitude, req.longitude, req.epsg)
+            .await
+            .map(|value| (Some(value), None, None)),
+    };
+
+    match result {
+        Ok((exposure_value, mapped_value, reclass_matched)) => {
             let execution_time_ms = start.elapsed().as_secs_f64() * 1000.0;
 
             Ok(Json(CoordinateResponse {
                 latitude: req.latitude,
                 longitude: req.longitude,
-                exposure_value: Some(value),
+                exposure_value,
+                mapped_value,
+                reclass_matched,
                 execution_time_ms,
             }))
         }
@@ -44,6 +80,8 @@ pub async fn upload_csv(
 
     let mut csv_data: Option<Vec<u8>> = None;
     let mut tiff_path: Option<String> = None;
+    let mut tiff_data: Option<Bytes> = None;
+    let mut reclass_data: Option<String> = None;
     let mut epsg: u16 = 4326;
 
     while let Some(field) = multipart.next_field().await.unwrap_or(None) {
@@ -56,11 +94,17 @@ pub async fn upload_csv(
             "tiff_path" => {
                 tiff_path = Some(field.text().await.unwrap_or_default());
             }
+            "tiff" => {
+                tiff_data = Some(field.bytes().await.map_err(|e| multipart_error("tiff", e))?);
+            }
             "epsg" => {
                 if let Ok(text) = field.text().await {
                     epsg = text.parse().unwrap_or(4326);
                 }
             }
+            "reclass" => {
+                reclass_data = Some(field.text().await.map_err(|e| multipart_error("reclass", e))?);
+            }
             _ => {}
         }
     }
@@ -74,16 +118,32 @@ pub async fn upload_csv(
         )
     })?;
 
-    let tiff_path = tiff_path.ok_or_else(|| {
-        (
+    // An uploaded raster takes precedence and is read from memory
+    let raster = match (tiff_data, tiff_path) {
+        (Some(data), _) => RasterInput::Upload(data),
+        (None, Some(path)) => {
+            check_tiff_path(&path)?;
+            RasterInput::Path(path)
+        }
+        (None, None) => return Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: "Missing tiff_path parameter".to_string(),
+                error: "Missing tiff_path parameter or tiff file".to_string(),
             }),
-        )
-    })?;
+        )),
+    };
 
-    match process_csv_batch_stream(&csv_data, &tiff_path, epsg, start) {
+    let table = match reclass_data.as_deref().map(parse_table).transpose() {
+        Ok(table) => table,
+        Err(e) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("Invalid reclass table: {}", e),
+            }),
+        )),
+    };
+
+    match process_csv_batch_stream(&csv_data, raster, epsg, table, start).await {
         Ok(stream_body) => {
             Ok(Response::builder()
                 .status(StatusCode::OK)
@@ -101,50 +161,116 @@ pub async fn upload_csv(
     }
 }
 
-fn extract_single_value(tiff_path: &str, latitude: f64, longitude: f64, source_epsg: u16) -> RasterkitResult<u8> {
-    use crate::formats::tiff::geotiff::GeoInfo;
-    use crate::projection::Coordinate;
+pub async fn post_profile(
+    Json(req): Json<ProfileRequest>,
+) -> Result<Json<ProfileResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let start = Instant::now();
 
-    let mut reader = TiffReader::open(tiff_path)?;
-    let tiff = reader.read()?;
-    let ifd = tiff.main_ifd().ok_or_else(|| {
-        crate::Error::InvalidFormat("No main IFD found".to_string())
-    })?;
+    if req.coordinates.len() < 2 || !req.spacing.is_finite() || req.spacing <= 0.0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "A profile needs at least two coordinates and a positive spacing".to_string(),
+            }),
+        ));
+    }
 
-    let geo_info = GeoInfo::from_ifd(ifd, &mut reader)?
-        .ok_or_else(|| crate::Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+    check_tiff_path(&req.tiff_path)?;
 
-    let coord = Coordinate {
-        x: longitude,
-        y: latitude,
-        z: 0.0,
-    };
+    let line: Vec<Coordinate> = req.coordinates.iter().map(|&[x, y]| Coordinate::new(x, y)).collect();
 
-    let (pixel_x, pixel_y) = geo_info.transform_crs_to_pixel(coord, source_epsg)?;
+    let (tiff_path, epsg, spacing) = (req.tiff_path.clone(), req.epsg, req.spacing);
+    match blocking(move || profile_values(&tiff_path, &line, epsg, spacing)).await {
+        Ok(samples) => {
+            let execution_time_ms = start.elapsed().as_secs_f64() * 1000.0;
 
-    reader.read_pixel_value(ifd, pixel_x as u64, pixel_y as u64)
+            Ok(Json(ProfileResponse {
+                length_m: samples.last().map_or(0.0, |s| s.distance),
+                samples: samples.into_iter().map(|s| ProfilePointResponse {
+                    distance: s.distance,
+                    x: s.coordinate.x,
+                    y: s.coordinate.y,
+                    value: s.value,
+                }).collect(),
+                execution_time_ms,
+            }))
+        }
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Failed to sample profile: {}", e),
+            }),
+        )),
+    }
 }
 
-fn process_csv_batch_stream(
-    csv_data: &[u8],
-    tiff_path: &str,
-    source_epsg: u16,
-    start: Instant,
-) -> RasterkitResult<Body> {
-    use crate::formats::tiff::geotiff::GeoInfo;
-    use crate::projection::Coordinate;
+/// Samples a profile from a GeoTIFF or a mosaic description
+fn profile_values(path: &str, line: &[Coordinate], source_epsg: u16, spacing: f64) -> RasterkitResult<Vec<ProfileSample>> {
+    if Mosaic::is_description_path(path) {
+        let points = densify_line(line, source_epsg, spacing)?;
+        let coords: Vec<Coordinate> = points.iter().map(|&(_, c)| c).collect();
+        let mut mosaic = Mosaic::open(path)?;
+        let values = mosaic.read_values_batch_crs(&coords, source_epsg)?;
+        return Ok(points.into_iter().zip(values)
+            .map(|((distance, coordinate), value)| ProfileSample { distance, coordinate, value })
+            .collect());
+    }
 
-    let mut reader = TiffReader::open(tiff_path)?;
-    let tiff = reader.read()?;
-    let ifd = tiff.main_ifd().ok_or_else(|| {
+    let mut reader = TiffReader::open(path)?;
+    let ifd = reader.ifd(0)?.ok_or_else(|| {
         crate::Error::InvalidFormat("No main IFD found".to_string())
     })?;
+    sample_profile(&mut reader, &ifd, line, source_epsg, spacing)
+}
+
+async fn extract_single_value(tiff_path: &str, latitude: f64, longitude: f64, source_epsg: u16) -> RasterkitResult<u8> {
+    let coord = Coordinate::from_lonlat(longitude, latitude);
+
+    if Mosaic::is_description_path(tiff_path) {
+        let path = tiff_path.to_string();
+        let values = blocking(move || Mosaic::open(&path)?.read_values_batch_crs(&[coord], source_epsg)).await?;
 
-    let geo_info = GeoInfo::from_ifd(ifd, &mut reader)?
+        return values[0].map(|v| v as u8).ok_or_else(|| crate::Error::OutOfBounds(format!(
+            "Coordinate ({}, {}) is not covered by the mosaic",
+            longitude, latitude
+        )));
+    }
+
+    let reader = AsyncTiffReader::open(tiff_path).await?;
+    let ifd = reader.ifd(0).await?.ok_or_else(|| {
+        crate::Error::InvalidFormat("No main IFD found".to_string())
+    })?;
+
+    let geo_info = reader.geo_info(&ifd)?
         .ok_or_else(|| crate::Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
 
-    reader.enable_prefetch(ifd);
+    let (pixel_x, pixel_y) = blocking(move || geo_info.transform_crs_to_pixel(coord, source_epsg)).await?;
+
+    reader.read_pixel_value(&ifd, pixel_x as u64, pixel_y as u64).await
+}
+
+/// Parses an uploaded lookup table, JSON when it starts with `{` or `[`, CSV otherwise
+fn parse_table(text: &str) -> RasterkitResult<Reclassifier> {
+    if text.trim_start().starts_with(['{', '[']) {
+        Reclassifier::from_json(text)
+    } else {
+        Reclassifier::from_csv(text)
+    }
+}
+
+/// Raster to sample: a path on the server or a GeoTIFF uploaded with the request
+enum RasterInput {
+    Path(String),
+    Upload(Bytes),
+}
 
+async fn process_csv_batch_stream(
+    csv_data: &[u8],
+    raster: RasterInput,
+    source_epsg: u16,
+    table: Option<Reclassifier>,
+    start: Instant,
+) -> RasterkitResult<Body> {
     let mut csv_reader = csv::Reader::from_reader(Cursor::new(csv_data));
     let mut points: Vec<CsvPoint> = Vec::new();
 
@@ -154,17 +280,151 @@ fn process_csv_batch_stream(
         }
     }
 
-    let dims = ifd.dimensions().ok_or_else(|| {
-        crate::Error::InvalidFormat("Missing dimensions".to_string())
-    })?;
-
     let input_coords: Vec<Coordinate> = points.iter().map(|point| Coordinate {
         x: point.longitude,
         y: point.latitude,
         z: 0.0,
     }).collect();
 
-    let pixel_coords = geo_info.transform_crs_to_pixel_batch(&input_coords, source_epsg)?;
+    // Plain GeoTIFFs are sampled with the async reader; mosaics and
+    // reclassification use blocking readers on the blocking pool
+    let sampled: Vec<Option<String>> = match (raster, table) {
+        (RasterInput::Path(path), None) if !Mosaic::is_description_path(&path) => {
+            to_strings(sample_tiff(&AsyncTiffReader::open(&path).await?, &input_coords, source_epsg).await?)
+        }
+        (RasterInput::Upload(data), None) => {
+            to_strings(sample_tiff(&AsyncTiffReader::from_bytes(data).await?, &input_coords, source_epsg).await?)
+        }
+        (RasterInput::Path(path), None) => {
+            to_strings(blocking(move || sample_mosaic(&path, &input_coords, source_epsg)).await?)
+        }
+        (RasterInput::Path(path), Some(table)) => {
+            mapped_to_strings(blocking(move || sample_values(&path, &input_coords, source_epsg, &table)).await?)
+        }
+        (RasterInput::Upload(data), Some(table)) => mapped_to_strings(blocking(move || {
+            sample_values_reader(&mut TiffReader::from_bytes(data)?, &input_coords, source_epsg, &table)
+        }).await?),
+    };
+
+    write_csv_results(&points, &sampled, start)
+}
+
+/// Environment variable listing the http(s) and `s3://` URL prefixes
+/// `tiff_path` may name, separated by commas
+pub const URL_ALLOW_ENV: &str = "SKYFOREST_URL_ALLOW";
+
+/// Rejects http(s) and `s3://` URLs outside the allow-list, so clients
+/// cannot make the server fetch arbitrary URLs or read arbitrary buckets
+/// with its credentials; local paths pass
+/// Helper: 400 response for a multipart field that could not be read
+fn multipart_error(field: &str, e: MultipartError) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: format!("Failed to read field '{}': {}", field, e),
+        }),
+    )
+}
+
+fn check_tiff_path(path: &str) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let allow_list = std::env::var(URL_ALLOW_ENV).unwrap_or_default();
+    if remote_url_allowed(path, &allow_list) {
+        return Ok(());
+    }
+    Err((
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse {
+            error: format!("tiff_path URL is not allowed; list its prefix in {}", URL_ALLOW_ENV),
+        }),
+    ))
+}
+
+/// Helper: Checks a path against comma-separated URL prefixes
+///
+/// A prefix must end at a `/` of the URL, so `https://data.example.com`
+/// does not admit `https://data.example.com.evil.net`, nor `s3://rasters`
+/// the bucket `s3://rasters-private`.
+fn remote_url_allowed(path: &str, allow_list: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    if !["http://", "https://", "s3://"].iter().any(|scheme| lower.starts_with(scheme)) {
+        return true;
+    }
+    allow_list.split(',')
+        .map(str::trim)
+        .filter(|prefix| !prefix.is_empty())
+        .any(|prefix| match path.strip_prefix(prefix) {
+            Some(rest) => prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        })
+}
+
+/// Runs blocking raster work on the blocking thread pool
+async fn blocking<T, F>(job: F) -> RasterkitResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> RasterkitResult<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(job)
+        .await
+        .map_err(|e| crate::Error::Io(std::io::Error::other(e)))?
+}
+
+/// Formats raw sampled values
+fn to_strings(values: Vec<Option<u8>>) -> Vec<Option<String>> {
+    values.into_iter().map(|v| v.map(|v| v.to_string())).collect()
+}
+
+/// Formats reclassified values, writing NoData as `NODATA`
+fn mapped_to_strings(values: Vec<Option<f64>>) -> Vec<Option<String>> {
+    values.into_iter()
+        .map(|v| v.map(|v| if v.is_nan() { "NODATA".to_string() } else { v.to_string() }))
+        .collect()
+}
+
+/// Samples points as f64 and maps them through a lookup table
+///
+/// `None` marks uncovered points; NaN marks NoData.
+fn sample_values(path: &str, coords: &[Coordinate], source_epsg: u16, table: &Reclassifier) -> RasterkitResult<Vec<Option<f64>>> {
+    if Mosaic::is_description_path(path) {
+        let mut mosaic = Mosaic::open(path)?;
+        let values = mosaic.read_values_batch_crs(coords, source_epsg)?;
+        return Ok(values.into_iter().map(|v| v.map(|v| table.apply(v))).collect());
+    }
+
+    sample_values_reader(&mut TiffReader::open(path)?, coords, source_epsg, table)
+}
+
+/// Samples points from an open GeoTIFF and maps them through a lookup table
+fn sample_values_reader(reader: &mut TiffReader, coords: &[Coordinate], source_epsg: u16, table: &Reclassifier) -> RasterkitResult<Vec<Option<f64>>> {
+    let ifd = reader.ifd(0)?.ok_or_else(|| {
+        crate::Error::InvalidFormat("No main IFD found".to_string())
+    })?;
+    sample_reclassified(reader, &ifd, coords, source_epsg, table)
+}
+
+/// Samples points from a mosaic description; `None` marks uncovered points
+fn sample_mosaic(mosaic_path: &str, coords: &[Coordinate], source_epsg: u16) -> RasterkitResult<Vec<Option<u8>>> {
+    let mut mosaic = Mosaic::open(mosaic_path)?;
+    let values = mosaic.read_values_batch_crs(coords, source_epsg)?;
+    Ok(values.into_iter().map(|v| v.map(|v| v as u8)).collect())
+}
+
+/// Samples points from a single GeoTIFF; `None` marks out-of-bounds points
+async fn sample_tiff(reader: &AsyncTiffReader, input_coords: &[Coordinate], source_epsg: u16) -> RasterkitResult<Vec<Option<u8>>> {
+    let ifd = reader.ifd(0).await?.ok_or_else(|| {
+        crate::Error::InvalidFormat("No main IFD found".to_string())
+    })?;
+
+    let geo_info = reader.geo_info(&ifd)?
+        .ok_or_else(|| crate::Error::InvalidFormat("Not a GeoTIFF".to_string()))?;
+
+    let dims = ifd.dimensions().ok_or_else(|| {
+        crate::Error::InvalidFormat("Missing dimensions".to_string())
+    })?;
+
+    // Projection work runs off the executor
+    let input_coords = input_coords.to_vec();
+    let pixel_coords = blocking(move || geo_info.transform_crs_to_pixel_batch(&input_coords, source_epsg)).await?;
 
     let coords: Vec<(u64, u64)> = pixel_coords.iter().map(|&(pixel_x, pixel_y)| {
         if pixel_x >= 0.0 && pixel_y >= 0.0
@@ -184,15 +444,20 @@ fn process_csv_batch_stream(
         .map(|&i| coords[i])
         .collect();
 
-    let valid_values = reader.read_pixels_batch(ifd, &valid_coords_only)?;
+    let valid_values = reader.read_pixels_batch(&ifd, &valid_coords_only).await?;
 
-    let mut values = vec![0u8; coords.len()];
+    let mut values = vec![None; coords.len()];
     for (i, &original_idx) in valid_indices.iter().enumerate() {
-        values[original_idx] = valid_values[i];
+        values[original_idx] = Some(valid_values[i]);
     }
 
+    Ok(values)
+}
+
+/// Formats sampled values as the CSV response body with a statistics preamble
+fn write_csv_results(points: &[CsvPoint], values: &[Option<String>], start: Instant) -> RasterkitResult<Body> {
     let execution_time_ms = start.elapsed().as_secs_f64() * 1000.0;
-    let successful = valid_coords_only.len();
+    let successful = values.iter().filter(|v| v.is_some()).count();
     let pixels_per_second = (successful as f64 / start.elapsed().as_secs_f64()).round();
 
     let mut csv_output = String::with_capacity(points.len() * 40);
@@ -212,12 +477,8 @@ fn process_csv_batch_stream(
         csv_output.push_str("latitude,longitude,exposure_value\n");
     }
 
-    for (i, point) in points.iter().enumerate() {
-        let exposure_value = if coords[i].0 == u64::MAX {
-            "OUT_OF_BOUNDS"
-        } else {
-            &values[i].to_string()
-        };
+    for (point, value) in points.iter().zip(values) {
+        let exposure_value = value.as_deref().unwrap_or("OUT_OF_BOUNDS");
 
         if has_names {
             let name = point.name.as_deref().unwrap_or("");
@@ -235,3 +496,84 @@ fn process_csv_batch_stream(
 
     Ok(Body::from(csv_output))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+
+    #[test]
+    fn test_remote_url_allowed() {
+        let allow = "https://data.example.com, http://10.0.0.5/rasters/";
+        assert!(remote_url_allowed("/data/dem.tif", ""));
+        assert!(!remote_url_allowed("https://data.example.com/dem.tif", ""));
+        assert!(remote_url_allowed("https://data.example.com/dem.tif", allow));
+        assert!(remote_url_allowed("http://10.0.0.5/rasters/a.zip!/dem.tif", allow));
+        assert!(!remote_url_allowed("https://data.example.com.evil.net/dem.tif", allow));
+        assert!(!remote_url_allowed("HTTP://169.254.169.254/latest", allow));
+    }
+
+    #[test]
+    fn test_s3_url_allowed() {
+        let allow = "s3://rasters, s3://shared/public/";
+        assert!(!remote_url_allowed("s3://bucket/dem.tif", ""));
+        assert!(!remote_url_allowed("s3://bucket/dem.tif", allow));
+        assert!(remote_url_allowed("s3://rasters/dem.tif", allow));
+        assert!(!remote_url_allowed("s3://rasters-private/dem.tif", allow));
+        assert!(remote_url_allowed("s3://shared/public/a.zip!/dem.tif", allow));
+        assert!(!remote_url_allowed("s3://shared/private/dem.tif", allow));
+    }
+
+    /// Multipart request whose body ends inside the `field` part
+    async fn truncated_upload(field: &str) -> Multipart {
+        use axum::extract::FromRequest;
+        let body = format!(
+            "--X\r\nContent-Disposition: form-data; name=\"csv\"\r\n\r\nlat,lon\r\n\
+             --X\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\nII*\0", field
+        );
+        let request = axum::http::Request::builder()
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+            .body(Body::from(body))
+            .unwrap();
+        Multipart::from_request(request, &()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_truncated_upload_is_rejected() {
+        let Err((status, Json(error))) = upload_csv(truncated_upload("tiff").await).await else {
+            panic!("truncated upload accepted");
+        };
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.error.contains("'tiff'"), "{}", error.error);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_reclass_is_rejected() {
+        let Err((status, Json(error))) = upload_csv(truncated_upload("reclass").await).await else {
+            panic!("truncated reclass table accepted");
+        };
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.error.contains("'reclass'"), "{}", error.error);
+    }
+
+    #[tokio::test]
+    async fn test_coordinate_reclass_match() {
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.nodata = Some(0.0);
+        let file = write_geotiff(&spec, |x, _| (x % 4) as f64);
+        let query = |x: f64| CoordinateRequest {
+            latitude: 10.5,
+            longitude: x,
+            tiff_path: file.path().to_str().unwrap().to_string(),
+            epsg: 3857,
+            reclass: Some("value,new_value\n1,10\n".to_string()),
+        };
+
+        let matched = get_coordinate_value(Query(query(1.5))).await.unwrap().0;
+        assert_eq!((matched.mapped_value, matched.reclass_matched), (Some(10.0), Some(true)));
+        let unmatched = get_coordinate_value(Query(query(2.5))).await.unwrap().0;
+        assert_eq!((unmatched.mapped_value, unmatched.reclass_matched), (None, Some(false)));
+        let nodata = get_coordinate_value(Query(query(0.5))).await.unwrap().0;
+        assert_eq!((nodata.mapped_value, nodata.reclass_matched), (None, None));
+    }
+}
@@ -4,9 +4,13 @@ use serde::{Deserialize, Serialize};
 pub struct CoordinateRequest {
     pub latitude: f64,
     pub longitude: f64,
+    /// Local path, or allow-listed `s3://` or `http(s)://` URL (optionally `archive.zip!/member` or `.gz`), or mosaic description
     pub tiff_path: String,
     #[serde(default = "default_epsg")]
     pub epsg: u16,
+    /// Inline lookup table (CSV, or JSON starting with `{` or `[`) applied to the sampled value
+    #[serde(default)]
+    pub reclass: Option<String>,
 }
 
 fn default_epsg() -> u16 {
@@ -18,6 +22,13 @@ pub struct CoordinateResponse {
     pub latitude: f64,
     pub longitude: f64,
     pub exposure_value: Option<u8>,
+    /// Value mapped through the lookup table, when one was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mapped_value: Option<f64>,
+    /// Whether a table rule matched the sampled value; absent without a
+    /// table and for NoData or uncovered points
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reclass_matched: Option<bool>,
     pub execution_time_ms: f64,
 }
 
@@ -48,6 +59,35 @@ pub struct CsvResult {
     pub exposure_value: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ProfileRequest {
+    /// Local path, or allow-listed `s3://` or `http(s)://` URL (optionally `archive.zip!/member` or `.gz`), or mosaic description
+    pub tiff_path: String,
+    #[serde(default = "default_epsg")]
+    pub epsg: u16,
+    /// Sample spacing in metres
+    pub spacing: f64,
+    /// Polyline vertices as [x, y] (longitude/latitude for EPSG:4326)
+    pub coordinates: Vec<[f64; 2]>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfilePointResponse {
+    /// Distance along the line in metres
+    pub distance: f64,
+    pub x: f64,
+    pub y: f64,
+    /// `null` outside the raster or on NoData
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileResponse {
+    pub length_m: f64,
+    pub samples: Vec<ProfilePointResponse>,
+    pub execution_time_ms: f64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
     pub error: String,
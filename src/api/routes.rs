@@ -12,6 +12,7 @@ pub fn create_router() -> Router {
     Router::new()
         .route("/api/coordinate", get(get_coordinate_value))
         .route("/api/upload", post(upload_csv))
+        .route("/api/profile", post(post_profile))
         .layer(
             ServiceBuilder::new()
                 .layer(DefaultBodyLimit::max(100 * 1024 * 1024)) // 100MB limit
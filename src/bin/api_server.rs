@@ -13,7 +13,11 @@ async fn main() {
     println!();
     println!("📍 Endpoints:");
     println!("  GET  /api/coordinate?latitude=<lat>&longitude=<lon>&tiff_path=<path>");
-    println!("  POST /api/upload (multipart/form-data: csv file + tiff_path)");
+    println!("  POST /api/upload (multipart/form-data: csv file + tiff_path or tiff file)");
+    println!("  POST /api/profile (JSON: tiff_path, spacing in metres, coordinates [[x, y], ...], optional epsg)");
+    println!("  tiff_path may be a local path, or an s3://bucket/key or http(s):// URL");
+    println!("  under a prefix listed in {} (comma-separated)", skyforest_rasterizer::api::URL_ALLOW_ENV);
+    println!("  and may name a ZIP member (archive.zip!/dem.tif) or a gzip file (dem.tif.gz)");
     println!();
 
     axum::serve(listener, app)
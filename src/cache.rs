@@ -47,8 +47,12 @@ impl TileCache {
     /// * `tile_index` - Tile index within the IFD
     /// * `data` - Decompressed tile data
     pub fn put(&self, ifd_index: usize, tile_index: usize, data: Vec<u8>) {
+        self.put_shared(ifd_index, tile_index, Arc::new(data));
+    }
+
+    /// Puts a tile that is already shared into the cache without copying it
+    pub fn put_shared(&self, ifd_index: usize, tile_index: usize, data: Arc<Vec<u8>>) {
         let key = (ifd_index, tile_index);
-        let data_arc = Arc::new(data);
 
         while self.cache.len() >= self.max_tiles {
             if let Some(old_key) = self.lru.pop() {
@@ -58,7 +62,7 @@ impl TileCache {
             }
         }
 
-        self.cache.insert(key, data_arc);
+        self.cache.insert(key, data);
         self.lru.push(key);
     }
 
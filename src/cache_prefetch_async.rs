@@ -16,6 +16,8 @@ pub struct PrefetchRequest {
 
 /// Result from prefetching
 pub struct PrefetchResult {
+    /// Number of the IFD the tile belongs to
+    pub ifd_index: usize,
     pub tile_index: usize,
     pub tile_data: Vec<u8>,
 }
@@ -46,6 +48,7 @@ impl PrefetchWorker {
                         tile_idx,
                     ) {
                         let result = PrefetchResult {
+                            ifd_index: request.ifd.number,
                             tile_index: tile_idx,
                             tile_data,
                         };
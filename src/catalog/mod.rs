@@ -0,0 +1,275 @@
+//! Spatial catalog of GeoTIFF files
+//!
+//! A [`Catalog`] records each file's footprint, CRS, resolution and data
+//! type, and keeps one R-tree per CRS so point and envelope lookups stay
+//! fast with thousands of files.
+
+pub mod rtree;
+
+pub use rtree::{Envelope, RTree};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, TiffReader};
+use crate::types::{DataType, Dimensions};
+
+/// Metadata about one cataloged raster
+#[derive(Debug, Clone)]
+pub struct CatalogEntry {
+    /// Path to the GeoTIFF
+    pub path: PathBuf,
+    /// Footprint in the file's CRS
+    pub envelope: Envelope,
+    /// EPSG code of the file's CRS, if known
+    pub epsg: Option<u16>,
+    /// Pixel size (x, y) in CRS units
+    pub resolution: (f64, f64),
+    /// Pixel data type, if supported
+    pub data_type: Option<DataType>,
+    /// Image dimensions in pixels
+    pub dimensions: Dimensions,
+    /// NoData value, if declared
+    pub nodata: Option<f64>,
+}
+
+impl CatalogEntry {
+    /// Reads the catalog metadata of a GeoTIFF file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = TiffReader::open_with_options(path, false, 0)?;
+        let ifd = reader.ifd(0)?
+            .ok_or_else(|| Error::InvalidFormat("No main IFD found".to_string()))?;
+
+        let geo_info = GeoInfo::from_ifd(&ifd, &mut reader)?
+            .ok_or_else(|| Error::InvalidFormat(format!("{} is not a GeoTIFF", path.display())))?;
+
+        let dimensions = ifd.dimensions()
+            .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+        let bounds = geo_info.bounding_box(dimensions.width, dimensions.height)
+            .ok_or_else(|| Error::InvalidFormat(format!("{} has no geotransform", path.display())))?;
+        let (scale_x, scale_y, _) = geo_info.pixel_scale.unwrap_or((0.0, 0.0, 0.0));
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            envelope: Envelope::from_bounds(bounds),
+            epsg: geo_info.epsg_code,
+            resolution: (scale_x, scale_y),
+            data_type: ifd.data_type(),
+            dimensions,
+            nodata: geo_info.nodata,
+        })
+    }
+}
+
+/// Spatially indexed collection of rasters
+pub struct Catalog {
+    entries: Vec<CatalogEntry>,
+    indexes: HashMap<Option<u16>, RTree>,
+    skipped: Vec<(PathBuf, String)>,
+}
+
+impl Catalog {
+    /// Creates an empty catalog
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            indexes: HashMap::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Builds a catalog from entries using bulk-loaded indexes
+    pub fn from_entries(entries: Vec<CatalogEntry>) -> Self {
+        let mut grouped: HashMap<Option<u16>, Vec<(Envelope, usize)>> = HashMap::new();
+        for (id, entry) in entries.iter().enumerate() {
+            grouped.entry(entry.epsg).or_default().push((entry.envelope, id));
+        }
+
+        let indexes = grouped.into_iter()
+            .map(|(epsg, items)| (epsg, RTree::bulk_load(items)))
+            .collect();
+
+        Self {
+            entries,
+            indexes,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Recursively scans a directory for `.tif`/`.tiff` files
+    ///
+    /// Files that cannot be read as GeoTIFFs are recorded in [`Catalog::skipped`]
+    /// instead of aborting the scan.
+    pub fn scan_directory<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut paths = Vec::new();
+        collect_tiff_paths(dir.as_ref(), &mut paths)?;
+        paths.sort();
+
+        let mut entries = Vec::with_capacity(paths.len());
+        let mut skipped = Vec::new();
+        for path in paths {
+            match CatalogEntry::from_file(&path) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => skipped.push((path, e.to_string())),
+            }
+        }
+
+        let mut catalog = Self::from_entries(entries);
+        catalog.skipped = skipped;
+        Ok(catalog)
+    }
+
+    /// Adds an entry and returns its identifier
+    pub fn add(&mut self, entry: CatalogEntry) -> usize {
+        let id = self.entries.len();
+        self.indexes.entry(entry.epsg).or_default().insert(entry.envelope, id);
+        self.entries.push(entry);
+        id
+    }
+
+    /// Reads a file's metadata and adds it
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let entry = CatalogEntry::from_file(path)?;
+        Ok(self.add(entry))
+    }
+
+    /// Returns all entries; identifiers are indices into this slice
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+
+    /// Returns an entry by identifier
+    pub fn get(&self, id: usize) -> Option<&CatalogEntry> {
+        self.entries.get(id)
+    }
+
+    /// Returns the files that were skipped while scanning, with the reason
+    pub fn skipped(&self) -> &[(PathBuf, String)] {
+        &self.skipped
+    }
+
+    /// Returns the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the catalog is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the distinct CRSs present in the catalog
+    pub fn crs_codes(&self) -> Vec<Option<u16>> {
+        let mut codes: Vec<Option<u16>> = self.indexes.keys().copied().collect();
+        codes.sort();
+        codes
+    }
+
+    /// Returns identifiers of rasters in `epsg` whose footprint contains the point
+    pub fn query_point(&self, x: f64, y: f64, epsg: Option<u16>) -> Vec<usize> {
+        let mut ids = self.indexes.get(&epsg)
+            .map(|index| index.query_point(x, y))
+            .unwrap_or_default();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Returns identifiers of rasters in `epsg` whose footprint intersects the envelope
+    pub fn query_envelope(&self, envelope: &Envelope, epsg: Option<u16>) -> Vec<usize> {
+        let mut ids = self.indexes.get(&epsg)
+            .map(|index| index.query_envelope(envelope))
+            .unwrap_or_default();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Helper: Collects TIFF file paths below a directory
+fn collect_tiff_paths(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_tiff_paths(&path, paths)?;
+            continue;
+        }
+
+        let is_tiff = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.eq_ignore_ascii_case("tif") || e.eq_ignore_ascii_case("tiff"))
+            .unwrap_or(false);
+        if is_tiff {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{build_geotiff, FixtureSpec};
+
+    fn write_tile(dir: &Path, name: &str, origin: (f64, f64), epsg: u16) {
+        let mut spec = FixtureSpec::new(16, 16);
+        spec.origin = origin;
+        spec.epsg = epsg;
+        spec.pixel_size = (2.0, 2.0);
+        std::fs::write(dir.join(name), build_geotiff(&spec, |_, _| 1.0)).unwrap();
+    }
+
+    #[test]
+    fn test_scan_directory_and_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+
+        write_tile(dir.path(), "a.tif", (0.0, 32.0), 3857);
+        write_tile(dir.path(), "b.tif", (32.0, 32.0), 3857);
+        write_tile(&nested, "c.TIFF", (0.0, 32.0), 4326);
+        std::fs::write(dir.path().join("broken.tif"), b"not a tiff").unwrap();
+        std::fs::write(dir.path().join("readme.txt"), b"ignored").unwrap();
+
+        let catalog = Catalog::scan_directory(dir.path()).unwrap();
+        assert_eq!(catalog.len(), 3);
+        assert_eq!(catalog.skipped().len(), 1);
+        assert_eq!(catalog.crs_codes(), vec![Some(3857), Some(4326)]);
+
+        let hits = catalog.query_point(40.0, 10.0, Some(3857));
+        assert_eq!(hits.len(), 1);
+        let entry = catalog.get(hits[0]).unwrap();
+        assert!(entry.path.ends_with("b.tif"));
+        assert_eq!(entry.resolution, (2.0, 2.0));
+        assert_eq!(entry.data_type, Some(DataType::U8));
+        assert_eq!(entry.envelope, Envelope::new(32.0, 0.0, 64.0, 32.0));
+
+        let both = catalog.query_envelope(&Envelope::new(10.0, 10.0, 40.0, 12.0), Some(3857));
+        assert_eq!(both.len(), 2);
+        assert_eq!(catalog.query_point(10.0, 10.0, Some(4326)).len(), 1);
+        assert!(catalog.query_point(10.0, 10.0, Some(32633)).is_empty());
+    }
+
+    #[test]
+    fn test_add_after_build() {
+        let mut catalog = Catalog::from_entries(Vec::new());
+        let entry = CatalogEntry {
+            path: PathBuf::from("x.tif"),
+            envelope: Envelope::new(0.0, 0.0, 1.0, 1.0),
+            epsg: Some(3857),
+            resolution: (1.0, 1.0),
+            data_type: Some(DataType::F32),
+            dimensions: Dimensions::new(1, 1),
+            nodata: None,
+        };
+
+        let id = catalog.add(entry);
+        assert_eq!(catalog.query_point(0.5, 0.5, Some(3857)), vec![id]);
+    }
+}
@@ -0,0 +1,378 @@
+//! R-tree over axis-aligned envelopes
+//!
+//! Supports Sort-Tile-Recursive bulk loading for large catalogs and
+//! incremental insertion with a median split along the longer axis.
+
+/// Axis-aligned bounding rectangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Envelope {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Envelope {
+    /// Creates a new envelope
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Self { min_x, min_y, max_x, max_y }
+    }
+
+    /// Creates an envelope from a (min_x, min_y, max_x, max_y) tuple
+    pub fn from_bounds(bounds: (f64, f64, f64, f64)) -> Self {
+        Self::new(bounds.0, bounds.1, bounds.2, bounds.3)
+    }
+
+    /// Creates a degenerate envelope covering a single point
+    pub fn point(x: f64, y: f64) -> Self {
+        Self::new(x, y, x, y)
+    }
+
+    /// Returns whether a point lies inside or on the boundary
+    pub fn contains_point(&self, x: f64, y: f64) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+
+    /// Returns whether two envelopes overlap or touch
+    pub fn intersects(&self, other: &Envelope) -> bool {
+        self.min_x <= other.max_x && self.max_x >= other.min_x
+            && self.min_y <= other.max_y && self.max_y >= other.min_y
+    }
+
+    /// Returns the smallest envelope covering both
+    pub fn union(&self, other: &Envelope) -> Envelope {
+        Envelope::new(
+            self.min_x.min(other.min_x),
+            self.min_y.min(other.min_y),
+            self.max_x.max(other.max_x),
+            self.max_y.max(other.max_y),
+        )
+    }
+
+    /// Returns the area
+    pub fn area(&self) -> f64 {
+        (self.max_x - self.min_x) * (self.max_y - self.min_y)
+    }
+
+    /// Returns the centre point
+    pub fn center(&self) -> (f64, f64) {
+        ((self.min_x + self.max_x) / 2.0, (self.min_y + self.max_y) / 2.0)
+    }
+}
+
+/// Maximum entries per node
+const NODE_CAPACITY: usize = 16;
+
+enum NodeKind {
+    Leaf(Vec<(Envelope, usize)>),
+    Inner(Vec<usize>),
+}
+
+struct Node {
+    envelope: Envelope,
+    kind: NodeKind,
+}
+
+/// R-tree mapping envelopes to item identifiers
+pub struct RTree {
+    nodes: Vec<Node>,
+    root: Option<usize>,
+    len: usize,
+}
+
+impl RTree {
+    /// Creates an empty tree
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: None,
+            len: 0,
+        }
+    }
+
+    /// Builds a packed tree from all items at once (Sort-Tile-Recursive)
+    pub fn bulk_load(items: Vec<(Envelope, usize)>) -> Self {
+        let mut tree = Self::new();
+        tree.len = items.len();
+        if items.is_empty() {
+            return tree;
+        }
+
+        let leaves = str_partition(items, |(env, _)| env.center());
+        let mut level: Vec<usize> = leaves.into_iter()
+            .map(|chunk| tree.push_node(NodeKind::Leaf(chunk)))
+            .collect();
+
+        while level.len() > 1 {
+            let with_centers: Vec<(Envelope, usize)> = level.iter()
+                .map(|&id| (tree.nodes[id].envelope, id))
+                .collect();
+            level = str_partition(with_centers, |(env, _)| env.center())
+                .into_iter()
+                .map(|chunk| tree.push_node(NodeKind::Inner(chunk.into_iter().map(|(_, id)| id).collect())))
+                .collect();
+        }
+
+        tree.root = level.first().copied();
+        tree
+    }
+
+    /// Inserts a single item
+    pub fn insert(&mut self, envelope: Envelope, item: usize) {
+        self.len += 1;
+
+        let root = match self.root {
+            Some(root) => root,
+            None => {
+                let leaf = self.push_node(NodeKind::Leaf(vec![(envelope, item)]));
+                self.root = Some(leaf);
+                return;
+            }
+        };
+
+        if let Some(sibling) = self.insert_into(root, envelope, item) {
+            let new_root = self.push_node(NodeKind::Inner(vec![root, sibling]));
+            self.root = Some(new_root);
+        }
+    }
+
+    /// Returns the number of items
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the tree holds no items
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the items whose envelope contains a point
+    pub fn query_point(&self, x: f64, y: f64) -> Vec<usize> {
+        self.query_envelope(&Envelope::point(x, y))
+    }
+
+    /// Returns the items whose envelope intersects the query envelope
+    pub fn query_envelope(&self, query: &Envelope) -> Vec<usize> {
+        let mut results = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            if !node.envelope.intersects(query) {
+                continue;
+            }
+
+            match &node.kind {
+                NodeKind::Leaf(items) => {
+                    results.extend(items.iter()
+                        .filter(|(env, _)| env.intersects(query))
+                        .map(|&(_, item)| item));
+                }
+                NodeKind::Inner(children) => stack.extend(children.iter().copied()),
+            }
+        }
+
+        results
+    }
+
+    /// Helper: Appends a node and computes its envelope
+    fn push_node(&mut self, kind: NodeKind) -> usize {
+        let envelope = self.envelope_of(&kind);
+        self.nodes.push(Node { envelope, kind });
+        self.nodes.len() - 1
+    }
+
+    /// Helper: Computes the envelope of a node's entries
+    fn envelope_of(&self, kind: &NodeKind) -> Envelope {
+        let mut envelopes: Box<dyn Iterator<Item = Envelope>> = match kind {
+            NodeKind::Leaf(items) => Box::new(items.iter().map(|(env, _)| *env)),
+            NodeKind::Inner(children) => Box::new(children.iter().map(|&c| self.nodes[c].envelope)),
+        };
+
+        let first = envelopes.next().unwrap_or(Envelope::point(0.0, 0.0));
+        envelopes.fold(first, |acc, env| acc.union(&env))
+    }
+
+    /// Helper: Recursively inserts and returns a new sibling if the node split
+    fn insert_into(&mut self, node_id: usize, envelope: Envelope, item: usize) -> Option<usize> {
+        self.nodes[node_id].envelope = self.nodes[node_id].envelope.union(&envelope);
+
+        let child = match &self.nodes[node_id].kind {
+            NodeKind::Leaf(_) => None,
+            NodeKind::Inner(children) => Some(self.choose_subtree(children, &envelope)),
+        };
+
+        match child {
+            None => {
+                if let NodeKind::Leaf(items) = &mut self.nodes[node_id].kind {
+                    items.push((envelope, item));
+                }
+            }
+            Some(child) => {
+                if let Some(sibling) = self.insert_into(child, envelope, item) {
+                    if let NodeKind::Inner(children) = &mut self.nodes[node_id].kind {
+                        children.push(sibling);
+                    }
+                }
+            }
+        }
+
+        self.split_if_full(node_id)
+    }
+
+    /// Helper: Picks the child needing the least enlargement, then the smallest
+    fn choose_subtree(&self, children: &[usize], envelope: &Envelope) -> usize {
+        let cost = |id: usize| {
+            let env = self.nodes[id].envelope;
+            (env.union(envelope).area() - env.area(), env.area())
+        };
+
+        *children.iter()
+            .min_by(|&&a, &&b| cost(a).partial_cmp(&cost(b)).unwrap_or(std::cmp::Ordering::Equal))
+            .expect("inner nodes always have children")
+    }
+
+    /// Helper: Splits an overflowing node at the median of its longer axis
+    fn split_if_full(&mut self, node_id: usize) -> Option<usize> {
+        let entry_count = match &self.nodes[node_id].kind {
+            NodeKind::Leaf(items) => items.len(),
+            NodeKind::Inner(children) => children.len(),
+        };
+        if entry_count <= NODE_CAPACITY {
+            return None;
+        }
+
+        let node_env = self.nodes[node_id].envelope;
+        let along_x = node_env.max_x - node_env.min_x >= node_env.max_y - node_env.min_y;
+        let key = |env: &Envelope| if along_x { env.center().0 } else { env.center().1 };
+
+        let kind = std::mem::replace(&mut self.nodes[node_id].kind, NodeKind::Inner(Vec::new()));
+        let (keep, sibling) = match kind {
+            NodeKind::Leaf(mut items) => {
+                items.sort_by(|a, b| key(&a.0).total_cmp(&key(&b.0)));
+                let upper = items.split_off(items.len() / 2);
+                (NodeKind::Leaf(items), NodeKind::Leaf(upper))
+            }
+            NodeKind::Inner(mut children) => {
+                children.sort_by(|&a, &b| key(&self.nodes[a].envelope).total_cmp(&key(&self.nodes[b].envelope)));
+                let upper = children.split_off(children.len() / 2);
+                (NodeKind::Inner(children), NodeKind::Inner(upper))
+            }
+        };
+
+        self.nodes[node_id].envelope = self.envelope_of(&keep);
+        self.nodes[node_id].kind = keep;
+
+        Some(self.push_node(sibling))
+    }
+}
+
+impl Default for RTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Helper: Partitions entries into node-sized chunks with Sort-Tile-Recursive ordering
+fn str_partition<T, F>(mut entries: Vec<T>, center: F) -> Vec<Vec<T>>
+where
+    F: Fn(&T) -> (f64, f64),
+{
+    let node_count = entries.len().div_ceil(NODE_CAPACITY);
+    let slab_count = (node_count as f64).sqrt().ceil() as usize;
+    let slab_size = (slab_count * NODE_CAPACITY).max(1);
+
+    entries.sort_by(|a, b| center(a).0.total_cmp(&center(b).0));
+
+    let mut chunks = Vec::with_capacity(node_count);
+    while !entries.is_empty() {
+        let rest = entries.split_off(slab_size.min(entries.len()));
+        let mut slab = std::mem::replace(&mut entries, rest);
+        slab.sort_by(|a, b| center(a).1.total_cmp(&center(b).1));
+
+        while !slab.is_empty() {
+            let rest = slab.split_off(NODE_CAPACITY.min(slab.len()));
+            chunks.push(std::mem::replace(&mut slab, rest));
+        }
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_items(n: usize) -> Vec<(Envelope, usize)> {
+        (0..n * n)
+            .map(|i| {
+                let x = (i % n) as f64;
+                let y = (i / n) as f64;
+                (Envelope::new(x, y, x + 1.0, y + 1.0), i)
+            })
+            .collect()
+    }
+
+    fn brute_force(items: &[(Envelope, usize)], query: &Envelope) -> Vec<usize> {
+        let mut ids: Vec<usize> = items.iter()
+            .filter(|(env, _)| env.intersects(query))
+            .map(|&(_, id)| id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_envelope_ops() {
+        let a = Envelope::new(0.0, 0.0, 2.0, 2.0);
+        let b = Envelope::new(1.0, 1.0, 3.0, 4.0);
+        assert!(a.intersects(&b));
+        assert!(!a.intersects(&Envelope::new(5.0, 5.0, 6.0, 6.0)));
+        assert_eq!(a.union(&b), Envelope::new(0.0, 0.0, 3.0, 4.0));
+        assert_eq!(a.area(), 4.0);
+        assert!(a.contains_point(2.0, 0.0));
+    }
+
+    #[test]
+    fn test_bulk_load_matches_brute_force() {
+        let items = grid_items(40);
+        let tree = RTree::bulk_load(items.clone());
+        assert_eq!(tree.len(), 1600);
+
+        for query in [
+            Envelope::new(10.5, 10.5, 12.5, 11.5),
+            Envelope::point(0.5, 39.5),
+            Envelope::new(-5.0, -5.0, -1.0, -1.0),
+        ] {
+            let mut found = tree.query_envelope(&query);
+            found.sort();
+            assert_eq!(found, brute_force(&items, &query));
+        }
+    }
+
+    #[test]
+    fn test_insert_matches_brute_force() {
+        let items = grid_items(25);
+        let mut tree = RTree::new();
+        for &(env, id) in &items {
+            tree.insert(env, id);
+        }
+        assert_eq!(tree.len(), 625);
+
+        let query = Envelope::new(3.2, 17.7, 9.1, 18.2);
+        let mut found = tree.query_envelope(&query);
+        found.sort();
+        assert_eq!(found, brute_force(&items, &query));
+
+        let mut at_point = tree.query_point(4.5, 4.5);
+        at_point.sort();
+        assert_eq!(at_point, vec![4 * 25 + 4]);
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let tree = RTree::bulk_load(Vec::new());
+        assert!(tree.is_empty());
+        assert!(tree.query_point(0.0, 0.0).is_empty());
+    }
+}
@@ -1,8 +1,9 @@
-//! Deflate/ZIP decompression
+//! Deflate/ZIP compression and decompression
 
 use crate::error::Result;
 use flate2::read::ZlibDecoder;
-use std::io::Read;
+use flate2::write::ZlibEncoder;
+use std::io::{Read, Write};
 
 /// Decompresses Deflate/ZIP compressed data
 pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
@@ -12,12 +13,17 @@ pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
     Ok(decompressed)
 }
 
+/// Compresses data with Deflate/ZIP (zlib framing, as TIFF expects)
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use flate2::write::ZlibEncoder;
     use flate2::Compression;
-    use std::io::Write;
 
     #[test]
     fn test_deflate_decompression() {
@@ -29,4 +35,12 @@ mod tests {
         let decompressed = decompress(&compressed).unwrap();
         assert_eq!(decompressed, original);
     }
+
+    #[test]
+    fn test_deflate_round_trip() {
+        let original: Vec<u8> = (0..4096).map(|i| (i % 17) as u8).collect();
+        let compressed = compress(&original).unwrap();
+        assert!(compressed.len() < original.len());
+        assert_eq!(decompress(&compressed).unwrap(), original);
+    }
 }
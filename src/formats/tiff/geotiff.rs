@@ -1,13 +1,14 @@
 //! GeoTIFF specific functionality
 
 use crate::error::Result;
-use crate::projection::{Coordinate, Transformer};
-use super::ifd::IFD;
+use crate::projection::{epsg, Coordinate, Transformer};
+use crate::projection::coordinate::EARTH_RADIUS_M;
+use super::ifd::{IFD, IFDEntry};
 use super::tags;
 use super::reader::TiffReader;
 
 /// GeoTIFF information extracted from an IFD
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GeoInfo {
     /// Model pixel scale (ScaleX, ScaleY, ScaleZ)
     pub pixel_scale: Option<(f64, f64, f64)>,
@@ -19,10 +20,14 @@ pub struct GeoInfo {
     pub epsg_code: Option<u16>,
     /// CRS name
     pub crs_name: Option<String>,
+    /// NoData value from the GDAL_NODATA tag
+    pub nodata: Option<f64>,
+    /// Whether the model is geographic (degrees) rather than projected
+    pub geographic: bool,
 }
 
 /// Represents a GeoTIFF tiepoint
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TiePoint {
     pub pixel_x: f64,
     pub pixel_y: f64,
@@ -32,15 +37,42 @@ pub struct TiePoint {
     pub geo_z: f64,
 }
 
+/// Tag value access [`GeoInfo::from_ifd`] needs, implemented by the
+/// blocking and async readers
+pub trait GeoTagReader {
+    fn read_tag_doubles(&mut self, entry: &IFDEntry) -> Result<Vec<f64>>;
+    fn read_tag_u16s(&mut self, entry: &IFDEntry) -> Result<Vec<u16>>;
+    fn read_tag_ascii(&mut self, entry: &IFDEntry) -> Result<String>;
+}
+
+impl GeoTagReader for TiffReader {
+    fn read_tag_doubles(&mut self, entry: &IFDEntry) -> Result<Vec<f64>> {
+        TiffReader::read_tag_doubles(self, entry)
+    }
+
+    fn read_tag_u16s(&mut self, entry: &IFDEntry) -> Result<Vec<u16>> {
+        TiffReader::read_tag_u16s(self, entry)
+    }
+
+    fn read_tag_ascii(&mut self, entry: &IFDEntry) -> Result<String> {
+        TiffReader::read_tag_ascii(self, entry)
+    }
+}
+
+/// Sphere radius of the Web Mercator projection
+const WEB_MERCATOR_RADIUS: f64 = 6_378_137.0;
+
 /// GeoKey constants
-mod geo_keys {
+pub(crate) mod geo_keys {
+    pub const MODEL_TYPE: u16 = 1024;
+    pub const RASTER_TYPE: u16 = 1025;
     pub const GEOGRAPHIC_TYPE: u16 = 2048;
     pub const PROJECTED_CS_TYPE: u16 = 3072;
 }
 
 impl GeoInfo {
     /// Extracts GeoTIFF information from an IFD
-    pub fn from_ifd(ifd: &IFD, reader: &mut TiffReader) -> Result<Option<Self>> {
+    pub fn from_ifd<R: GeoTagReader + ?Sized>(ifd: &IFD, reader: &mut R) -> Result<Option<Self>> {
         let has_geo_tags = ifd.get_entry(tags::MODEL_PIXEL_SCALE).is_some()
             || ifd.get_entry(tags::MODEL_TIEPOINT).is_some()
             || ifd.get_entry(tags::GEO_KEY_DIRECTORY).is_some();
@@ -55,6 +87,8 @@ impl GeoInfo {
             transform: None,
             epsg_code: None,
             crs_name: None,
+            nodata: None,
+            geographic: false,
         };
 
         if let Some(entry) = ifd.get_entry(tags::MODEL_PIXEL_SCALE) {
@@ -85,6 +119,9 @@ impl GeoInfo {
 
             if keys.len() >= 4 {
                 let num_keys = keys[3] as usize;
+                let mut model_type = None;
+                let mut has_geographic = false;
+                let mut has_projected = false;
 
                 for i in 0..num_keys {
                     let offset = 4 + (i * 4);
@@ -95,13 +132,24 @@ impl GeoInfo {
                         let value_offset = keys[offset + 3];
 
                         match key_id {
-                            geo_keys::GEOGRAPHIC_TYPE | geo_keys::PROJECTED_CS_TYPE => {
+                            geo_keys::MODEL_TYPE => model_type = Some(value_offset),
+                            geo_keys::GEOGRAPHIC_TYPE => {
+                                has_geographic = true;
+                                geo_info.epsg_code = Some(value_offset);
+                            }
+                            geo_keys::PROJECTED_CS_TYPE => {
+                                has_projected = true;
                                 geo_info.epsg_code = Some(value_offset);
                             }
                             _ => {}
                         }
                     }
                 }
+
+                geo_info.geographic = match model_type {
+                    Some(t) => t == 2,
+                    None => has_geographic && !has_projected,
+                };
             }
         }
 
@@ -112,9 +160,32 @@ impl GeoInfo {
             }
         }
 
+        if let Some(entry) = ifd.get_entry(tags::GDAL_NODATA) {
+            let ascii = reader.read_tag_ascii(entry)?;
+            geo_info.nodata = ascii.trim().parse::<f64>().ok();
+        }
+
         Ok(Some(geo_info))
     }
 
+    /// Returns whether a value matches the NoData value (NaN always counts as NoData)
+    pub fn is_nodata(&self, value: f64) -> bool {
+        Self::is_nodata_value(value, self.nodata)
+    }
+
+    /// Returns whether a value matches a NoData value read without a full
+    /// `GeoInfo`, e.g. from a plain TIFF
+    pub fn is_nodata_value(value: f64, nodata: Option<f64>) -> bool {
+        if value.is_nan() {
+            return true;
+        }
+        match nodata {
+            Some(nodata) if nodata.is_nan() => false,
+            Some(nodata) => value == nodata,
+            None => false,
+        }
+    }
+
     /// Computes the affine transform from pixel to geo coordinates
     ///
     /// Returns [a, b, c, d, e, f] where:
@@ -177,6 +248,31 @@ impl GeoInfo {
         Some((pixel_x, pixel_y))
     }
 
+    /// Returns the ground size (x, y) in metres of a pixel in the given row
+    ///
+    /// Geographic CRSs convert degrees at the row's latitude; Web Mercator
+    /// applies its latitude scale factor. Other projected CRSs are assumed
+    /// to use metres.
+    pub fn ground_pixel_size(&self, pixel_y: f64) -> Option<(f64, f64)> {
+        let (scale_x, scale_y, _) = self.pixel_scale?;
+        let transform = self.affine_transform()?;
+        let y = transform[3] + transform[5] * pixel_y;
+
+        if self.geographic {
+            let metres_per_degree = EARTH_RADIUS_M.to_radians();
+            let lat = y.clamp(-90.0, 90.0).to_radians();
+            return Some((scale_x * metres_per_degree * lat.cos(), scale_y * metres_per_degree));
+        }
+
+        match self.epsg_code {
+            Some(epsg::WEB_MERCATOR) | Some(epsg::PSEUDO_MERCATOR) => {
+                let lat = (y / WEB_MERCATOR_RADIUS).sinh().atan();
+                Some((scale_x * lat.cos(), scale_y * lat.cos()))
+            }
+            _ => Some((scale_x, scale_y)),
+        }
+    }
+
     /// Transforms a coordinate to a different CRS
     pub fn transform_coordinate(&self, coord: Coordinate, target_epsg: u16) -> Result<Coordinate> {
         let source_epsg = self.epsg_code
@@ -248,6 +344,10 @@ impl std::fmt::Display for GeoInfo {
             writeln!(f, "  Origin (geo): ({}, {})", tp.geo_x, tp.geo_y)?;
         }
 
+        if let Some(nodata) = self.nodata {
+            writeln!(f, "  NoData: {}", nodata)?;
+        }
+
         Ok(())
     }
 }
@@ -119,19 +119,7 @@ impl IFD {
     /// Determines the pixel data type based on TIFF tags
     pub fn data_type(&self) -> Option<DataType> {
         let bits = self.bits_per_sample()?;
-        let format = self.sample_format();
-
-        match (format, bits) {
-            (1, 8) => Some(DataType::U8),
-            (1, 16) => Some(DataType::U16),
-            (1, 32) => Some(DataType::U32),
-            (2, 8) => Some(DataType::I8),
-            (2, 16) => Some(DataType::I16),
-            (2, 32) => Some(DataType::I32),
-            (3, 32) => Some(DataType::F32),
-            (3, 64) => Some(DataType::F64),
-            _ => None,
-        }
+        DataType::from_tiff(self.sample_format(), bits)
     }
 
     /// Returns whether this IFD represents a tiled image
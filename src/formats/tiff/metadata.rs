@@ -0,0 +1,370 @@
+//! GDAL_METADATA tag support
+//!
+//! GDAL stores dataset and band metadata as a small XML document:
+//!
+//! ```xml
+//! <GDALMetadata>
+//!   <Item name="STATISTICS_MEAN" sample="0">12.5</Item>
+//! </GDALMetadata>
+//! ```
+//!
+//! [`write_gdal_metadata`] updates the tag in place by appending the text and
+//! a rewritten copy of the IFD to the file, then relinking the IFD chain.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::error::{Error, Result};
+use super::{tags, IFD, TiffReader};
+
+/// One metadata item; `sample` is the 0-based band or `None` for the dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataItem {
+    pub name: String,
+    pub sample: Option<usize>,
+    pub value: String,
+}
+
+/// Parsed GDAL_METADATA document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GdalMetadata {
+    pub items: Vec<MetadataItem>,
+}
+
+impl GdalMetadata {
+    /// Creates an empty document
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the GDAL_METADATA tag of an IFD, if present
+    pub fn from_ifd(ifd: &IFD, reader: &mut TiffReader) -> Result<Option<Self>> {
+        match ifd.get_entry(tags::GDAL_METADATA) {
+            Some(entry) => Ok(Some(Self::parse(&reader.read_tag_ascii(entry)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the XML document
+    pub fn parse(xml: &str) -> Result<Self> {
+        let mut items = Vec::new();
+        let mut rest = xml;
+
+        while let Some(start) = rest.find("<Item") {
+            let after = &rest[start + 5..];
+            let tag_end = after.find('>')
+                .ok_or_else(|| Error::InvalidFormat("Unterminated <Item> in GDAL metadata".to_string()))?;
+            let attrs = &after[..tag_end];
+
+            let name = attribute(attrs, "name")
+                .ok_or_else(|| Error::InvalidFormat("GDAL metadata item without name".to_string()))?;
+            let sample = match attribute(attrs, "sample") {
+                Some(s) => Some(s.trim().parse()
+                    .map_err(|_| Error::InvalidFormat(format!("Invalid metadata sample: {}", s)))?),
+                None => None,
+            };
+
+            if attrs.trim_end().ends_with('/') {
+                items.push(MetadataItem { name, sample, value: String::new() });
+                rest = &after[tag_end + 1..];
+                continue;
+            }
+
+            let body = &after[tag_end + 1..];
+            let close = body.find("</Item>")
+                .ok_or_else(|| Error::InvalidFormat("Missing </Item> in GDAL metadata".to_string()))?;
+            items.push(MetadataItem { name, sample, value: unescape_xml(&body[..close]) });
+            rest = &body[close + 7..];
+        }
+
+        Ok(Self { items })
+    }
+
+    /// Serializes the document
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<GDALMetadata>\n");
+        for item in &self.items {
+            xml.push_str(&format!("  <Item name=\"{}\"", escape_xml(&item.name)));
+            if let Some(sample) = item.sample {
+                xml.push_str(&format!(" sample=\"{}\"", sample));
+            }
+            xml.push_str(&format!(">{}</Item>\n", escape_xml(&item.value)));
+        }
+        xml.push_str("</GDALMetadata>");
+        xml
+    }
+
+    /// Returns an item value
+    pub fn get(&self, name: &str, sample: Option<usize>) -> Option<&str> {
+        self.items.iter()
+            .find(|i| i.name == name && i.sample == sample)
+            .map(|i| i.value.as_str())
+    }
+
+    /// Sets an item value, replacing an existing one
+    pub fn set(&mut self, name: &str, sample: Option<usize>, value: impl Into<String>) {
+        let value = value.into();
+        match self.items.iter_mut().find(|i| i.name == name && i.sample == sample) {
+            Some(item) => item.value = value,
+            None => self.items.push(MetadataItem { name: name.to_string(), sample, value }),
+        }
+    }
+
+    /// Copies every item of `other` into this document
+    pub fn merge(&mut self, other: &GdalMetadata) {
+        for item in &other.items {
+            self.set(&item.name, item.sample, item.value.clone());
+        }
+    }
+}
+
+/// Writes a GDAL_METADATA document into the IFD at `ifd_index` of a TIFF file
+///
+/// Any existing GDAL_METADATA tag of that IFD is replaced; other tags are
+/// copied verbatim.
+pub fn write_gdal_metadata<P: AsRef<Path>>(path: P, ifd_index: usize, metadata: &GdalMetadata) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+    let mut header = [0u8; 16];
+    file.read_exact(&mut header[..8])?;
+    let little_endian = match &header[..2] {
+        b"II" => true,
+        b"MM" => false,
+        _ => return Err(Error::InvalidByteOrder(u16::from_be_bytes([header[0], header[1]]))),
+    };
+    let layout = IfdLayout::new(little_endian, read_uint(&header[2..4], little_endian) == 43);
+    if layout.big_tiff {
+        file.read_exact(&mut header[8..16])?;
+    }
+
+    // Follow the IFD chain, remembering where the link to the target lives
+    let mut pointer_pos = layout.first_pointer();
+    let mut ifd_offset = layout.read_pointer(&header[pointer_pos as usize..]);
+    for _ in 0..ifd_index {
+        if ifd_offset == 0 {
+            return Err(Error::InvalidFormat(format!("IFD {} does not exist", ifd_index)));
+        }
+        let count = layout.read_entry_count(&mut file, ifd_offset)?;
+        pointer_pos = ifd_offset + layout.count_size + count * layout.entry_size;
+        ifd_offset = layout.read_pointer_at(&mut file, pointer_pos)?;
+    }
+    if ifd_offset == 0 {
+        return Err(Error::InvalidFormat(format!("IFD {} does not exist", ifd_index)));
+    }
+
+    let count = layout.read_entry_count(&mut file, ifd_offset)?;
+    let mut raw = vec![0u8; (count * layout.entry_size + layout.pointer_size) as usize];
+    file.seek(SeekFrom::Start(ifd_offset + layout.count_size))?;
+    file.read_exact(&mut raw)?;
+    let (entry_bytes, next_pointer) = raw.split_at((count * layout.entry_size) as usize);
+
+    let mut entries: Vec<Vec<u8>> = entry_bytes
+        .chunks(layout.entry_size as usize)
+        .filter(|e| read_uint(&e[..2], little_endian) as u16 != tags::GDAL_METADATA)
+        .map(|e| e.to_vec())
+        .collect();
+
+    let mut text = metadata.to_xml().into_bytes();
+    text.push(0);
+
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let ifd_start = match end + end % 2 {
+        start if text.len() as u64 <= layout.pointer_size => start,
+        start => (start + text.len() as u64).next_multiple_of(2),
+    };
+    if !layout.big_tiff && ifd_start > u32::MAX as u64 {
+        return Err(Error::Unsupported("Output larger than 4 GiB requires BigTIFF".to_string()));
+    }
+
+    if end % 2 == 1 {
+        file.write_all(&[0])?;
+        end += 1;
+    }
+
+    let mut entry = Vec::with_capacity(layout.entry_size as usize);
+    entry.extend_from_slice(&layout.encode(tags::GDAL_METADATA as u64, 2));
+    entry.extend_from_slice(&layout.encode(tags::field_types::ASCII as u64, 2));
+    entry.extend_from_slice(&layout.encode(text.len() as u64, layout.count_size_for_entry()));
+    if text.len() as u64 <= layout.pointer_size {
+        let mut inline = text.clone();
+        inline.resize(layout.pointer_size as usize, 0);
+        entry.extend_from_slice(&inline);
+    } else {
+        file.write_all(&text)?;
+        entry.extend_from_slice(&layout.encode(end, layout.pointer_size as usize));
+        end += text.len() as u64;
+        if end % 2 == 1 {
+            file.write_all(&[0])?;
+            end += 1;
+        }
+    }
+    entries.push(entry);
+    entries.sort_by_key(|e| read_uint(&e[..2], little_endian));
+
+    let mut ifd = layout.encode(entries.len() as u64, layout.count_size as usize);
+    for entry in &entries {
+        ifd.extend_from_slice(entry);
+    }
+    ifd.extend_from_slice(next_pointer);
+    file.write_all(&ifd)?;
+
+    file.seek(SeekFrom::Start(pointer_pos))?;
+    file.write_all(&layout.encode(end, layout.pointer_size as usize))?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Field sizes of classic TIFF or BigTIFF IFDs
+struct IfdLayout {
+    little_endian: bool,
+    big_tiff: bool,
+    count_size: u64,
+    entry_size: u64,
+    pointer_size: u64,
+}
+
+impl IfdLayout {
+    fn new(little_endian: bool, big_tiff: bool) -> Self {
+        let (count_size, entry_size, pointer_size) = if big_tiff { (8, 20, 8) } else { (2, 12, 4) };
+        Self { little_endian, big_tiff, count_size, entry_size, pointer_size }
+    }
+
+    fn first_pointer(&self) -> u64 {
+        if self.big_tiff { 8 } else { 4 }
+    }
+
+    fn count_size_for_entry(&self) -> usize {
+        if self.big_tiff { 8 } else { 4 }
+    }
+
+    fn read_pointer(&self, bytes: &[u8]) -> u64 {
+        read_uint(&bytes[..self.pointer_size as usize], self.little_endian)
+    }
+
+    fn read_pointer_at<R: Read + Seek>(&self, reader: &mut R, pos: u64) -> Result<u64> {
+        let mut buf = vec![0u8; self.pointer_size as usize];
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut buf)?;
+        Ok(self.read_pointer(&buf))
+    }
+
+    fn read_entry_count<R: Read + Seek>(&self, reader: &mut R, ifd_offset: u64) -> Result<u64> {
+        let mut buf = vec![0u8; self.count_size as usize];
+        reader.seek(SeekFrom::Start(ifd_offset))?;
+        reader.read_exact(&mut buf)?;
+        Ok(read_uint(&buf, self.little_endian))
+    }
+
+    fn encode(&self, value: u64, size: usize) -> Vec<u8> {
+        let bytes = value.to_le_bytes();
+        let mut out = bytes[..size].to_vec();
+        if !self.little_endian {
+            out.reverse();
+        }
+        out
+    }
+}
+
+/// Helper: Decodes an unsigned integer of 1-8 bytes
+fn read_uint(bytes: &[u8], little_endian: bool) -> u64 {
+    let fold = |acc: u64, b: &u8| (acc << 8) | *b as u64;
+    if little_endian {
+        bytes.iter().rev().fold(0, fold)
+    } else {
+        bytes.iter().fold(0, fold)
+    }
+}
+
+/// Helper: Extracts a quoted attribute value from a tag's attribute text
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(pos) = rest.find(name) {
+        let before_ok = pos == 0 || rest[..pos].ends_with(char::is_whitespace);
+        let after = rest[pos + name.len()..].trim_start();
+        if before_ok {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+                let end = value[1..].find(quote)?;
+                return Some(unescape_xml(&value[1..1 + end]));
+            }
+        }
+        rest = &rest[pos + name.len()..];
+    }
+    None
+}
+
+/// Escapes text for XML attributes and content
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Reverses [`escape_xml`] (also accepts `&apos;`)
+pub(crate) fn unescape_xml(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+
+    #[test]
+    fn test_parse_and_serialize() {
+        let xml = r#"<GDALMetadata>
+  <Item name="AREA_OR_POINT">Area</Item>
+  <Item name="STATISTICS_MEAN" sample="0" role="stats">1.5</Item>
+  <Item name="DESCRIPTION" sample="1">a &amp; b</Item>
+  <Item name="EMPTY"/>
+</GDALMetadata>"#;
+
+        let metadata = GdalMetadata::parse(xml).unwrap();
+        assert_eq!(metadata.items.len(), 4);
+        assert_eq!(metadata.get("STATISTICS_MEAN", Some(0)), Some("1.5"));
+        assert_eq!(metadata.get("DESCRIPTION", Some(1)), Some("a & b"));
+        assert_eq!(metadata.get("AREA_OR_POINT", None), Some("Area"));
+        assert_eq!(GdalMetadata::parse(&metadata.to_xml()).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_write_into_file() {
+        let spec = FixtureSpec::new(32, 32);
+        let file = write_geotiff(&spec, |x, _| x as f64);
+
+        let mut metadata = GdalMetadata::new();
+        metadata.set("STATISTICS_MAXIMUM", Some(0), "31");
+        write_gdal_metadata(file.path(), 0, &metadata).unwrap();
+
+        metadata.set("STATISTICS_MINIMUM", Some(0), "0");
+        write_gdal_metadata(file.path(), 0, &metadata).unwrap();
+
+        let mut reader = TiffReader::open(file.path()).unwrap();
+        let tiff = reader.read().unwrap();
+        let ifd = tiff.main_ifd().unwrap();
+        let stored = GdalMetadata::from_ifd(ifd, &mut reader).unwrap().unwrap();
+        assert_eq!(stored, metadata);
+        assert_eq!(reader.read_pixel_value(ifd, 17, 3).unwrap(), 17);
+
+        assert!(write_gdal_metadata(file.path(), 3, &metadata).is_err());
+    }
+
+    #[test]
+    fn test_classic_tiff_offset_limit() {
+        let spec = FixtureSpec::new(16, 16);
+        let file = write_geotiff(&spec, |x, _| x as f64);
+        // Sparse padding pushes the appended IFD past what a 4-byte offset can hold
+        file.as_file().set_len(u32::MAX as u64 - 8).unwrap();
+
+        let mut metadata = GdalMetadata::new();
+        metadata.set("STATISTICS_MAXIMUM", Some(0), "15");
+        assert!(matches!(write_gdal_metadata(file.path(), 0, &metadata), Err(Error::Unsupported(_))));
+        assert_eq!(file.as_file().metadata().unwrap().len(), u32::MAX as u64 - 8);
+    }
+}
@@ -5,11 +5,18 @@ pub mod ifd;
 pub mod types;
 pub mod reader;
 pub mod geotiff;
+pub mod metadata;
+pub mod writer;
+
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use ifd::{IFD, IFDEntry};
 pub use types::Tiff;
-pub use reader::TiffReader;
+pub use reader::{AsyncTiffReader, SharedTiffReader, TiffReader};
 pub use geotiff::GeoInfo;
+pub use metadata::{GdalMetadata, write_gdal_metadata};
+pub use writer::{GeoReference, GeoTiffWriter, WriterConfig};
 
 /// TIFF magic number (42)
 pub const TIFF_MAGIC: u16 = 42;
@@ -0,0 +1,618 @@
+//! Non-blocking TIFF reader for async runtimes
+//!
+//! [`AsyncTiffReader`] reads only the header when it opens. IFDs are parsed
+//! as they are requested, together with their out-of-line tag values, so tag
+//! and GeoTIFF lookups on a parsed IFD never touch the source. The tile
+//! offset arrays of an IFD are fetched when its first tile is read. Tiles
+//! are fetched asynchronously and decompressed on the rayon pool; no method
+//! blocks the executor thread it is polled on.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use bytes::Bytes;
+use dashmap::DashMap;
+use rayon::prelude::*;
+use tokio::sync::Mutex;
+use crate::cache::TileCache;
+use crate::error::{Error, Result};
+use crate::io::{open_path, AsyncRangeSource, BlockingSource, BufferedReader, ByteOrder, MemorySource};
+use crate::formats::tiff::{Tiff, IFD, IFDEntry, GeoInfo, TIFF_MAGIC, BIGTIFF_MAGIC, tags};
+use crate::formats::tiff::geotiff::GeoTagReader;
+use super::locations::TileLocations;
+use super::parallel::decode_tile;
+use super::pixels::PixelReader;
+use super::tags::TagReader;
+
+/// Codec settings shared by all tiles of an IFD
+#[derive(Debug, Clone, Copy)]
+struct TileCodec {
+    compression: u64,
+    predictor: u64,
+    width: u64,
+    height: u64,
+    /// Bytes in one decoded tile
+    size: usize,
+}
+
+impl TileCodec {
+    /// Decodes one tile; sparse tiles without bytes decode to zeros
+    fn decode(&self, compressed: &[u8]) -> Result<Vec<u8>> {
+        if compressed.is_empty() {
+            return Ok(vec![0u8; self.size]);
+        }
+        decode_tile(compressed, self.compression, self.predictor, self.width, self.height)
+    }
+}
+
+/// Tile locations and codec of a tiled IFD, fetched with its first tile
+struct TileLayout {
+    locations: TileLocations,
+    codec: TileCodec,
+}
+
+/// IFD offsets found so far by following the chain
+#[derive(Default)]
+struct IfdChain {
+    offsets: Vec<u64>,
+    visited: HashSet<u64>,
+    complete: bool,
+}
+
+/// TIFF reader whose I/O and decompression never block the async runtime
+///
+/// Reads take `&self`, so one reader can be shared across tasks in an
+/// `Arc`; decompressed tiles are kept in a shared [`TileCache`].
+pub struct AsyncTiffReader {
+    source: Arc<dyn AsyncRangeSource>,
+    byte_order: ByteOrder,
+    is_big_tiff: bool,
+    first_ifd_offset: u64,
+    chain: Mutex<IfdChain>,
+    ifds: DashMap<usize, IFD>,
+    tag_data: DashMap<u64, Bytes>,
+    layouts: DashMap<u64, Arc<TileLayout>>,
+    cache: TileCache,
+}
+
+impl AsyncTiffReader {
+    /// Opens a local file, URL or archive member (see [`open_path`])
+    ///
+    /// Blocking sources are read on tokio's blocking thread pool.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let source = tokio::task::spawn_blocking(move || open_path(path, true))
+            .await
+            .map_err(std::io::Error::other)??;
+        Self::from_source(Arc::new(BlockingSource::new(source)), 256).await
+    }
+
+    /// Creates a reader over a TIFF held in memory
+    pub async fn from_bytes(data: impl Into<MemorySource>) -> Result<Self> {
+        Self::from_source(Arc::new(data.into()), 256).await
+    }
+
+    /// Creates a reader over any async byte source
+    ///
+    /// # Arguments
+    /// * `source` - Source of the TIFF bytes
+    /// * `cache_size` - Number of decompressed tiles to cache
+    pub async fn from_source(source: Arc<dyn AsyncRangeSource>, cache_size: usize) -> Result<Self> {
+        let header = source.read_range(0, source.len().min(16)).await?;
+        if header.len() < 8 {
+            return Err(Error::InvalidFormat("File too small for a TIFF header".to_string()));
+        }
+
+        let byte_order = ByteOrder::from_tiff_magic([header[0], header[1]])
+            .ok_or(Error::InvalidByteOrder(u16::from_be_bytes([header[0], header[1]])))?;
+        let handler = byte_order.handler();
+        let mut cursor = Cursor::new(&header[2..]);
+
+        let is_big_tiff = match handler.read_u16(&mut cursor)? {
+            TIFF_MAGIC => false,
+            BIGTIFF_MAGIC => true,
+            magic => return Err(Error::InvalidMagic(magic)),
+        };
+
+        let first_ifd_offset = if is_big_tiff {
+            let offset_size = handler.read_u16(&mut cursor)?;
+            if offset_size != 8 {
+                return Err(Error::InvalidFormat(
+                    format!("Invalid BigTIFF offset size: {}", offset_size)
+                ));
+            }
+            let _reserved = handler.read_u16(&mut cursor)?;
+            handler.read_u64(&mut cursor)?
+        } else {
+            handler.read_u32(&mut cursor)? as u64
+        };
+
+        Ok(Self {
+            source,
+            byte_order,
+            is_big_tiff,
+            first_ifd_offset,
+            chain: Mutex::new(IfdChain::default()),
+            ifds: DashMap::new(),
+            tag_data: DashMap::new(),
+            layouts: DashMap::new(),
+            cache: TileCache::new(cache_size),
+        })
+    }
+
+    /// Parses every IFD in the chain
+    pub async fn read(&self) -> Result<Tiff> {
+        let mut tiff = Tiff::new(self.is_big_tiff);
+        while let Some(ifd) = self.ifd(tiff.ifd_count()).await? {
+            tiff.add_ifd(ifd);
+        }
+        Ok(tiff)
+    }
+
+    /// Parses the IFD at position `number` in the chain
+    ///
+    /// Only the entry counts and next pointers of the IFDs before it are
+    /// read. Its tag values are fetched along with it, except the tile
+    /// offset arrays. Returns `None` past the last IFD.
+    pub async fn ifd(&self, number: usize) -> Result<Option<IFD>> {
+        if let Some(ifd) = self.ifds.get(&number) {
+            return Ok(Some(ifd.clone()));
+        }
+
+        let Some(offset) = self.ifd_offset(number).await? else {
+            return Ok(None);
+        };
+        let ifd = self.read_ifd(number, offset).await?;
+        self.load_tag_data(&ifd).await?;
+        Ok(Some(self.ifds.entry(number).or_insert(ifd).clone()))
+    }
+
+    /// Returns the byte source this reader reads from
+    pub fn source(&self) -> &Arc<dyn AsyncRangeSource> {
+        &self.source
+    }
+
+    /// Extracts GeoTIFF information from an IFD without further I/O
+    pub fn geo_info(&self, ifd: &IFD) -> Result<Option<GeoInfo>> {
+        let mut tags = self;
+        GeoInfo::from_ifd(ifd, &mut tags)
+    }
+
+    /// Reads tag values as f64 array
+    pub fn read_tag_doubles(&self, entry: &IFDEntry) -> Result<Vec<f64>> {
+        self.with_tag_reader(entry, |reader, entry| reader.read_doubles(entry))
+    }
+
+    /// Reads tag values as u16 array
+    pub fn read_tag_u16s(&self, entry: &IFDEntry) -> Result<Vec<u16>> {
+        self.with_tag_reader(entry, |reader, entry| reader.read_u16s(entry))
+    }
+
+    /// Reads ASCII string from tag
+    pub fn read_tag_ascii(&self, entry: &IFDEntry) -> Result<String> {
+        self.with_tag_reader(entry, |reader, entry| reader.read_ascii(entry))
+    }
+
+    /// Reads a tile, from the cache when possible
+    pub async fn read_tile(&self, ifd: &IFD, tile_index: usize) -> Result<Arc<Vec<u8>>> {
+        let mut tiles = self.read_tiles(ifd, &[tile_index]).await?;
+        Ok(tiles.remove(0))
+    }
+
+    /// Reads several tiles
+    ///
+    /// Uncached tiles are fetched with one batched source read and
+    /// decompressed in parallel on the rayon pool.
+    pub async fn read_tiles(&self, ifd: &IFD, tile_indices: &[usize]) -> Result<Vec<Arc<Vec<u8>>>> {
+        let layout = self.layout(ifd).await?;
+        let mut tiles: HashMap<usize, Arc<Vec<u8>>> = HashMap::with_capacity(tile_indices.len());
+        let mut missing: Vec<usize> = tile_indices.iter()
+            .copied()
+            .filter(|&tile_index| match self.cache.get(ifd.number, tile_index) {
+                Some(data) => { tiles.insert(tile_index, data); false }
+                None => true,
+            })
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+
+        if !missing.is_empty() {
+            let ranges = missing.iter()
+                .map(|&tile_index| layout.locations.get(tile_index))
+                .collect::<Result<Vec<_>>>()?;
+
+            // Sparse tiles have no bytes to fetch
+            let fetch: Vec<(u64, u64)> = ranges.iter().copied().filter(|&(_, length)| length > 0).collect();
+            let mut fetched = self.fetch_all(fetch).await?.into_iter();
+            let compressed: Vec<Bytes> = ranges.iter()
+                .map(|&(_, length)| if length == 0 { Bytes::new() } else { fetched.next().unwrap_or_default() })
+                .collect();
+
+            let codec = layout.codec;
+            let decoded = on_rayon(move || {
+                compressed.par_iter().map(|data| codec.decode(data)).collect::<Result<Vec<_>>>()
+            }).await??;
+
+            for (tile_index, data) in missing.into_iter().zip(decoded) {
+                let data = Arc::new(data);
+                self.cache.put_shared(ifd.number, tile_index, data.clone());
+                tiles.insert(tile_index, data);
+            }
+        }
+
+        Ok(tile_indices.iter().map(|tile_index| tiles[tile_index].clone()).collect())
+    }
+
+    /// Reads a pixel value at specific pixel coordinates (u8)
+    pub async fn read_pixel_value(&self, ifd: &IFD, x: u64, y: u64) -> Result<u8> {
+        let values = self.read_pixels_batch(ifd, &[(x, y)]).await?;
+        Ok(values[0])
+    }
+
+    /// Reads multiple pixel values, loading each tile they touch once
+    pub async fn read_pixels_batch(&self, ifd: &IFD, coords: &[(u64, u64)]) -> Result<Vec<u8>> {
+        self.read_pixels_batch_with(ifd, coords, PixelReader::read_u8_from_tile).await
+    }
+
+    /// Reads multiple pixel values of any supported data type, widened to f64
+    pub async fn read_pixels_batch_f64(&self, ifd: &IFD, coords: &[(u64, u64)]) -> Result<Vec<f64>> {
+        let data_type = PixelReader::require_data_type(ifd)?;
+        self.read_pixels_batch_with(ifd, coords, |tile_data, pixel_index| {
+            PixelReader::read_as_f64_from_tile(tile_data, pixel_index, data_type)
+        }).await
+    }
+
+    /// Helper: Groups pixels by tile, loads the tiles and extracts values
+    async fn read_pixels_batch_with<T, F>(&self, ifd: &IFD, coords: &[(u64, u64)], extract: F) -> Result<Vec<T>>
+    where
+        F: Fn(&[u8], usize) -> Result<T>,
+    {
+        PixelReader::validate_tiled_access(ifd)?;
+
+        let mut tile_indices = Vec::with_capacity(coords.len());
+        for &(x, y) in coords {
+            PixelReader::validate_pixel_bounds(ifd, x, y)?;
+            tile_indices.push(PixelReader::calculate_tile_index(ifd, x, y)?);
+        }
+
+        let mut unique = tile_indices.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        let tiles: HashMap<usize, Arc<Vec<u8>>> = unique.iter().copied()
+            .zip(self.read_tiles(ifd, &unique).await?)
+            .collect();
+
+        coords.iter().zip(&tile_indices)
+            .map(|(&(x, y), tile_index)| {
+                let pixel_index = PixelReader::calculate_pixel_index(ifd, x, y)?;
+                extract(&tiles[tile_index], pixel_index)
+            })
+            .collect()
+    }
+
+    /// Helper: Follows the IFD chain far enough to find the offset of IFD `number`
+    async fn ifd_offset(&self, number: usize) -> Result<Option<u64>> {
+        let mut chain = self.chain.lock().await;
+
+        while chain.offsets.len() <= number && !chain.complete {
+            let next = match chain.offsets.last() {
+                None => self.first_ifd_offset,
+                Some(&offset) => self.read_next_ifd_offset(offset).await?,
+            };
+
+            if next == 0 {
+                chain.complete = true;
+            } else if next >= self.source.len() || !chain.visited.insert(next) {
+                return Err(Error::InvalidOffset(next));
+            } else {
+                chain.offsets.push(next);
+            }
+        }
+
+        Ok(chain.offsets.get(number).copied())
+    }
+
+    /// Helper: Reads the entry count of the IFD at `offset`
+    async fn read_entry_count(&self, offset: u64) -> Result<u64> {
+        let handler = self.byte_order.handler();
+        let count_bytes = self.fetch(offset, self.ifd_sizes().0).await?;
+        let mut cursor = Cursor::new(&count_bytes[..]);
+        if self.is_big_tiff {
+            Ok(handler.read_u64(&mut cursor)?)
+        } else {
+            Ok(handler.read_u16(&mut cursor)? as u64)
+        }
+    }
+
+    /// Helper: Reads the next IFD pointer of the IFD at `offset`
+    async fn read_next_ifd_offset(&self, offset: u64) -> Result<u64> {
+        let handler = self.byte_order.handler();
+        let (count_size, entry_size, pointer_size) = self.ifd_sizes();
+        let entry_count = self.read_entry_count(offset).await?;
+
+        let pointer_offset = entry_count.checked_mul(entry_size)
+            .and_then(|length| length.checked_add(offset + count_size))
+            .ok_or(Error::InvalidOffset(offset))?;
+        let pointer = self.fetch(pointer_offset, pointer_size).await?;
+        let mut cursor = Cursor::new(&pointer[..]);
+        if self.is_big_tiff {
+            Ok(handler.read_u64(&mut cursor)?)
+        } else {
+            Ok(handler.read_u32(&mut cursor)? as u64)
+        }
+    }
+
+    /// Helper: Reads the entries of the IFD at `offset`
+    async fn read_ifd(&self, number: usize, offset: u64) -> Result<IFD> {
+        let handler = self.byte_order.handler();
+        let (count_size, entry_size, _) = self.ifd_sizes();
+        let entry_count = self.read_entry_count(offset).await?;
+
+        let length = entry_count.checked_mul(entry_size).ok_or(Error::InvalidOffset(offset))?;
+        let body = self.fetch(offset + count_size, length).await?;
+        let mut cursor = Cursor::new(&body[..]);
+
+        let mut ifd = IFD::new(number, offset);
+        for _ in 0..entry_count {
+            let tag = handler.read_u16(&mut cursor)?;
+            let field_type = handler.read_u16(&mut cursor)?;
+            let (count, value_offset) = if self.is_big_tiff {
+                (handler.read_u64(&mut cursor)?, handler.read_u64(&mut cursor)?)
+            } else {
+                (handler.read_u32(&mut cursor)? as u64, handler.read_u32(&mut cursor)? as u64)
+            };
+            ifd.add_entry(IFDEntry::new(tag, field_type, count, value_offset));
+        }
+
+        Ok(ifd)
+    }
+
+    /// Helper: Reads a range that must lie within the source
+    ///
+    /// Lengths come from the file, so a corrupt count fails here instead of
+    /// turning into a huge allocation or remote fetch.
+    async fn fetch(&self, offset: u64, length: u64) -> Result<Bytes> {
+        self.check_range(offset, length)?;
+        Ok(self.source.read_range(offset, length).await?)
+    }
+
+    /// Helper: Reads several ranges that must lie within the source
+    async fn fetch_all(&self, ranges: Vec<(u64, u64)>) -> Result<Vec<Bytes>> {
+        for &(offset, length) in &ranges {
+            self.check_range(offset, length)?;
+        }
+        Ok(self.source.read_ranges(ranges).await?)
+    }
+
+    /// Helper: Rejects ranges that run past the end of the source
+    fn check_range(&self, offset: u64, length: u64) -> Result<()> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.source.len() => Ok(()),
+            _ => Err(Error::OutOfBounds(format!(
+                "Range {}+{} exceeds file size {}", offset, length, self.source.len()
+            ))),
+        }
+    }
+
+    /// Helper: Returns the (entry count, entry, next pointer) sizes of an IFD
+    fn ifd_sizes(&self) -> (u64, u64, u64) {
+        if self.is_big_tiff { (8, 20, 8) } else { (2, 12, 4) }
+    }
+
+    /// Helper: Fetches the out-of-line tag values of an IFD in one batch
+    ///
+    /// The tile offset arrays can be large and are left to [`layout`](Self::layout).
+    async fn load_tag_data(&self, ifd: &IFD) -> Result<()> {
+        let mut ranges = Vec::new();
+        for entry in &ifd.entries {
+            if entry.is_inline(self.is_big_tiff)
+                || matches!(entry.tag, tags::TILE_OFFSETS | tags::TILE_BYTE_COUNTS)
+                || self.tag_data.contains_key(&entry.value_offset)
+            {
+                continue;
+            }
+            let length = entry.count.checked_mul(entry.field_type_size() as u64)
+                .ok_or_else(|| Error::InvalidFormat(format!("Tag {} count {} is too large", entry.tag, entry.count)))?;
+            ranges.push((entry.value_offset, length));
+        }
+        ranges.sort_unstable();
+        ranges.dedup();
+
+        let values = self.fetch_all(ranges.clone()).await?;
+        for ((offset, _), value) in ranges.into_iter().zip(values) {
+            self.tag_data.insert(offset, value);
+        }
+        Ok(())
+    }
+
+    /// Helper: Returns the tile layout of an IFD of this file, fetching its
+    /// tile offset arrays on first use
+    async fn layout(&self, ifd: &IFD) -> Result<Arc<TileLayout>> {
+        if self.ifds.get(&ifd.number).is_none_or(|parsed| parsed.offset != ifd.offset) {
+            return Err(Error::InvalidFormat(
+                format!("IFD {} at offset {} is not part of this file", ifd.number, ifd.offset)
+            ));
+        }
+        if let Some(layout) = self.layouts.get(&ifd.offset) {
+            return Ok(layout.clone());
+        }
+
+        let (Some(tile_dims), Some(_), Some(_)) = (
+            ifd.tile_dimensions(),
+            ifd.get_entry(tags::TILE_OFFSETS),
+            ifd.get_entry(tags::TILE_BYTE_COUNTS),
+        ) else {
+            return Err(Error::Unsupported("Only tiled TIFFs are supported".to_string()));
+        };
+
+        let ranges = TileLocations::value_ranges(ifd, self.is_big_tiff)?;
+        let mut fetched = self.fetch_all(ranges.iter().flatten().copied().collect()).await?.into_iter();
+        let [offsets, byte_counts] = ranges.map(|range| range.and_then(|_| fetched.next()));
+
+        // Multi-band IFDs store one BitsPerSample value per band
+        let bits_per_sample = match ifd.get_entry(tags::BITS_PER_SAMPLE) {
+            Some(entry) => self.read_tag_u16s(entry)?.first().copied().unwrap_or(1) as u64,
+            None => 1,
+        };
+        let locations = TileLocations::decode(
+            ifd, offsets.as_deref(), byte_counts.as_deref(), bits_per_sample, self.byte_order, self.is_big_tiff,
+        )?;
+
+        let layout = Arc::new(TileLayout {
+            codec: TileCodec {
+                compression: ifd.compression().unwrap_or(1),
+                predictor: ifd.get_tag_value(tags::PREDICTOR).unwrap_or(1),
+                width: tile_dims.width,
+                height: tile_dims.height,
+                size: locations.tile_size,
+            },
+            locations,
+        });
+        Ok(self.layouts.entry(ifd.offset).or_insert(layout).clone())
+    }
+
+    /// Helper: Runs a [`TagReader`] over the prefetched value bytes of an entry
+    fn with_tag_reader<T, F>(&self, entry: &IFDEntry, read: F) -> Result<T>
+    where
+        F: FnOnce(&mut TagReader<'_, Cursor<Bytes>>, &IFDEntry) -> Result<T>,
+    {
+        let (data, entry) = if entry.is_inline(self.is_big_tiff) {
+            (Bytes::new(), entry.clone())
+        } else {
+            let data = self.tag_data.get(&entry.value_offset)
+                .map(|data| data.clone())
+                .ok_or(Error::InvalidOffset(entry.value_offset))?;
+            (data, IFDEntry { value_offset: 0, ..entry.clone() })
+        };
+
+        let handler = self.byte_order.handler();
+        let mut reader = BufferedReader::new(Cursor::new(data));
+        read(&mut TagReader::new(&mut reader, &*handler, self.is_big_tiff), &entry)
+    }
+}
+
+impl GeoTagReader for &AsyncTiffReader {
+    fn read_tag_doubles(&mut self, entry: &IFDEntry) -> Result<Vec<f64>> {
+        AsyncTiffReader::read_tag_doubles(self, entry)
+    }
+
+    fn read_tag_u16s(&mut self, entry: &IFDEntry) -> Result<Vec<u16>> {
+        AsyncTiffReader::read_tag_u16s(self, entry)
+    }
+
+    fn read_tag_ascii(&mut self, entry: &IFDEntry) -> Result<String> {
+        AsyncTiffReader::read_tag_ascii(self, entry)
+    }
+}
+
+/// Helper: Runs CPU-bound work on the rayon pool and awaits its result
+async fn on_rayon<T, F>(job: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    rayon::spawn(move || {
+        let _ = sender.send(job());
+    });
+    receiver.await.map_err(|_| Error::Io(std::io::Error::other("Tile decompression task panicked")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{build_geotiff, open_fixture, write_geotiff, FixtureSpec};
+    use crate::io::http::test_server;
+
+    #[tokio::test]
+    async fn test_matches_blocking_reader() {
+        let file = write_geotiff(&FixtureSpec::new(64, 48), |x, y| (x * 3 + y * 5) as f64);
+        let reader = AsyncTiffReader::open(file.path()).await.unwrap();
+        let ifd = &reader.ifd(0).await.unwrap().unwrap();
+
+        let (mut blocking, blocking_ifd) = open_fixture(file.path());
+        assert_eq!(ifd.entries.len(), blocking_ifd.entries.len());
+
+        let coords = [(0, 0), (63, 47), (17, 30), (17, 31)];
+        assert_eq!(
+            reader.read_pixels_batch(ifd, &coords).await.unwrap(),
+            blocking.read_pixels_batch(&blocking_ifd, &coords).unwrap(),
+        );
+        assert_eq!(reader.read_pixels_batch_f64(ifd, &[(10, 2)]).await.unwrap(), vec![40.0]);
+
+        let geo = reader.geo_info(ifd).unwrap().unwrap();
+        assert_eq!(geo.epsg_code, Some(3857));
+        assert_eq!(geo.transform_crs_to_pixel(crate::projection::Coordinate::new(5.5, 40.5), 3857).unwrap(), (5.5, 7.5));
+
+        let tile = reader.read_tile(ifd, 1).await.unwrap();
+        assert!(Arc::ptr_eq(&tile, &reader.read_tile(ifd, 1).await.unwrap()));
+        assert!(reader.read_pixel_value(ifd, 64, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reads_from_memory_and_http() {
+        let data = build_geotiff(&FixtureSpec::new(256, 256), |x, y| ((x + y) % 251) as f64);
+
+        let reader = AsyncTiffReader::from_bytes(data.clone()).await.unwrap();
+        let ifd = &reader.ifd(0).await.unwrap().unwrap();
+        assert_eq!(reader.read_pixel_value(ifd, 200, 100).await.unwrap(), 49);
+
+        let served = test_server::serve(data, 0);
+        let reader = AsyncTiffReader::open(&served.url).await.unwrap();
+        let ifd = &reader.ifd(0).await.unwrap().unwrap();
+        let row: Vec<(u64, u64)> = (0..16).map(|col| (col * 16, 130)).collect();
+        let values = reader.read_pixels_batch(ifd, &row).await.unwrap();
+        assert_eq!(values, row.iter().map(|&(x, y)| ((x + y) % 251) as u8).collect::<Vec<_>>());
+
+        assert!(AsyncTiffReader::from_bytes(b"not a tiff".to_vec()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lazy_ifds() {
+        use crate::formats::tiff::test_support::{build_geotiff_images, SampleFn};
+
+        let spec = FixtureSpec::new(32, 32);
+        let pages: Vec<_> = (0..40u64)
+            .map(|page| move |x: u64, _: u64, _: u16| (page * 5 + x) as f64)
+            .collect();
+        let images: Vec<(&FixtureSpec, SampleFn)> = pages.iter().map(|page| (&spec, page as SampleFn)).collect();
+        let mut data = build_geotiff_images(&images);
+
+        let reader = AsyncTiffReader::from_bytes(data.clone()).await.unwrap();
+        let page = reader.ifd(37).await.unwrap().unwrap();
+        assert_eq!(page.number, 37);
+        assert_eq!(reader.chain.lock().await.offsets.len(), 38);
+        assert!(reader.layouts.is_empty());
+        assert!(reader.geo_info(&page).unwrap().is_some());
+        assert_eq!(reader.read_pixel_value(&page, 3, 0).await.unwrap(), 188);
+        assert!(reader.ifd(40).await.unwrap().is_none());
+        assert_eq!(reader.read().await.unwrap().ifd_count(), 40);
+
+        // A chain pointing back at an earlier IFD is rejected
+        let first_offset = data[4..8].to_vec();
+        let last_pointer = data.len() - 4;
+        data[last_pointer..].copy_from_slice(&first_offset);
+        let reader = AsyncTiffReader::from_bytes(data).await.unwrap();
+        assert!(reader.read().await.is_err());
+        assert!(reader.ifd(39).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_counts_are_rejected() {
+        let data = build_geotiff(&FixtureSpec::new(32, 32), |x, _| x as f64);
+        let first = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+
+        // An entry count whose entries would run past the end of the file
+        let mut entries = data.clone();
+        entries[first..first + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+        let reader = AsyncTiffReader::from_bytes(entries).await.unwrap();
+        assert!(matches!(reader.ifd(0).await, Err(Error::OutOfBounds(_))));
+
+        // A tag value count of 2^32 - 1
+        let mut values = data;
+        values[first + 6..first + 10].copy_from_slice(&u32::MAX.to_le_bytes());
+        let reader = AsyncTiffReader::from_bytes(values).await.unwrap();
+        assert!(matches!(reader.ifd(0).await, Err(Error::OutOfBounds(_))));
+    }
+}
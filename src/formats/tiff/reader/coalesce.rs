@@ -0,0 +1,119 @@
+//! Coalesced I/O planning for batch tile reads
+//!
+//! Tiles requested together are sorted by file offset and neighbours at most
+//! a gap threshold apart are merged, up to a size limit, so a batch costs a
+//! few large reads instead of one read per tile. Each tile is then a slice
+//! of a read buffer.
+
+/// Default largest gap, in bytes, read through to merge two tile ranges
+pub const DEFAULT_MAX_GAP: u64 = 64 * 1024;
+
+/// Default largest merged read, in bytes
+pub const DEFAULT_MAX_READ_SIZE: u64 = 16 * 1024 * 1024;
+
+/// One contiguous read covering one or more tiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoalescedRead {
+    pub offset: u64,
+    pub length: u64,
+    /// (request position, start within the read, byte count) of each tile
+    pub tiles: Vec<(usize, usize, usize)>,
+}
+
+/// The reads needed to fetch a batch of tiles
+#[derive(Debug, Clone, Default)]
+pub struct ReadPlan {
+    pub reads: Vec<CoalescedRead>,
+}
+
+impl ReadPlan {
+    /// Plans reads for (offset, byte count) ranges, merging ranges at most
+    /// `max_gap` bytes apart while the merged read stays within `max_read_size`
+    ///
+    /// Empty ranges need no read and are left out of the plan. A single
+    /// range larger than `max_read_size` still gets a read of its own.
+    pub fn new(ranges: &[(u64, u64)], max_gap: u64, max_read_size: u64) -> Self {
+        let mut order: Vec<usize> = (0..ranges.len())
+            .filter(|&position| ranges[position].1 > 0)
+            .collect();
+        order.sort_by_key(|&position| ranges[position].0);
+
+        let mut reads: Vec<CoalescedRead> = Vec::new();
+        for position in order {
+            let (offset, length) = ranges[position];
+            let end = offset.saturating_add(length);
+
+            match reads.last_mut() {
+                Some(read) if offset <= (read.offset + read.length).saturating_add(max_gap)
+                    && end - read.offset <= max_read_size =>
+                {
+                    read.length = read.length.max(end - read.offset);
+                    read.tiles.push((position, (offset - read.offset) as usize, length as usize));
+                }
+                _ => reads.push(CoalescedRead {
+                    offset,
+                    length: end - offset,
+                    tiles: vec![(position, 0, length as usize)],
+                }),
+            }
+        }
+
+        Self { reads }
+    }
+
+    /// Returns the number of reads
+    pub fn len(&self) -> usize {
+        self.reads.len()
+    }
+
+    /// Returns whether nothing needs to be read
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty()
+    }
+
+    /// Returns the total bytes read, including the gaps read through
+    pub fn total_bytes(&self) -> u64 {
+        self.reads.iter().map(|read| read.length).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merges_ranges_within_gap() {
+        // Out of file order, with a duplicate and an empty tile
+        let ranges = [(300, 50), (100, 100), (200, 50), (1000, 10), (100, 100), (0, 0)];
+        let plan = ReadPlan::new(&ranges, 50, DEFAULT_MAX_READ_SIZE);
+
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan.reads[0], CoalescedRead {
+            offset: 100,
+            length: 250,
+            tiles: vec![(1, 0, 100), (4, 0, 100), (2, 100, 50), (0, 200, 50)],
+        });
+        assert_eq!(plan.reads[1], CoalescedRead { offset: 1000, length: 10, tiles: vec![(3, 0, 10)] });
+        assert_eq!(plan.total_bytes(), 260);
+    }
+
+    #[test]
+    fn test_gap_threshold() {
+        let ranges = [(0, 10), (10, 10), (40, 10)];
+        assert_eq!(ReadPlan::new(&ranges, 0, DEFAULT_MAX_READ_SIZE).len(), 2);
+        assert_eq!(ReadPlan::new(&ranges, 20, DEFAULT_MAX_READ_SIZE).len(), 1);
+        assert!(ReadPlan::new(&[], DEFAULT_MAX_GAP, DEFAULT_MAX_READ_SIZE).is_empty());
+    }
+
+    #[test]
+    fn test_read_size_limit() {
+        let ranges = [(0, 10), (10, 10), (20, 10), (30, 100)];
+        let plan = ReadPlan::new(&ranges, DEFAULT_MAX_GAP, 20);
+
+        // Ranges merge up to the limit; an oversized range reads alone
+        assert_eq!(plan.len(), 3);
+        assert_eq!(plan.reads[0].length, 20);
+        assert_eq!(plan.reads[2], CoalescedRead { offset: 30, length: 100, tiles: vec![(3, 0, 100)] });
+        assert_eq!(plan.total_bytes(), 130);
+    }
+}
@@ -0,0 +1,263 @@
+//! Tile offset and byte count arrays
+//!
+//! Both arrays are read in one range read per IFD and cached, so loading
+//! a tile never costs an extra seek to find where it lives. The decoded
+//! tile size is worked out alongside, for zero-filling sparse tiles.
+
+use std::sync::Arc;
+use dashmap::DashMap;
+use crate::error::{Error, Result};
+use crate::io::{ByteOrder, RangeSource};
+use crate::formats::tiff::{IFD, IFDEntry, tags};
+use crate::formats::tiff::tags::field_types;
+
+/// TileOffsets and TileByteCounts of one IFD, loaded in full
+#[derive(Debug, Clone, Default)]
+pub struct TileLocations {
+    pub offsets: Vec<u64>,
+    pub byte_counts: Vec<u64>,
+    /// Bytes in one decoded tile, all samples included
+    pub tile_size: usize,
+}
+
+impl TileLocations {
+    /// Reads both arrays of a tiled IFD
+    ///
+    /// Accepts SHORT, LONG and LONG8 arrays in either byte order, stored
+    /// inline in the entry or elsewhere in the file.
+    pub fn read(ifd: &IFD, source: &dyn RangeSource, byte_order: ByteOrder, is_big_tiff: bool) -> Result<Self> {
+        let [offsets, byte_counts] = Self::value_ranges(ifd, is_big_tiff)?
+            .map(|range| range.map(|(offset, length)| source.read_range(offset, length)).transpose());
+
+        // Multi-band IFDs store one BitsPerSample value per band
+        let bits_per_sample = match ifd.get_entry(tags::BITS_PER_SAMPLE) {
+            Some(entry) => {
+                let data = value_range(entry, is_big_tiff)?
+                    .map(|(offset, length)| source.read_range(offset, length))
+                    .transpose()?;
+                decode_integers(entry, data.as_deref(), byte_order, is_big_tiff)?.first().copied().unwrap_or(1)
+            }
+            None => 1,
+        };
+
+        Self::decode(ifd, offsets?.as_deref(), byte_counts?.as_deref(), bits_per_sample, byte_order, is_big_tiff)
+    }
+
+    /// Returns the (offset, length) of the TileOffsets and TileByteCounts
+    /// values, `None` for an array stored inline in its entry
+    pub fn value_ranges(ifd: &IFD, is_big_tiff: bool) -> Result<[Option<(u64, u64)>; 2]> {
+        let (offsets, byte_counts) = entries(ifd)?;
+        Ok([value_range(offsets, is_big_tiff)?, value_range(byte_counts, is_big_tiff)?])
+    }
+
+    /// Decodes both arrays from the bytes at their [`value_ranges`](Self::value_ranges)
+    ///
+    /// `bits_per_sample` is the first BitsPerSample value of the IFD.
+    pub fn decode(
+        ifd: &IFD,
+        offsets_data: Option<&[u8]>,
+        byte_counts_data: Option<&[u8]>,
+        bits_per_sample: u64,
+        byte_order: ByteOrder,
+        is_big_tiff: bool,
+    ) -> Result<Self> {
+        let (offsets, byte_counts) = entries(ifd)?;
+        let locations = Self {
+            offsets: decode_integers(offsets, offsets_data, byte_order, is_big_tiff)?,
+            byte_counts: decode_integers(byte_counts, byte_counts_data, byte_order, is_big_tiff)?,
+            tile_size: decoded_tile_size(ifd, bits_per_sample)?,
+        };
+
+        if locations.offsets.len() != locations.byte_counts.len() {
+            return Err(Error::InvalidFormat(format!(
+                "{} tile offsets but {} tile byte counts",
+                locations.offsets.len(), locations.byte_counts.len()
+            )));
+        }
+
+        Ok(locations)
+    }
+
+    /// Returns the number of tiles
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns whether the IFD has no tiles
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Returns the (offset, byte count) of a tile
+    pub fn get(&self, tile_index: usize) -> Result<(u64, u64)> {
+        match (self.offsets.get(tile_index), self.byte_counts.get(tile_index)) {
+            (Some(&offset), Some(&byte_count)) => Ok((offset, byte_count)),
+            _ => Err(Error::OutOfBounds(format!(
+                "Tile index {} exceeds tile count {}", tile_index, self.offsets.len()
+            ))),
+        }
+    }
+}
+
+/// Thread-safe cache of [`TileLocations`] by IFD offset
+pub struct LocationIndex {
+    source: Arc<dyn RangeSource>,
+    byte_order: ByteOrder,
+    is_big_tiff: bool,
+    locations: DashMap<u64, Arc<TileLocations>>,
+}
+
+impl LocationIndex {
+    pub fn new(source: Arc<dyn RangeSource>, byte_order: ByteOrder, is_big_tiff: bool) -> Self {
+        Self {
+            source,
+            byte_order,
+            is_big_tiff,
+            locations: DashMap::new(),
+        }
+    }
+
+    /// Returns the tile locations of an IFD, reading them on first use
+    pub fn get(&self, ifd: &IFD) -> Result<Arc<TileLocations>> {
+        if let Some(locations) = self.locations.get(&ifd.offset) {
+            return Ok(locations.clone());
+        }
+
+        let locations = Arc::new(TileLocations::read(ifd, &*self.source, self.byte_order, self.is_big_tiff)?);
+        Ok(self.locations.entry(ifd.offset).or_insert(locations).clone())
+    }
+}
+
+/// Helper: Returns the TileOffsets and TileByteCounts entries of an IFD
+fn entries(ifd: &IFD) -> Result<(&IFDEntry, &IFDEntry)> {
+    let offsets = ifd.get_entry(tags::TILE_OFFSETS)
+        .ok_or(Error::MissingTag(tags::TILE_OFFSETS))?;
+    let byte_counts = ifd.get_entry(tags::TILE_BYTE_COUNTS)
+        .ok_or(Error::MissingTag(tags::TILE_BYTE_COUNTS))?;
+    Ok((offsets, byte_counts))
+}
+
+/// Helper: Returns the bytes in one decoded tile of an IFD
+fn decoded_tile_size(ifd: &IFD, bits_per_sample: u64) -> Result<usize> {
+    let tile = ifd.tile_dimensions()
+        .ok_or_else(|| Error::InvalidFormat("Missing tile dimensions".to_string()))?;
+    // Planar tiles hold a single band
+    let samples = if ifd.get_tag_value(tags::PLANAR_CONFIGURATION) == Some(2) {
+        1
+    } else {
+        ifd.samples_per_pixel().max(1)
+    };
+
+    tile.width.checked_mul(tile.height)
+        .and_then(|pixels| pixels.checked_mul(samples))
+        .and_then(|values| values.checked_mul(bits_per_sample.div_ceil(8).max(1)))
+        .and_then(|size| usize::try_from(size).ok())
+        .ok_or_else(|| Error::InvalidFormat(format!("Tile of {}x{} pixels is too large", tile.width, tile.height)))
+}
+
+/// Helper: Returns the element size of a SHORT, LONG or LONG8 array
+fn integer_size(entry: &IFDEntry) -> Result<u64> {
+    match entry.field_type {
+        field_types::SHORT => Ok(2),
+        field_types::LONG => Ok(4),
+        field_types::LONG8 | field_types::IFD8 => Ok(8),
+        _ => Err(Error::InvalidFormat(format!("Tag {} is not an integer array", entry.tag))),
+    }
+}
+
+/// Helper: Returns where an integer array's values lie, `None` when inline
+fn value_range(entry: &IFDEntry, is_big_tiff: bool) -> Result<Option<(u64, u64)>> {
+    let length = entry.count.checked_mul(integer_size(entry)?)
+        .ok_or_else(|| Error::InvalidFormat(format!("Tag {} count {} is too large", entry.tag, entry.count)))?;
+    let slot = if is_big_tiff { 8 } else { 4 };
+    Ok((length > slot).then_some((entry.value_offset, length)))
+}
+
+/// Helper: Decodes a SHORT, LONG or LONG8 array widened to u64
+///
+/// `data` holds the out-of-line values; inline values are taken from the
+/// entry, in the order the file stored them.
+fn decode_integers(entry: &IFDEntry, data: Option<&[u8]>, byte_order: ByteOrder, is_big_tiff: bool) -> Result<Vec<u64>> {
+    let size = integer_size(entry)? as usize;
+    let inline;
+    let bytes = match (value_range(entry, is_big_tiff)?, data) {
+        (Some((_, length)), Some(data)) if data.len() as u64 == length => data,
+        (Some(_), _) => return Err(Error::InvalidFormat(format!("Missing values of tag {}", entry.tag))),
+        (None, _) => {
+            inline = match (byte_order, is_big_tiff) {
+                (ByteOrder::LittleEndian, false) => (entry.value_offset as u32).to_le_bytes().to_vec(),
+                (ByteOrder::BigEndian, false) => (entry.value_offset as u32).to_be_bytes().to_vec(),
+                (ByteOrder::LittleEndian, true) => entry.value_offset.to_le_bytes().to_vec(),
+                (ByteOrder::BigEndian, true) => entry.value_offset.to_be_bytes().to_vec(),
+            };
+            &inline[..entry.count as usize * size]
+        }
+    };
+
+    Ok(bytes.chunks_exact(size).map(|chunk| decode_integer(chunk, byte_order)).collect())
+}
+
+/// Helper: Decodes one 2, 4 or 8 byte unsigned integer
+fn decode_integer(bytes: &[u8], byte_order: ByteOrder) -> u64 {
+    match (bytes.len(), byte_order) {
+        (2, ByteOrder::LittleEndian) => u16::from_le_bytes([bytes[0], bytes[1]]) as u64,
+        (2, ByteOrder::BigEndian) => u16::from_be_bytes([bytes[0], bytes[1]]) as u64,
+        (4, ByteOrder::LittleEndian) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64,
+        (4, ByteOrder::BigEndian) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64,
+        (_, ByteOrder::LittleEndian) => u64::from_le_bytes(bytes.try_into().unwrap_or_default()),
+        (_, ByteOrder::BigEndian) => u64::from_be_bytes(bytes.try_into().unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MemorySource;
+
+    fn tiled_ifd() -> IFD {
+        let mut ifd = IFD::new(0, 0);
+        ifd.add_entry(IFDEntry::new(tags::TILE_WIDTH, field_types::LONG, 1, 16));
+        ifd.add_entry(IFDEntry::new(tags::TILE_LENGTH, field_types::LONG, 1, 16));
+        ifd
+    }
+
+    #[test]
+    fn test_read_widths_and_inline_values() {
+        let mut data = vec![0u8; 8];
+        data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let source = MemorySource::new(data);
+
+        let mut ifd = tiled_ifd();
+        ifd.add_entry(IFDEntry::new(tags::TILE_OFFSETS, field_types::LONG8, 2, 8));
+        // Two SHORT counts fit in the entry itself
+        ifd.add_entry(IFDEntry::new(tags::TILE_BYTE_COUNTS, field_types::SHORT, 2, 0x0007_0005));
+
+        let locations = TileLocations::read(&ifd, &source, ByteOrder::LittleEndian, false).unwrap();
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations.get(0).unwrap(), (1, 5));
+        assert_eq!(locations.get(1).unwrap(), (2, 7));
+        assert!(locations.get(2).is_err());
+        assert_eq!(locations.tile_size, 16 * 16);
+    }
+
+    #[test]
+    fn test_read_big_endian() {
+        let source = MemorySource::new(vec![0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0]);
+
+        let mut ifd = tiled_ifd();
+        ifd.add_entry(IFDEntry::new(tags::TILE_OFFSETS, field_types::LONG, 2, 4));
+        // Inline SHORTs keep file order, so the first is the high half of the slot
+        ifd.add_entry(IFDEntry::new(tags::TILE_BYTE_COUNTS, field_types::SHORT, 2, 0x0003_0004));
+
+        let locations = TileLocations::read(&ifd, &source, ByteOrder::BigEndian, false).unwrap();
+        assert_eq!(locations.offsets, vec![256, 512]);
+        assert_eq!(locations.byte_counts, vec![3, 4]);
+
+        // Two 32-bit bands, the first BitsPerSample value in the high half
+        ifd.add_entry(IFDEntry::new(tags::BITS_PER_SAMPLE, field_types::SHORT, 2, 0x0020_0020));
+        ifd.add_entry(IFDEntry::new(tags::SAMPLES_PER_PIXEL, field_types::SHORT, 1, 2));
+        let index = LocationIndex::new(Arc::new(source), ByteOrder::BigEndian, false);
+        assert_eq!(index.get(&ifd).unwrap().tile_size, 16 * 16 * 2 * 4);
+        assert!(Arc::ptr_eq(&index.get(&ifd).unwrap(), &index.get(&ifd).unwrap()));
+    }
+}
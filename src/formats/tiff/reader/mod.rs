@@ -4,29 +4,46 @@ pub mod tags;
 pub mod tiles;
 pub mod pixels;
 pub mod parallel;
+pub mod locations;
+pub mod coalesce;
+pub mod async_reader;
+pub mod shared;
 
-use std::fs::File;
+use std::collections::HashSet;
 use std::io::{Seek, SeekFrom};
 use std::path::Path;
 use std::sync::Arc;
-use memmap2::Mmap;
 use crate::error::{Error, Result};
-use crate::io::{BufferedReader, ByteOrder};
+use crate::io::{BufferedReader, ByteOrder, MemorySource, RangeSource, SourceReader};
 use crate::formats::tiff::{Tiff, IFD, IFDEntry, TIFF_MAGIC, BIGTIFF_MAGIC};
 
 use self::tags::TagReader;
 use self::tiles::TileReader;
 use self::pixels::PixelReader;
-use self::parallel::ParallelReader;
+use self::parallel::{ParallelConfig, ParallelReader};
 
 pub use crate::cache_prefetch::PrefetchConfig;
+pub use self::async_reader::AsyncTiffReader;
+pub use self::shared::SharedTiffReader;
 
 /// TIFF file reader with modular architecture
+///
+/// Reads from any [`RangeSource`]; [`TiffReader::open`] uses a memory-mapped
+/// local file.
 pub struct TiffReader {
-    tile_reader: TileReader<File>,
+    tile_reader: TileReader<SourceReader>,
     byte_order: ByteOrder,
     is_big_tiff: bool,
-    current_ifd_index: usize,
+    /// Offsets of the IFDs discovered so far, in chain order
+    ifd_offsets: Vec<u64>,
+    /// The same offsets, for loop detection
+    visited_offsets: HashSet<u64>,
+    /// Whether the IFD chain has been followed to its end
+    chain_complete: bool,
+    /// Largest gap merged into one read by batch tile reads
+    max_read_gap: u64,
+    /// Largest merged read of batch tile reads
+    max_read_size: u64,
 }
 
 impl TiffReader {
@@ -50,6 +67,8 @@ impl TiffReader {
         let handler = self.byte_order.handler();
         let reader = self.tile_reader.reader_mut();
 
+        reader.seek(SeekFrom::Start(if self.is_big_tiff { 8 } else { 4 }))?;
+
         if self.is_big_tiff {
             Ok(handler.read_u64(reader)?)
         } else {
@@ -81,7 +100,7 @@ impl TiffReader {
     /// Opens a TIFF file with custom options
     ///
     /// # Arguments
-    /// * `path` - Path to the TIFF file
+    /// * `path` - Path to the TIFF file, or an `http(s)://` or `s3://` URL
     /// * `use_mmap` - Whether to use memory mapping (faster for large files)
     /// * `cache_size` - Number of tiles to cache (0 to disable caching)
     pub fn open_with_options<P: AsRef<Path>>(
@@ -89,8 +108,16 @@ impl TiffReader {
         use_mmap: bool,
         cache_size: usize,
     ) -> Result<Self> {
-        let file = File::open(&path)?;
-        let mut reader = BufferedReader::new(file);
+        Self::from_source(crate::io::open_path(path, use_mmap)?, cache_size)
+    }
+
+    /// Creates a reader over any byte source
+    ///
+    /// # Arguments
+    /// * `source` - Source of the TIFF bytes
+    /// * `cache_size` - Number of tiles to cache (0 to disable caching)
+    pub fn from_source(source: Arc<dyn RangeSource>, cache_size: usize) -> Result<Self> {
+        let mut reader = BufferedReader::new(SourceReader::new(source.clone()));
 
         let byte_order = ByteOrder::detect(&mut reader)?;
         let handler = byte_order.handler();
@@ -113,55 +140,84 @@ impl TiffReader {
             let _reserved = handler.read_u16(&mut reader)?;
         }
 
-        let mmap = if use_mmap {
-            let file_for_mmap = File::open(path)?;
-            let mmap = unsafe { Mmap::map(&file_for_mmap)? };
-
-            #[cfg(unix)]
-            unsafe {
-                libc::madvise(
-                    mmap.as_ptr() as *mut libc::c_void,
-                    mmap.len(),
-                    libc::MADV_SEQUENTIAL | libc::MADV_WILLNEED,
-                );
-            }
-
-            Some(Arc::new(mmap))
-        } else {
-            None
-        };
-
-        let tile_reader = TileReader::new(reader, byte_order, mmap, cache_size);
+        let tile_reader = TileReader::new(reader, byte_order, is_big_tiff, source, cache_size);
 
         Ok(Self {
             tile_reader,
             byte_order,
             is_big_tiff,
-            current_ifd_index: 0,
+            ifd_offsets: Vec::new(),
+            visited_offsets: HashSet::new(),
+            chain_complete: false,
+            max_read_gap: coalesce::DEFAULT_MAX_GAP,
+            max_read_size: coalesce::DEFAULT_MAX_READ_SIZE,
         })
     }
 
+    /// Creates a reader over a TIFF held in memory
+    ///
+    /// Accepts `Vec<u8>`, `Bytes` or `Arc<[u8]>` without copying; tiles are
+    /// decompressed straight from the buffer.
+    pub fn from_bytes(data: impl Into<MemorySource>) -> Result<Self> {
+        Self::from_source(Arc::new(data.into()), 256)
+    }
+
+    /// Returns the byte source this reader reads from
+    pub fn source(&self) -> &Arc<dyn RangeSource> {
+        self.tile_reader.source()
+    }
+
     /// Reads the TIFF file and returns the structure
+    ///
+    /// Parses every IFD in the chain; use [`TiffReader::ifd`] or
+    /// [`TiffReader::ifds`] to parse pages on demand instead.
     pub fn read(&mut self) -> Result<Tiff> {
         let mut tiff = Tiff::new(self.is_big_tiff);
-        let mut next_ifd_offset = self.read_first_ifd_offset()?;
-        let mut ifd_number = 0;
+        for ifd in self.ifds() {
+            tiff.add_ifd(ifd?);
+        }
+        Ok(tiff)
+    }
 
-        while next_ifd_offset != 0 {
-            if ifd_number > 1000 {
-                return Err(Error::InvalidFormat("Too many IFDs".to_string()));
-            }
+    /// Parses the IFD at position `number` in the chain
+    ///
+    /// Only the entry counts and next pointers of the IFDs before it are
+    /// read. Tag values stay in the file until requested. Returns `None`
+    /// past the last IFD.
+    pub fn ifd(&mut self, number: usize) -> Result<Option<IFD>> {
+        match self.ifd_offset(number)? {
+            Some(offset) => Ok(Some(self.read_ifd(number, offset)?)),
+            None => Ok(None),
+        }
+    }
 
-            let ifd = self.read_ifd(ifd_number, next_ifd_offset)?;
-            let entry_count = self.read_entry_count_at(next_ifd_offset)?;
-            let entries_end = self.calculate_entries_end(next_ifd_offset, entry_count);
+    /// Iterates over the IFDs, parsing each one as it is reached
+    pub fn ifds(&mut self) -> Ifds<'_> {
+        Ifds { reader: self, next: 0, failed: false }
+    }
+
+    /// Helper: Follows the IFD chain far enough to find the offset of IFD `number`
+    fn ifd_offset(&mut self, number: usize) -> Result<Option<u64>> {
+        while self.ifd_offsets.len() <= number && !self.chain_complete {
+            let next = match self.ifd_offsets.last() {
+                None => self.read_first_ifd_offset()?,
+                Some(&offset) => {
+                    let entry_count = self.read_entry_count_at(offset)?;
+                    let entries_end = self.calculate_entries_end(offset, entry_count);
+                    self.read_next_ifd_offset(entries_end)?
+                }
+            };
 
-            next_ifd_offset = self.read_next_ifd_offset(entries_end)?;
-            tiff.add_ifd(ifd);
-            ifd_number += 1;
+            if next == 0 {
+                self.chain_complete = true;
+            } else if next >= self.source().len() || !self.visited_offsets.insert(next) {
+                return Err(Error::InvalidOffset(next));
+            } else {
+                self.ifd_offsets.push(next);
+            }
         }
 
-        Ok(tiff)
+        Ok(self.ifd_offsets.get(number).copied())
     }
 
     /// Helper: Reads entry count at given offset
@@ -274,7 +330,7 @@ impl TiffReader {
 
     /// Reads a tile from the file with caching
     pub fn read_tile(&mut self, ifd: &IFD, tile_index: usize) -> Result<Vec<u8>> {
-        self.tile_reader.set_current_ifd(self.current_ifd_index);
+        self.tile_reader.set_current_ifd(ifd.number);
         self.tile_reader.read_tile(ifd, tile_index)
     }
 
@@ -334,16 +390,38 @@ impl TiffReader {
 
     /// Reads multiple tiles in parallel
     pub fn read_tiles_parallel(&mut self, ifd: &IFD, tile_indices: &[usize]) -> Result<Vec<Vec<u8>>> {
-        self.tile_reader.set_current_ifd(self.current_ifd_index);
+        self.tile_reader.set_current_ifd(ifd.number);
+
+        let config = self.parallel_config(ifd)?;
+        ParallelReader::read_tiles_parallel(tile_indices, self.tile_reader.cache(), ifd.number, &config)
+    }
+
+    /// Reads multiple tiles in parallel, bypassing the tile cache
+    ///
+    /// Suited to bulk reads that would otherwise evict the whole cache.
+    pub fn read_tiles_direct(&mut self, ifd: &IFD, tile_indices: &[usize]) -> Result<Vec<Vec<u8>>> {
+        let config = self.parallel_config(ifd)?;
+        ParallelReader::read_tiles_parallel_direct(tile_indices, &config)
+    }
+
+    /// Sets the largest gap in bytes that batch tile reads read through to
+    /// merge neighbouring tiles into one request
+    ///
+    /// Remote sources favour a larger gap, local files a smaller one.
+    pub fn set_max_read_gap(&mut self, max_gap: u64) {
+        self.max_read_gap = max_gap;
+    }
 
-        ParallelReader::read_tiles_parallel(
-            ifd,
-            tile_indices,
-            self.tile_reader.cache(),
-            self.current_ifd_index,
-            self.tile_reader.mmap(),
-            self.tile_reader.byte_order(),
-        )
+    /// Sets the largest read in bytes that batch tile reads merge tiles
+    /// into, bounding the buffer one merged read allocates
+    pub fn set_max_read_size(&mut self, max_read_size: u64) {
+        self.max_read_size = max_read_size;
+    }
+
+    /// Helper: Builds the parallel read configuration of an IFD
+    fn parallel_config(&self, ifd: &IFD) -> Result<ParallelConfig> {
+        let config = ParallelConfig::from_ifd(ifd, self.tile_reader.source().clone(), self.tile_reader.tile_locations(ifd)?)?;
+        Ok(config.with_max_gap(self.max_read_gap).with_max_read_size(self.max_read_size))
     }
 
     /// Reads a pixel value at geographic coordinates
@@ -377,6 +455,13 @@ impl TiffReader {
         self.read_pixel_value(ifd, pixel_x as u64, pixel_y as u64)
     }
 
+    /// Reads a pixel value of any supported data type, widened to f64
+    pub fn read_pixel_as_f64(&mut self, ifd: &IFD, x: u64, y: u64) -> Result<f64> {
+        let data_type = PixelReader::require_data_type(ifd)?;
+        let (tile_data, pixel_index) = self.read_pixel_data(ifd, x, y)?;
+        PixelReader::read_as_f64_from_tile(&tile_data, pixel_index, data_type)
+    }
+
     /// Reads multiple pixel values in parallel by batching tile loads
     ///
     /// This method groups pixels by their tiles, loads tiles in parallel,
@@ -390,6 +475,23 @@ impl TiffReader {
     /// # Returns
     /// Vec of pixel values in the same order as input coordinates
     pub fn read_pixels_batch(&mut self, ifd: &IFD, coords: &[(u64, u64)]) -> Result<Vec<u8>> {
+        self.read_pixels_batch_with(ifd, coords, PixelReader::read_u8_from_tile)
+    }
+
+    /// Batch variant of [`TiffReader::read_pixel_as_f64`] using the same tile grouping
+    pub fn read_pixels_batch_f64(&mut self, ifd: &IFD, coords: &[(u64, u64)]) -> Result<Vec<f64>> {
+        let data_type = PixelReader::require_data_type(ifd)?;
+        self.read_pixels_batch_with(ifd, coords, |tile_data, pixel_index| {
+            PixelReader::read_as_f64_from_tile(tile_data, pixel_index, data_type)
+        })
+    }
+
+    /// Helper: Groups pixels by tile, loads the tiles in parallel and extracts values
+    fn read_pixels_batch_with<T, F>(&mut self, ifd: &IFD, coords: &[(u64, u64)], extract: F) -> Result<Vec<T>>
+    where
+        T: Copy + Default,
+        F: Fn(&[u8], usize) -> Result<T>,
+    {
         use std::collections::HashMap;
 
         let mut tile_pixels: HashMap<usize, Vec<(usize, u64, u64)>> = HashMap::new();
@@ -400,25 +502,20 @@ impl TiffReader {
             let tile_index = PixelReader::calculate_tile_index(ifd, x, y)?;
 
             tile_pixels.entry(tile_index)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push((result_idx, x, y));
         }
 
         let tile_indices: Vec<usize> = tile_pixels.keys().copied().collect();
 
-        let tiles = ParallelReader::read_tiles_parallel_direct(
-            ifd,
-            &tile_indices,
-            self.tile_reader.mmap(),
-            self.tile_reader.byte_order(),
-        )?;
+        let tiles = self.read_tiles_direct(ifd, &tile_indices)?;
 
         let tile_map: HashMap<usize, &Vec<u8>> = tile_indices.iter()
             .zip(tiles.iter())
             .map(|(&idx, data)| (idx, data))
             .collect();
 
-        let mut results = vec![0u8; coords.len()];
+        let mut results = vec![T::default(); coords.len()];
 
         for (tile_index, pixels) in tile_pixels.iter() {
             let tile_data = tile_map.get(tile_index)
@@ -426,13 +523,106 @@ impl TiffReader {
 
             for &(result_idx, x, y) in pixels {
                 let pixel_index = PixelReader::calculate_pixel_index(ifd, x, y)?;
-                results[result_idx] = PixelReader::read_u8_from_tile(tile_data, pixel_index)?;
+                results[result_idx] = extract(tile_data, pixel_index)?;
             }
         }
 
         Ok(results)
     }
 
+    /// Reads a rectangular pixel window as f64 values in row-major order
+    ///
+    /// All tiles intersecting the window are loaded in parallel, then copied
+    /// into the output buffer. The window must lie inside the image.
+    pub fn read_window_f64(&mut self, ifd: &IFD, x: u64, y: u64, width: u64, height: u64) -> Result<Vec<f64>> {
+        PixelReader::validate_tiled_access(ifd)?;
+        let data_type = PixelReader::require_data_type(ifd)?;
+
+        if width == 0 || height == 0 {
+            return Ok(Vec::new());
+        }
+        let (Some(last_x), Some(last_y)) = (x.checked_add(width - 1), y.checked_add(height - 1)) else {
+            return Err(Error::OutOfBounds(format!(
+                "Window {}x{} at ({}, {}) overflows the pixel range", width, height, x, y
+            )));
+        };
+        PixelReader::validate_pixel_bounds(ifd, x, y)?;
+        PixelReader::validate_pixel_bounds(ifd, last_x, last_y)?;
+
+        let tile_dims = ifd.tile_dimensions()
+            .ok_or_else(|| Error::InvalidFormat("Missing tile dimensions".to_string()))?;
+
+        let first_col = x / tile_dims.width;
+        let last_col = last_x / tile_dims.width;
+        let first_row = y / tile_dims.height;
+        let last_row = last_y / tile_dims.height;
+
+        let mut tile_indices = Vec::new();
+        for tile_row in first_row..=last_row {
+            for tile_col in first_col..=last_col {
+                let px = tile_col * tile_dims.width;
+                let py = tile_row * tile_dims.height;
+                tile_indices.push(PixelReader::calculate_tile_index(ifd, px, py)?);
+            }
+        }
+
+        let tiles = self.read_tiles_direct(ifd, &tile_indices)?;
+
+        let mut window = vec![0.0; (width * height) as usize];
+        let cols = last_col - first_col + 1;
+
+        for (i, tile_data) in tiles.iter().enumerate() {
+            let tile_col = first_col + i as u64 % cols;
+            let tile_row = first_row + i as u64 / cols;
+            let tile_x0 = tile_col * tile_dims.width;
+            let tile_y0 = tile_row * tile_dims.height;
+
+            let x_start = x.max(tile_x0);
+            let x_end = (last_x + 1).min(tile_x0 + tile_dims.width);
+            let y_start = y.max(tile_y0);
+            let y_end = (last_y + 1).min(tile_y0 + tile_dims.height);
+
+            for py in y_start..y_end {
+                for px in x_start..x_end {
+                    let pixel_index = PixelReader::calculate_pixel_index(ifd, px, py)?;
+                    let value = PixelReader::read_as_f64_from_tile(tile_data, pixel_index, data_type)?;
+                    window[((py - y) * width + (px - x)) as usize] = value;
+                }
+            }
+        }
+
+        Ok(window)
+    }
+
+}
+
+/// Iterator over the IFDs of a [`TiffReader`], see [`TiffReader::ifds`]
+pub struct Ifds<'a> {
+    reader: &'a mut TiffReader,
+    next: usize,
+    failed: bool,
+}
+
+impl Iterator for Ifds<'_> {
+    type Item = Result<IFD>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        match self.reader.ifd(self.next) {
+            Ok(Some(ifd)) => {
+                self.next += 1;
+                Some(Ok(ifd))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -475,4 +665,115 @@ mod tests {
         assert!(!tiff.is_big_tiff);
         assert_eq!(tiff.ifd_count(), 1);
     }
+
+    #[test]
+    fn test_read_window_f64_across_tiles() {
+        use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+        use crate::types::DataType;
+
+        let mut spec = FixtureSpec::new(40, 40);
+        spec.data_type = DataType::F32;
+        let file = write_geotiff(&spec, |x, y| (y * 100 + x) as f64);
+
+        let mut reader = TiffReader::open(file.path()).unwrap();
+        let tiff = reader.read().unwrap();
+        let ifd = tiff.main_ifd().unwrap();
+
+        let window = reader.read_window_f64(ifd, 14, 14, 4, 3).unwrap();
+        assert_eq!(window.len(), 12);
+        assert_eq!(window[0], 1414.0);
+        assert_eq!(window[3], 1417.0);
+        assert_eq!(window[11], 1617.0);
+
+        assert_eq!(reader.read_pixel_as_f64(ifd, 33, 2).unwrap(), 233.0);
+        let batch = reader.read_pixels_batch_f64(ifd, &[(0, 0), (39, 39)]).unwrap();
+        assert_eq!(batch, vec![0.0, 3939.0]);
+        assert!(reader.read_window_f64(ifd, 38, 38, 4, 4).is_err());
+        // Sizes that overflow the end coordinate are rejected, not wrapped
+        assert!(matches!(reader.read_window_f64(ifd, 2, 0, u64::MAX, 1), Err(Error::OutOfBounds(_))));
+        assert!(matches!(reader.read_window_f64(ifd, 0, 3, 1, u64::MAX - 1), Err(Error::OutOfBounds(_))));
+    }
+
+    #[test]
+    fn test_read_from_custom_source() {
+        use std::sync::atomic::Ordering;
+        use crate::formats::tiff::test_support::{write_geotiff, CountingSource, FixtureSpec};
+
+        let file = write_geotiff(&FixtureSpec::new(32, 32), |x, y| (x + y) as f64);
+        let source = Arc::new(CountingSource::new(std::fs::read(file.path()).unwrap()));
+        let mut reader = TiffReader::from_source(source.clone(), 16).unwrap();
+        let ifd = reader.read().unwrap().main_ifd().unwrap().clone();
+
+        assert_eq!(reader.read_pixel_value(&ifd, 20, 3).unwrap(), 23);
+        let batch = reader.read_pixels_batch(&ifd, &[(0, 0), (31, 31), (17, 2)]).unwrap();
+        assert_eq!(batch, vec![0, 62, 19]);
+        assert!(source.reads.load(Ordering::Relaxed) > 0);
+
+        let mut unmapped = TiffReader::open_with_options(file.path(), false, 0).unwrap();
+        assert_eq!(unmapped.read_pixels_batch(&ifd, &[(31, 31)]).unwrap(), vec![62]);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+
+        let file = write_geotiff(&FixtureSpec::new(32, 32), |x, y| (x * y % 251) as f64);
+        let data: Arc<[u8]> = std::fs::read(file.path()).unwrap().into();
+
+        let mut reader = TiffReader::from_bytes(data.clone()).unwrap();
+        let ifd = reader.read().unwrap().main_ifd().unwrap().clone();
+        assert_eq!(reader.source().len(), data.len() as u64);
+        assert_eq!(reader.read_pixel_value(&ifd, 30, 20).unwrap(), 98);
+        assert_eq!(reader.read_pixels_batch(&ifd, &[(5, 7), (31, 31)]).unwrap(), vec![35, 208]);
+
+        let mut reader = TiffReader::from_bytes(bytes::Bytes::from(data.to_vec())).unwrap();
+        assert_eq!(reader.read_pixel_value(&ifd, 30, 20).unwrap(), 98);
+        assert!(TiffReader::from_bytes(b"not a tiff".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_lazy_ifd_access() {
+        use crate::formats::tiff::test_support::{build_geotiff_images, FixtureSpec, SampleFn};
+
+        let spec = FixtureSpec::new(32, 32);
+        let pages: Vec<_> = (0..40u64)
+            .map(|page| move |x: u64, _: u64, _: u16| (page * 5 + x) as f64)
+            .collect();
+        let images: Vec<(&FixtureSpec, SampleFn)> = pages.iter().map(|page| (&spec, page as SampleFn)).collect();
+        let mut data = build_geotiff_images(&images);
+
+        let mut reader = TiffReader::from_bytes(data.clone()).unwrap();
+        let first = reader.ifd(0).unwrap().unwrap();
+        assert_eq!(reader.ifd_offsets.len(), 1);
+        assert_eq!(reader.read_pixel_value(&first, 3, 0).unwrap(), 3);
+
+        // Tiles of different pages are cached separately
+        let page = reader.ifd(37).unwrap().unwrap();
+        assert_eq!(page.number, 37);
+        assert_eq!(reader.ifd_offsets.len(), 38);
+        assert_eq!(reader.read_pixel_value(&page, 3, 0).unwrap(), 188);
+        assert!(reader.ifd(40).unwrap().is_none());
+        assert_eq!(reader.ifds().count(), 40);
+        assert_eq!(reader.read().unwrap().ifd_count(), 40);
+
+        // A chain pointing back at an earlier IFD is rejected
+        let first_offset = data[4..8].to_vec();
+        let last_pointer = data.len() - 4;
+        data[last_pointer..].copy_from_slice(&first_offset);
+        let mut reader = TiffReader::from_bytes(data).unwrap();
+        assert!(reader.read().is_err());
+        assert!(reader.ifd(39).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_single_tile_image() {
+        use crate::formats::tiff::test_support::{build_geotiff, FixtureSpec};
+
+        // One tile keeps TileOffsets and TileByteCounts inline in the IFD
+        let data = build_geotiff(&FixtureSpec::new(16, 16), |x, y| (x + y * 16) as f64);
+        let mut reader = TiffReader::from_bytes(data).unwrap();
+        let ifd = reader.ifd(0).unwrap().unwrap();
+        assert_eq!(reader.read_pixel_value(&ifd, 15, 15).unwrap(), 255);
+        assert_eq!(reader.read_pixel_value(&ifd, 4, 1).unwrap(), 20);
+    }
 }
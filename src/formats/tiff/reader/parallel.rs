@@ -1,14 +1,15 @@
 //! Parallel tile processing operations
 
+use std::borrow::Cow;
 use std::sync::Arc;
-use memmap2::Mmap;
 use rayon::prelude::*;
 use crate::error::{Error, Result};
-use crate::io::ByteOrder;
+use crate::io::RangeSource;
 use crate::compression::Compression;
 use crate::cache::TileCache;
-use crate::formats::tiff::{IFD, IFDEntry, tags};
-use super::tiles::TileReader;
+use crate::formats::tiff::{IFD, tags};
+use super::coalesce::{ReadPlan, DEFAULT_MAX_GAP, DEFAULT_MAX_READ_SIZE};
+use super::locations::TileLocations;
 
 /// Configuration for parallel tile processing
 pub struct ParallelConfig {
@@ -16,13 +17,17 @@ pub struct ParallelConfig {
     pub predictor: u64,
     pub tile_width: u64,
     pub tile_height: u64,
-    pub mmap: Option<Arc<Mmap>>,
-    pub byte_order: ByteOrder,
+    pub source: Arc<dyn RangeSource>,
+    pub locations: Arc<TileLocations>,
+    /// Largest gap in bytes read through to merge neighbouring tiles
+    pub max_gap: u64,
+    /// Largest merged read in bytes
+    pub max_read_size: u64,
 }
 
 impl ParallelConfig {
     /// Creates configuration from IFD and reader state
-    pub fn from_ifd(ifd: &IFD, mmap: Option<Arc<Mmap>>, byte_order: ByteOrder) -> Result<Self> {
+    pub fn from_ifd(ifd: &IFD, source: Arc<dyn RangeSource>, locations: Arc<TileLocations>) -> Result<Self> {
         let tile_dims = ifd.tile_dimensions()
             .ok_or_else(|| Error::InvalidFormat("Missing tile dimensions".to_string()))?;
 
@@ -31,10 +36,24 @@ impl ParallelConfig {
             predictor: ifd.get_tag_value(tags::PREDICTOR).unwrap_or(1),
             tile_width: tile_dims.width,
             tile_height: tile_dims.height,
-            mmap,
-            byte_order,
+            source,
+            locations,
+            max_gap: DEFAULT_MAX_GAP,
+            max_read_size: DEFAULT_MAX_READ_SIZE,
         })
     }
+
+    /// Sets the largest gap merged into one read, see [`ReadPlan`]
+    pub fn with_max_gap(mut self, max_gap: u64) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Sets the largest merged read, see [`ReadPlan`]
+    pub fn with_max_read_size(mut self, max_read_size: u64) -> Self {
+        self.max_read_size = max_read_size;
+        self
+    }
 }
 
 /// Handles parallel tile reading operations
@@ -44,26 +63,16 @@ impl ParallelReader {
     /// Reads multiple tiles in parallel
     ///
     /// This method leverages rayon to decompress multiple tiles simultaneously.
-    /// Requires memory mapping to be enabled.
     pub fn read_tiles_parallel(
-        ifd: &IFD,
         tile_indices: &[usize],
         cache: &TileCache,
         current_ifd_index: usize,
-        mmap: &Option<Arc<Mmap>>,
-        byte_order: ByteOrder,
+        config: &ParallelConfig,
     ) -> Result<Vec<Vec<u8>>> {
         let uncached_indices = Self::find_uncached_indices(tile_indices, cache, current_ifd_index);
 
         if !uncached_indices.is_empty() {
-            Self::load_uncached_tiles_parallel(
-                ifd,
-                &uncached_indices,
-                cache,
-                current_ifd_index,
-                mmap,
-                byte_order,
-            )?;
+            Self::load_uncached_tiles_parallel(&uncached_indices, cache, current_ifd_index, config)?;
         }
 
         Self::collect_tiles_from_cache(tile_indices, cache, current_ifd_index)
@@ -73,33 +82,8 @@ impl ParallelReader {
     ///
     /// This method loads tiles directly and returns them without cache interaction.
     /// Useful for batch operations where tiles may exceed cache capacity.
-    pub fn read_tiles_parallel_direct(
-        ifd: &IFD,
-        tile_indices: &[usize],
-        mmap: &Option<Arc<Mmap>>,
-        byte_order: ByteOrder,
-    ) -> Result<Vec<Vec<u8>>> {
-        let offsets_entry = ifd.get_entry(tags::TILE_OFFSETS)
-            .ok_or(Error::MissingTag(tags::TILE_OFFSETS))?;
-        let byte_counts_entry = ifd.get_entry(tags::TILE_BYTE_COUNTS)
-            .ok_or(Error::MissingTag(tags::TILE_BYTE_COUNTS))?;
-
-        let config = ParallelConfig::from_ifd(ifd, mmap.clone(), byte_order)?;
-
-        let tile_results: Vec<_> = tile_indices
-            .par_iter()
-            .map(|&tile_idx| {
-                Self::load_single_tile(tile_idx, offsets_entry, byte_counts_entry, &config)
-            })
-            .collect();
-
-        let mut tiles = Vec::with_capacity(tile_indices.len());
-        for result in tile_results {
-            let (_, data) = result?;
-            tiles.push(data);
-        }
-
-        Ok(tiles)
+    pub fn read_tiles_parallel_direct(tile_indices: &[usize], config: &ParallelConfig) -> Result<Vec<Vec<u8>>> {
+        Self::load_tiles_coalesced(tile_indices, config)
     }
 
     /// Identifies which tiles are not in cache
@@ -117,64 +101,78 @@ impl ParallelReader {
 
     /// Loads uncached tiles in parallel and puts them in cache
     fn load_uncached_tiles_parallel(
-        ifd: &IFD,
         uncached_indices: &[usize],
         cache: &TileCache,
         ifd_index: usize,
-        mmap: &Option<Arc<Mmap>>,
-        byte_order: ByteOrder,
+        config: &ParallelConfig,
     ) -> Result<()> {
-        let offsets_entry = ifd.get_entry(tags::TILE_OFFSETS)
-            .ok_or(Error::MissingTag(tags::TILE_OFFSETS))?;
-        let byte_counts_entry = ifd.get_entry(tags::TILE_BYTE_COUNTS)
-            .ok_or(Error::MissingTag(tags::TILE_BYTE_COUNTS))?;
+        let tiles = Self::load_tiles_coalesced(uncached_indices, config)?;
 
-        let config = ParallelConfig::from_ifd(ifd, mmap.clone(), byte_order)?;
+        for (&tile_idx, data) in uncached_indices.iter().zip(tiles) {
+            cache.put(ifd_index, tile_idx, data);
+        }
 
-        let tile_results: Vec<_> = uncached_indices
-            .par_iter()
+        Ok(())
+    }
+
+    /// Helper: Fetches tiles with as few reads as the [`ReadPlan`] allows,
+    /// then decompresses them in parallel, in request order
+    fn load_tiles_coalesced(tile_indices: &[usize], config: &ParallelConfig) -> Result<Vec<Vec<u8>>> {
+        let ranges = tile_indices.iter()
             .map(|&tile_idx| {
-                Self::load_single_tile(tile_idx, offsets_entry, byte_counts_entry, &config)
+                let (offset, byte_count) = config.locations.get(tile_idx)?;
+                Self::check_tile_range(tile_idx, offset, byte_count, config)?;
+                Ok((offset, byte_count))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let plan = ReadPlan::new(&ranges, config.max_gap, config.max_read_size);
+
+        // Borrow in-memory sources directly; read other sources into buffers
+        let buffers = plan.reads
+            .par_iter()
+            .map(|read| match config.source.slice(read.offset, read.length) {
+                Some(bytes) => Ok(Cow::Borrowed(bytes)),
+                None => Ok(Cow::Owned(config.source.read_range(read.offset, read.length)?)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Decode tile by tile so the tiles of one merged read spread over the pool
+        let compressed: Vec<(usize, &[u8])> = plan.reads.iter()
+            .zip(&buffers)
+            .flat_map(|(read, buffer)| {
+                read.tiles.iter().map(move |&(position, start, length)| (position, &buffer[start..start + length]))
             })
             .collect();
+        let decoded = compressed
+            .par_iter()
+            .map(|&(position, compressed)| {
+                let tile = decode_tile(compressed, config.compression_value, config.predictor, config.tile_width, config.tile_height)?;
+                Ok((position, tile))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        for result in tile_results {
-            match result {
-                Ok((tile_idx, data)) => {
-                    cache.put(ifd_index, tile_idx, data);
-                },
-                Err(e) => return Err(e),
+        // Tiles without data are left out of the plan and stay zero-filled
+        let mut tiles = vec![Vec::new(); tile_indices.len()];
+        for (position, tile) in decoded {
+            tiles[position] = tile;
+        }
+        for (tile, &(_, byte_count)) in tiles.iter_mut().zip(&ranges) {
+            if byte_count == 0 {
+                *tile = vec![0u8; config.locations.tile_size];
             }
         }
 
-        Ok(())
+        Ok(tiles)
     }
 
     /// Loads a single tile in parallel context
-    pub fn load_single_tile(
-        tile_idx: usize,
-        offsets_entry: &IFDEntry,
-        byte_counts_entry: &IFDEntry,
-        config: &ParallelConfig,
-    ) -> Result<(usize, Vec<u8>)> {
-        let offset = TileReader::<std::fs::File>::read_tile_offset_static(
-            offsets_entry,
-            tile_idx,
-            &config.mmap,
-            config.byte_order,
-        )?;
-
-        let byte_count = TileReader::<std::fs::File>::read_tile_byte_count_static(
-            byte_counts_entry,
-            tile_idx,
-            &config.mmap,
-            config.byte_order,
-        )?;
+    pub fn load_single_tile(tile_idx: usize, config: &ParallelConfig) -> Result<(usize, Vec<u8>)> {
+        let (offset, byte_count) = config.locations.get(tile_idx)?;
 
         if byte_count == 0 {
             return Ok((
                 tile_idx,
-                vec![0u8; (config.tile_width * config.tile_height) as usize],
+                vec![0u8; config.locations.tile_size],
             ));
         }
 
@@ -182,6 +180,18 @@ impl ParallelReader {
         Ok((tile_idx, decompressed))
     }
 
+    /// Helper: Rejects tile data ranges that run past the end of the source
+    fn check_tile_range(tile_idx: usize, offset: u64, byte_count: u64, config: &ParallelConfig) -> Result<()> {
+        let end = offset.saturating_add(byte_count);
+        if end > config.source.len() {
+            return Err(Error::OutOfBounds(format!(
+                "Tile {} data range {}-{} exceeds file size {}",
+                tile_idx, offset, end, config.source.len()
+            )));
+        }
+        Ok(())
+    }
+
     /// Decompresses a tile in parallel context
     fn decompress_tile(
         tile_idx: usize,
@@ -189,33 +199,19 @@ impl ParallelReader {
         byte_count: u64,
         config: &ParallelConfig,
     ) -> Result<Vec<u8>> {
-        let mmap = config.mmap.as_ref()
-            .ok_or_else(|| Error::Unsupported("Parallel reading requires memory mapping".to_string()))?;
-
-        let start = offset as usize;
-        let end = start + byte_count as usize;
-
-        if end > mmap.len() {
-            return Err(Error::OutOfBounds(format!(
-                "Tile {} data range {}-{} exceeds file size {}",
-                tile_idx, start, end, mmap.len()
-            )));
-        }
-
-        let compressed = &mmap[start..end];
-
-        let compression = Compression::from_tag(config.compression_value)?;
-        let mut decompressed = compression.decompress(compressed)?;
-
-        if config.predictor == 2 {
-            apply_horizontal_predictor(
-                &mut decompressed,
-                config.tile_width as usize,
-                config.tile_height as usize,
-            );
-        }
+        Self::check_tile_range(tile_idx, offset, byte_count, config)?;
+
+        // Borrow in-memory sources directly; read other sources into a buffer
+        let buffer;
+        let compressed = match config.source.slice(offset, byte_count) {
+            Some(bytes) => bytes,
+            None => {
+                buffer = config.source.read_range(offset, byte_count)?;
+                &buffer
+            }
+        };
 
-        Ok(decompressed)
+        decode_tile(compressed, config.compression_value, config.predictor, config.tile_width, config.tile_height)
     }
 
     /// Collects requested tiles from cache
@@ -236,6 +232,24 @@ impl ParallelReader {
     }
 }
 
+/// Decompresses tile bytes and undoes the horizontal differencing predictor
+pub(crate) fn decode_tile(
+    compressed: &[u8],
+    compression_value: u64,
+    predictor: u64,
+    tile_width: u64,
+    tile_height: u64,
+) -> Result<Vec<u8>> {
+    let compression = Compression::from_tag(compression_value)?;
+    let mut decompressed = compression.decompress(compressed)?;
+
+    if predictor == 2 {
+        apply_horizontal_predictor(&mut decompressed, tile_width as usize, tile_height as usize);
+    }
+
+    Ok(decompressed)
+}
+
 /// Apply horizontal differencing predictor with SIMD optimization for x86_64
 #[cfg(target_arch = "x86_64")]
 fn apply_horizontal_predictor(data: &mut [u8], width: usize, height: usize) {
@@ -372,7 +386,8 @@ mod tests {
         ifd.add_entry(IFDEntry::new(tags::TILE_LENGTH, field_types::LONG, 1, 256));
         ifd.add_entry(IFDEntry::new(tags::COMPRESSION, field_types::SHORT, 1, 1));
 
-        let config = ParallelConfig::from_ifd(&ifd, None, ByteOrder::LittleEndian).unwrap();
+        let source: Arc<dyn RangeSource> = Arc::new(crate::io::MemorySource::new(Vec::new()));
+        let config = ParallelConfig::from_ifd(&ifd, source, Arc::default()).unwrap();
 
         assert_eq!(config.tile_width, 256);
         assert_eq!(config.tile_height, 256);
@@ -405,4 +420,92 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_sparse_float_tiles() {
+        use crate::formats::tiff::{AsyncTiffReader, SharedTiffReader, TiffReader};
+        use crate::formats::tiff::test_support::{build_geotiff, FixtureSpec};
+        use crate::types::DataType;
+
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.data_type = DataType::F32;
+        let mut data = build_geotiff(&spec, |x, y| x as f64 + y as f64 * 0.5);
+
+        // Drop the bytes of tile 1 (x 16..32, y 0..16)
+        let ifd = TiffReader::from_bytes(data.clone()).unwrap().ifd(0).unwrap().unwrap();
+        let counts = ifd.get_entry(tags::TILE_BYTE_COUNTS).unwrap().value_offset as usize;
+        data[counts + 4..counts + 8].copy_from_slice(&0u32.to_le_bytes());
+        let coords = [(17, 0), (31, 15), (2, 4)];
+        let expected = vec![0.0, 0.0, 4.0];
+
+        let mut reader = TiffReader::from_bytes(data.clone()).unwrap();
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 31, 15).unwrap(), 0.0);
+        let tiles = reader.read_tiles_direct(&ifd, &[0, 1]).unwrap();
+        assert_eq!(tiles[1], vec![0u8; 16 * 16 * 4]);
+        assert_eq!(reader.read_pixels_batch_f64(&ifd, &coords).unwrap(), expected);
+
+        let shared = SharedTiffReader::from_bytes(data.clone()).unwrap();
+        assert_eq!(shared.read_pixels_batch_f64(&ifd, &coords).unwrap(), expected);
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let values = runtime.block_on(async {
+            let reader = AsyncTiffReader::from_bytes(data).await.unwrap();
+            let ifd = reader.ifd(0).await.unwrap().unwrap();
+            reader.read_pixels_batch_f64(&ifd, &coords).await.unwrap()
+        });
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_read_tiles_parallel_direct() {
+        use crate::formats::tiff::TiffReader;
+        use crate::formats::tiff::test_support::{build_geotiff, FixtureSpec};
+
+        // A single tile keeps its offset and byte count inline in the IFD
+        for (width, tiles) in [(16, vec![0]), (48, vec![2, 0, 1])] {
+            let data = build_geotiff(&FixtureSpec::new(width, 16), |x, _| x as f64);
+            let mut reader = TiffReader::from_bytes(data).unwrap();
+            let ifd = reader.ifd(0).unwrap().unwrap();
+
+            let direct = reader.read_tiles_direct(&ifd, &tiles).unwrap();
+            let cached = reader.read_tiles_parallel(&ifd, &tiles).unwrap();
+            for (tile, data) in tiles.iter().zip(&direct) {
+                assert_eq!(data[..16], (0..16).map(|x| (tile * 16 + x) as u8).collect::<Vec<_>>()[..]);
+            }
+            assert_eq!(direct, cached);
+        }
+    }
+
+    #[test]
+    fn test_batch_reads_are_coalesced() {
+        use std::sync::atomic::Ordering;
+        use crate::formats::tiff::TiffReader;
+        use crate::formats::tiff::test_support::{build_geotiff, CountingSource, FixtureSpec};
+
+        let data = build_geotiff(&FixtureSpec::new(64, 64), |x, y| ((x + y) % 251) as f64);
+        let source = Arc::new(CountingSource::new(data));
+        let mut reader = TiffReader::from_source(source.clone(), 0).unwrap();
+        let ifd = reader.ifd(0).unwrap().unwrap();
+        let tiles = [15, 0, 5, 10, 3];
+        let expected = reader.read_tiles_direct(&ifd, &tiles).unwrap();
+
+        source.reads.store(0, Ordering::Relaxed);
+        assert_eq!(reader.read_tiles_direct(&ifd, &tiles).unwrap(), expected);
+        assert_eq!(source.reads.load(Ordering::Relaxed), 1);
+
+        // Without gap merging only adjacent tiles share a read
+        reader.set_max_read_gap(0);
+        source.reads.store(0, Ordering::Relaxed);
+        assert_eq!(reader.read_tiles_direct(&ifd, &tiles).unwrap(), expected);
+        assert_eq!(source.reads.load(Ordering::Relaxed), 5);
+
+        // A size limit below one tile gives every tile its own read
+        reader.set_max_read_gap(u64::MAX);
+        reader.set_max_read_size(1);
+        source.reads.store(0, Ordering::Relaxed);
+        assert_eq!(reader.read_tiles_direct(&ifd, &tiles).unwrap(), expected);
+        assert_eq!(source.reads.load(Ordering::Relaxed), 5);
+        assert_eq!(expected[1][..3], [0, 1, 2]);
+        assert_eq!(expected[0][0], 96);
+    }
 }
@@ -2,6 +2,7 @@
 
 use crate::error::{Error, Result};
 use crate::formats::tiff::IFD;
+use crate::types::DataType;
 
 /// Handles pixel value reading and coordinate calculations
 pub struct PixelReader;
@@ -118,6 +119,26 @@ impl PixelReader {
         let bytes = Self::read_bytes_from_tile::<8>(tile_data, pixel_index)?;
         Ok(f64::from_le_bytes(bytes))
     }
+
+    /// Reads a pixel of any supported data type and widens it to f64
+    pub fn read_as_f64_from_tile(tile_data: &[u8], pixel_index: usize, data_type: DataType) -> Result<f64> {
+        match data_type {
+            DataType::U8 => Self::read_u8_from_tile(tile_data, pixel_index).map(|v| v as f64),
+            DataType::I8 => Self::read_u8_from_tile(tile_data, pixel_index).map(|v| v as i8 as f64),
+            DataType::U16 => Self::read_u16_from_tile(tile_data, pixel_index).map(|v| v as f64),
+            DataType::I16 => Self::read_i16_from_tile(tile_data, pixel_index).map(|v| v as f64),
+            DataType::U32 => Self::read_u32_from_tile(tile_data, pixel_index).map(|v| v as f64),
+            DataType::I32 => Self::read_i32_from_tile(tile_data, pixel_index).map(|v| v as f64),
+            DataType::F32 => Self::read_f32_from_tile(tile_data, pixel_index).map(|v| v as f64),
+            DataType::F64 => Self::read_f64_from_tile(tile_data, pixel_index),
+        }
+    }
+
+    /// Returns the IFD data type or an error if it is missing or unsupported
+    pub fn require_data_type(ifd: &IFD) -> Result<DataType> {
+        ifd.data_type()
+            .ok_or_else(|| Error::Unsupported("Unsupported or missing sample format".to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -222,6 +243,18 @@ mod tests {
         assert_eq!(PixelReader::read_f64_from_tile(&tile_data, 0).unwrap(), 3.14159);
     }
 
+    #[test]
+    fn test_read_as_f64_from_tile() {
+        let tile_data = vec![0xFF, 0xFF, 0x00, 0x00];
+        assert_eq!(PixelReader::read_as_f64_from_tile(&tile_data, 0, DataType::U8).unwrap(), 255.0);
+        assert_eq!(PixelReader::read_as_f64_from_tile(&tile_data, 0, DataType::I8).unwrap(), -1.0);
+        assert_eq!(PixelReader::read_as_f64_from_tile(&tile_data, 0, DataType::I16).unwrap(), -1.0);
+        assert_eq!(PixelReader::read_as_f64_from_tile(&tile_data, 0, DataType::U32).unwrap(), 65535.0);
+
+        let tile_data = 2.5f32.to_le_bytes().to_vec();
+        assert_eq!(PixelReader::read_as_f64_from_tile(&tile_data, 0, DataType::F32).unwrap(), 2.5);
+    }
+
     #[test]
     fn test_read_bytes_out_of_bounds() {
         let tile_data = vec![1, 2, 3, 4];
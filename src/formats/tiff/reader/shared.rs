@@ -0,0 +1,460 @@
+//! Thread-safe TIFF reader with `&self` reads
+//!
+//! [`SharedTiffReader`] serves reads from any number of threads: IFDs are
+//! parsed on request through a shared [`TiffReader`], tile data comes from
+//! positioned reads or the memory map, and the [`TileCache`] and prefetch
+//! pool are shared by all callers.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use crate::cache::TileCache;
+use crate::cache_prefetch::{AccessPattern, PrefetchConfig};
+use crate::cache_prefetch_async::{PrefetchPool, PrefetchRequest};
+use crate::error::{Error, Result};
+use crate::io::{open_path, BufferedReader, ByteOrder, MemorySource, RangeSource, SourceReader};
+use crate::formats::tiff::{Tiff, IFD, IFDEntry, GeoInfo, tags};
+use crate::formats::tiff::geotiff::GeoTagReader;
+use super::coalesce::{DEFAULT_MAX_GAP, DEFAULT_MAX_READ_SIZE};
+use super::locations::LocationIndex;
+use super::parallel::{ParallelConfig, ParallelReader};
+use super::pixels::PixelReader;
+use super::tags::TagReader;
+use super::TiffReader;
+
+/// Background prefetching shared by all threads
+struct SharedPrefetch {
+    pool: PrefetchPool,
+    max_prefetch: usize,
+    /// Access history per IFD number
+    patterns: Mutex<HashMap<usize, AccessPattern>>,
+}
+
+/// TIFF reader that can be shared across threads, e.g. in an `Arc`
+///
+/// Unlike [`TiffReader`], every read method takes `&self`.
+pub struct SharedTiffReader {
+    source: Arc<dyn RangeSource>,
+    byte_order: ByteOrder,
+    is_big_tiff: bool,
+    parser: Mutex<TiffReader>,
+    /// IFD chain offsets found so far, checked by tile reads without the parser lock
+    ifd_offsets: RwLock<Vec<u64>>,
+    cache: TileCache,
+    locations: Arc<LocationIndex>,
+    max_read_gap: u64,
+    max_read_size: u64,
+    prefetch: Option<SharedPrefetch>,
+}
+
+impl SharedTiffReader {
+    /// Opens a TIFF file with default options
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_options(path, true, 256)
+    }
+
+    /// Opens a TIFF file with custom options
+    ///
+    /// # Arguments
+    /// * `path` - Path to the TIFF file, a URL or an archive member
+    /// * `use_mmap` - Whether to use memory mapping instead of pread
+    /// * `cache_size` - Number of tiles in the shared cache
+    pub fn open_with_options<P: AsRef<Path>>(path: P, use_mmap: bool, cache_size: usize) -> Result<Self> {
+        Self::from_source(open_path(path, use_mmap)?, cache_size)
+    }
+
+    /// Creates a reader over a TIFF held in memory
+    pub fn from_bytes(data: impl Into<MemorySource>) -> Result<Self> {
+        Self::from_source(Arc::new(data.into()), 256)
+    }
+
+    /// Creates a reader over any byte source; IFDs are parsed when requested
+    pub fn from_source(source: Arc<dyn RangeSource>, cache_size: usize) -> Result<Self> {
+        let parser = TiffReader::from_source(source.clone(), 1)?;
+
+        Ok(Self {
+            locations: Arc::new(LocationIndex::new(source.clone(), parser.byte_order, parser.is_big_tiff)),
+            source,
+            byte_order: parser.byte_order,
+            is_big_tiff: parser.is_big_tiff,
+            parser: Mutex::new(parser),
+            ifd_offsets: RwLock::new(Vec::new()),
+            cache: TileCache::new(cache_size),
+            max_read_gap: DEFAULT_MAX_GAP,
+            max_read_size: DEFAULT_MAX_READ_SIZE,
+            prefetch: None,
+        })
+    }
+
+    /// Enables background prefetching of the tiles the access pattern predicts
+    pub fn with_prefetch(mut self, config: PrefetchConfig) -> Self {
+        if !config.enabled {
+            self.prefetch = None;
+            return self;
+        }
+
+        let source = self.source.clone();
+        let locations = self.locations.clone();
+        let load_fn = move |ifd: &IFD, _: &IFDEntry, _: &IFDEntry, tile_idx: usize| {
+            let config = ParallelConfig::from_ifd(ifd, source.clone(), locations.get(ifd)?)?;
+            let result = ParallelReader::load_single_tile(tile_idx, &config)?;
+            Ok(result.1)
+        };
+
+        self.prefetch = Some(SharedPrefetch {
+            pool: PrefetchPool::new(4, load_fn),
+            max_prefetch: config.max_prefetch,
+            patterns: Mutex::new(HashMap::new()),
+        });
+        self
+    }
+
+    /// Sets the largest gap in bytes that batch tile reads read through to
+    /// merge neighbouring tiles into one request
+    pub fn with_max_read_gap(mut self, max_gap: u64) -> Self {
+        self.max_read_gap = max_gap;
+        self
+    }
+
+    /// Sets the largest read in bytes that batch tile reads merge tiles into
+    pub fn with_max_read_size(mut self, max_read_size: u64) -> Self {
+        self.max_read_size = max_read_size;
+        self
+    }
+
+    /// Parses every IFD in the chain
+    pub fn read(&self) -> Result<Tiff> {
+        self.with_parser(|parser| parser.read())
+    }
+
+    /// Parses the IFD at position `number` in the chain, `None` past the last
+    ///
+    /// See [`TiffReader::ifd`]; the chain offsets found are kept for all threads.
+    pub fn ifd(&self, number: usize) -> Result<Option<IFD>> {
+        self.with_parser(|parser| parser.ifd(number))
+    }
+
+    /// Returns the byte source this reader reads from
+    pub fn source(&self) -> &Arc<dyn RangeSource> {
+        &self.source
+    }
+
+    /// Returns the tile cache shared by all threads
+    pub fn cache(&self) -> &TileCache {
+        &self.cache
+    }
+
+    /// Extracts GeoTIFF information from an IFD
+    pub fn geo_info(&self, ifd: &IFD) -> Result<Option<GeoInfo>> {
+        let mut tags = self;
+        GeoInfo::from_ifd(ifd, &mut tags)
+    }
+
+    /// Reads tag values as f64 array
+    pub fn read_tag_doubles(&self, entry: &IFDEntry) -> Result<Vec<f64>> {
+        self.with_tag_reader(|reader| reader.read_doubles(entry))
+    }
+
+    /// Reads tag values as u16 array
+    pub fn read_tag_u16s(&self, entry: &IFDEntry) -> Result<Vec<u16>> {
+        self.with_tag_reader(|reader| reader.read_u16s(entry))
+    }
+
+    /// Reads ASCII string from tag
+    pub fn read_tag_ascii(&self, entry: &IFDEntry) -> Result<String> {
+        self.with_tag_reader(|reader| reader.read_ascii(entry))
+    }
+
+    /// Reads a tile through the shared cache, prefetching predicted tiles
+    pub fn read_tile(&self, ifd: &IFD, tile_index: usize) -> Result<Arc<Vec<u8>>> {
+        self.check_ifd(ifd)?;
+        self.collect_prefetched_tiles();
+
+        if let Some(data) = self.cache.get(ifd.number, tile_index) {
+            return Ok(data);
+        }
+
+        let config = ParallelConfig::from_ifd(ifd, self.source.clone(), self.locations.get(ifd)?)?;
+        let (_, data) = ParallelReader::load_single_tile(tile_index, &config)?;
+        let data = Arc::new(data);
+        self.cache.put_shared(ifd.number, tile_index, data.clone());
+        self.prefetch_after(ifd, tile_index);
+
+        Ok(data)
+    }
+
+    /// Reads several tiles, loading the uncached ones in parallel
+    pub fn read_tiles(&self, ifd: &IFD, tile_indices: &[usize]) -> Result<Vec<Arc<Vec<u8>>>> {
+        self.check_ifd(ifd)?;
+        self.collect_prefetched_tiles();
+
+        let mut tiles: HashMap<usize, Arc<Vec<u8>>> = HashMap::with_capacity(tile_indices.len());
+        let mut missing: Vec<usize> = tile_indices.iter()
+            .copied()
+            .filter(|&tile_index| match self.cache.get(ifd.number, tile_index) {
+                Some(data) => { tiles.insert(tile_index, data); false }
+                None => true,
+            })
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+
+        if !missing.is_empty() {
+            let config = ParallelConfig::from_ifd(ifd, self.source.clone(), self.locations.get(ifd)?)?
+                .with_max_gap(self.max_read_gap)
+                .with_max_read_size(self.max_read_size);
+            let loaded = ParallelReader::read_tiles_parallel_direct(&missing, &config)?;
+            for (tile_index, data) in missing.into_iter().zip(loaded) {
+                let data = Arc::new(data);
+                self.cache.put_shared(ifd.number, tile_index, data.clone());
+                tiles.insert(tile_index, data);
+            }
+        }
+
+        Ok(tile_indices.iter().map(|tile_index| tiles[tile_index].clone()).collect())
+    }
+
+    /// Reads a pixel value at specific pixel coordinates (u8)
+    pub fn read_pixel_value(&self, ifd: &IFD, x: u64, y: u64) -> Result<u8> {
+        let (tile_data, pixel_index) = self.read_pixel_data(ifd, x, y)?;
+        PixelReader::read_u8_from_tile(&tile_data, pixel_index)
+    }
+
+    /// Reads a pixel value of any supported data type, widened to f64
+    pub fn read_pixel_as_f64(&self, ifd: &IFD, x: u64, y: u64) -> Result<f64> {
+        let data_type = PixelReader::require_data_type(ifd)?;
+        let (tile_data, pixel_index) = self.read_pixel_data(ifd, x, y)?;
+        PixelReader::read_as_f64_from_tile(&tile_data, pixel_index, data_type)
+    }
+
+    /// Reads multiple pixel values, loading each tile they touch once
+    pub fn read_pixels_batch(&self, ifd: &IFD, coords: &[(u64, u64)]) -> Result<Vec<u8>> {
+        self.read_pixels_batch_with(ifd, coords, PixelReader::read_u8_from_tile)
+    }
+
+    /// Batch variant of [`SharedTiffReader::read_pixel_as_f64`]
+    pub fn read_pixels_batch_f64(&self, ifd: &IFD, coords: &[(u64, u64)]) -> Result<Vec<f64>> {
+        let data_type = PixelReader::require_data_type(ifd)?;
+        self.read_pixels_batch_with(ifd, coords, |tile_data, pixel_index| {
+            PixelReader::read_as_f64_from_tile(tile_data, pixel_index, data_type)
+        })
+    }
+
+    /// Helper: Reads the tile holding a pixel and the pixel's index in it
+    fn read_pixel_data(&self, ifd: &IFD, x: u64, y: u64) -> Result<(Arc<Vec<u8>>, usize)> {
+        PixelReader::validate_tiled_access(ifd)?;
+        PixelReader::validate_pixel_bounds(ifd, x, y)?;
+
+        let tile_index = PixelReader::calculate_tile_index(ifd, x, y)?;
+        let tile_data = self.read_tile(ifd, tile_index)?;
+        let pixel_index = PixelReader::calculate_pixel_index(ifd, x, y)?;
+
+        Ok((tile_data, pixel_index))
+    }
+
+    /// Helper: Groups pixels by tile, loads the tiles and extracts values
+    fn read_pixels_batch_with<T, F>(&self, ifd: &IFD, coords: &[(u64, u64)], extract: F) -> Result<Vec<T>>
+    where
+        F: Fn(&[u8], usize) -> Result<T>,
+    {
+        PixelReader::validate_tiled_access(ifd)?;
+
+        let mut tile_indices = Vec::with_capacity(coords.len());
+        for &(x, y) in coords {
+            PixelReader::validate_pixel_bounds(ifd, x, y)?;
+            tile_indices.push(PixelReader::calculate_tile_index(ifd, x, y)?);
+        }
+
+        let mut unique = tile_indices.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        let tiles: HashMap<usize, Arc<Vec<u8>>> = unique.iter().copied()
+            .zip(self.read_tiles(ifd, &unique)?)
+            .collect();
+
+        coords.iter().zip(&tile_indices)
+            .map(|(&(x, y), tile_index)| {
+                let pixel_index = PixelReader::calculate_pixel_index(ifd, x, y)?;
+                extract(&tiles[tile_index], pixel_index)
+            })
+            .collect()
+    }
+
+    /// Helper: Runs `f` on the shared parser, then publishes the IFD offsets it found
+    fn with_parser<T>(&self, f: impl FnOnce(&mut TiffReader) -> Result<T>) -> Result<T> {
+        let mut parser = self.parser.lock().unwrap_or_else(|e| e.into_inner());
+        let result = f(&mut parser);
+        let mut offsets = self.ifd_offsets.write().unwrap_or_else(|e| e.into_inner());
+        if offsets.len() < parser.ifd_offsets.len() {
+            offsets.clone_from(&parser.ifd_offsets);
+        }
+        result
+    }
+
+    /// Helper: Rejects an IFD of another file, whose tiles would be cached
+    /// under this file's IFD number
+    fn check_ifd(&self, ifd: &IFD) -> Result<()> {
+        let known = self.ifd_offsets.read().unwrap_or_else(|e| e.into_inner()).get(ifd.number).copied();
+        let offset = match known {
+            Some(offset) => Some(offset),
+            None => self.with_parser(|parser| parser.ifd_offset(ifd.number))?,
+        };
+        if offset != Some(ifd.offset) {
+            return Err(Error::InvalidFormat(
+                format!("IFD {} at offset {} is not part of this file", ifd.number, ifd.offset)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Helper: Moves tiles finished by the prefetch workers into the cache
+    fn collect_prefetched_tiles(&self) {
+        if let Some(prefetch) = &self.prefetch {
+            for result in prefetch.pool.collect_results() {
+                self.cache.put(result.ifd_index, result.tile_index, result.tile_data);
+            }
+        }
+    }
+
+    /// Helper: Records an access and queues the tiles it predicts
+    fn prefetch_after(&self, ifd: &IFD, tile_index: usize) {
+        let Some(prefetch) = &self.prefetch else {
+            return;
+        };
+
+        let predicted = {
+            let mut patterns = prefetch.patterns.lock().unwrap_or_else(|e| e.into_inner());
+            let pattern = patterns.entry(ifd.number).or_insert_with(|| AccessPattern::new(tiles_per_row(ifd)));
+            pattern.record(tile_index);
+            pattern.predict_next()
+        };
+
+        let tile_indices: Vec<usize> = predicted.into_iter()
+            .take(prefetch.max_prefetch)
+            .filter(|&idx| self.cache.get(ifd.number, idx).is_none())
+            .collect();
+
+        let (Some(offsets_entry), Some(byte_counts_entry)) =
+            (ifd.get_entry(tags::TILE_OFFSETS), ifd.get_entry(tags::TILE_BYTE_COUNTS))
+        else {
+            return;
+        };
+
+        if !tile_indices.is_empty() {
+            prefetch.pool.prefetch(PrefetchRequest {
+                tile_indices,
+                ifd: Arc::new(ifd.clone()),
+                offsets_entry: offsets_entry.clone(),
+                byte_counts_entry: byte_counts_entry.clone(),
+            });
+        }
+    }
+
+    /// Helper: Runs a [`TagReader`] over a private cursor on the source
+    fn with_tag_reader<T>(&self, read: impl FnOnce(&mut TagReader<'_, SourceReader>) -> Result<T>) -> Result<T> {
+        let handler = self.byte_order.handler();
+        let mut reader = BufferedReader::with_capacity(4096, SourceReader::new(self.source.clone()));
+        read(&mut TagReader::new(&mut reader, &*handler, self.is_big_tiff))
+    }
+}
+
+impl GeoTagReader for &SharedTiffReader {
+    fn read_tag_doubles(&mut self, entry: &IFDEntry) -> Result<Vec<f64>> {
+        SharedTiffReader::read_tag_doubles(self, entry)
+    }
+
+    fn read_tag_u16s(&mut self, entry: &IFDEntry) -> Result<Vec<u16>> {
+        SharedTiffReader::read_tag_u16s(self, entry)
+    }
+
+    fn read_tag_ascii(&mut self, entry: &IFDEntry) -> Result<String> {
+        SharedTiffReader::read_tag_ascii(self, entry)
+    }
+}
+
+/// Helper: Number of tile columns of an IFD, for access pattern detection
+fn tiles_per_row(ifd: &IFD) -> usize {
+    match (ifd.dimensions(), ifd.tile_dimensions()) {
+        (Some(image), Some(tile)) if tile.width > 0 => image.width.div_ceil(tile.width) as usize,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_concurrent_reads_share_cache() {
+        assert_send_sync::<SharedTiffReader>();
+
+        let file = write_geotiff(&FixtureSpec::new(64, 64), |x, y| ((x * 3 + y) % 251) as f64);
+        for use_mmap in [true, false] {
+            let reader = SharedTiffReader::open_with_options(file.path(), use_mmap, 64).unwrap();
+            let ifd = &reader.ifd(0).unwrap().unwrap();
+
+            std::thread::scope(|scope| {
+                for worker in 0..4u64 {
+                    let reader = &reader;
+                    scope.spawn(move || {
+                        for y in (worker..64).step_by(4) {
+                            for x in 0..64 {
+                                assert_eq!(reader.read_pixel_value(ifd, x, y).unwrap() as u64, (x * 3 + y) % 251);
+                            }
+                        }
+                    });
+                }
+            });
+            assert_eq!(reader.cache().len(), 16);
+
+            let coords = [(63, 63), (0, 17), (40, 2)];
+            assert_eq!(reader.read_pixels_batch(ifd, &coords).unwrap(), vec![1, 17, 122]);
+            assert_eq!(reader.read_pixels_batch_f64(ifd, &coords[..1]).unwrap(), vec![1.0]);
+            assert!(reader.read_pixel_value(ifd, 64, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn test_geo_info_and_prefetch() {
+        let file = write_geotiff(&FixtureSpec::new(256, 32), |x, _| x as f64);
+        let reader = SharedTiffReader::open(file.path()).unwrap().with_prefetch(PrefetchConfig::default());
+        let ifd = &reader.ifd(0).unwrap().unwrap();
+        assert_eq!(reader.parser.lock().unwrap().ifd_offsets.len(), 1);
+        assert_eq!(*reader.ifd_offsets.read().unwrap(), reader.parser.lock().unwrap().ifd_offsets);
+        assert_eq!(reader.geo_info(ifd).unwrap().unwrap().epsg_code, Some(3857));
+        assert!(reader.ifd(1).unwrap().is_none());
+        assert_eq!(reader.read().unwrap().ifd_count(), 1);
+
+        // Reading tiles 0, 1, 2 in order predicts the following tiles
+        for tile in 0..3 {
+            assert_eq!(reader.read_pixel_value(ifd, tile * 16, 0).unwrap() as u64, tile * 16);
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while reader.cache().len() <= 3 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            reader.collect_prefetched_tiles();
+        }
+        assert!(reader.cache().len() > 3);
+        assert_eq!(reader.read_pixel_value(ifd, 3 * 16 + 5, 0).unwrap(), 53);
+    }
+
+    #[test]
+    fn test_rejects_ifd_of_other_file() {
+        let file = write_geotiff(&FixtureSpec::new(32, 32), |x, _| x as f64);
+        let other = write_geotiff(&FixtureSpec::new(48, 32), |_, _| 7.0);
+        let reader = SharedTiffReader::open(file.path()).unwrap();
+        let foreign = SharedTiffReader::open(other.path()).unwrap().ifd(0).unwrap().unwrap();
+
+        assert!(matches!(reader.read_tile(&foreign, 0), Err(Error::InvalidFormat(_))));
+        assert!(reader.read_tiles(&foreign, &[0, 1]).is_err());
+        assert!(reader.read_pixel_value(&foreign, 1, 0).is_err());
+        assert_eq!(reader.cache().len(), 0);
+
+        let ifd = reader.ifd(0).unwrap().unwrap();
+        assert_eq!(reader.read_pixel_value(&ifd, 5, 0).unwrap(), 5);
+    }
+}
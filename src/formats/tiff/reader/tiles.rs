@@ -1,15 +1,15 @@
 //! Tile loading and caching operations
 
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek};
 use std::sync::Arc;
-use memmap2::Mmap;
 use crate::error::{Error, Result};
-use crate::io::{BufferedReader, ByteOrder};
+use crate::io::{BufferedReader, ByteOrder, RangeSource};
 use crate::compression::Compression;
 use crate::cache::TileCache;
 use crate::cache_prefetch::{AccessPattern, PrefetchConfig};
 use crate::cache_prefetch_async::PrefetchPool;
 use crate::formats::tiff::{IFD, IFDEntry, tags};
+use super::locations::{LocationIndex, TileLocations};
 
 /// Apply horizontal differencing predictor with SIMD optimization for x86_64
 #[cfg(target_arch = "x86_64")]
@@ -103,12 +103,16 @@ fn apply_horizontal_predictor(data: &mut [u8], width: usize, height: usize) {
     }
 }
 
-/// Handles tile loading with caching and memory mapping
+/// Handles tile loading with caching
+///
+/// Headers and tags are parsed through the buffered `reader`; tile data is
+/// read from the positioned `source`.
 pub struct TileReader<R: Read + Seek + Send + Sync> {
     reader: BufferedReader<R>,
     byte_order: ByteOrder,
-    mmap: Option<Arc<Mmap>>,
+    source: Arc<dyn RangeSource>,
     cache: TileCache,
+    locations: Arc<LocationIndex>,
     current_ifd_index: usize,
     access_pattern: Option<AccessPattern>,
     prefetch_config: PrefetchConfig,
@@ -119,13 +123,15 @@ impl<R: Read + Seek + Send + Sync> TileReader<R> {
     pub fn new(
         reader: BufferedReader<R>,
         byte_order: ByteOrder,
-        mmap: Option<Arc<Mmap>>,
+        is_big_tiff: bool,
+        source: Arc<dyn RangeSource>,
         cache_size: usize,
     ) -> Self {
         Self {
             reader,
             byte_order,
-            mmap,
+            locations: Arc::new(LocationIndex::new(source.clone(), byte_order, is_big_tiff)),
+            source,
             cache: TileCache::new(cache_size),
             current_ifd_index: 0,
             access_pattern: None,
@@ -137,14 +143,16 @@ impl<R: Read + Seek + Send + Sync> TileReader<R> {
     pub fn new_with_prefetch(
         reader: BufferedReader<R>,
         byte_order: ByteOrder,
-        mmap: Option<Arc<Mmap>>,
+        is_big_tiff: bool,
+        source: Arc<dyn RangeSource>,
         cache_size: usize,
         prefetch_config: PrefetchConfig,
     ) -> Self {
         Self {
             reader,
             byte_order,
-            mmap,
+            locations: Arc::new(LocationIndex::new(source.clone(), byte_order, is_big_tiff)),
+            source,
             cache: TileCache::new(cache_size),
             current_ifd_index: 0,
             access_pattern: None,
@@ -157,14 +165,14 @@ impl<R: Read + Seek + Send + Sync> TileReader<R> {
         if self.prefetch_config.enabled {
             self.access_pattern = Some(AccessPattern::new(tiles_per_row));
 
-            let mmap = self.mmap.clone();
-            let byte_order = self.byte_order;
+            let source = self.source.clone();
+            let locations = self.locations.clone();
 
-            let load_fn = move |ifd: &IFD, offsets: &IFDEntry, counts: &IFDEntry, tile_idx: usize| {
+            let load_fn = move |ifd: &IFD, _: &IFDEntry, _: &IFDEntry, tile_idx: usize| {
                 use super::parallel::{ParallelReader, ParallelConfig};
 
-                let config = ParallelConfig::from_ifd(ifd, mmap.clone(), byte_order)?;
-                let result = ParallelReader::load_single_tile(tile_idx, offsets, counts, &config)?;
+                let config = ParallelConfig::from_ifd(ifd, source.clone(), locations.get(ifd)?)?;
+                let result = ParallelReader::load_single_tile(tile_idx, &config)?;
                 Ok(result.1)
             };
 
@@ -188,8 +196,8 @@ impl<R: Read + Seek + Send + Sync> TileReader<R> {
         &self.cache
     }
 
-    pub fn mmap(&self) -> &Option<Arc<Mmap>> {
-        &self.mmap
+    pub fn source(&self) -> &Arc<dyn RangeSource> {
+        &self.source
     }
 
     /// Reads a tile with caching and optional prefetching
@@ -265,44 +273,37 @@ impl<R: Read + Seek + Send + Sync> TileReader<R> {
         }
     }
 
+    /// Returns the tile offsets and byte counts of an IFD, reading both
+    /// arrays once and caching them
+    pub fn tile_locations(&self, ifd: &IFD) -> Result<Arc<TileLocations>> {
+        self.locations.get(ifd)
+    }
+
     /// Loads a tile that is not in cache
     fn load_uncached_tile(&mut self, ifd: &IFD, tile_index: usize) -> Result<Vec<u8>> {
-        let offsets_entry = ifd.get_entry(tags::TILE_OFFSETS)
-            .ok_or(Error::MissingTag(tags::TILE_OFFSETS))?;
-
-        let byte_counts_entry = ifd.get_entry(tags::TILE_BYTE_COUNTS)
-            .ok_or(Error::MissingTag(tags::TILE_BYTE_COUNTS))?;
-
-        let offset = self.read_tile_offset(offsets_entry, tile_index)?;
-        let byte_count = self.read_tile_byte_count(byte_counts_entry, tile_index)?;
+        let (offset, byte_count) = self.tile_locations(ifd)?.get(tile_index)?;
 
         if byte_count == 0 {
             return self.create_empty_tile(ifd);
         }
 
+        // In-memory sources are decompressed in place without a copy
+        if let Some(compressed_data) = self.source.slice(offset, byte_count) {
+            return self.decompress_and_apply_predictor(ifd, compressed_data);
+        }
+
         let compressed_data = self.read_compressed_data(offset, byte_count)?;
         self.decompress_and_apply_predictor(ifd, &compressed_data)
     }
 
     /// Creates an empty tile filled with zeros
     fn create_empty_tile(&self, ifd: &IFD) -> Result<Vec<u8>> {
-        let tile_dims = ifd.tile_dimensions()
-            .ok_or_else(|| Error::InvalidFormat("Missing tile dimensions".to_string()))?;
-        Ok(vec![0u8; (tile_dims.width * tile_dims.height) as usize])
+        Ok(vec![0u8; self.tile_locations(ifd)?.tile_size])
     }
 
-    /// Reads compressed tile data from file or memory map
+    /// Reads compressed tile data from the source
     fn read_compressed_data(&mut self, offset: u64, byte_count: u64) -> Result<Vec<u8>> {
-        if let Some(ref mmap) = self.mmap {
-            let start = offset as usize;
-            let end = start + byte_count as usize;
-            return Ok(mmap[start..end].to_vec());
-        }
-
-        self.reader.seek(SeekFrom::Start(offset))?;
-        let mut buffer = vec![0u8; byte_count as usize];
-        self.reader.read_exact(&mut buffer)?;
-        Ok(buffer)
+        Ok(self.source.read_range(offset, byte_count)?)
     }
 
     /// Decompresses tile data and applies predictor if needed
@@ -320,94 +321,6 @@ impl<R: Read + Seek + Send + Sync> TileReader<R> {
 
         Ok(decompressed)
     }
-
-    pub fn read_tile_offset(&mut self, entry: &IFDEntry, index: usize) -> Result<u64> {
-        let offset_size = entry.field_type_size();
-        let file_offset = entry.value_offset + (index as u64 * offset_size as u64);
-
-        self.reader.seek(SeekFrom::Start(file_offset))?;
-        let handler = self.byte_order.handler();
-
-        let offset = if offset_size == 8 {
-            handler.read_u64(&mut self.reader)?
-        } else {
-            handler.read_u32(&mut self.reader)? as u64
-        };
-
-        Ok(offset)
-    }
-
-    pub fn read_tile_byte_count(&mut self, entry: &IFDEntry, index: usize) -> Result<u64> {
-        let count_size = entry.field_type_size();
-        let file_offset = entry.value_offset + (index as u64 * count_size as u64);
-
-        self.reader.seek(SeekFrom::Start(file_offset))?;
-        let handler = self.byte_order.handler();
-
-        let byte_count = if count_size == 8 {
-            handler.read_u64(&mut self.reader)?
-        } else {
-            handler.read_u32(&mut self.reader)? as u64
-        };
-
-        Ok(byte_count)
-    }
-
-    /// Static method for reading tile offset from memory map (for parallel processing)
-    pub fn read_tile_offset_static(
-        entry: &IFDEntry,
-        index: usize,
-        mmap: &Option<Arc<Mmap>>,
-        _byte_order: ByteOrder,
-    ) -> Result<u64> {
-        let offset_size = entry.field_type_size();
-        let file_offset = entry.value_offset + (index as u64 * offset_size as u64);
-
-        if let Some(ref mmap) = mmap {
-            let start = file_offset as usize;
-
-            if offset_size == 8 {
-                let bytes = &mmap[start..start + 8];
-                Ok(u64::from_le_bytes([
-                    bytes[0], bytes[1], bytes[2], bytes[3],
-                    bytes[4], bytes[5], bytes[6], bytes[7],
-                ]))
-            } else {
-                let bytes = &mmap[start..start + 4];
-                Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64)
-            }
-        } else {
-            Err(Error::Unsupported("Parallel reading requires memory mapping".to_string()))
-        }
-    }
-
-    /// Static method for reading tile byte count from memory map (for parallel processing)
-    pub fn read_tile_byte_count_static(
-        entry: &IFDEntry,
-        index: usize,
-        mmap: &Option<Arc<Mmap>>,
-        _byte_order: ByteOrder,
-    ) -> Result<u64> {
-        let count_size = entry.field_type_size();
-        let file_offset = entry.value_offset + (index as u64 * count_size as u64);
-
-        if let Some(ref mmap) = mmap {
-            let start = file_offset as usize;
-
-            if count_size == 8 {
-                let bytes = &mmap[start..start + 8];
-                Ok(u64::from_le_bytes([
-                    bytes[0], bytes[1], bytes[2], bytes[3],
-                    bytes[4], bytes[5], bytes[6], bytes[7],
-                ]))
-            } else {
-                let bytes = &mmap[start..start + 4];
-                Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64)
-            }
-        } else {
-            Err(Error::Unsupported("Parallel reading requires memory mapping".to_string()))
-        }
-    }
 }
 
 #[cfg(test)]
@@ -415,6 +328,11 @@ mod tests {
     use super::*;
     use std::io::Cursor;
     use crate::formats::tiff::tags::field_types;
+    use crate::io::MemorySource;
+
+    fn empty_source() -> Arc<dyn RangeSource> {
+        Arc::new(MemorySource::new(Vec::new()))
+    }
 
     #[test]
     fn test_apply_horizontal_predictor() {
@@ -434,35 +352,22 @@ mod tests {
         let mut ifd = IFD::new(0, 0);
         ifd.add_entry(IFDEntry::new(tags::TILE_WIDTH, field_types::LONG, 1, 256));
         ifd.add_entry(IFDEntry::new(tags::TILE_LENGTH, field_types::LONG, 1, 256));
+        ifd.add_entry(IFDEntry::new(tags::TILE_OFFSETS, field_types::LONG, 1, 0));
+        ifd.add_entry(IFDEntry::new(tags::TILE_BYTE_COUNTS, field_types::LONG, 1, 0));
+        ifd.add_entry(IFDEntry::new(tags::BITS_PER_SAMPLE, field_types::SHORT, 1, 32));
 
         let reader = BufferedReader::new(Cursor::new(vec![]));
-        let tile_reader = TileReader::new(reader, ByteOrder::LittleEndian, None, 0);
+        let tile_reader = TileReader::new(reader, ByteOrder::LittleEndian, false, empty_source(), 0);
 
         let empty_tile = tile_reader.create_empty_tile(&ifd).unwrap();
-        assert_eq!(empty_tile.len(), 256 * 256);
+        assert_eq!(empty_tile.len(), 256 * 256 * 4);
         assert!(empty_tile.iter().all(|&b| b == 0));
     }
 
-    #[test]
-    fn test_read_tile_offset_static_u32() {
-        let mut mmap_data = vec![0u8; 100];
-        mmap_data[20] = 0x78;
-        mmap_data[21] = 0x56;
-        mmap_data[22] = 0x34;
-        mmap_data[23] = 0x12;
-
-        let entry = IFDEntry::new(tags::TILE_OFFSETS, field_types::LONG, 5, 16);
-
-        let offset = entry.value_offset + (1 * 4);
-        assert_eq!(offset, 20);
-
-        assert_eq!(mmap_data[20], 0x78);
-    }
-
     #[test]
     fn test_cache_integration() {
         let reader = BufferedReader::new(Cursor::new(vec![]));
-        let mut tile_reader = TileReader::new(reader, ByteOrder::LittleEndian, None, 10);
+        let mut tile_reader = TileReader::new(reader, ByteOrder::LittleEndian, false, empty_source(), 10);
 
         tile_reader.set_current_ifd(0);
         assert_eq!(tile_reader.current_ifd_index, 0);
@@ -1,5 +1,8 @@
 //! TIFF tag constants
 
+/// Subfile kind bit flags (1 = reduced resolution, 4 = transparency mask)
+pub const NEW_SUBFILE_TYPE: u16 = 254;
+
 /// Image width in pixels
 pub const IMAGE_WIDTH: u16 = 256;
 
@@ -93,6 +96,7 @@ pub const GDAL_NODATA: u16 = 42113;
 /// Returns the name of a TIFF tag
 pub fn tag_name(tag: u16) -> &'static str {
     match tag {
+        NEW_SUBFILE_TYPE => "NewSubfileType",
         IMAGE_WIDTH => "ImageWidth",
         IMAGE_LENGTH => "ImageLength",
         BITS_PER_SAMPLE => "BitsPerSample",
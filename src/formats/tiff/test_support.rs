@@ -0,0 +1,284 @@
+//! Synthetic GeoTIFF fixtures for unit tests
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::NamedTempFile;
+use crate::io::{MemorySource, RangeSource};
+use crate::types::DataType;
+use super::tags::{self, field_types};
+use super::{Tiff, TiffReader, IFD};
+
+/// Describes a small uncompressed, tiled, little-endian GeoTIFF
+pub struct FixtureSpec {
+    pub width: u64,
+    pub height: u64,
+    pub tile_width: u64,
+    pub tile_height: u64,
+    pub data_type: DataType,
+    /// Upper-left corner in CRS units
+    pub origin: (f64, f64),
+    /// Pixel size (x, y), both positive
+    pub pixel_size: (f64, f64),
+    /// EPSG code of the CRS; 0 writes no CRS key
+    pub epsg: u16,
+    pub nodata: Option<f64>,
+    /// Samples per pixel (interleaved)
+    pub bands: u16,
+    /// NewSubfileType value (1 = overview, 4 = mask)
+    pub subfile_type: u32,
+}
+
+impl FixtureSpec {
+    /// Creates a single-band U8 fixture with 16x16 tiles, origin (0, height) and 1.0 pixel size
+    pub fn new(width: u64, height: u64) -> Self {
+        Self {
+            width,
+            height,
+            tile_width: 16,
+            tile_height: 16,
+            data_type: DataType::U8,
+            origin: (0.0, height as f64),
+            pixel_size: (1.0, 1.0),
+            epsg: 3857,
+            nodata: None,
+            bands: 1,
+            subfile_type: 0,
+        }
+    }
+}
+
+fn encode_value(data_type: DataType, value: f64, out: &mut Vec<u8>) {
+    match data_type {
+        DataType::U8 => out.push(value as u8),
+        DataType::I8 => out.push((value as i8) as u8),
+        DataType::U16 => out.extend_from_slice(&(value as u16).to_le_bytes()),
+        DataType::I16 => out.extend_from_slice(&(value as i16).to_le_bytes()),
+        DataType::U32 => out.extend_from_slice(&(value as u32).to_le_bytes()),
+        DataType::I32 => out.extend_from_slice(&(value as i32).to_le_bytes()),
+        DataType::F32 => out.extend_from_slice(&(value as f32).to_le_bytes()),
+        DataType::F64 => out.extend_from_slice(&value.to_le_bytes()),
+    }
+}
+
+fn sample_format(data_type: DataType) -> (u64, u64) {
+    match data_type {
+        DataType::U8 => (8, 1),
+        DataType::U16 => (16, 1),
+        DataType::U32 => (32, 1),
+        DataType::I8 => (8, 2),
+        DataType::I16 => (16, 2),
+        DataType::I32 => (32, 2),
+        DataType::F32 => (32, 3),
+        DataType::F64 => (64, 3),
+    }
+}
+
+/// Builds the GeoTIFF bytes; `value` is called with (x, y) for every pixel
+pub fn build_geotiff<F: Fn(u64, u64) -> f64>(spec: &FixtureSpec, value: F) -> Vec<u8> {
+    build_geotiff_images(&[(spec, &|x, y, _| value(x, y))])
+}
+
+/// Sample generator called with (x, y, band)
+pub type SampleFn<'a> = &'a dyn Fn(u64, u64, u16) -> f64;
+
+/// Builds a GeoTIFF with one IFD per image (e.g. overviews or masks)
+///
+/// Bands are interleaved (chunky).
+pub fn build_geotiff_images(images: &[(&FixtureSpec, SampleFn)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(b"II");
+    out.extend_from_slice(&42u16.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    // Position of the pointer to the next IFD
+    let mut next_pointer = 4;
+    for (spec, value) in images {
+        let ifd_pos = append_image(&mut out, spec, *value);
+        out[next_pointer..next_pointer + 4].copy_from_slice(&ifd_pos.to_le_bytes());
+        next_pointer = out.len() - 4;
+    }
+    out
+}
+
+/// Helper: Appends tile data, tag arrays and the IFD of one image; returns the IFD position
+fn append_image(out: &mut Vec<u8>, spec: &FixtureSpec, value: SampleFn) -> u32 {
+    let tiles_across = spec.width.div_ceil(spec.tile_width);
+    let tiles_down = spec.height.div_ceil(spec.tile_height);
+    let tile_count = (tiles_across * tiles_down) as usize;
+
+    let mut tile_blobs = Vec::with_capacity(tile_count);
+    for ty in 0..tiles_down {
+        for tx in 0..tiles_across {
+            let mut blob = Vec::new();
+            for row in 0..spec.tile_height {
+                for col in 0..spec.tile_width {
+                    let x = tx * spec.tile_width + col;
+                    let y = ty * spec.tile_height + row;
+                    for band in 0..spec.bands {
+                        let v = if x < spec.width && y < spec.height { value(x, y, band) } else { 0.0 };
+                        encode_value(spec.data_type, v, &mut blob);
+                    }
+                }
+            }
+            tile_blobs.push(blob);
+        }
+    }
+
+    let scale = [spec.pixel_size.0, spec.pixel_size.1, 0.0];
+    let tiepoint = [0.0, 0.0, 0.0, spec.origin.0, spec.origin.1, 0.0];
+    let key_type = if spec.epsg == 4326 || (4000..5000).contains(&spec.epsg) { 2048 } else { 3072 };
+    let geokeys: Vec<u16> = match spec.epsg {
+        0 => vec![1, 1, 0, 0],
+        epsg => vec![1, 1, 0, 1, key_type, 0, 1, epsg],
+    };
+    let nodata = spec.nodata.map(|v| format!("{}\0", v));
+    let (bits, format) = sample_format(spec.data_type);
+
+    // Layout: tile data | offsets | counts | sample arrays | doubles | geokeys | nodata | IFD
+    let mut offsets = Vec::with_capacity(tile_count);
+    for blob in &tile_blobs {
+        offsets.push(out.len() as u32);
+        out.extend_from_slice(blob);
+    }
+
+    let offsets_pos = out.len() as u64;
+    for off in &offsets {
+        out.extend_from_slice(&off.to_le_bytes());
+    }
+    let counts_pos = out.len() as u64;
+    for blob in &tile_blobs {
+        out.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+    }
+    let bits_pos = out.len() as u64;
+    for _ in 0..spec.bands {
+        out.extend_from_slice(&(bits as u16).to_le_bytes());
+    }
+    let format_pos = out.len() as u64;
+    for _ in 0..spec.bands {
+        out.extend_from_slice(&(format as u16).to_le_bytes());
+    }
+    let scale_pos = out.len() as u64;
+    for v in scale {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    let tiepoint_pos = out.len() as u64;
+    for v in tiepoint {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    let geokeys_pos = out.len() as u64;
+    for &v in &geokeys {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    let nodata_pos = out.len() as u64;
+    if let Some(ref text) = nodata {
+        out.extend_from_slice(text.as_bytes());
+    }
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+
+    let inline_or = |count: usize, pos: u64, first: u32| -> u64 {
+        if count == 1 { first as u64 } else { pos }
+    };
+    let shorts = |count: u16, pos: u64, v: u64| -> u64 {
+        match count {
+            1 => v,
+            2 => v | (v << 16),
+            _ => pos,
+        }
+    };
+
+    let mut entries: Vec<(u16, u16, u64, u64)> = vec![
+        (tags::IMAGE_WIDTH, field_types::LONG, 1, spec.width),
+        (tags::IMAGE_LENGTH, field_types::LONG, 1, spec.height),
+        (tags::BITS_PER_SAMPLE, field_types::SHORT, spec.bands as u64, shorts(spec.bands, bits_pos, bits)),
+        (tags::COMPRESSION, field_types::SHORT, 1, 1),
+        (tags::SAMPLES_PER_PIXEL, field_types::SHORT, 1, spec.bands as u64),
+        (tags::TILE_WIDTH, field_types::LONG, 1, spec.tile_width),
+        (tags::TILE_LENGTH, field_types::LONG, 1, spec.tile_height),
+        (tags::TILE_OFFSETS, field_types::LONG, tile_count as u64, inline_or(tile_count, offsets_pos, offsets[0])),
+        (tags::TILE_BYTE_COUNTS, field_types::LONG, tile_count as u64,
+            inline_or(tile_count, counts_pos, tile_blobs[0].len() as u32)),
+        (tags::SAMPLE_FORMAT, field_types::SHORT, spec.bands as u64, shorts(spec.bands, format_pos, format)),
+        (tags::MODEL_PIXEL_SCALE, field_types::DOUBLE, 3, scale_pos),
+        (tags::MODEL_TIEPOINT, field_types::DOUBLE, 6, tiepoint_pos),
+        (tags::GEO_KEY_DIRECTORY, field_types::SHORT, geokeys.len() as u64, geokeys_pos),
+    ];
+    if spec.subfile_type != 0 {
+        entries.push((tags::NEW_SUBFILE_TYPE, field_types::LONG, 1, spec.subfile_type as u64));
+    }
+    if let Some(ref text) = nodata {
+        let len = text.len() as u64;
+        let value = if len <= 4 {
+            let mut inline = [0u8; 8];
+            inline[..text.len()].copy_from_slice(text.as_bytes());
+            u64::from_le_bytes(inline)
+        } else {
+            nodata_pos
+        };
+        entries.push((tags::GDAL_NODATA, field_types::ASCII, len, value));
+    }
+    entries.sort_by_key(|e| e.0);
+
+    let ifd_pos = out.len() as u32;
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, field_type, count, value) in entries {
+        out.extend_from_slice(&tag.to_le_bytes());
+        out.extend_from_slice(&field_type.to_le_bytes());
+        out.extend_from_slice(&(count as u32).to_le_bytes());
+        if field_type == field_types::SHORT && count == 1 {
+            out.extend_from_slice(&(value as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        } else {
+            out.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+    }
+    out.extend_from_slice(&0u32.to_le_bytes());
+    ifd_pos
+}
+
+/// Copies every read and counts them, like a remote source would
+pub struct CountingSource {
+    inner: MemorySource,
+    pub reads: AtomicUsize,
+}
+
+impl CountingSource {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { inner: MemorySource::new(data), reads: AtomicUsize::new(0) }
+    }
+}
+
+impl RangeSource for CountingSource {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.inner.read_at(offset, buf)
+    }
+}
+
+/// Writes a fixture GeoTIFF to a temporary file
+pub fn write_geotiff<F: Fn(u64, u64) -> f64>(spec: &FixtureSpec, value: F) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(&build_geotiff(spec, value)).unwrap();
+    file.flush().unwrap();
+    file
+}
+
+/// Opens a GeoTIFF and returns the reader with its main IFD
+pub fn open_fixture<P: AsRef<Path>>(path: P) -> (TiffReader, IFD) {
+    let (reader, tiff) = open_fixture_tiff(path);
+    let ifd = tiff.main_ifd().unwrap().clone();
+    (reader, ifd)
+}
+
+/// Opens a GeoTIFF and returns the reader with every parsed IFD
+pub fn open_fixture_tiff<P: AsRef<Path>>(path: P) -> (TiffReader, Tiff) {
+    let mut reader = TiffReader::open(path).unwrap();
+    let tiff = reader.read().unwrap();
+    (reader, tiff)
+}
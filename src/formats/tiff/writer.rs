@@ -0,0 +1,379 @@
+//! Tiled GeoTIFF writer
+//!
+//! Writes single-band, little-endian classic TIFFs. Tiles may be written in
+//! any order (e.g. as parallel workers finish); the tile arrays, GeoTIFF
+//! tags and IFD are appended by [`GeoTiffWriter::finish`], which then points
+//! the header at the IFD. Tiles never written are filled with NoData.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::compression::{deflate, Compression};
+use crate::error::{Error, Result};
+use crate::types::DataType;
+use super::geotiff::geo_keys;
+use super::tags::{self, field_types};
+use super::{GdalMetadata, GeoInfo};
+
+/// Georeferencing of a north-up grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoReference {
+    /// Upper-left corner in CRS units
+    pub origin: (f64, f64),
+    /// Pixel size (x, y), both positive
+    pub pixel_size: (f64, f64),
+    pub epsg: u16,
+    /// Whether `epsg` is a geographic CRS
+    pub geographic: bool,
+}
+
+impl GeoReference {
+    /// Takes the grid of an existing GeoTIFF
+    pub fn from_geo_info(geo_info: &GeoInfo) -> Option<Self> {
+        let transform = geo_info.affine_transform()?;
+        Some(Self {
+            origin: (transform[0], transform[3]),
+            pixel_size: (transform[1], -transform[5]),
+            epsg: geo_info.epsg_code?,
+            geographic: geo_info.geographic,
+        })
+    }
+}
+
+/// Layout and tags of a file to write
+#[derive(Debug, Clone)]
+pub struct WriterConfig {
+    pub width: u64,
+    pub height: u64,
+    pub data_type: DataType,
+    /// Tile width, a multiple of 16
+    pub tile_width: u64,
+    /// Tile height, a multiple of 16
+    pub tile_height: u64,
+    /// `None` or `Deflate`
+    pub compression: Compression,
+    pub nodata: Option<f64>,
+    pub georef: Option<GeoReference>,
+    pub metadata: Option<GdalMetadata>,
+}
+
+impl WriterConfig {
+    /// Creates an uncompressed configuration with 256x256 tiles
+    pub fn new(width: u64, height: u64, data_type: DataType) -> Self {
+        Self {
+            width,
+            height,
+            data_type,
+            tile_width: 256,
+            tile_height: 256,
+            compression: Compression::None,
+            nodata: None,
+            georef: None,
+            metadata: None,
+        }
+    }
+
+    /// Returns the number of tiles across and down
+    pub fn tiles_across_down(&self) -> (u64, u64) {
+        (self.width.div_ceil(self.tile_width), self.height.div_ceil(self.tile_height))
+    }
+
+    /// Returns the (x, y, width, height) pixel window of a tile, clipped to the image
+    pub fn tile_window(&self, tile_index: usize) -> (u64, u64, u64, u64) {
+        let (across, _) = self.tiles_across_down();
+        let x = (tile_index as u64 % across) * self.tile_width;
+        let y = (tile_index as u64 / across) * self.tile_height;
+        (x, y, self.tile_width.min(self.width - x), self.tile_height.min(self.height - y))
+    }
+}
+
+/// Streaming writer for a tiled GeoTIFF
+pub struct GeoTiffWriter {
+    file: BufWriter<File>,
+    config: WriterConfig,
+    /// (offset, byte count) of each written tile
+    tiles: Vec<Option<(u64, u64)>>,
+    position: u64,
+}
+
+impl GeoTiffWriter {
+    /// Creates the file and writes the header
+    pub fn create<P: AsRef<Path>>(path: P, config: WriterConfig) -> Result<Self> {
+        if config.width == 0 || config.height == 0 {
+            return Err(Error::InvalidFormat("Image dimensions must be non-zero".to_string()));
+        }
+        if config.tile_width == 0 || !config.tile_width.is_multiple_of(16)
+            || config.tile_height == 0 || !config.tile_height.is_multiple_of(16) {
+            return Err(Error::InvalidFormat(format!(
+                "Tile size {}x{} is not a multiple of 16", config.tile_width, config.tile_height
+            )));
+        }
+        if !matches!(config.compression, Compression::None | Compression::Deflate) {
+            return Err(Error::Unsupported(format!("Writing {} compression", config.compression.name())));
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(b"II")?;
+        file.write_all(&42u16.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        let (across, down) = config.tiles_across_down();
+        Ok(Self {
+            file,
+            tiles: vec![None; (across * down) as usize],
+            config,
+            position: 8,
+        })
+    }
+
+    /// Returns the configuration
+    pub fn config(&self) -> &WriterConfig {
+        &self.config
+    }
+
+    /// Writes one tile
+    ///
+    /// `values` cover the part of the tile inside the image, row-major. NaN
+    /// becomes NoData; integer types round and saturate.
+    pub fn write_tile(&mut self, tile_index: usize, values: &[f64]) -> Result<()> {
+        if tile_index >= self.tiles.len() {
+            return Err(Error::OutOfBounds(format!("Tile {} of {}", tile_index, self.tiles.len())));
+        }
+        let (_, _, width, height) = self.config.tile_window(tile_index);
+        if values.len() as u64 != width * height {
+            return Err(Error::InvalidFormat(format!(
+                "Tile {} expects {} values, got {}", tile_index, width * height, values.len()
+            )));
+        }
+
+        let bytes = self.encode_tile(values, width as usize)?;
+        self.tiles[tile_index] = Some(self.append(&bytes)?);
+        Ok(())
+    }
+
+    /// Fills missing tiles, writes the IFD and closes the file
+    pub fn finish(mut self) -> Result<()> {
+        if self.tiles.iter().any(Option::is_none) {
+            let fill = self.encode_tile(&[], 0)?;
+            let location = self.append(&fill)?;
+            for tile in self.tiles.iter_mut().filter(|t| t.is_none()) {
+                *tile = Some(location);
+            }
+        }
+
+        let config = self.config.clone();
+        let (sample_format, bits) = config.data_type.to_tiff();
+        let compression: u16 = match config.compression {
+            Compression::Deflate => 8,
+            _ => 1,
+        };
+
+        let mut offsets = Vec::new();
+        let mut counts = Vec::new();
+        for &(offset, count) in self.tiles.iter().flatten() {
+            offsets.extend_from_slice(&(offset as u32).to_le_bytes());
+            counts.extend_from_slice(&(count as u32).to_le_bytes());
+        }
+        let tile_count = self.tiles.len() as u32;
+
+        let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = vec![
+            (tags::IMAGE_WIDTH, field_types::LONG, 1, (config.width as u32).to_le_bytes().to_vec()),
+            (tags::IMAGE_LENGTH, field_types::LONG, 1, (config.height as u32).to_le_bytes().to_vec()),
+            (tags::BITS_PER_SAMPLE, field_types::SHORT, 1, bits.to_le_bytes().to_vec()),
+            (tags::COMPRESSION, field_types::SHORT, 1, compression.to_le_bytes().to_vec()),
+            // BlackIsZero
+            (tags::PHOTOMETRIC_INTERPRETATION, field_types::SHORT, 1, 1u16.to_le_bytes().to_vec()),
+            (tags::SAMPLES_PER_PIXEL, field_types::SHORT, 1, 1u16.to_le_bytes().to_vec()),
+            (tags::PLANAR_CONFIGURATION, field_types::SHORT, 1, 1u16.to_le_bytes().to_vec()),
+            (tags::TILE_WIDTH, field_types::LONG, 1, (config.tile_width as u32).to_le_bytes().to_vec()),
+            (tags::TILE_LENGTH, field_types::LONG, 1, (config.tile_height as u32).to_le_bytes().to_vec()),
+            (tags::TILE_OFFSETS, field_types::LONG, tile_count, offsets),
+            (tags::TILE_BYTE_COUNTS, field_types::LONG, tile_count, counts),
+            (tags::SAMPLE_FORMAT, field_types::SHORT, 1, sample_format.to_le_bytes().to_vec()),
+        ];
+
+        if let Some(georef) = config.georef {
+            let scale = [georef.pixel_size.0, georef.pixel_size.1, 0.0];
+            let tiepoint = [0.0, 0.0, 0.0, georef.origin.0, georef.origin.1, 0.0];
+            let (model_type, crs_key) = if georef.geographic {
+                (2, geo_keys::GEOGRAPHIC_TYPE)
+            } else {
+                (1, geo_keys::PROJECTED_CS_TYPE)
+            };
+            // Header, then (key, location, count, value) with PixelIsArea raster type
+            let geokeys: [u16; 16] = [
+                1, 1, 0, 3,
+                geo_keys::MODEL_TYPE, 0, 1, model_type,
+                geo_keys::RASTER_TYPE, 0, 1, 1,
+                crs_key, 0, 1, georef.epsg,
+            ];
+
+            entries.push((tags::MODEL_PIXEL_SCALE, field_types::DOUBLE, 3, doubles(&scale)));
+            entries.push((tags::MODEL_TIEPOINT, field_types::DOUBLE, 6, doubles(&tiepoint)));
+            entries.push((tags::GEO_KEY_DIRECTORY, field_types::SHORT, geokeys.len() as u32,
+                geokeys.iter().flat_map(|k| k.to_le_bytes()).collect()));
+        }
+        if let Some(ref metadata) = config.metadata {
+            entries.push(ascii(tags::GDAL_METADATA, &metadata.to_xml()));
+        }
+        if let Some(nodata) = config.nodata {
+            entries.push(ascii(tags::GDAL_NODATA, &format!("{}", nodata)));
+        }
+        entries.sort_by_key(|e| e.0);
+
+        // Out-of-line values go before the IFD, word aligned
+        let mut values = Vec::with_capacity(entries.len());
+        for (_, _, _, data) in &entries {
+            if data.len() > 4 {
+                self.align()?;
+                values.push(self.append(data)?.0 as u32);
+            } else {
+                let mut inline = [0u8; 4];
+                inline[..data.len()].copy_from_slice(data);
+                values.push(u32::from_le_bytes(inline));
+            }
+        }
+
+        self.align()?;
+        let ifd_offset = self.position;
+        let mut ifd = Vec::with_capacity(2 + entries.len() * 12 + 4);
+        ifd.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for ((tag, field_type, count, _), value) in entries.iter().zip(values) {
+            ifd.extend_from_slice(&tag.to_le_bytes());
+            ifd.extend_from_slice(&field_type.to_le_bytes());
+            ifd.extend_from_slice(&count.to_le_bytes());
+            ifd.extend_from_slice(&value.to_le_bytes());
+        }
+        ifd.extend_from_slice(&0u32.to_le_bytes());
+        self.append(&ifd)?;
+
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(ifd_offset as u32).to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Helper: Encodes (and compresses) a full tile, padding with NoData
+    fn encode_tile(&self, values: &[f64], width: usize) -> Result<Vec<u8>> {
+        let config = &self.config;
+        let fill = config.nodata.unwrap_or(0.0);
+        let (min, max) = config.data_type.value_range();
+        let mut bytes = Vec::with_capacity((config.tile_width * config.tile_height) as usize * config.data_type.size());
+
+        for row in 0..config.tile_height as usize {
+            for col in 0..config.tile_width as usize {
+                let value = if col < width { values.get(row * width + col).copied() } else { None };
+                let mut value = match value {
+                    Some(v) if !v.is_nan() => v,
+                    _ => fill,
+                };
+                if config.data_type.is_integer() {
+                    value = value.round();
+                }
+                encode(config.data_type, value.clamp(min, max), &mut bytes);
+            }
+        }
+
+        match config.compression {
+            Compression::Deflate => deflate::compress(&bytes),
+            _ => Ok(bytes),
+        }
+    }
+
+    /// Helper: Appends bytes at the end of the file; returns (offset, length)
+    fn append(&mut self, bytes: &[u8]) -> Result<(u64, u64)> {
+        let offset = self.position;
+        let end = offset + bytes.len() as u64;
+        if end > u32::MAX as u64 {
+            return Err(Error::Unsupported("Output larger than 4 GiB requires BigTIFF".to_string()));
+        }
+        self.file.write_all(bytes)?;
+        self.position = end;
+        Ok((offset, bytes.len() as u64))
+    }
+
+    /// Helper: Pads the file to an even offset
+    fn align(&mut self) -> Result<()> {
+        if self.position % 2 == 1 {
+            self.append(&[0])?;
+        }
+        Ok(())
+    }
+}
+
+/// Helper: Writes one sample in little-endian order
+fn encode(data_type: DataType, value: f64, out: &mut Vec<u8>) {
+    match data_type {
+        DataType::U8 => out.push(value as u8),
+        DataType::I8 => out.push((value as i8) as u8),
+        DataType::U16 => out.extend_from_slice(&(value as u16).to_le_bytes()),
+        DataType::I16 => out.extend_from_slice(&(value as i16).to_le_bytes()),
+        DataType::U32 => out.extend_from_slice(&(value as u32).to_le_bytes()),
+        DataType::I32 => out.extend_from_slice(&(value as i32).to_le_bytes()),
+        DataType::F32 => out.extend_from_slice(&(value as f32).to_le_bytes()),
+        DataType::F64 => out.extend_from_slice(&value.to_le_bytes()),
+    }
+}
+
+/// Helper: Serializes doubles in little-endian order
+fn doubles(values: &[f64]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Helper: Builds a NUL-terminated ASCII entry
+fn ascii(tag: u16, text: &str) -> (u16, u16, u32, Vec<u8>) {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.push(0);
+    (tag, field_types::ASCII, bytes.len() as u32, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::open_fixture;
+
+    #[test]
+    fn test_round_trip_out_of_order() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut config = WriterConfig::new(40, 20, DataType::I16);
+        config.tile_width = 16;
+        config.tile_height = 16;
+        config.compression = Compression::Deflate;
+        config.nodata = Some(-1.0);
+        config.georef = Some(GeoReference { origin: (10.0, 50.0), pixel_size: (0.5, 0.5), epsg: 4326, geographic: true });
+        let mut metadata = GdalMetadata::new();
+        metadata.set("SOURCE", None, "test");
+        config.metadata = Some(metadata.clone());
+
+        let mut writer = GeoTiffWriter::create(file.path(), config.clone()).unwrap();
+        for index in (0..6).rev().filter(|&i| i != 4) {
+            let (x, y, w, h) = config.tile_window(index);
+            let values: Vec<f64> = (0..h).flat_map(|r| (0..w).map(move |c| ((x + c) + (y + r) * 100) as f64 + 0.4)).collect();
+            writer.write_tile(index, &values).unwrap();
+        }
+        assert!(writer.write_tile(1, &[1.0]).is_err());
+        writer.finish().unwrap();
+
+        let (mut reader, ifd) = open_fixture(file.path());
+        let geo_info = GeoInfo::from_ifd(&ifd, &mut reader).unwrap().unwrap();
+        assert_eq!(GeoReference::from_geo_info(&geo_info), config.georef);
+        assert_eq!(geo_info.nodata, Some(-1.0));
+        assert_eq!(GdalMetadata::from_ifd(&ifd, &mut reader).unwrap(), Some(metadata));
+
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 39, 0).unwrap(), 39.0);
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 3, 15).unwrap(), 1503.0);
+        // Tile 4 (x 16..32, y 16..20) was never written
+        assert_eq!(reader.read_pixel_as_f64(&ifd, 20, 17).unwrap(), -1.0);
+    }
+
+    #[test]
+    fn test_rejects_bad_layout() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut config = WriterConfig::new(10, 10, DataType::U8);
+        config.tile_width = 10;
+        assert!(GeoTiffWriter::create(file.path(), config.clone()).is_err());
+        config.tile_width = 16;
+        config.compression = Compression::Jpeg;
+        assert!(GeoTiffWriter::create(file.path(), config).is_err());
+    }
+}
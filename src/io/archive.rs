@@ -0,0 +1,603 @@
+//! Sources for files inside ZIP and gzip archives
+//!
+//! A ZIP member stored without compression is a plain byte range of the
+//! archive, so it is read in place with true random access — from a local
+//! file, a memory map or a remote object alike. Deflated members and gzip
+//! files cannot be read at random offsets; they are inflated once and held
+//! in memory, per archive and, for paths opened through
+//! [`open_path`](super::open_path), in a process-wide cache bounded by
+//! [`INFLATED_CACHE_BYTES`]. Inflating stops at a size ceiling, by default
+//! [`MAX_INFLATED_SIZE`] or the value of [`MAX_INFLATED_ENV`], so a small
+//! archive cannot expand without bound.
+//! Paths name members as `archive.zip!/dir/dem.tif`.
+
+use std::io::{self, Read, Result};
+use std::sync::{Arc, Mutex, OnceLock};
+use flate2::read::{DeflateDecoder, GzDecoder};
+use flate2::Crc;
+use super::source::{check_range, MemorySource, RangeSource};
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+
+/// Fixed part of the end of central directory record
+const EOCD_SIZE: u64 = 22;
+/// Largest archive comment plus the EOCD record
+const EOCD_SEARCH: u64 = EOCD_SIZE + u16::MAX as u64;
+
+/// Size of a central directory record without its variable fields
+const CENTRAL_RECORD_SIZE: u64 = 46;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Total inflated bytes kept across opens; the least recently used go first
+pub const INFLATED_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Default largest inflated ZIP member or gzip file
+pub const MAX_INFLATED_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Environment variable overriding [`MAX_INFLATED_SIZE`], in bytes
+pub const MAX_INFLATED_ENV: &str = "SKYFOREST_MAX_INFLATED_SIZE";
+
+/// Returns the default inflated size ceiling, from [`MAX_INFLATED_ENV`]
+/// when set to a number of bytes
+pub fn default_max_inflated_size() -> u64 {
+    std::env::var(MAX_INFLATED_ENV).ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(MAX_INFLATED_SIZE)
+}
+
+/// Splits `archive.zip!/member` into the archive path and member name
+pub fn split_archive_path(path: &str) -> Option<(&str, &str)> {
+    let (archive, member) = path.split_once("!/")?;
+    (archive.to_ascii_lowercase().ends_with(".zip") && !member.is_empty()).then_some((archive, member))
+}
+
+/// Central directory record of a ZIP member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    pub name: String,
+    /// Compression method (0 stored, 8 deflated)
+    pub method: u16,
+    pub compressed_size: u64,
+    pub size: u64,
+    pub crc32: u32,
+    encrypted: bool,
+    header_offset: u64,
+}
+
+/// ZIP archive read through any byte source
+pub struct ZipArchive {
+    source: Arc<dyn RangeSource>,
+    entries: Vec<ZipEntry>,
+    /// Deflated members inflated so far, by entry index
+    inflated: Vec<OnceLock<Arc<MemorySource>>>,
+    /// Location of the archive, naming its members in the shared cache
+    cache_key: Option<String>,
+    /// Largest member size that will be inflated
+    max_inflated_size: u64,
+}
+
+impl ZipArchive {
+    /// Reads the central directory, including ZIP64 records
+    pub fn open(source: Arc<dyn RangeSource>) -> Result<Self> {
+        let len = source.len();
+        let tail_start = len.saturating_sub(EOCD_SEARCH);
+        let tail = source.read_range(tail_start, len - tail_start)?;
+        let eocd = (0..tail.len().saturating_sub(EOCD_SIZE as usize - 1))
+            .rev()
+            .find(|&i| u32_at(&tail, i) == EOCD_SIGNATURE)
+            .ok_or_else(|| invalid("No ZIP end of central directory record".to_string()))?;
+
+        // The directory must end before the end records
+        let mut directory_limit = tail_start + eocd as u64;
+        let mut count = u16_at(&tail, eocd + 10) as u64;
+        let mut directory_size = u32_at(&tail, eocd + 12) as u64;
+        let mut directory_offset = u32_at(&tail, eocd + 16) as u64;
+
+        // ZIP64: the locator sits right before the EOCD record
+        if eocd >= 20 && u32_at(&tail, eocd - 20) == ZIP64_LOCATOR_SIGNATURE {
+            let record_offset = u64_at(&tail, eocd - 12);
+            if record_offset.checked_add(56).is_none_or(|end| end > directory_limit - 20) {
+                return Err(invalid("Bad ZIP64 end of central directory locator".to_string()));
+            }
+            directory_limit = record_offset;
+            let record = source.read_range(record_offset, 56)?;
+            if u32_at(&record, 0) != ZIP64_EOCD_SIGNATURE {
+                return Err(invalid("Bad ZIP64 end of central directory record".to_string()));
+            }
+            count = u64_at(&record, 32);
+            directory_size = u64_at(&record, 40);
+            directory_offset = u64_at(&record, 48);
+        }
+
+        if directory_offset.checked_add(directory_size).is_none_or(|end| end > directory_limit) {
+            return Err(invalid("ZIP central directory lies outside the archive".to_string()));
+        }
+        let directory = source.read_range(directory_offset, directory_size)?;
+        // The count is untrusted; every record takes at least 46 bytes
+        let mut entries = Vec::with_capacity(count.min(directory_size / CENTRAL_RECORD_SIZE) as usize);
+        let mut pos = 0;
+        for _ in 0..count {
+            if pos + 46 > directory.len() || u32_at(&directory, pos) != CENTRAL_SIGNATURE {
+                return Err(invalid("Corrupt ZIP central directory".to_string()));
+            }
+            let name_len = u16_at(&directory, pos + 28) as usize;
+            let extra_len = u16_at(&directory, pos + 30) as usize;
+            let comment_len = u16_at(&directory, pos + 32) as usize;
+            let end = pos + 46 + name_len + extra_len + comment_len;
+            if end > directory.len() {
+                return Err(invalid("Corrupt ZIP central directory".to_string()));
+            }
+
+            let mut entry = ZipEntry {
+                name: String::from_utf8_lossy(&directory[pos + 46..pos + 46 + name_len]).into_owned(),
+                method: u16_at(&directory, pos + 10),
+                compressed_size: u32_at(&directory, pos + 20) as u64,
+                size: u32_at(&directory, pos + 24) as u64,
+                crc32: u32_at(&directory, pos + 16),
+                encrypted: u16_at(&directory, pos + 8) & 1 != 0,
+                header_offset: u32_at(&directory, pos + 42) as u64,
+            };
+            apply_zip64_extra(&mut entry, &directory[pos + 46 + name_len..pos + 46 + name_len + extra_len]);
+            entries.push(entry);
+            pos = end;
+        }
+
+        let inflated = entries.iter().map(|_| OnceLock::new()).collect();
+        Ok(Self { source, entries, inflated, cache_key: None, max_inflated_size: default_max_inflated_size() })
+    }
+
+    /// Sets the largest member size, in bytes, that will be inflated
+    pub fn with_max_inflated_size(mut self, max_size: u64) -> Self {
+        self.max_inflated_size = max_size;
+        self
+    }
+
+    /// Also keeps inflated members in the process-wide cache, under the
+    /// archive's `location`, so reopening the archive skips inflating
+    pub fn with_shared_cache(mut self, location: &str) -> Self {
+        self.cache_key = Some(location.to_string());
+        self
+    }
+
+    /// Returns the members in central directory order
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Opens a member by name
+    ///
+    /// Stored members are read in place; deflated members are inflated
+    /// into memory once, checked against their CRC, and kept.
+    pub fn open_member(&self, name: &str) -> Result<Arc<dyn RangeSource>> {
+        let index = self.entries.iter()
+            .position(|e| e.name == name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No member '{}' in ZIP archive", name)))?;
+        let entry = &self.entries[index];
+        if entry.encrypted {
+            return Err(unsupported(format!("ZIP member '{}' is encrypted", name)));
+        }
+
+        match entry.method {
+            METHOD_STORED => Ok(Arc::new(SubrangeSource::new(self.source.clone(), self.data_offset(entry)?, entry.size)?)),
+            METHOD_DEFLATED => {
+                if let Some(data) = self.inflated[index].get() {
+                    return Ok(data.clone());
+                }
+                let data = match &self.cache_key {
+                    Some(location) => {
+                        let check = (entry.crc32, entry.size, entry.compressed_size);
+                        cached_inflate(&format!("{}!/{}", location, name), check, || self.inflate_member(entry))?
+                    }
+                    None => Arc::new(self.inflate_member(entry)?),
+                };
+                Ok(self.inflated[index].get_or_init(|| data).clone())
+            }
+            method => Err(unsupported(format!("ZIP compression method {} of member '{}'", method, name))),
+        }
+    }
+
+    /// Helper: Returns where a member's data starts, after its local header
+    fn data_offset(&self, entry: &ZipEntry) -> Result<u64> {
+        let local = self.source.read_range(entry.header_offset, 30)?;
+        if u32_at(&local, 0) != LOCAL_SIGNATURE {
+            return Err(invalid(format!("Bad local header for ZIP member '{}'", entry.name)));
+        }
+        Ok(entry.header_offset + 30 + u16_at(&local, 26) as u64 + u16_at(&local, 28) as u64)
+    }
+
+    /// Helper: Inflates a deflated member and checks its size and CRC
+    fn inflate_member(&self, entry: &ZipEntry) -> Result<MemorySource> {
+        if entry.size > self.max_inflated_size {
+            return Err(invalid(format!(
+                "ZIP member '{}' inflates to {} bytes, over the {} byte limit",
+                entry.name, entry.size, self.max_inflated_size
+            )));
+        }
+        let compressed = SubrangeSource::new(self.source.clone(), self.data_offset(entry)?, entry.compressed_size)?;
+        let data = inflate(DeflateDecoder::new(SourceBytes::new(&compressed)), entry.size)?;
+        if data.len() as u64 != entry.size {
+            return Err(invalid(format!("Size mismatch in ZIP member '{}'", entry.name)));
+        }
+        let mut crc = Crc::new();
+        crc.update(&data);
+        if crc.sum() != entry.crc32 {
+            return Err(invalid(format!("CRC mismatch in ZIP member '{}'", entry.name)));
+        }
+        Ok(MemorySource::new(data))
+    }
+}
+
+/// Inflates a gzip file at `location` through the process-wide cache
+///
+/// The gzip trailer (CRC and size) and the file length tell a changed file
+/// apart from the cached one.
+pub fn inflate_gzip_cached(source: &dyn RangeSource, location: &str, max_size: u64) -> Result<Arc<MemorySource>> {
+    let len = source.len();
+    if len < 8 {
+        return Ok(Arc::new(inflate_gzip(source, max_size)?));
+    }
+    let trailer = source.read_range(len - 8, 8)?;
+    let check = (u32_at(&trailer, 0), u32_at(&trailer, 4) as u64, len);
+    cached_inflate(location, check, || inflate_gzip(source, max_size))
+}
+
+/// Inflates a whole gzip file into memory, failing past `max_size` bytes
+///
+/// gzip declares no usable size up front, so the ceiling is the only bound.
+pub fn inflate_gzip(source: &dyn RangeSource, max_size: u64) -> Result<MemorySource> {
+    Ok(MemorySource::new(inflate(GzDecoder::new(SourceBytes::new(source)), max_size)?))
+}
+
+/// Window of another source, e.g. a stored ZIP member
+pub struct SubrangeSource {
+    inner: Arc<dyn RangeSource>,
+    start: u64,
+    len: u64,
+}
+
+impl SubrangeSource {
+    /// Exposes `start..start + len` of `inner`
+    pub fn new(inner: Arc<dyn RangeSource>, start: u64, len: u64) -> Result<Self> {
+        check_range(start, len, inner.len())?;
+        Ok(Self { inner, start, len })
+    }
+}
+
+impl RangeSource for SubrangeSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(offset, buf.len() as u64, self.len)?;
+        self.inner.read_at(self.start + offset, buf)
+    }
+
+    fn slice(&self, offset: u64, length: u64) -> Option<&[u8]> {
+        check_range(offset, length, self.len).ok()?;
+        self.inner.slice(self.start + offset, length)
+    }
+
+    fn prefetch(&self, ranges: &[(u64, u64)]) -> Result<()> {
+        let shifted: Vec<(u64, u64)> = ranges.iter().map(|&(offset, length)| (self.start + offset, length)).collect();
+        self.inner.prefetch(&shifted)
+    }
+}
+
+/// Inflated data kept in the process-wide cache
+struct CachedInflate {
+    key: String,
+    /// (CRC, size, compressed size) the data was inflated from
+    check: (u32, u64, u64),
+    data: Arc<MemorySource>,
+}
+
+/// Inflated data by location, least recently used first
+static INFLATED: Mutex<Vec<CachedInflate>> = Mutex::new(Vec::new());
+
+/// Helper: Returns cached inflated data for `key`, inflating it on a miss
+///
+/// An entry only matches when its `check` fingerprint is unchanged.
+fn cached_inflate(
+    key: &str,
+    check: (u32, u64, u64),
+    inflate: impl FnOnce() -> Result<MemorySource>,
+) -> Result<Arc<MemorySource>> {
+    {
+        let mut cache = INFLATED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pos) = cache.iter().position(|c| c.key == key && c.check == check) {
+            let hit = cache.remove(pos);
+            let data = hit.data.clone();
+            cache.push(hit);
+            return Ok(data);
+        }
+    }
+
+    // Inflate without holding the lock; a concurrent miss may inflate twice
+    let data = Arc::new(inflate()?);
+    let mut cache = INFLATED.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|c| c.key != key);
+    cache.push(CachedInflate { key: key.to_string(), check, data: data.clone() });
+
+    let mut total: u64 = cache.iter().map(|c| c.data.bytes().len() as u64).sum();
+    while total > INFLATED_CACHE_BYTES && cache.len() > 1 {
+        total -= cache.remove(0).data.bytes().len() as u64;
+    }
+    Ok(data)
+}
+
+/// Helper: Sequential reader over a source in large chunks, for decoders
+struct SourceBytes<'a> {
+    source: &'a dyn RangeSource,
+    position: u64,
+}
+
+impl<'a> SourceBytes<'a> {
+    fn new(source: &'a dyn RangeSource) -> Self {
+        Self { source, position: 0 }
+    }
+}
+
+impl Read for SourceBytes<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = (buf.len() as u64).min(self.source.len() - self.position) as usize;
+        if n > 0 {
+            self.source.read_at(self.position, &mut buf[..n])?;
+            self.position += n as u64;
+        }
+        Ok(n)
+    }
+}
+
+/// Helper: Reads a decoder to the end, reading the source in 1 MiB chunks
+///
+/// The output grows as data is inflated rather than from a declared size,
+/// and reading fails once it passes `max_size` bytes.
+fn inflate<R: Read>(decoder: R, max_size: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    io::BufReader::with_capacity(1 << 20, decoder)
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > max_size {
+        return Err(invalid(format!("Compressed data inflates past {} bytes", max_size)));
+    }
+    Ok(data)
+}
+
+/// Helper: Replaces saturated sizes and offset with their ZIP64 extra values
+fn apply_zip64_extra(entry: &mut ZipEntry, extra: &[u8]) {
+    let mut pos = 0;
+    while pos + 4 <= extra.len() {
+        let id = u16_at(extra, pos);
+        let size = u16_at(extra, pos + 2) as usize;
+        let end = (pos + 4 + size).min(extra.len());
+        if id == 0x0001 {
+            let mut field = pos + 4;
+            for value in [&mut entry.size, &mut entry.compressed_size, &mut entry.header_offset] {
+                if *value == u32::MAX as u64 && field + 8 <= end {
+                    *value = u64_at(extra, field);
+                    field += 8;
+                }
+            }
+        }
+        pos = end;
+    }
+}
+
+fn u16_at(bytes: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap())
+}
+
+/// Helper: Builds an `InvalidData` error
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Helper: Builds an `Unsupported` error
+fn unsupported(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::{DeflateEncoder, GzEncoder};
+    use flate2::Compression as Level;
+    use crate::formats::tiff::test_support::{build_geotiff, open_fixture, FixtureSpec};
+    use crate::formats::tiff::TiffReader;
+
+    /// Writes a ZIP archive; members are (name, data, deflate)
+    fn build_zip(members: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        for &(name, data, deflate) in members {
+            let mut crc = Crc::new();
+            crc.update(data);
+            let stored = if deflate {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Level::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                data.to_vec()
+            };
+            let method: u16 = if deflate { METHOD_DEFLATED } else { METHOD_STORED };
+            let offset = out.len() as u32;
+
+            let mut fields = Vec::new();
+            fields.extend_from_slice(&method.to_le_bytes());
+            fields.extend_from_slice(&[0u8; 4]);
+            fields.extend_from_slice(&crc.sum().to_le_bytes());
+            fields.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            fields.extend_from_slice(&0u16.to_le_bytes());
+
+            out.extend_from_slice(&LOCAL_SIGNATURE.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0]);
+            out.extend_from_slice(&fields);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&stored);
+
+            directory.extend_from_slice(&CENTRAL_SIGNATURE.to_le_bytes());
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            directory.extend_from_slice(&fields);
+            directory.extend_from_slice(&[0u8; 10]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let directory_offset = out.len() as u32;
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&[0u8; 4]);
+        out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&directory_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    fn fixture() -> Vec<u8> {
+        build_geotiff(&FixtureSpec::new(32, 32), |x, y| (x * 7 + y) as f64)
+    }
+
+    #[test]
+    fn test_stored_member_is_read_in_place() {
+        let tiff = fixture();
+        let zip = build_zip(&[("readme.txt", b"hello", true), ("data/dem.tif", &tiff, false)]);
+        let archive = ZipArchive::open(Arc::new(MemorySource::new(zip))).unwrap();
+        assert_eq!(archive.entries().len(), 2);
+        assert_eq!(archive.entries()[1].size, tiff.len() as u64);
+
+        let member = archive.open_member("data/dem.tif").unwrap();
+        assert_eq!(member.len(), tiff.len() as u64);
+        // Borrowed from the archive buffer
+        assert_eq!(member.slice(0, 4), Some(&tiff[..4]));
+        assert_eq!(member.read_range(100, 50).unwrap(), &tiff[100..150]);
+
+        let mut reader = TiffReader::from_source(member, 16).unwrap();
+        let ifd = reader.read().unwrap().main_ifd().unwrap().clone();
+        assert_eq!(reader.read_pixels_batch(&ifd, &[(3, 4), (31, 20)]).unwrap(), vec![25, 237]);
+
+        assert_eq!(archive.open_member("readme.txt").unwrap().read_range(0, 5).unwrap(), b"hello");
+        assert!(archive.open_member("missing.tif").is_err());
+    }
+
+    #[test]
+    fn test_open_archive_paths() {
+        let tiff = fixture();
+        let dir = tempfile::tempdir().unwrap();
+
+        let zip_path = dir.path().join("vendor.zip");
+        std::fs::write(&zip_path, build_zip(&[("dem.tif", &tiff, true)])).unwrap();
+        let gz_path = dir.path().join("dem.tif.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Level::fast());
+        encoder.write_all(&tiff).unwrap();
+        std::fs::write(&gz_path, encoder.finish().unwrap()).unwrap();
+
+        let member = format!("{}!/dem.tif", zip_path.display());
+        for path in [member.as_str(), gz_path.to_str().unwrap()] {
+            let (mut reader, ifd) = open_fixture(path);
+            assert_eq!(reader.read_pixel_value(&ifd, 31, 20).unwrap(), 237);
+        }
+
+        assert_eq!(split_archive_path("s3://b/v.ZIP!/a/dem.tif"), Some(("s3://b/v.ZIP", "a/dem.tif")));
+        assert_eq!(split_archive_path("dem.tif!/x"), None);
+        assert!(TiffReader::open(format!("{}!/other.tif", zip_path.display())).is_err());
+        assert!(ZipArchive::open(Arc::new(MemorySource::new(tiff))).is_err());
+    }
+
+    #[test]
+    fn test_inflated_members_are_cached() {
+        let tiff = fixture();
+        let zip = Arc::new(MemorySource::new(build_zip(&[("dem.tif", &tiff, true)])));
+        let address = |source: &Arc<dyn RangeSource>| Arc::as_ptr(source) as *const u8;
+
+        let archive = ZipArchive::open(zip.clone()).unwrap();
+        let first = archive.open_member("dem.tif").unwrap();
+        assert_eq!(address(&first), address(&archive.open_member("dem.tif").unwrap()));
+
+        // Separate opens of one location share the inflated member
+        let shared = |zip: &Arc<MemorySource>| {
+            ZipArchive::open(zip.clone()).unwrap().with_shared_cache("test://cached.zip").open_member("dem.tif").unwrap()
+        };
+        let member = shared(&zip);
+        assert_ne!(address(&first), address(&member));
+        assert_eq!(address(&member), address(&shared(&zip)));
+        assert_eq!(member.read_range(0, 8).unwrap(), &tiff[..8]);
+    }
+
+    #[test]
+    fn test_untrusted_zip64_records() {
+        // ZIP64 record with the given member count and directory size at offset 0
+        let zip64 = |count: u64, directory_size: u64| {
+            let mut zip = ZIP64_EOCD_SIGNATURE.to_le_bytes().to_vec();
+            zip.extend_from_slice(&[0u8; 28]);
+            zip.extend_from_slice(&count.to_le_bytes());
+            zip.extend_from_slice(&directory_size.to_le_bytes());
+            zip.extend_from_slice(&[0u8; 8]);
+            zip.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+            zip.extend_from_slice(&[0u8; 4]);
+            zip.extend_from_slice(&0u64.to_le_bytes());
+            zip.extend_from_slice(&[0u8; 4]);
+            zip.extend_from_slice(&build_zip(&[]));
+            ZipArchive::open(Arc::new(MemorySource::new(zip)))
+        };
+
+        assert!(zip64(0, 0).unwrap().entries().is_empty());
+        // 2^62 members in an empty central directory
+        assert!(zip64(1 << 62, 0).is_err());
+        // Directories running past the end, or into the end records
+        assert!(zip64(0, u64::MAX).is_err());
+        assert!(zip64(0, 20).is_err());
+    }
+
+    #[test]
+    fn test_inflating_is_bounded() {
+        let zeros = vec![0u8; 1 << 20];
+        let zip = build_zip(&[("zeros.bin", &zeros, true)]);
+        let open = |zip: Vec<u8>| ZipArchive::open(Arc::new(MemorySource::new(zip))).unwrap();
+
+        assert!(open(zip.clone()).open_member("zeros.bin").is_ok());
+        assert!(open(zip.clone()).with_max_inflated_size(1000).open_member("zeros.bin").is_err());
+
+        // A member inflating past its declared size is cut off and rejected
+        let mut understated = zip;
+        let central = understated.windows(4).position(|w| w == CENTRAL_SIGNATURE.to_le_bytes()).unwrap();
+        understated[central + 24..central + 28].copy_from_slice(&100u32.to_le_bytes());
+        assert!(open(understated).open_member("zeros.bin").is_err());
+
+        let mut encoder = GzEncoder::new(Vec::new(), Level::fast());
+        encoder.write_all(&zeros).unwrap();
+        let gzip = MemorySource::new(encoder.finish().unwrap());
+        assert_eq!(inflate_gzip(&gzip, 1 << 20).unwrap().len(), 1 << 20);
+        assert!(inflate_gzip(&gzip, 1000).is_err());
+    }
+
+    #[test]
+    fn test_inflated_size_limit_is_configurable() {
+        // Raising the limit cannot break archive tests running alongside
+        std::env::set_var(MAX_INFLATED_ENV, "8589934592");
+        assert_eq!(default_max_inflated_size(), 8 << 30);
+        std::env::set_var(MAX_INFLATED_ENV, "lots");
+        assert_eq!(default_max_inflated_size(), MAX_INFLATED_SIZE);
+        std::env::remove_var(MAX_INFLATED_ENV);
+        assert_eq!(default_max_inflated_size(), MAX_INFLATED_SIZE);
+    }
+}
@@ -0,0 +1,138 @@
+//! Asynchronous byte-range sources
+//!
+//! [`AsyncRangeSource`] is the non-blocking counterpart of [`RangeSource`]
+//! for readers driven from an async runtime. In-memory buffers answer
+//! immediately; [`BlockingSource`] runs any blocking source on tokio's
+//! blocking pool, so executor threads never wait on a disk or a socket.
+
+use std::future::Future;
+use std::io::{self, Result};
+use std::pin::Pin;
+use std::sync::Arc;
+use bytes::Bytes;
+use super::source::{check_range, MemorySource, RangeSource};
+
+/// Boxed future returned by [`AsyncRangeSource`] reads
+pub type ReadFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Random-access byte source with non-blocking reads
+pub trait AsyncRangeSource: Send + Sync {
+    /// Returns the total length in bytes
+    fn len(&self) -> u64;
+
+    /// Returns whether the source holds no bytes
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads `length` bytes starting at `offset`
+    ///
+    /// Fails with `UnexpectedEof` if the range extends past the end.
+    fn read_range(&self, offset: u64, length: u64) -> ReadFuture<'_, Bytes>;
+
+    /// Reads several (offset, length) ranges, concurrently by default
+    fn read_ranges(&self, ranges: Vec<(u64, u64)>) -> ReadFuture<'_, Vec<Bytes>> {
+        Box::pin(futures::future::try_join_all(
+            ranges.into_iter().map(|(offset, length)| self.read_range(offset, length)),
+        ))
+    }
+}
+
+impl AsyncRangeSource for MemorySource {
+    fn len(&self) -> u64 {
+        self.bytes().len() as u64
+    }
+
+    fn read_range(&self, offset: u64, length: u64) -> ReadFuture<'_, Bytes> {
+        let data = self.bytes();
+        let result = check_range(offset, length, data.len() as u64)
+            .map(|()| data.slice(offset as usize..(offset + length) as usize));
+        Box::pin(std::future::ready(result))
+    }
+}
+
+/// Blocking [`RangeSource`] read on tokio's blocking thread pool
+pub struct BlockingSource {
+    inner: Arc<dyn RangeSource>,
+}
+
+impl BlockingSource {
+    pub fn new(inner: Arc<dyn RangeSource>) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the wrapped source
+    pub fn inner(&self) -> &Arc<dyn RangeSource> {
+        &self.inner
+    }
+}
+
+impl AsyncRangeSource for BlockingSource {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn read_range(&self, offset: u64, length: u64) -> ReadFuture<'_, Bytes> {
+        let inner = self.inner.clone();
+        Box::pin(run_blocking(move || inner.read_range(offset, length).map(Bytes::from)))
+    }
+
+    /// Reads all ranges in one blocking task, announcing them to the source
+    /// first so remote sources can merge them into fewer requests
+    fn read_ranges(&self, ranges: Vec<(u64, u64)>) -> ReadFuture<'_, Vec<Bytes>> {
+        let inner = self.inner.clone();
+        Box::pin(run_blocking(move || {
+            if ranges.len() > 1 {
+                inner.prefetch(&ranges)?;
+            }
+            ranges.iter()
+                .map(|&(offset, length)| inner.read_range(offset, length).map(Bytes::from))
+                .collect()
+        }))
+    }
+}
+
+/// Helper: Runs blocking I/O on the blocking pool and awaits its result
+async fn run_blocking<T, F>(job: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(job).await.map_err(io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::FileSource;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_memory_source_reads_without_copying() {
+        let source = MemorySource::new((0u8..10).collect::<Vec<_>>());
+        let range = AsyncRangeSource::read_range(&source, 4, 3).await.unwrap();
+        assert_eq!(&range[..], &[4, 5, 6]);
+        assert_eq!(range.as_ptr(), source.bytes()[4..].as_ptr());
+
+        let ranges = source.read_ranges(vec![(0, 2), (8, 2)]).await.unwrap();
+        assert_eq!(ranges, vec![Bytes::from_static(&[0, 1]), Bytes::from_static(&[8, 9])]);
+
+        let error = AsyncRangeSource::read_range(&source, 8, 3).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_source() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&(0u8..=255).collect::<Vec<_>>()).unwrap();
+        file.flush().unwrap();
+
+        let source = BlockingSource::new(Arc::new(FileSource::open(file.path()).unwrap()));
+        assert_eq!(source.len(), 256);
+        assert_eq!(&source.read_range(100, 3).await.unwrap()[..], &[100, 101, 102]);
+
+        let ranges = source.read_ranges(vec![(0, 1), (255, 1)]).await.unwrap();
+        assert_eq!(ranges, vec![Bytes::from_static(&[0]), Bytes::from_static(&[255])]);
+        assert!(source.read_range(250, 10).await.is_err());
+    }
+}
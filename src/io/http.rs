@@ -0,0 +1,484 @@
+//! HTTP byte-range source for remote files such as COGs
+//!
+//! The file is read in fixed-size blocks fetched with `Range` requests and
+//! kept in an LRU [`TileCache`]. Opening fetches the header blocks and the
+//! file length in one request. Missing blocks needed together — a single
+//! read or a [`RangeSource::prefetch`] hint for a batch of tiles — are
+//! fetched with as few requests as possible, merging runs separated by
+//! small gaps. Transport errors, 429 and 5xx responses are retried with
+//! exponential backoff. Redirects are not followed, so a URL checked
+//! against an allow-list cannot lead to another host.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Read, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::cache::TileCache;
+use super::source::{check_range, RangeSource};
+
+/// Tuning of an [`HttpSource`]
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    /// Bytes per cached block
+    pub block_size: u64,
+    /// Blocks kept in memory
+    pub cache_blocks: usize,
+    /// Bytes fetched when opening, to cover the TIFF header and first IFDs
+    pub header_bytes: u64,
+    /// Largest gap in bytes bridged when merging requests
+    pub max_gap: u64,
+    /// Retries after the first failed attempt
+    pub retries: u32,
+    /// Delay before the first retry; doubled for each further retry up to
+    /// [`MAX_RETRY_DELAY`]
+    pub retry_delay: Duration,
+    /// Timeout of a single request
+    pub timeout: Duration,
+    /// Extra request headers, e.g. authorization
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 64 * 1024,
+            cache_blocks: 256,
+            header_bytes: 64 * 1024,
+            max_gap: 256 * 1024,
+            retries: 3,
+            retry_delay: Duration::from_millis(200),
+            timeout: Duration::from_secs(30),
+            headers: Vec::new(),
+        }
+    }
+}
+
+/// Longest sleep between two attempts
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Produces per-request headers (e.g. a signature) from the `Range` value
+pub(crate) type Signer = Arc<dyn Fn(&str) -> Vec<(String, String)> + Send + Sync>;
+
+/// Remote file read with HTTP range requests
+pub struct HttpSource {
+    url: String,
+    agent: ureq::Agent,
+    options: HttpOptions,
+    signer: Option<Signer>,
+    len: u64,
+    blocks: TileCache,
+    requests: AtomicUsize,
+}
+
+/// Helper: Outcome of a failed request
+enum Failure {
+    Retry(io::Error),
+    Fatal(io::Error),
+}
+
+impl HttpSource {
+    /// Opens a URL with default options
+    pub fn open(url: &str) -> Result<Self> {
+        Self::with_options(url, HttpOptions::default())
+    }
+
+    /// Opens a URL, fetching the header blocks and the file length
+    pub fn with_options(url: &str, options: HttpOptions) -> Result<Self> {
+        Self::with_signer(url, options, None)
+    }
+
+    /// Opens a URL whose requests carry headers computed by `signer`
+    pub(crate) fn with_signer(url: &str, options: HttpOptions, signer: Option<Signer>) -> Result<Self> {
+        if options.block_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Block size must be positive"));
+        }
+        let agent = ureq::AgentBuilder::new().timeout(options.timeout).redirects(0).build();
+        let mut source = Self {
+            url: url.to_string(),
+            agent,
+            blocks: TileCache::new(options.cache_blocks),
+            options,
+            signer,
+            len: u64::MAX,
+            requests: AtomicUsize::new(0),
+        };
+
+        let header_blocks = source.options.header_bytes.div_ceil(source.options.block_size).max(1);
+        let (data, len) = source.fetch(0, header_blocks * source.options.block_size)?;
+        source.len = len;
+        source.store_blocks(0, data);
+        Ok(source)
+    }
+
+    /// Returns the URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the number of HTTP requests sent, including retries
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Helper: Returns cached blocks and fetches the missing ones in merged runs
+    fn load_blocks(&self, wanted: &BTreeSet<u64>) -> Result<HashMap<u64, Arc<Vec<u8>>>> {
+        let mut loaded = HashMap::with_capacity(wanted.len());
+        let mut missing = Vec::new();
+        for &block in wanted {
+            match self.blocks.get(0, block as usize) {
+                Some(data) => {
+                    loaded.insert(block, data);
+                }
+                None => missing.push(block),
+            }
+        }
+
+        let max_gap_blocks = self.options.max_gap / self.options.block_size;
+        let mut runs: Vec<(u64, u64)> = Vec::new();
+        for block in missing {
+            match runs.last_mut() {
+                Some((_, last)) if block - *last <= max_gap_blocks + 1 => *last = block,
+                _ => runs.push((block, block)),
+            }
+        }
+
+        for (first, last) in runs {
+            let start = first * self.options.block_size;
+            let end = ((last + 1) * self.options.block_size).min(self.len);
+            let (data, _) = self.fetch(start, end)?;
+            for (block, data) in self.store_blocks(first, data) {
+                if wanted.contains(&block) {
+                    loaded.insert(block, data);
+                }
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Helper: Splits fetched bytes starting at `first_block` into cached blocks
+    fn store_blocks(&self, first_block: u64, data: Vec<u8>) -> Vec<(u64, Arc<Vec<u8>>)> {
+        data.chunks(self.options.block_size as usize)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let block = first_block + i as u64;
+                let data = Arc::new(chunk.to_vec());
+                self.blocks.put_shared(0, block as usize, data.clone());
+                (block, data)
+            })
+            .collect()
+    }
+
+    /// Helper: Fetches `start..end` with retries; returns the bytes and the file length
+    ///
+    /// The range is clipped to the end of the file.
+    fn fetch(&self, start: u64, end: u64) -> Result<(Vec<u8>, u64)> {
+        let mut attempt = 0;
+        loop {
+            match self.fetch_once(start, end) {
+                Ok(result) => return Ok(result),
+                Err(Failure::Fatal(error)) => return Err(error),
+                Err(Failure::Retry(error)) if attempt >= self.options.retries => return Err(error),
+                Err(Failure::Retry(_)) => {
+                    std::thread::sleep(backoff(self.options.retry_delay, attempt));
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Helper: Sends one range request
+    fn fetch_once(&self, start: u64, end: u64) -> std::result::Result<(Vec<u8>, u64), Failure> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let range = format!("bytes={}-{}", start, end - 1);
+        let mut request = self.agent.get(&self.url).set("Range", &range);
+        for (name, value) in &self.options.headers {
+            request = request.set(name, value);
+        }
+        for (name, value) in self.signer.as_ref().map(|sign| sign(&range)).unwrap_or_default() {
+            request = request.set(&name, &value);
+        }
+
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => {
+                return Err(Failure::Retry(http_error(format!("HTTP {} from {}", status, self.url))));
+            }
+            Err(ureq::Error::Status(status, _)) => {
+                return Err(Failure::Fatal(http_error(format!("HTTP {} from {}", status, self.url))));
+            }
+            Err(error) => return Err(Failure::Retry(http_error(error.to_string()))),
+        };
+        if (300..400).contains(&response.status()) {
+            return Err(Failure::Fatal(http_error(format!(
+                "HTTP {} redirect from {} is not followed", response.status(), self.url
+            ))));
+        }
+        if response.status() != 206 {
+            return Err(Failure::Fatal(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} does not support range requests (HTTP {})", self.url, response.status()),
+            )));
+        }
+
+        let len = response.header("Content-Range")
+            .and_then(|range| range.rsplit('/').next())
+            .and_then(|total| total.trim().parse::<u64>().ok())
+            .ok_or_else(|| Failure::Fatal(http_error(format!("Missing Content-Range from {}", self.url))))?;
+
+        let expected = end.min(len).saturating_sub(start);
+        let mut data = Vec::with_capacity(expected as usize);
+        response.into_reader().take(expected).read_to_end(&mut data).map_err(Failure::Retry)?;
+        if data.len() as u64 != expected {
+            return Err(Failure::Retry(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("Short response from {}: {} of {} bytes", self.url, data.len(), expected),
+            )));
+        }
+        Ok((data, len))
+    }
+
+    /// Helper: Returns the blocks covering `offset..offset + length`
+    fn blocks_of(&self, offset: u64, length: u64) -> impl Iterator<Item = u64> {
+        let block_size = self.options.block_size;
+        let end = offset + length;
+        (offset / block_size..end.div_ceil(block_size)).filter(move |_| length > 0)
+    }
+}
+
+impl RangeSource for HttpSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(offset, buf.len() as u64, self.len)?;
+        let wanted: BTreeSet<u64> = self.blocks_of(offset, buf.len() as u64).collect();
+        let blocks = self.load_blocks(&wanted)?;
+
+        let block_size = self.options.block_size;
+        let mut filled = 0;
+        while filled < buf.len() {
+            let position = offset + filled as u64;
+            let block = &blocks[&(position / block_size)];
+            let start = (position % block_size) as usize;
+            let n = (block.len() - start).min(buf.len() - filled);
+            buf[filled..filled + n].copy_from_slice(&block[start..start + n]);
+            filled += n;
+        }
+        Ok(())
+    }
+
+    fn prefetch(&self, ranges: &[(u64, u64)]) -> Result<()> {
+        let mut wanted = BTreeSet::new();
+        for &(offset, length) in ranges {
+            check_range(offset, length, self.len)?;
+            wanted.extend(self.blocks_of(offset, length));
+            // Blocks beyond the cache capacity would evict each other
+            if wanted.len() >= self.options.cache_blocks {
+                break;
+            }
+        }
+        self.load_blocks(&wanted).map(|_| ())
+    }
+}
+
+/// Helper: Returns the sleep before retry `attempt`, counted from zero
+fn backoff(retry_delay: Duration, attempt: u32) -> Duration {
+    retry_delay.saturating_mul(2u32.saturating_pow(attempt.min(31))).min(MAX_RETRY_DELAY)
+}
+
+/// Helper: Wraps an HTTP failure as an I/O error
+fn http_error(message: String) -> io::Error {
+    io::Error::other(message)
+}
+
+#[cfg(test)]
+pub(crate) mod test_server {
+    //! Minimal HTTP/1.1 server answering range GETs, for source tests
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Request log of a running server
+    pub struct Served {
+        pub url: String,
+        /// Requests answered, including failures
+        pub requests: Arc<AtomicUsize>,
+        /// Header lines of every request
+        pub headers: Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+    }
+
+    /// Serves `data` under any path; the first `failures` requests get a 503
+    pub fn serve(data: Vec<u8>, failures: usize) -> Served {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data.tif", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let headers = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let data = Arc::new(data);
+
+        let (counter, log) = (requests.clone(), headers.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let (data, counter, log) = (data.clone(), counter.clone(), log.clone());
+                std::thread::spawn(move || {
+                    let mut lines = Vec::new();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                            break;
+                        }
+                        lines.push(line.trim_end().to_string());
+                    }
+                    let range = lines.iter()
+                        .find_map(|l| l.to_ascii_lowercase().strip_prefix("range: bytes=").map(str::to_string));
+                    log.lock().unwrap().push(lines);
+
+                    if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                        let _ = stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                        return;
+                    }
+                    let (start, end) = range
+                        .and_then(|r| {
+                            let (a, b) = r.split_once('-')?;
+                            Some((a.parse::<usize>().ok()?, b.parse::<usize>().ok()?))
+                        })
+                        .unwrap_or((0, data.len() - 1));
+                    let end = end.min(data.len() - 1);
+                    let head = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                        end + 1 - start, start, end, data.len()
+                    );
+                    let _ = stream.write_all(head.as_bytes());
+                    let _ = stream.write_all(&data[start..=end]);
+                });
+            }
+        });
+        Served { url, requests, headers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_server::serve;
+    use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+    use crate::formats::tiff::TiffReader;
+
+    fn small_blocks() -> HttpOptions {
+        HttpOptions {
+            block_size: 256,
+            cache_blocks: 128,
+            header_bytes: 512,
+            max_gap: 512,
+            retry_delay: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_block_cache_and_merging() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let served = serve(data.clone(), 0);
+        let source = HttpSource::with_options(&served.url, small_blocks()).unwrap();
+        assert_eq!(source.len(), 10_000);
+        assert_eq!(source.requests(), 1);
+        assert!(served.headers.lock().unwrap()[0].iter().any(|h| h.eq_ignore_ascii_case("range: bytes=0-511")));
+
+        // Inside the header blocks: no request
+        let mut buf = [0u8; 100];
+        source.read_at(300, &mut buf).unwrap();
+        assert_eq!(&buf[..], &data[300..400]);
+        assert_eq!(source.requests(), 1);
+
+        // Spanning two uncached blocks: one request, then cached
+        source.read_at(5_100, &mut buf).unwrap();
+        source.read_at(5_110, &mut buf).unwrap();
+        assert_eq!(&buf[..], &data[5_110..5_210]);
+        assert_eq!(source.requests(), 2);
+
+        // Ranges within the merge gap share a request, distant ones do not
+        source.prefetch(&[(7_000, 10), (7_600, 10), (9_990, 10)]).unwrap();
+        assert_eq!(source.requests(), 4);
+        source.read_at(9_990, &mut buf[..10]).unwrap();
+        assert_eq!(&buf[..10], &data[9_990..]);
+        assert_eq!(source.requests(), 4);
+
+        assert!(source.read_at(9_995, &mut buf[..10]).is_err());
+    }
+
+    #[test]
+    fn test_retries() {
+        let data = vec![7u8; 1_000];
+        let served = serve(data.clone(), 2);
+        let source = HttpSource::with_options(&served.url, small_blocks()).unwrap();
+        assert_eq!(source.requests(), 3);
+        assert_eq!(served.requests.load(Ordering::SeqCst), 3);
+        assert_eq!(source.read_range(900, 100).unwrap(), vec![7u8; 100]);
+
+        let served = serve(data, 2);
+        let options = HttpOptions { retries: 1, ..small_blocks() };
+        assert!(HttpSource::with_options(&served.url, options).is_err());
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let delay = Duration::from_millis(200);
+        assert_eq!(backoff(delay, 0), delay);
+        assert_eq!(backoff(delay, 3), Duration::from_millis(1_600));
+        assert_eq!(backoff(delay, 5), MAX_RETRY_DELAY);
+        assert_eq!(backoff(delay, 100), MAX_RETRY_DELAY);
+        assert_eq!(backoff(Duration::MAX, 1), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_redirects_are_not_followed() {
+        use std::io::{BufRead, BufReader, Write};
+
+        let served = serve(vec![7u8; 1_000], 0);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data.tif", listener.local_addr().unwrap());
+        let target = served.url.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let head = format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", target);
+                let _ = stream.write_all(head.as_bytes());
+            }
+        });
+
+        let error = HttpSource::with_options(&url, small_blocks()).err().unwrap();
+        assert!(error.to_string().contains("redirect"), "{}", error);
+        assert_eq!(served.requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_tiff_over_http() {
+        // 256 tiles of 256 bytes stored row by row, tag arrays and IFD at the end
+        let file = write_geotiff(&FixtureSpec::new(256, 256), |x, y| ((x * 3 + y) % 256) as f64);
+        let data = std::fs::read(file.path()).unwrap();
+        let served = serve(data, 0);
+        let source = Arc::new(HttpSource::with_options(&served.url, small_blocks()).unwrap());
+
+        let mut reader = TiffReader::from_source(source.clone(), 0).unwrap();
+        let ifd = reader.read().unwrap().main_ifd().unwrap().clone();
+        let coords: Vec<(u64, u64)> = (0..16).map(|i| (i * 16 + 3, 133)).collect();
+        let before = source.requests();
+        let values = reader.read_pixels_batch(&ifd, &coords).unwrap();
+
+        let expected: Vec<u8> = coords.iter().map(|&(x, y)| ((x * 3 + y) % 256) as u8).collect();
+        assert_eq!(values, expected);
+        // One block each of the offset and count arrays, then the sixteen
+        // adjacent tiles of tile row 8 in a single merged request
+        assert_eq!(source.requests() - before, 3);
+    }
+}
@@ -5,7 +5,20 @@
 pub mod traits;
 pub mod byte_order;
 pub mod buffer;
+pub mod source;
+pub mod http;
+pub mod s3;
+pub mod archive;
+pub mod async_source;
 
 pub use traits::SeekableReader;
 pub use byte_order::ByteOrder;
 pub use buffer::BufferedReader;
+pub use source::{is_remote_path, open_path, FileSource, MemorySource, MmapSource, RangeSource, SourceReader};
+pub use http::{HttpOptions, HttpSource};
+pub use s3::{S3Config, S3Credentials, S3Location, S3Source};
+pub use archive::{
+    default_max_inflated_size, inflate_gzip, inflate_gzip_cached, SubrangeSource, ZipArchive, ZipEntry,
+    INFLATED_CACHE_BYTES, MAX_INFLATED_ENV, MAX_INFLATED_SIZE,
+};
+pub use async_source::{AsyncRangeSource, BlockingSource, ReadFuture};
@@ -0,0 +1,421 @@
+//! Positioned byte-range sources
+//!
+//! [`RangeSource`] abstracts where raster bytes come from: local files,
+//! memory maps, in-memory buffers or remote objects. Reads take `&self`, so
+//! one source can serve parallel tile loads. [`SourceReader`] adapts a
+//! source to [`Read`] + [`Seek`] for the sequential header and tag parsers.
+
+use std::fs::File;
+use std::io::{self, Read, Result, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+use bytes::Bytes;
+use memmap2::Mmap;
+use super::archive::{default_max_inflated_size, inflate_gzip_cached, split_archive_path, ZipArchive};
+use super::http::HttpSource;
+use super::s3::S3Source;
+
+/// Random-access byte source
+pub trait RangeSource: Send + Sync {
+    /// Returns the total length in bytes
+    fn len(&self) -> u64;
+
+    /// Returns whether the source holds no bytes
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fills `buf` with the bytes starting at `offset`
+    ///
+    /// Fails with `UnexpectedEof` if the range extends past the end.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()>;
+
+    /// Borrows a range without copying, for sources held in memory
+    fn slice(&self, _offset: u64, _length: u64) -> Option<&[u8]> {
+        None
+    }
+
+    /// Hints that the given (offset, length) ranges are about to be read
+    ///
+    /// Remote sources use this to fetch them with fewer, merged requests.
+    fn prefetch(&self, _ranges: &[(u64, u64)]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Reads a range into a new buffer
+    ///
+    /// The range is checked against [`len`](Self::len) before anything is
+    /// allocated, so lengths taken from a corrupt file fail cleanly.
+    fn read_range(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        check_range(offset, length, self.len())?;
+        if let Some(bytes) = self.slice(offset, length) {
+            return Ok(bytes.to_vec());
+        }
+        let mut buf = vec![0u8; length as usize];
+        self.read_at(offset, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<S: RangeSource + ?Sized> RangeSource for Arc<S> {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        (**self).read_at(offset, buf)
+    }
+
+    fn slice(&self, offset: u64, length: u64) -> Option<&[u8]> {
+        (**self).slice(offset, length)
+    }
+
+    fn prefetch(&self, ranges: &[(u64, u64)]) -> Result<()> {
+        (**self).prefetch(ranges)
+    }
+
+    fn read_range(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        (**self).read_range(offset, length)
+    }
+}
+
+impl<S: RangeSource + ?Sized> RangeSource for Box<S> {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        (**self).read_at(offset, buf)
+    }
+
+    fn slice(&self, offset: u64, length: u64) -> Option<&[u8]> {
+        (**self).slice(offset, length)
+    }
+
+    fn prefetch(&self, ranges: &[(u64, u64)]) -> Result<()> {
+        (**self).prefetch(ranges)
+    }
+
+    fn read_range(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        (**self).read_range(offset, length)
+    }
+}
+
+/// Local file read with positioned reads (pread), without a shared cursor
+pub struct FileSource {
+    file: File,
+    len: u64,
+}
+
+impl FileSource {
+    /// Opens a file for positioned reads
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(File::open(path)?)
+    }
+
+    /// Wraps an open file
+    pub fn new(file: File) -> Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self { file, len })
+    }
+}
+
+impl RangeSource for FileSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        check_range(offset, buf.len() as u64, self.len)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileExt;
+            self.file.read_exact_at(buf, offset)
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::FileExt;
+            let mut filled = 0;
+            while filled < buf.len() {
+                match self.file.seek_read(&mut buf[filled..], offset + filled as u64)? {
+                    0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    n => filled += n,
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Memory-mapped file; ranges are borrowed from the map
+pub struct MmapSource {
+    mmap: Mmap,
+}
+
+impl MmapSource {
+    /// Maps a file read-only, advising the kernel of sequential access
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        #[cfg(unix)]
+        unsafe {
+            libc::madvise(
+                mmap.as_ptr() as *mut libc::c_void,
+                mmap.len(),
+                libc::MADV_SEQUENTIAL | libc::MADV_WILLNEED,
+            );
+        }
+
+        Ok(Self { mmap })
+    }
+}
+
+impl RangeSource for MmapSource {
+    fn len(&self) -> u64 {
+        self.mmap.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        read_from_slice(&self.mmap, offset, buf)
+    }
+
+    fn slice(&self, offset: u64, length: u64) -> Option<&[u8]> {
+        slice_of(&self.mmap, offset, length)
+    }
+}
+
+/// Bytes held in memory; ranges are borrowed from the buffer
+///
+/// Construction never copies: `Vec<u8>`, [`Bytes`] and `Arc<[u8]>` buffers
+/// are all adopted as they are.
+#[derive(Clone)]
+pub struct MemorySource {
+    data: Bytes,
+}
+
+impl MemorySource {
+    pub fn new(data: impl Into<Bytes>) -> Self {
+        Self { data: data.into() }
+    }
+
+    /// Returns the whole buffer
+    pub fn bytes(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+impl From<Vec<u8>> for MemorySource {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<Bytes> for MemorySource {
+    fn from(data: Bytes) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<Arc<[u8]>> for MemorySource {
+    fn from(data: Arc<[u8]>) -> Self {
+        Self::new(Bytes::from_owner(data))
+    }
+}
+
+impl RangeSource for MemorySource {
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        read_from_slice(&self.data, offset, buf)
+    }
+
+    fn slice(&self, offset: u64, length: u64) -> Option<&[u8]> {
+        slice_of(&self.data, offset, length)
+    }
+}
+
+/// Opens the source a path names: `http://`, `https://` and `s3://` URLs
+/// are read with range requests, anything else as a local file
+///
+/// `archive.zip!/member` opens a member of a ZIP archive at any of those
+/// locations, and a `.gz` path is inflated into memory up to
+/// [`default_max_inflated_size`] bytes. Inflated data is cached, so opening
+/// the same member or file again reuses it.
+pub fn open_path<P: AsRef<Path>>(path: P, use_mmap: bool) -> Result<Arc<dyn RangeSource>> {
+    let path = path.as_ref();
+    if let Some(name) = path.to_str() {
+        if let Some((archive, member)) = split_archive_path(name) {
+            return ZipArchive::open(open_location(Path::new(archive), use_mmap)?)?
+                .with_shared_cache(archive)
+                .open_member(member);
+        }
+        if name.to_ascii_lowercase().ends_with(".gz") {
+            return Ok(inflate_gzip_cached(&*open_location(path, use_mmap)?, name, default_max_inflated_size())?);
+        }
+    }
+    open_location(path, use_mmap)
+}
+
+/// Returns whether [`open_path`] reads a path remotely (`http://`, `https://` or `s3://`)
+pub fn is_remote_path(path: &str) -> bool {
+    ["http://", "https://", "s3://"].iter().any(|scheme| path.starts_with(scheme))
+}
+
+/// Helper: Opens a URL or local file as stored, without unpacking archives
+fn open_location(path: &Path, use_mmap: bool) -> Result<Arc<dyn RangeSource>> {
+    if let Some(url) = path.to_str().filter(|url| is_remote_path(url)) {
+        if url.starts_with("s3://") {
+            return Ok(Arc::new(S3Source::open(url)?));
+        }
+        return Ok(Arc::new(HttpSource::open(url)?));
+    }
+    if use_mmap {
+        Ok(Arc::new(MmapSource::open(path)?))
+    } else {
+        Ok(Arc::new(FileSource::open(path)?))
+    }
+}
+
+/// Sequential [`Read`] + [`Seek`] view of a shared source
+pub struct SourceReader {
+    source: Arc<dyn RangeSource>,
+    position: u64,
+}
+
+impl SourceReader {
+    pub fn new(source: Arc<dyn RangeSource>) -> Self {
+        Self { source, position: 0 }
+    }
+
+    /// Returns the underlying source
+    pub fn source(&self) -> &Arc<dyn RangeSource> {
+        &self.source
+    }
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let remaining = self.source.len().saturating_sub(self.position);
+        let n = (buf.len() as u64).min(remaining) as usize;
+        if n == 0 {
+            return Ok(0);
+        }
+        self.source.read_at(self.position, &mut buf[..n])?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for SourceReader {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.source.len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Seek before start of source")
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// Helper: Fails with `UnexpectedEof`, wrapping [`Error::OutOfBounds`](crate::Error::OutOfBounds),
+/// if a range extends past `len`
+pub(crate) fn check_range(offset: u64, length: u64, len: u64) -> Result<()> {
+    match offset.checked_add(length) {
+        Some(end) if end <= len => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            crate::Error::OutOfBounds(format!("Range {}+{} exceeds source length {}", offset, length, len)),
+        )),
+    }
+}
+
+/// Helper: Borrows a range of a slice, `None` if out of bounds
+fn slice_of(data: &[u8], offset: u64, length: u64) -> Option<&[u8]> {
+    let end = offset.checked_add(length)?;
+    data.get(offset as usize..end as usize)
+}
+
+/// Helper: Copies a range of a slice into `buf`
+fn read_from_slice(data: &[u8], offset: u64, buf: &mut [u8]) -> Result<()> {
+    check_range(offset, buf.len() as u64, data.len() as u64)?;
+    let start = offset as usize;
+    buf.copy_from_slice(&data[start..start + buf.len()]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_memory_source() {
+        let source = MemorySource::new((0u8..10).collect::<Vec<_>>());
+        assert_eq!(source.len(), 10);
+
+        let mut buf = [0u8; 3];
+        source.read_at(4, &mut buf).unwrap();
+        assert_eq!(buf, [4, 5, 6]);
+        assert_eq!(source.slice(8, 2), Some(&[8u8, 9][..]));
+        assert_eq!(source.slice(8, 3), None);
+
+        let error = source.read_at(8, &mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        // Shared buffers are borrowed, not copied
+        let shared: Arc<[u8]> = Arc::from(&[1u8, 2, 3][..]);
+        let source = MemorySource::from(shared.clone());
+        assert_eq!(source.slice(0, 3).unwrap().as_ptr(), shared.as_ptr());
+    }
+
+    #[test]
+    fn test_file_and_mmap_sources_agree() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&(0u8..=255).collect::<Vec<_>>()).unwrap();
+        file.flush().unwrap();
+
+        let pread = FileSource::open(file.path()).unwrap();
+        let mapped = MmapSource::open(file.path()).unwrap();
+        assert_eq!(pread.len(), 256);
+        assert!(pread.slice(0, 4).is_none());
+        assert_eq!(pread.read_range(100, 5).unwrap(), mapped.read_range(100, 5).unwrap());
+        assert_eq!(mapped.slice(254, 2), Some(&[254u8, 255][..]));
+        assert!(pread.read_range(250, 10).is_err());
+    }
+
+    #[test]
+    fn test_read_range_rejects_huge_lengths() {
+        let source = MemorySource::new(vec![0u8; 16]);
+        for length in [u64::MAX, 17] {
+            let error = source.read_range(0, length).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+            let inner = error.get_ref().and_then(|e| e.downcast_ref::<crate::Error>());
+            assert!(matches!(inner, Some(crate::Error::OutOfBounds(_))));
+        }
+        assert!(source.read_range(u64::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn test_source_reader() {
+        let source: Arc<dyn RangeSource> = Arc::new(MemorySource::new((0u8..10).collect::<Vec<_>>()));
+        let mut reader = SourceReader::new(source);
+
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2, 3]);
+
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, vec![8, 9]);
+        assert!(reader.seek(SeekFrom::Current(-20)).is_err());
+    }
+}
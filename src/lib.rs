@@ -57,13 +57,20 @@ pub mod cache;
 pub mod cache_prefetch;
 pub mod cache_prefetch_async;
 pub mod projection;
+pub mod catalog;
+pub mod mosaic;
+pub mod vector;
+pub mod analysis;
 pub mod api;
 
 pub use error::{Error, Result};
-pub use types::{DataType, Dimensions};
+pub use types::{DataType, Dimensions, Pixel};
 pub use formats::tiff::{
-    Tiff, TiffReader, IFD, IFDEntry, GeoInfo,
+    Tiff, TiffReader, AsyncTiffReader, SharedTiffReader, IFD, IFDEntry, GeoInfo,
     tags, TIFF_MAGIC, BIGTIFF_MAGIC
 };
 pub use io::{ByteOrder, BufferedReader, SeekableReader};
 pub use projection::{Coordinate, Transformer, Datum, DatumTransform, GridShift, CustomProjection};
+pub use catalog::{Catalog, CatalogEntry, Envelope};
+pub use mosaic::{Mosaic, MosaicSource, MosaicWindow};
+pub use vector::{Feature, Geometry, Polygon};
@@ -0,0 +1,297 @@
+//! Persistable mosaic descriptions (JSON and a small XML dialect)
+//!
+//! The XML dialect is specific to this crate, not GDAL VRT, and is saved
+//! with a `.xml` or `.mosaic` extension. XML descriptions look like:
+//!
+//! ```xml
+//! <Mosaic epsg="3857" nodata="0">
+//!   <Source path="tiles/a.tif" priority="1" bounds="0,0,1000,1000"/>
+//! </Mosaic>
+//! ```
+
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::formats::tiff::metadata::{escape_xml, unescape_xml};
+
+/// Serializable description of a mosaic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MosaicDescription {
+    /// CRS shared by all sources
+    #[serde(default)]
+    pub epsg: Option<u16>,
+    /// NoData value reported for uncovered locations
+    #[serde(default, with = "nodata_json")]
+    pub nodata: Option<f64>,
+    /// Participating GeoTIFFs
+    pub sources: Vec<SourceDescription>,
+}
+
+/// Serializable description of one mosaic source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceDescription {
+    /// Path to the GeoTIFF, relative paths resolve against the description file
+    pub path: String,
+    /// Overlap priority (higher wins)
+    #[serde(default)]
+    pub priority: i32,
+    /// Cached footprint (min_x, min_y, max_x, max_y); computed on load if absent
+    #[serde(default)]
+    pub bounds: Option<[f64; 4]>,
+}
+
+impl MosaicDescription {
+    /// Parses a description, detecting JSON or XML from the first character
+    pub fn parse(text: &str) -> Result<Self> {
+        if text.trim_start().starts_with('<') {
+            Self::from_xml(text)
+        } else {
+            Self::from_json(text)
+        }
+    }
+
+    /// Parses a JSON description
+    pub fn from_json(text: &str) -> Result<Self> {
+        serde_json::from_str(text)
+            .map_err(|e| Error::InvalidFormat(format!("Invalid mosaic JSON: {}", e)))
+    }
+
+    /// Serializes to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| Error::InvalidFormat(format!("Failed to serialize mosaic: {}", e)))
+    }
+
+    /// Serializes to the XML dialect
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<Mosaic");
+        if let Some(epsg) = self.epsg {
+            xml.push_str(&format!(" epsg=\"{}\"", epsg));
+        }
+        if let Some(nodata) = self.nodata {
+            xml.push_str(&format!(" nodata=\"{}\"", nodata));
+        }
+        xml.push_str(">\n");
+
+        for source in &self.sources {
+            xml.push_str(&format!(
+                "  <Source path=\"{}\" priority=\"{}\"",
+                escape_xml(&source.path), source.priority
+            ));
+            if let Some(b) = source.bounds {
+                xml.push_str(&format!(" bounds=\"{},{},{},{}\"", b[0], b[1], b[2], b[3]));
+            }
+            xml.push_str("/>\n");
+        }
+
+        xml.push_str("</Mosaic>\n");
+        xml
+    }
+
+    /// Parses the XML dialect
+    pub fn from_xml(text: &str) -> Result<Self> {
+        let mut description = MosaicDescription {
+            epsg: None,
+            nodata: None,
+            sources: Vec::new(),
+        };
+        let mut saw_root = false;
+
+        for element in elements(text) {
+            let (name, attrs) = parse_element(element)?;
+            match name {
+                "Mosaic" => {
+                    saw_root = true;
+                    for (key, value) in attrs {
+                        match key {
+                            "epsg" => description.epsg = Some(parse_attr(key, &value)?),
+                            "nodata" => description.nodata = Some(parse_attr(key, &value)?),
+                            _ => {}
+                        }
+                    }
+                }
+                "Source" => {
+                    let mut source = SourceDescription {
+                        path: String::new(),
+                        priority: 0,
+                        bounds: None,
+                    };
+                    for (key, value) in attrs {
+                        match key {
+                            "path" => source.path = value,
+                            "priority" => source.priority = parse_attr(key, &value)?,
+                            "bounds" => source.bounds = Some(parse_bounds(&value)?),
+                            _ => {}
+                        }
+                    }
+                    if source.path.is_empty() {
+                        return Err(Error::InvalidFormat("Mosaic source without path".to_string()));
+                    }
+                    description.sources.push(source);
+                }
+                _ => {}
+            }
+        }
+
+        if !saw_root {
+            return Err(Error::InvalidFormat("Missing <Mosaic> root element".to_string()));
+        }
+
+        Ok(description)
+    }
+}
+
+/// NoData in JSON: finite values as numbers, NaN and infinities as strings
+/// (JSON numbers cannot hold them)
+mod nodata_json {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(nodata: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match nodata {
+            Some(value) if !value.is_finite() => serializer.serialize_str(&value.to_string()),
+            Some(value) => serializer.serialize_f64(*value),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+        match Option::<Value>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(Value::Number(value)) => Ok(Some(value)),
+            Some(Value::Text(text)) => text.trim().parse().map(Some)
+                .map_err(|_| serde::de::Error::custom(format!("invalid nodata: {}", text))),
+        }
+    }
+}
+
+/// Helper: Yields the inner text of every opening or self-closing tag
+fn elements(text: &str) -> impl Iterator<Item = &str> {
+    text.split('<')
+        .skip(1)
+        .filter_map(|chunk| chunk.split_once('>').map(|(tag, _)| tag))
+        .filter(|tag| !tag.starts_with('/') && !tag.starts_with('?') && !tag.starts_with('!'))
+}
+
+/// Helper: Splits a tag into its name and unescaped attributes
+fn parse_element(tag: &str) -> Result<(&str, Vec<(&str, String)>)> {
+    let tag = tag.trim_end_matches('/').trim();
+    let (name, mut rest) = match tag.find(char::is_whitespace) {
+        Some(pos) => (&tag[..pos], &tag[pos..]),
+        None => (tag, ""),
+    };
+
+    let mut attrs = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        let eq = rest.find('=')
+            .ok_or_else(|| Error::InvalidFormat(format!("Malformed attribute in <{}>", name)))?;
+        let key = rest[..eq].trim();
+        let after = rest[eq + 1..].trim_start();
+        let quote = after.chars().next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| Error::InvalidFormat(format!("Unquoted attribute {} in <{}>", key, name)))?;
+        let end = after[1..].find(quote)
+            .ok_or_else(|| Error::InvalidFormat(format!("Unterminated attribute {} in <{}>", key, name)))?;
+
+        attrs.push((key, unescape_xml(&after[1..1 + end])));
+        rest = &after[end + 2..];
+    }
+
+    Ok((name, attrs))
+}
+
+fn parse_attr<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value.trim().parse()
+        .map_err(|_| Error::InvalidFormat(format!("Invalid value for {}: {}", key, value)))
+}
+
+fn parse_bounds(value: &str) -> Result<[f64; 4]> {
+    let parts: Vec<f64> = value.split(',')
+        .map(|p| parse_attr("bounds", p))
+        .collect::<Result<_>>()?;
+
+    match parts.as_slice() {
+        &[a, b, c, d] => Ok([a, b, c, d]),
+        _ => Err(Error::InvalidFormat(format!("Bounds need 4 values: {}", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> MosaicDescription {
+        MosaicDescription {
+            epsg: Some(3857),
+            nodata: Some(-9999.0),
+            sources: vec![
+                SourceDescription {
+                    path: "tiles/a&b.tif".to_string(),
+                    priority: 2,
+                    bounds: Some([0.0, 0.0, 10.5, 20.0]),
+                },
+                SourceDescription {
+                    path: "tiles/c.tif".to_string(),
+                    priority: 0,
+                    bounds: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let description = sample();
+        let xml = description.to_xml();
+        assert!(xml.contains("a&amp;b.tif"));
+        assert_eq!(MosaicDescription::parse(&xml).unwrap(), description);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let description = sample();
+        let json = description.to_json().unwrap();
+        assert_eq!(MosaicDescription::parse(&json).unwrap(), description);
+    }
+
+    #[test]
+    fn test_non_finite_nodata_round_trip() {
+        for nodata in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let description = MosaicDescription { nodata: Some(nodata), ..sample() };
+            let json = description.to_json().unwrap();
+            let xml = description.to_xml();
+            for text in [json, xml] {
+                let parsed = MosaicDescription::parse(&text).unwrap();
+                let value = parsed.nodata.unwrap();
+                assert!(value.is_nan() && nodata.is_nan() || value == nodata, "{}", text);
+                assert_eq!(parsed.sources, description.sources);
+            }
+        }
+        assert!(MosaicDescription::from_json(r#"{"nodata": "none", "sources": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_json_defaults() {
+        let description = MosaicDescription::from_json(r#"{"sources": [{"path": "a.tif"}]}"#).unwrap();
+        assert_eq!(description.epsg, None);
+        assert_eq!(description.sources[0].priority, 0);
+        assert_eq!(description.sources[0].bounds, None);
+    }
+
+    #[test]
+    fn test_xml_errors() {
+        assert!(MosaicDescription::from_xml("<Other/>").is_err());
+        assert!(MosaicDescription::from_xml("<Mosaic><Source priority=\"1\"/></Mosaic>").is_err());
+        assert!(MosaicDescription::from_xml("<Mosaic><Source path=\"a\" bounds=\"1,2\"/></Mosaic>").is_err());
+    }
+}
@@ -0,0 +1,572 @@
+//! Virtual mosaics over many GeoTIFFs
+//!
+//! A [`Mosaic`] indexes a collection of GeoTIFFs by their bounding boxes and
+//! routes point, window and batch reads to the source that covers each
+//! location. Overlaps are resolved by priority: the highest priority source
+//! with a valid (non-NoData) value wins, and among equal priorities the
+//! source added last wins, matching the painter's order of GDAL VRTs.
+
+pub mod description;
+
+pub use description::{MosaicDescription, SourceDescription};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::catalog::{Catalog, Envelope, RTree};
+use crate::error::{Error, Result};
+use crate::formats::tiff::{GeoInfo, TiffReader, IFD};
+use crate::io::is_remote_path;
+use crate::projection::{Coordinate, Transformer};
+
+/// Extension of XML mosaic descriptions
+pub const MOSAIC_EXTENSION: &str = ".mosaic";
+
+/// A single GeoTIFF participating in a mosaic
+#[derive(Debug, Clone)]
+pub struct MosaicSource {
+    /// Path to the GeoTIFF
+    pub path: PathBuf,
+    /// Overlap priority (higher wins)
+    pub priority: i32,
+    /// Footprint as (min_x, min_y, max_x, max_y) in the mosaic CRS
+    pub bounds: (f64, f64, f64, f64),
+}
+
+impl MosaicSource {
+    /// Returns whether the footprint contains a coordinate
+    ///
+    /// The left/top edges are inclusive and the right/bottom edges exclusive,
+    /// so adjacent tiles never both claim a shared edge.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.bounds;
+        x >= min_x && x < max_x && y > min_y && y <= max_y
+    }
+}
+
+/// An opened source with its parsed main IFD and georeferencing
+struct OpenSource {
+    reader: TiffReader,
+    ifd: IFD,
+    geo_info: GeoInfo,
+}
+
+/// A pixel-aligned window over a mosaic
+#[derive(Debug, Clone, Copy)]
+pub struct MosaicWindow {
+    /// Upper-left corner X in the mosaic CRS
+    pub origin_x: f64,
+    /// Upper-left corner Y in the mosaic CRS
+    pub origin_y: f64,
+    /// Pixel size (x, y), both positive
+    pub pixel_size: (f64, f64),
+    /// Width in pixels
+    pub width: usize,
+    /// Height in pixels
+    pub height: usize,
+}
+
+/// Virtual raster composed of many GeoTIFFs
+pub struct Mosaic {
+    sources: Vec<MosaicSource>,
+    epsg: Option<u16>,
+    nodata: Option<f64>,
+    index: RTree,
+    open_sources: HashMap<usize, OpenSource>,
+}
+
+impl Mosaic {
+    /// Creates an empty mosaic
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            epsg: None,
+            nodata: None,
+            index: RTree::new(),
+            open_sources: HashMap::new(),
+        }
+    }
+
+    /// Returns whether a path looks like a mosaic description rather than a GeoTIFF
+    ///
+    /// `.vrt` is not accepted: descriptions are not GDAL VRT files.
+    pub fn is_description_path(path: &str) -> bool {
+        let lower = path.to_ascii_lowercase();
+        lower.ends_with(".json") || lower.ends_with(".xml") || lower.ends_with(MOSAIC_EXTENSION)
+    }
+
+    /// Opens a mosaic from a JSON or XML description file
+    ///
+    /// Relative source paths are resolved against the directory of the
+    /// description; URLs are kept as they are.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let description = MosaicDescription::parse(&text)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::from_description(&description, base_dir)
+    }
+
+    /// Builds a mosaic from a parsed description
+    ///
+    /// Sources that carry their bounds are indexed without being opened;
+    /// others are opened once to compute their footprint.
+    pub fn from_description(description: &MosaicDescription, base_dir: &Path) -> Result<Self> {
+        let mut mosaic = Self::new();
+        mosaic.epsg = description.epsg;
+        mosaic.nodata = description.nodata;
+
+        for source in &description.sources {
+            // URLs (including archives behind them) are used as given
+            let mut path = PathBuf::from(&source.path);
+            if path.is_relative() && !is_remote_path(&source.path) {
+                path = base_dir.join(path);
+            }
+
+            match source.bounds {
+                Some([min_x, min_y, max_x, max_y]) => {
+                    mosaic.push_source(MosaicSource {
+                        path,
+                        priority: source.priority,
+                        bounds: (min_x, min_y, max_x, max_y),
+                    });
+                }
+                None => {
+                    mosaic.add_source(path, source.priority)?;
+                }
+            }
+        }
+
+        Ok(mosaic)
+    }
+
+    /// Builds a mosaic from all catalog entries in one CRS, without opening them
+    ///
+    /// Priorities follow catalog order, so where entries overlap the one
+    /// added to the catalog last wins.
+    pub fn from_catalog(catalog: &Catalog, epsg: u16) -> Self {
+        let mut mosaic = Self::new();
+        mosaic.epsg = Some(epsg);
+
+        for id in catalog.query_envelope(&Envelope::new(f64::MIN, f64::MIN, f64::MAX, f64::MAX), Some(epsg)) {
+            let entry = &catalog.entries()[id];
+            if mosaic.nodata.is_none() {
+                mosaic.nodata = entry.nodata;
+            }
+            mosaic.push_source(MosaicSource {
+                path: entry.path.clone(),
+                priority: i32::try_from(id).unwrap_or(i32::MAX),
+                bounds: (entry.envelope.min_x, entry.envelope.min_y, entry.envelope.max_x, entry.envelope.max_y),
+            });
+        }
+
+        mosaic
+    }
+
+    /// Returns a serializable description of this mosaic
+    pub fn description(&self) -> MosaicDescription {
+        MosaicDescription {
+            epsg: self.epsg,
+            nodata: self.nodata,
+            sources: self.sources.iter().map(|s| SourceDescription {
+                path: s.path.to_string_lossy().into_owned(),
+                priority: s.priority,
+                bounds: Some([s.bounds.0, s.bounds.1, s.bounds.2, s.bounds.3]),
+            }).collect(),
+        }
+    }
+
+    /// Writes the description to a file, as XML for `.xml` and [`MOSAIC_EXTENSION`] paths and JSON otherwise
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let description = self.description();
+        let is_xml = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.eq_ignore_ascii_case("xml") || e.eq_ignore_ascii_case(&MOSAIC_EXTENSION[1..]))
+            .unwrap_or(false);
+
+        let text = if is_xml { description.to_xml() } else { description.to_json()? };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Adds a GeoTIFF to the mosaic and returns its source index
+    ///
+    /// The first source fixes the mosaic CRS; later sources must share it.
+    pub fn add_source<P: AsRef<Path>>(&mut self, path: P, priority: i32) -> Result<usize> {
+        let path = path.as_ref().to_path_buf();
+        let opened = Self::open_source(&path)?;
+
+        let dims = opened.ifd.dimensions()
+            .ok_or_else(|| Error::InvalidFormat("Missing dimensions".to_string()))?;
+        let bounds = opened.geo_info.bounding_box(dims.width, dims.height)
+            .ok_or_else(|| Error::InvalidFormat(format!("{} has no geotransform", path.display())))?;
+
+        match (self.epsg, opened.geo_info.epsg_code) {
+            (Some(expected), Some(found)) if expected != found => {
+                return Err(Error::InvalidFormat(format!(
+                    "{} uses EPSG:{} but the mosaic uses EPSG:{}",
+                    path.display(), found, expected
+                )));
+            }
+            (None, found) => self.epsg = found,
+            _ => {}
+        }
+
+        if self.nodata.is_none() {
+            self.nodata = opened.geo_info.nodata;
+        }
+
+        let index = self.push_source(MosaicSource { path, priority, bounds });
+        self.open_sources.insert(index, opened);
+        Ok(index)
+    }
+
+    /// Helper: Registers a source in the list and the spatial index
+    fn push_source(&mut self, source: MosaicSource) -> usize {
+        let index = self.sources.len();
+        self.index.insert(Envelope::from_bounds(source.bounds), index);
+        self.sources.push(source);
+        index
+    }
+
+    /// Returns all sources
+    pub fn sources(&self) -> &[MosaicSource] {
+        &self.sources
+    }
+
+    /// Returns the mosaic CRS as an EPSG code
+    pub fn epsg(&self) -> Option<u16> {
+        self.epsg
+    }
+
+    /// Returns the NoData value reported for uncovered locations
+    pub fn nodata(&self) -> Option<f64> {
+        self.nodata
+    }
+
+    /// Returns the union of all source footprints
+    pub fn bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        self.sources.iter().map(|s| s.bounds).reduce(|a, b| {
+            (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+        })
+    }
+
+    /// Returns the indices of sources covering a coordinate, best candidate first
+    pub fn sources_at(&self, x: f64, y: f64) -> Vec<usize> {
+        let mut candidates: Vec<usize> = self.index.query_point(x, y)
+            .into_iter()
+            .filter(|&i| self.sources[i].contains(x, y))
+            .collect();
+
+        self.sort_by_priority(&mut candidates);
+        candidates
+    }
+
+    /// Helper: Orders candidates by descending priority, later sources first on ties
+    fn sort_by_priority(&self, candidates: &mut [usize]) {
+        candidates.sort_by(|&a, &b| {
+            self.sources[b].priority.cmp(&self.sources[a].priority).then(b.cmp(&a))
+        });
+    }
+
+    /// Reads the value at a coordinate in the mosaic CRS
+    ///
+    /// Returns `None` when no source covers the location with valid data.
+    pub fn read_value(&mut self, x: f64, y: f64) -> Result<Option<f64>> {
+        for index in self.sources_at(x, y) {
+            let source = self.ensure_open(index)?;
+            let pixel = match Self::pixel_in_source(source, x, y) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            let value = source.reader.read_pixel_as_f64(&source.ifd, pixel.0, pixel.1)?;
+            if !source.geo_info.is_nodata(value) {
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads values for many coordinates in the mosaic CRS
+    ///
+    /// Points are grouped by their best candidate source and read with one
+    /// tile-grouped batch per source. Points that hit NoData fall through to
+    /// their next candidate in a further round.
+    pub fn read_values_batch(&mut self, coords: &[Coordinate]) -> Result<Vec<Option<f64>>> {
+        let candidates: Vec<Vec<usize>> = coords.iter()
+            .map(|c| self.sources_at(c.x, c.y))
+            .collect();
+
+        let mut results = vec![None; coords.len()];
+        let mut pending: Vec<usize> = (0..coords.len()).filter(|&i| !candidates[i].is_empty()).collect();
+        let mut round = 0;
+
+        while !pending.is_empty() {
+            let mut by_source: HashMap<usize, Vec<usize>> = HashMap::new();
+            for &point in &pending {
+                if let Some(&source) = candidates[point].get(round) {
+                    by_source.entry(source).or_default().push(point);
+                }
+            }
+
+            if by_source.is_empty() {
+                break;
+            }
+
+            let mut next_pending = Vec::new();
+            for (source_index, points) in by_source {
+                let source = self.ensure_open(source_index)?;
+
+                let mut pixel_coords = Vec::with_capacity(points.len());
+                let mut pixel_points = Vec::with_capacity(points.len());
+                for &point in &points {
+                    match Self::pixel_in_source(source, coords[point].x, coords[point].y) {
+                        Some(pixel) => {
+                            pixel_coords.push(pixel);
+                            pixel_points.push(point);
+                        }
+                        None => next_pending.push(point),
+                    }
+                }
+
+                let values = source.reader.read_pixels_batch_f64(&source.ifd, &pixel_coords)?;
+                for (point, value) in pixel_points.into_iter().zip(values) {
+                    if source.geo_info.is_nodata(value) {
+                        next_pending.push(point);
+                    } else {
+                        results[point] = Some(value);
+                    }
+                }
+            }
+
+            pending = next_pending;
+            round += 1;
+        }
+
+        Ok(results)
+    }
+
+    /// Reads values for coordinates given in another CRS
+    pub fn read_values_batch_crs(&mut self, coords: &[Coordinate], source_epsg: u16) -> Result<Vec<Option<f64>>> {
+        let target_epsg = self.epsg
+            .ok_or_else(|| Error::Projection("No EPSG code available".to_string()))?;
+
+        if source_epsg == target_epsg {
+            return self.read_values_batch(coords);
+        }
+
+        let transformer = Transformer::new(source_epsg, target_epsg)?;
+        let projected = transformer.transform_many(coords)?;
+        self.read_values_batch(&projected)
+    }
+
+    /// Reads a window by sampling every pixel centre through the batch path
+    ///
+    /// Values are returned in row-major order; uncovered pixels are `None`.
+    pub fn read_window(&mut self, window: &MosaicWindow) -> Result<Vec<Option<f64>>> {
+        let mut centres = Vec::with_capacity(window.width * window.height);
+        for row in 0..window.height {
+            let y = window.origin_y - (row as f64 + 0.5) * window.pixel_size.1;
+            for col in 0..window.width {
+                let x = window.origin_x + (col as f64 + 0.5) * window.pixel_size.0;
+                centres.push(Coordinate::new(x, y));
+            }
+        }
+
+        self.read_values_batch(&centres)
+    }
+
+    /// Helper: Opens a source lazily and returns it
+    fn ensure_open(&mut self, index: usize) -> Result<&mut OpenSource> {
+        if !self.open_sources.contains_key(&index) {
+            let opened = Self::open_source(&self.sources[index].path)?;
+            self.open_sources.insert(index, opened);
+        }
+
+        Ok(self.open_sources.get_mut(&index).expect("source was just opened"))
+    }
+
+    /// Helper: Opens a GeoTIFF and parses its main IFD and georeferencing
+    fn open_source(path: &Path) -> Result<OpenSource> {
+        let mut reader = TiffReader::open(path)?;
+        let ifd = reader.ifd(0)?
+            .ok_or_else(|| Error::InvalidFormat("No main IFD found".to_string()))?;
+
+        let geo_info = GeoInfo::from_ifd(&ifd, &mut reader)?
+            .ok_or_else(|| Error::InvalidFormat(format!("{} is not a GeoTIFF", path.display())))?;
+
+        Ok(OpenSource { reader, ifd, geo_info })
+    }
+
+    /// Helper: Converts a coordinate to an in-bounds pixel of a source
+    fn pixel_in_source(source: &OpenSource, x: f64, y: f64) -> Option<(u64, u64)> {
+        let dims = source.ifd.dimensions()?;
+        let (px, py) = source.geo_info.geo_to_pixel(Coordinate::new(x, y))?;
+
+        if px < 0.0 || py < 0.0 || px >= dims.width as f64 || py >= dims.height as f64 {
+            return None;
+        }
+
+        Some((px as u64, py as u64))
+    }
+}
+
+impl Default for Mosaic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::tiff::test_support::{write_geotiff, FixtureSpec};
+
+    /// 32x32 tile of `value` whose first `nodata_cols` columns are NoData
+    fn tile_at(origin_x: f64, origin_y: f64, value: f64, nodata_cols: u64) -> tempfile::NamedTempFile {
+        let mut spec = FixtureSpec::new(32, 32);
+        spec.origin = (origin_x, origin_y);
+        spec.nodata = Some(0.0);
+        write_geotiff(&spec, move |x, _| if x < nodata_cols { 0.0 } else { value })
+    }
+
+    #[test]
+    fn test_routes_points_to_adjacent_tiles() {
+        let west = tile_at(0.0, 32.0, 10.0, 0);
+        let east = tile_at(32.0, 32.0, 20.0, 0);
+
+        let mut mosaic = Mosaic::new();
+        mosaic.add_source(west.path(), 0).unwrap();
+        mosaic.add_source(east.path(), 0).unwrap();
+
+        assert_eq!(mosaic.bounds(), Some((0.0, 0.0, 64.0, 32.0)));
+        assert_eq!(mosaic.epsg(), Some(3857));
+        assert_eq!(mosaic.read_value(10.5, 10.5).unwrap(), Some(10.0));
+        assert_eq!(mosaic.read_value(50.5, 10.5).unwrap(), Some(20.0));
+        assert_eq!(mosaic.read_value(100.0, 10.0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_priority_and_nodata_fallback() {
+        let low = tile_at(0.0, 32.0, 10.0, 0);
+        let high = tile_at(2.0, 32.0, 20.0, 4);
+
+        let mut mosaic = Mosaic::new();
+        mosaic.add_source(low.path(), 0).unwrap();
+        mosaic.add_source(high.path(), 5).unwrap();
+
+        assert_eq!(mosaic.sources_at(10.0, 10.0), vec![1, 0]);
+
+        // x = 3.5 is in the NoData strip of the high priority tile
+        let coords = vec![
+            Coordinate::new(10.5, 10.5),
+            Coordinate::new(3.5, 10.5),
+            Coordinate::new(-5.0, 10.5),
+        ];
+        let values = mosaic.read_values_batch(&coords).unwrap();
+        assert_eq!(values, vec![Some(20.0), Some(10.0), None]);
+        assert_eq!(mosaic.read_value(3.5, 10.5).unwrap(), Some(10.0));
+    }
+
+    #[test]
+    fn test_read_window_spans_sources() {
+        let west = tile_at(0.0, 32.0, 10.0, 0);
+        let east = tile_at(32.0, 32.0, 20.0, 0);
+
+        let mut mosaic = Mosaic::new();
+        mosaic.add_source(west.path(), 0).unwrap();
+        mosaic.add_source(east.path(), 0).unwrap();
+
+        let window = MosaicWindow {
+            origin_x: 30.0,
+            origin_y: 20.0,
+            pixel_size: (2.0, 2.0),
+            width: 2,
+            height: 1,
+        };
+        let values = mosaic.read_window(&window).unwrap();
+        assert_eq!(values, vec![Some(10.0), Some(20.0)]);
+    }
+
+    #[test]
+    fn test_description_round_trip() {
+        let west = tile_at(0.0, 32.0, 10.0, 0);
+        let east = tile_at(32.0, 32.0, 20.0, 0);
+
+        let mut mosaic = Mosaic::new();
+        mosaic.add_source(west.path(), 1).unwrap();
+        mosaic.add_source(east.path(), 2).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["mosaic.json", "mosaic.xml", "hazard.mosaic"] {
+            let path = dir.path().join(name);
+            mosaic.save(&path).unwrap();
+
+            let mut reopened = Mosaic::open(&path).unwrap();
+            assert_eq!(reopened.sources().len(), 2);
+            assert_eq!(reopened.sources()[1].priority, 2);
+            assert_eq!(reopened.epsg(), Some(3857));
+            assert_eq!(reopened.read_value(50.5, 10.5).unwrap(), Some(20.0));
+        }
+    }
+
+    #[test]
+    fn test_description_keeps_urls() {
+        let source = |path: &str| SourceDescription { path: path.to_string(), priority: 0, bounds: Some([0.0, 0.0, 1.0, 1.0]) };
+        let description = MosaicDescription {
+            epsg: Some(3857),
+            nodata: None,
+            sources: vec![
+                source("https://data.example.com/dem.tif"),
+                source("s3://rasters/tiles.zip!/dem.tif"),
+                source("tiles/dem.tif"),
+                source("tiles.zip!/dem.tif"),
+            ],
+        };
+
+        let mosaic = Mosaic::from_description(&description, Path::new("/srv/mosaics")).unwrap();
+        let paths: Vec<&Path> = mosaic.sources().iter().map(|s| s.path.as_path()).collect();
+        assert_eq!(paths, [
+            Path::new("https://data.example.com/dem.tif"),
+            Path::new("s3://rasters/tiles.zip!/dem.tif"),
+            Path::new("/srv/mosaics/tiles/dem.tif"),
+            Path::new("/srv/mosaics/tiles.zip!/dem.tif"),
+        ]);
+    }
+
+    #[test]
+    fn test_from_catalog() {
+        let west = tile_at(0.0, 32.0, 10.0, 0);
+        let east = tile_at(32.0, 32.0, 20.0, 0);
+
+        let mut catalog = Catalog::new();
+        catalog.add_file(west.path()).unwrap();
+        catalog.add_file(east.path()).unwrap();
+
+        let mut mosaic = Mosaic::from_catalog(&catalog, 3857);
+        assert_eq!(mosaic.sources().len(), 2);
+        assert_eq!(mosaic.nodata(), Some(0.0));
+        assert_eq!(mosaic.read_value(40.5, 1.5).unwrap(), Some(20.0));
+        assert!(Mosaic::from_catalog(&catalog, 4326).sources().is_empty());
+
+        // An overlapping entry added later takes precedence
+        let overlap = tile_at(16.0, 32.0, 30.0, 0);
+        catalog.add_file(overlap.path()).unwrap();
+        let mut mosaic = Mosaic::from_catalog(&catalog, 3857);
+        let priorities: Vec<i32> = mosaic.sources().iter().map(|s| s.priority).collect();
+        assert_eq!(priorities, vec![0, 1, 2]);
+        assert_eq!(mosaic.read_value(20.5, 1.5).unwrap(), Some(30.0));
+        assert_eq!(mosaic.read_value(40.5, 1.5).unwrap(), Some(30.0));
+        assert_eq!(mosaic.read_value(50.5, 1.5).unwrap(), Some(20.0));
+    }
+
+    #[test]
+    fn test_is_description_path() {
+        assert!(Mosaic::is_description_path("/data/hazard.json"));
+        assert!(Mosaic::is_description_path("hazard.Mosaic"));
+        assert!(!Mosaic::is_description_path("hazard.vrt"));
+        assert!(!Mosaic::is_description_path("hazard.tif"));
+    }
+}
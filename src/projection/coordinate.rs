@@ -27,3 +27,18 @@ impl Coordinate {
         Self::new_3d(lon, lat, alt)
     }
 }
+
+/// Mean Earth radius in metres used for great-circle distances
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+impl Coordinate {
+    /// Great-circle distance in metres between two longitude/latitude coordinates
+    pub fn haversine_distance(&self, other: &Coordinate) -> f64 {
+        let (lat1, lat2) = (self.y.to_radians(), other.y.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.x - self.x).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+}
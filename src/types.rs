@@ -32,6 +32,85 @@ impl DataType {
         }
     }
 
+    /// Maps a TIFF SampleFormat (1=unsigned, 2=signed, 3=float) and bit depth to a data type
+    pub fn from_tiff(sample_format: u64, bits: u64) -> Option<Self> {
+        match (sample_format, bits) {
+            (1, 8) => Some(DataType::U8),
+            (1, 16) => Some(DataType::U16),
+            (1, 32) => Some(DataType::U32),
+            (2, 8) => Some(DataType::I8),
+            (2, 16) => Some(DataType::I16),
+            (2, 32) => Some(DataType::I32),
+            (3, 32) => Some(DataType::F32),
+            (3, 64) => Some(DataType::F64),
+            _ => None,
+        }
+    }
+
+    /// Returns the TIFF (SampleFormat, BitsPerSample) pair for this data type
+    pub fn to_tiff(&self) -> (u16, u16) {
+        let format = match self {
+            DataType::U8 | DataType::U16 | DataType::U32 => 1,
+            DataType::I8 | DataType::I16 | DataType::I32 => 2,
+            DataType::F32 | DataType::F64 => 3,
+        };
+        (format, self.size() as u16 * 8)
+    }
+
+    /// Returns the smallest and largest representable values
+    pub fn value_range(&self) -> (f64, f64) {
+        match self {
+            DataType::U8 => (0.0, u8::MAX as f64),
+            DataType::U16 => (0.0, u16::MAX as f64),
+            DataType::U32 => (0.0, u32::MAX as f64),
+            DataType::I8 => (i8::MIN as f64, i8::MAX as f64),
+            DataType::I16 => (i16::MIN as f64, i16::MAX as f64),
+            DataType::I32 => (i32::MIN as f64, i32::MAX as f64),
+            DataType::F32 => (f32::MIN as f64, f32::MAX as f64),
+            DataType::F64 => (f64::MIN, f64::MAX),
+        }
+    }
+
+    /// Returns whether this is a signed integer type
+    pub fn is_signed_integer(&self) -> bool {
+        matches!(self, DataType::I8 | DataType::I16 | DataType::I32)
+    }
+
+    /// Returns the smallest type that represents every value of both types
+    ///
+    /// Unsigned 32-bit combined with a signed type, and 32-bit integers
+    /// combined with F32, promote to F64.
+    pub fn promote(self, other: DataType) -> DataType {
+        use DataType::*;
+        if self == other {
+            return self;
+        }
+        match (self, other) {
+            (F64, _) | (_, F64) => F64,
+            (F32, t) | (t, F32) => if t.size() == 4 { F64 } else { F32 },
+            (a, b) if a.is_signed_integer() == b.is_signed_integer() => {
+                if a.size() >= b.size() { a } else { b }
+            }
+            (a, b) => {
+                let (signed, unsigned) = if a.is_signed_integer() { (a, b) } else { (b, a) };
+                if signed.size() > unsigned.size() {
+                    signed
+                } else {
+                    match unsigned {
+                        U8 => I16,
+                        U16 => I32,
+                        _ => F64,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns whether this is an integer type
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, DataType::F32 | DataType::F64)
+    }
+
     /// Returns the name of this data type
     pub fn name(&self) -> &'static str {
         match self {
@@ -47,6 +126,48 @@ impl DataType {
     }
 }
 
+/// A Rust sample type with a matching [`DataType`]
+pub trait Pixel: Copy + Default + Send + Sync + 'static {
+    /// The corresponding data type
+    const DATA_TYPE: DataType;
+
+    /// Converts from f64, rounding and saturating for integer types (NaN becomes 0)
+    fn from_f64(value: f64) -> Self;
+
+    /// Converts to f64
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_pixel {
+    ($($t:ty => $data_type:ident, $round:expr;)*) => {
+        $(
+            impl Pixel for $t {
+                const DATA_TYPE: DataType = DataType::$data_type;
+
+                fn from_f64(value: f64) -> Self {
+                    let round: fn(f64) -> f64 = $round;
+                    round(value) as $t
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_pixel! {
+    u8 => U8, f64::round;
+    u16 => U16, f64::round;
+    u32 => U32, f64::round;
+    i8 => I8, f64::round;
+    i16 => I16, f64::round;
+    i32 => I32, f64::round;
+    f32 => F32, |v| v;
+    f64 => F64, |v| v;
+}
+
 /// Represents image dimensions
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Dimensions {
@@ -87,6 +208,25 @@ mod tests {
         assert_eq!(DataType::F32.name(), "F32");
     }
 
+    #[test]
+    fn test_data_type_promote() {
+        assert_eq!(DataType::U8.promote(DataType::U16), DataType::U16);
+        assert_eq!(DataType::U8.promote(DataType::I8), DataType::I16);
+        assert_eq!(DataType::I32.promote(DataType::U16), DataType::I32);
+        assert_eq!(DataType::U32.promote(DataType::I16), DataType::F64);
+        assert_eq!(DataType::I16.promote(DataType::F32), DataType::F32);
+        assert_eq!(DataType::I32.promote(DataType::F32), DataType::F64);
+    }
+
+    #[test]
+    fn test_pixel_conversion() {
+        assert_eq!(u8::from_f64(2.6), 3);
+        assert_eq!(u8::from_f64(300.0), 255);
+        assert_eq!(i16::from_f64(-1e9), i16::MIN);
+        assert_eq!(u16::from_f64(f64::NAN), 0);
+        assert_eq!(<f32 as Pixel>::DATA_TYPE, DataType::F32);
+    }
+
     #[test]
     fn test_dimensions() {
         let dims = Dimensions::new(100, 200);
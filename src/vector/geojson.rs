@@ -0,0 +1,243 @@
+//! GeoJSON parsing and serialization
+
+use serde_json::{Map, Value};
+use crate::error::{Error, Result};
+use crate::projection::Coordinate;
+use super::geometry::{Geometry, Polygon};
+
+/// A geometry with its properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feature {
+    /// Geometry, `None` for features with a null geometry
+    pub geometry: Option<Geometry>,
+    /// Feature properties
+    pub properties: Map<String, Value>,
+}
+
+impl Feature {
+    /// Creates a feature without properties
+    pub fn new(geometry: Geometry) -> Self {
+        Self {
+            geometry: Some(geometry),
+            properties: Map::new(),
+        }
+    }
+
+    /// Returns a numeric property
+    pub fn property_f64(&self, name: &str) -> Option<f64> {
+        match self.properties.get(name)? {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse().ok(),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    /// Converts the feature to a GeoJSON Feature object
+    pub fn to_value(&self) -> Value {
+        serde_json::json!({
+            "type": "Feature",
+            "properties": self.properties,
+            "geometry": self.geometry.as_ref().map_or(Value::Null, geometry_to_value),
+        })
+    }
+}
+
+/// Parses a GeoJSON document into features
+///
+/// Accepts a FeatureCollection, a single Feature or a bare geometry.
+pub fn parse_features(text: &str) -> Result<Vec<Feature>> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| Error::InvalidFormat(format!("Invalid GeoJSON: {}", e)))?;
+    features_from_value(&value)
+}
+
+/// Converts a parsed GeoJSON value into features
+pub fn features_from_value(value: &Value) -> Result<Vec<Feature>> {
+    match type_of(value)? {
+        "FeatureCollection" => value.get("features")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("FeatureCollection without features array"))?
+            .iter()
+            .map(feature_from_value)
+            .collect(),
+        "Feature" => Ok(vec![feature_from_value(value)?]),
+        _ => Ok(vec![Feature::new(geometry_from_value(value)?)]),
+    }
+}
+
+/// Converts a GeoJSON geometry object
+pub fn geometry_from_value(value: &Value) -> Result<Geometry> {
+    let kind = type_of(value)?;
+    let coords = || value.get("coordinates")
+        .ok_or_else(|| invalid(&format!("{} without coordinates", kind)));
+
+    match kind {
+        "Point" => Ok(Geometry::Point(position(coords()?)?)),
+        "MultiPoint" => Ok(Geometry::MultiPoint(positions(coords()?)?)),
+        "LineString" => Ok(Geometry::LineString(positions(coords()?)?)),
+        "MultiLineString" => Ok(Geometry::MultiLineString(
+            array(coords()?)?.iter().map(positions).collect::<Result<_>>()?
+        )),
+        "Polygon" => Ok(Geometry::Polygon(polygon(coords()?)?)),
+        "MultiPolygon" => Ok(Geometry::MultiPolygon(
+            array(coords()?)?.iter().map(polygon).collect::<Result<_>>()?
+        )),
+        other => Err(invalid(&format!("Unsupported geometry type: {}", other))),
+    }
+}
+
+/// Builds a GeoJSON FeatureCollection
+pub fn features_to_value(features: &[Feature]) -> Value {
+    serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features.iter().map(Feature::to_value).collect::<Vec<_>>(),
+    })
+}
+
+/// Converts a geometry to a GeoJSON geometry object
+///
+/// Polygon rings are closed if they are stored open.
+pub fn geometry_to_value(geometry: &Geometry) -> Value {
+    let (kind, coordinates) = match geometry {
+        Geometry::Point(c) => ("Point", position_value(c)),
+        Geometry::MultiPoint(points) => ("MultiPoint", positions_value(points)),
+        Geometry::LineString(line) => ("LineString", positions_value(line)),
+        Geometry::MultiLineString(lines) => ("MultiLineString", lines.iter().map(|l| positions_value(l)).collect()),
+        Geometry::Polygon(p) => ("Polygon", polygon_value(p)),
+        Geometry::MultiPolygon(ps) => ("MultiPolygon", ps.iter().map(polygon_value).collect()),
+    };
+    serde_json::json!({ "type": kind, "coordinates": coordinates })
+}
+
+fn feature_from_value(value: &Value) -> Result<Feature> {
+    let geometry = match value.get("geometry") {
+        None | Some(Value::Null) => None,
+        Some(g) => Some(geometry_from_value(g)?),
+    };
+    let properties = value.get("properties")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(Feature { geometry, properties })
+}
+
+fn type_of(value: &Value) -> Result<&str> {
+    value.get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("Missing \"type\" member"))
+}
+
+fn array(value: &Value) -> Result<&Vec<Value>> {
+    value.as_array().ok_or_else(|| invalid("Expected an array of coordinates"))
+}
+
+fn position(value: &Value) -> Result<Coordinate> {
+    let parts = array(value)?;
+    let number = |i: usize| parts.get(i).and_then(Value::as_f64);
+
+    match (number(0), number(1)) {
+        (Some(x), Some(y)) => Ok(match number(2) {
+            Some(z) => Coordinate::new_3d(x, y, z),
+            None => Coordinate::new(x, y),
+        }),
+        _ => Err(invalid("Position needs at least two numbers")),
+    }
+}
+
+fn positions(value: &Value) -> Result<Vec<Coordinate>> {
+    array(value)?.iter().map(position).collect()
+}
+
+fn polygon(value: &Value) -> Result<Polygon> {
+    let mut rings = array(value)?.iter().map(positions);
+    let exterior = rings.next()
+        .ok_or_else(|| invalid("Polygon without rings"))??;
+    let interiors = rings.collect::<Result<_>>()?;
+    Ok(Polygon::with_holes(exterior, interiors))
+}
+
+fn position_value(c: &Coordinate) -> Value {
+    if c.z != 0.0 {
+        serde_json::json!([c.x, c.y, c.z])
+    } else {
+        serde_json::json!([c.x, c.y])
+    }
+}
+
+fn positions_value(coords: &[Coordinate]) -> Value {
+    coords.iter().map(position_value).collect()
+}
+
+fn polygon_value(polygon: &Polygon) -> Value {
+    polygon.rings()
+        .map(|ring| {
+            let mut coords = positions_value(ring);
+            if let (Some(first), Some(last), Value::Array(values)) = (ring.first(), ring.last(), &mut coords) {
+                if first != last {
+                    values.push(position_value(first));
+                }
+            }
+            coords
+        })
+        .collect()
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidFormat(format!("Invalid GeoJSON: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_feature_collection() {
+        let text = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "properties": {"id": 7, "name": "a"},
+                 "geometry": {"type": "Polygon", "coordinates": [
+                    [[0,0],[10,0],[10,10],[0,10],[0,0]],
+                    [[4,4],[6,4],[6,6],[4,6],[4,4]]]}},
+                {"type": "Feature", "properties": null, "geometry": null}
+            ]
+        }"#;
+
+        let features = parse_features(text).unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0].property_f64("id"), Some(7.0));
+
+        let polygons = features[0].geometry.as_ref().unwrap().polygons().len();
+        assert_eq!(polygons, 1);
+        assert!(features[1].geometry.is_none());
+    }
+
+    #[test]
+    fn test_parse_bare_geometries() {
+        let point = parse_features(r#"{"type": "Point", "coordinates": [1.5, 2.5, 3]}"#).unwrap();
+        assert_eq!(point[0].geometry, Some(Geometry::Point(Coordinate::new_3d(1.5, 2.5, 3.0))));
+
+        let lines = parse_features(r#"{"type": "MultiLineString", "coordinates": [[[0,0],[1,1]]]}"#).unwrap();
+        assert!(matches!(lines[0].geometry, Some(Geometry::MultiLineString(ref l)) if l[0].len() == 2));
+
+        assert!(parse_features(r#"{"type": "Circle", "coordinates": []}"#).is_err());
+        assert!(parse_features(r#"{"type": "Point", "coordinates": [1]}"#).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut feature = Feature::new(Geometry::Polygon(Polygon::rectangle(0.0, 0.0, 2.0, 1.0)));
+        feature.properties.insert("value".to_string(), Value::from(3));
+
+        let value = features_to_value(&[feature.clone()]);
+        let ring = &value["features"][0]["geometry"]["coordinates"][0];
+        assert_eq!(ring.as_array().unwrap().len(), 5);
+        assert_eq!(ring[0], ring[4]);
+
+        let parsed = features_from_value(&value).unwrap();
+        assert_eq!(parsed[0].properties, feature.properties);
+        assert_eq!(parsed[0].geometry.as_ref().unwrap().polygons()[0].area(), 2.0);
+    }
+}
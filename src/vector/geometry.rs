@@ -0,0 +1,227 @@
+//! Simple feature geometry types
+
+use crate::catalog::Envelope;
+use crate::error::Result;
+use crate::projection::{Coordinate, Transformer};
+
+/// Polygon with an exterior ring and optional holes
+///
+/// Rings are stored as vertex lists; closing the ring by repeating the first
+/// vertex is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    /// Outer boundary
+    pub exterior: Vec<Coordinate>,
+    /// Holes
+    pub interiors: Vec<Vec<Coordinate>>,
+}
+
+impl Polygon {
+    /// Creates a polygon without holes
+    pub fn new(exterior: Vec<Coordinate>) -> Self {
+        Self {
+            exterior,
+            interiors: Vec::new(),
+        }
+    }
+
+    /// Creates a polygon with holes
+    pub fn with_holes(exterior: Vec<Coordinate>, interiors: Vec<Vec<Coordinate>>) -> Self {
+        Self { exterior, interiors }
+    }
+
+    /// Creates an axis-aligned rectangle
+    pub fn rectangle(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Self::new(vec![
+            Coordinate::new(min_x, min_y),
+            Coordinate::new(max_x, min_y),
+            Coordinate::new(max_x, max_y),
+            Coordinate::new(min_x, max_y),
+        ])
+    }
+
+    /// Returns all rings, exterior first
+    pub fn rings(&self) -> impl Iterator<Item = &Vec<Coordinate>> {
+        std::iter::once(&self.exterior).chain(self.interiors.iter())
+    }
+
+    /// Returns the area (holes subtracted)
+    pub fn area(&self) -> f64 {
+        let holes: f64 = self.interiors.iter().map(|r| ring_area(r).abs()).sum();
+        ring_area(&self.exterior).abs() - holes
+    }
+
+    /// Returns whether a point is inside (even-odd rule over all rings)
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.rings().filter(|ring| ring_contains(ring, x, y)).count() % 2 == 1
+    }
+
+    /// Returns the bounding envelope of the exterior ring
+    pub fn envelope(&self) -> Option<Envelope> {
+        coords_envelope(&self.exterior)
+    }
+
+    /// Applies a function to every vertex
+    pub fn map_coords<F: FnMut(Coordinate) -> Result<Coordinate>>(&self, mut f: F) -> Result<Polygon> {
+        let exterior = self.exterior.iter().map(|&c| f(c)).collect::<Result<_>>()?;
+        let interiors = self.interiors.iter()
+            .map(|ring| ring.iter().map(|&c| f(c)).collect::<Result<_>>())
+            .collect::<Result<_>>()?;
+        Ok(Polygon { exterior, interiors })
+    }
+}
+
+/// Geometry variants supported by the vector readers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    Point(Coordinate),
+    MultiPoint(Vec<Coordinate>),
+    LineString(Vec<Coordinate>),
+    MultiLineString(Vec<Vec<Coordinate>>),
+    Polygon(Polygon),
+    MultiPolygon(Vec<Polygon>),
+}
+
+impl Geometry {
+    /// Returns the polygons of polygonal geometries (empty for points and lines)
+    pub fn polygons(&self) -> Vec<&Polygon> {
+        match self {
+            Geometry::Polygon(p) => vec![p],
+            Geometry::MultiPolygon(ps) => ps.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the bounding envelope
+    pub fn envelope(&self) -> Option<Envelope> {
+        match self {
+            Geometry::Point(c) => Some(Envelope::point(c.x, c.y)),
+            Geometry::MultiPoint(cs) | Geometry::LineString(cs) => coords_envelope(cs),
+            Geometry::MultiLineString(lines) => lines.iter()
+                .filter_map(|l| coords_envelope(l))
+                .reduce(|a, b| a.union(&b)),
+            Geometry::Polygon(p) => p.envelope(),
+            Geometry::MultiPolygon(ps) => ps.iter()
+                .filter_map(|p| p.envelope())
+                .reduce(|a, b| a.union(&b)),
+        }
+    }
+
+    /// Applies a function to every vertex
+    pub fn map_coords<F: FnMut(Coordinate) -> Result<Coordinate>>(&self, mut f: F) -> Result<Geometry> {
+        let mut map_line = |cs: &Vec<Coordinate>| cs.iter().map(|&c| f(c)).collect::<Result<Vec<_>>>();
+
+        Ok(match self {
+            Geometry::Point(c) => Geometry::Point(map_line(&vec![*c])?[0]),
+            Geometry::MultiPoint(cs) => Geometry::MultiPoint(map_line(cs)?),
+            Geometry::LineString(cs) => Geometry::LineString(map_line(cs)?),
+            Geometry::MultiLineString(lines) => Geometry::MultiLineString(
+                lines.iter().map(&mut map_line).collect::<Result<_>>()?
+            ),
+            Geometry::Polygon(p) => Geometry::Polygon(Polygon {
+                exterior: map_line(&p.exterior)?,
+                interiors: p.interiors.iter().map(&mut map_line).collect::<Result<_>>()?,
+            }),
+            Geometry::MultiPolygon(ps) => Geometry::MultiPolygon(
+                ps.iter()
+                    .map(|p| Ok(Polygon {
+                        exterior: map_line(&p.exterior)?,
+                        interiors: p.interiors.iter().map(&mut map_line).collect::<Result<_>>()?,
+                    }))
+                    .collect::<Result<_>>()?
+            ),
+        })
+    }
+
+    /// Reprojects the geometry between EPSG codes (no-op when they match)
+    pub fn transform(&self, from_epsg: u16, to_epsg: u16) -> Result<Geometry> {
+        if from_epsg == to_epsg {
+            return Ok(self.clone());
+        }
+
+        let transformer = Transformer::new(from_epsg, to_epsg)?;
+        self.map_coords(|c| transformer.transform(c))
+    }
+}
+
+/// Returns the signed shoelace area of a ring (positive when counter-clockwise)
+pub fn ring_area(ring: &[Coordinate]) -> f64 {
+    if ring.len() < 3 {
+        return 0.0;
+    }
+
+    let mut sum = 0.0;
+    for i in 0..ring.len() {
+        let a = ring[i];
+        let b = ring[(i + 1) % ring.len()];
+        sum += a.x * b.y - b.x * a.y;
+    }
+    sum / 2.0
+}
+
+/// Returns whether a point is inside a ring (crossing number test)
+pub fn ring_contains(ring: &[Coordinate], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let n = ring.len();
+    if n < 3 {
+        return false;
+    }
+
+    let mut j = n - 1;
+    for i in 0..n {
+        let (a, b) = (ring[i], ring[j]);
+        if (a.y > y) != (b.y > y) && x < (b.x - a.x) * (y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn coords_envelope(coords: &[Coordinate]) -> Option<Envelope> {
+    coords.iter()
+        .map(|c| Envelope::point(c.x, c.y))
+        .reduce(|a, b| a.union(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square_with_hole() -> Polygon {
+        let outer = Polygon::rectangle(0.0, 0.0, 10.0, 10.0).exterior;
+        let hole = Polygon::rectangle(4.0, 4.0, 6.0, 6.0).exterior;
+        Polygon::with_holes(outer, vec![hole])
+    }
+
+    #[test]
+    fn test_area_and_contains() {
+        let polygon = square_with_hole();
+        assert_eq!(polygon.area(), 96.0);
+        assert!(polygon.contains(1.0, 1.0));
+        assert!(!polygon.contains(5.0, 5.0));
+        assert!(!polygon.contains(11.0, 5.0));
+    }
+
+    #[test]
+    fn test_ring_area_orientation() {
+        let ccw = Polygon::rectangle(0.0, 0.0, 2.0, 1.0).exterior;
+        let cw: Vec<Coordinate> = ccw.iter().rev().copied().collect();
+        assert_eq!(ring_area(&ccw), 2.0);
+        assert_eq!(ring_area(&cw), -2.0);
+    }
+
+    #[test]
+    fn test_geometry_envelope_and_map() {
+        let geometry = Geometry::MultiPolygon(vec![
+            Polygon::rectangle(0.0, 0.0, 1.0, 1.0),
+            Polygon::rectangle(5.0, -2.0, 6.0, 3.0),
+        ]);
+        assert_eq!(geometry.envelope(), Some(Envelope::new(0.0, -2.0, 6.0, 3.0)));
+        assert_eq!(geometry.polygons().len(), 2);
+
+        let shifted = geometry.map_coords(|c| Ok(Coordinate::new(c.x + 1.0, c.y))).unwrap();
+        assert_eq!(shifted.envelope(), Some(Envelope::new(1.0, -2.0, 7.0, 3.0)));
+        assert_eq!(geometry.transform(3857, 3857).unwrap(), geometry);
+    }
+}
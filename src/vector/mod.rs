@@ -0,0 +1,9 @@
+//! Vector geometries with GeoJSON and WKT input and GeoJSON output
+
+pub mod geometry;
+pub mod geojson;
+pub mod wkt;
+
+pub use geometry::{Geometry, Polygon};
+pub use geojson::{features_to_value, geometry_to_value, parse_features, Feature};
+pub use wkt::parse_wkt;
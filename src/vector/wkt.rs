@@ -0,0 +1,216 @@
+//! Well-Known Text parsing
+//!
+//! Supports POINT, MULTIPOINT, LINESTRING, MULTILINESTRING, POLYGON and
+//! MULTIPOLYGON. Z and M ordinates are accepted; Z is kept and M dropped.
+
+use crate::error::{Error, Result};
+use crate::projection::Coordinate;
+use super::geometry::{Geometry, Polygon};
+
+/// Parses a WKT geometry
+pub fn parse_wkt(text: &str) -> Result<Geometry> {
+    let mut parser = WktParser { text, pos: 0 };
+    let geometry = parser.geometry()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(invalid(format!("trailing text at {}", parser.pos)));
+    }
+    Ok(geometry)
+}
+
+/// Helper: Builds a WKT syntax error
+fn invalid(message: String) -> Error {
+    Error::InvalidFormat(format!("Invalid WKT: {}", message))
+}
+
+/// Helper: Cursor over WKT text
+struct WktParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl WktParser<'_> {
+    /// Helper: Parses a tagged geometry
+    fn geometry(&mut self) -> Result<Geometry> {
+        let tag = self.word().to_ascii_uppercase();
+        // Dimension suffix: Z, M or ZM
+        let dims = match self.peek_word().to_ascii_uppercase().as_str() {
+            "Z" | "M" | "ZM" => self.word().to_ascii_uppercase(),
+            _ => String::new(),
+        };
+        if self.peek_word().eq_ignore_ascii_case("EMPTY") {
+            return Err(Error::Unsupported(format!("Empty {} geometry", tag)));
+        }
+        let has_m = dims.contains('M');
+
+        match tag.as_str() {
+            "POINT" => {
+                self.expect('(')?;
+                let point = self.coordinate(has_m)?;
+                self.expect(')')?;
+                Ok(Geometry::Point(point))
+            }
+            "MULTIPOINT" => {
+                // Both MULTIPOINT (1 2, 3 4) and MULTIPOINT ((1 2), (3 4))
+                self.expect('(')?;
+                let mut points = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.rest().starts_with('(') {
+                        self.expect('(')?;
+                        points.push(self.coordinate(has_m)?);
+                        self.expect(')')?;
+                    } else {
+                        points.push(self.coordinate(has_m)?);
+                    }
+                    if !self.comma() {
+                        break;
+                    }
+                }
+                self.expect(')')?;
+                Ok(Geometry::MultiPoint(points))
+            }
+            "LINESTRING" => Ok(Geometry::LineString(self.coordinates(has_m)?)),
+            "MULTILINESTRING" => Ok(Geometry::MultiLineString(self.list(|p| p.coordinates(has_m))?)),
+            "POLYGON" => Ok(Geometry::Polygon(self.polygon(has_m)?)),
+            "MULTIPOLYGON" => Ok(Geometry::MultiPolygon(self.list(|p| p.polygon(has_m))?)),
+            "" => Err(invalid(format!("expected a geometry type at {}", self.pos))),
+            other => Err(Error::Unsupported(format!("WKT geometry type {}", other))),
+        }
+    }
+
+    /// Helper: Parses `(ring, ring, ...)`
+    fn polygon(&mut self, has_m: bool) -> Result<Polygon> {
+        let mut rings = self.list(|p| p.coordinates(has_m))?;
+        let exterior = rings.remove(0);
+        Ok(Polygon::with_holes(exterior, rings))
+    }
+
+    /// Helper: Parses a parenthesised, comma-separated list
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        self.expect('(')?;
+        let mut items = vec![item(self)?];
+        while self.comma() {
+            items.push(item(self)?);
+        }
+        self.expect(')')?;
+        Ok(items)
+    }
+
+    /// Helper: Parses `(x y, x y, ...)`
+    fn coordinates(&mut self, has_m: bool) -> Result<Vec<Coordinate>> {
+        self.list(|p| p.coordinate(has_m))
+    }
+
+    /// Helper: Parses `x y [z] [m]`
+    fn coordinate(&mut self, has_m: bool) -> Result<Coordinate> {
+        let mut values = Vec::with_capacity(4);
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            let len = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+                .unwrap_or(rest.len());
+            if len == 0 {
+                break;
+            }
+            let value = rest[..len].parse::<f64>()
+                .map_err(|_| invalid(format!("bad number '{}'", &rest[..len])))?;
+            values.push(value);
+            self.pos += len;
+        }
+
+        // XYM carries the measure third; it is dropped either way
+        let z = if has_m && values.len() == 3 { None } else { values.get(2).copied() };
+        match values.len() {
+            2..=4 => Ok(Coordinate { x: values[0], y: values[1], z: z.unwrap_or(0.0) }),
+            _ => Err(invalid(format!("expected 2 to 4 ordinates at {}", self.pos))),
+        }
+    }
+
+    /// Helper: Consumes a comma if one follows
+    fn comma(&mut self) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(',') {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Helper: Consumes an expected character
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(invalid(format!("expected '{}' at {}", c, self.pos)))
+        }
+    }
+
+    /// Helper: Consumes an alphabetic word
+    fn word(&mut self) -> &str {
+        self.skip_whitespace();
+        let start = self.pos;
+        self.pos += self.peek_word().len();
+        &self.text[start..self.pos]
+    }
+
+    /// Helper: Returns the alphabetic word at the cursor without consuming it
+    fn peek_word(&self) -> &str {
+        let rest = self.rest().trim_start();
+        let len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        &rest[..len]
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_geometries() {
+        assert_eq!(parse_wkt("POINT (1 2)").unwrap(), Geometry::Point(Coordinate::new(1.0, 2.0)));
+        assert_eq!(parse_wkt("point z (1 2 3)").unwrap(), Geometry::Point(Coordinate { x: 1.0, y: 2.0, z: 3.0 }));
+        assert_eq!(parse_wkt("POINT M (1 2 9)").unwrap(), Geometry::Point(Coordinate::new(1.0, 2.0)));
+
+        let expected = Geometry::MultiPoint(vec![Coordinate::new(1.0, 2.0), Coordinate::new(-3.5, 4e2)]);
+        assert_eq!(parse_wkt("MULTIPOINT (1 2, -3.5 4e2)").unwrap(), expected);
+        assert_eq!(parse_wkt("MULTIPOINT ((1 2), (-3.5 4e2))").unwrap(), expected);
+
+        match parse_wkt("POLYGON ((0 0, 10 0, 10 10, 0 10, 0 0), (2 2, 4 2, 4 4, 2 2))").unwrap() {
+            Geometry::Polygon(p) => {
+                assert_eq!(p.exterior.len(), 5);
+                assert_eq!(p.interiors.len(), 1);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse_wkt(" MULTIPOLYGON(((0 0,1 0,1 1,0 0)),((5 5,6 5,6 6,5 5))) ").unwrap() {
+            Geometry::MultiPolygon(ps) => assert_eq!(ps.len(), 2),
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse_wkt("MULTILINESTRING ((0 0, 1 1), (2 2, 3 3, 4 4))").unwrap() {
+            Geometry::MultiLineString(lines) => assert_eq!(lines[1].len(), 3),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in ["", "POINT (1)", "POINT (1 2", "LINESTRING (0 0, 1 x)", "POINT (1 2) extra"] {
+            assert!(matches!(parse_wkt(bad), Err(Error::InvalidFormat(_))), "{}", bad);
+        }
+        assert!(matches!(parse_wkt("POINT EMPTY"), Err(Error::Unsupported(_))));
+        assert!(matches!(parse_wkt("GEOMETRYCOLLECTION (POINT (1 2))"), Err(Error::Unsupported(_))));
+    }
+}